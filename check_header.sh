@@ -0,0 +1,14 @@
+#!/usr/bin/env sh
+# Generates the C header and checks that it, and the C++ wrapper over it,
+# compile.
+set -e
+out=$(mktemp -d)
+trap 'rm -rf "$out"' EXIT
+cbindgen --config cbindgen.toml --crate kazumidiparser-cbind --output "$out/kazumidiparser.h"
+cp kazumidiparser.hpp "$out/"
+printf '#include "kazumidiparser.h"\nint main(void) { return 0; }\n' > "$out/check.c"
+printf '#include "kazumidiparser.hpp"\nint main() { return 0; }\n' > "$out/check.cpp"
+${CC:-cc} -std=c99 -Wall -Werror -fsyntax-only -I"$out" "$out/check.c"
+${CXX:-c++} -std=c++17 -Wall -Werror -fsyntax-only -I"$out" "$out/check.cpp"
+${CXX:-c++} -std=c++20 -Wall -Werror -fsyntax-only -I"$out" "$out/check.cpp"
+echo "kazumidiparser.h and kazumidiparser.hpp compile"
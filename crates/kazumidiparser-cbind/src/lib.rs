@@ -1,9 +1,86 @@
-use std::ffi::{CStr, c_char};
+#![allow(clippy::missing_safety_doc)]
 
-use kazumidiparser_core::MidiParser;
+use std::cell::UnsafeCell;
+use std::ffi::{CStr, CString, c_char, c_void};
+use std::sync::OnceLock;
+use std::thread::JoinHandle;
+
+use kazumidiparser_core::logging::{self, LogLevel};
+use kazumidiparser_core::{
+    CancelToken, EventColumns, MidiEvent, MidiParser, ParseError, ParseOptions,
+};
 
 pub enum KazuMIDIParserPtr {}
 
+#[repr(C)]
+pub enum KazuMIDIParserLogLevel {
+    Error = 0,
+    Warn = 1,
+    Info = 2,
+    Debug = 3,
+}
+
+pub type KazuMIDIParserLogCallback = Option<
+    unsafe extern "C" fn(
+        level: KazuMIDIParserLogLevel,
+        message: *const c_char,
+        user_data: *mut c_void,
+    ),
+>;
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum KazuMIDIParserParseStatus {
+    Idle = 0,
+    Running = 1,
+    Succeeded = 2,
+    Failed = 3,
+    Cancelled = 4,
+}
+
+/// Why the last parse failed; see `midiparser_last_error_code`.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum KazuMIDIParserErrorCode {
+    None = 0,
+    // A NULL path or data pointer.
+    InvalidArgument = 1,
+    // The path is not valid UTF-8.
+    InvalidPath = 2,
+    // A background parse is still running.
+    Busy = 3,
+    Io = 4,
+    InvalidHeader = 5,
+    UnexpectedHeaderLength = 6,
+    UnexpectedChunk = 7,
+    TruncatedTrack = 8,
+    Decode = 9,
+    Cancelled = 10,
+    // The parse thread panicked.
+    Internal = 11,
+}
+
+impl KazuMIDIParserErrorCode {
+    fn of(error: &ParseError) -> KazuMIDIParserErrorCode {
+        match error {
+            ParseError::Io(_) => KazuMIDIParserErrorCode::Io,
+            ParseError::InvalidHeader => KazuMIDIParserErrorCode::InvalidHeader,
+            ParseError::UnexpectedHeaderLength(_) => {
+                KazuMIDIParserErrorCode::UnexpectedHeaderLength
+            }
+            ParseError::UnexpectedChunk { .. } => KazuMIDIParserErrorCode::UnexpectedChunk,
+            ParseError::TruncatedTrack { .. } => KazuMIDIParserErrorCode::TruncatedTrack,
+            ParseError::Decode { .. } => KazuMIDIParserErrorCode::Decode,
+            ParseError::Cancelled => KazuMIDIParserErrorCode::Cancelled,
+            // Only raised by the streaming readers and the pool, which this
+            // library does not use.
+            ParseError::TrackOutOfRange { .. }
+            | ParseError::FileShrank { .. }
+            | ParseError::Pool(_) => KazuMIDIParserErrorCode::Internal,
+        }
+    }
+}
+
 #[repr(C)]
 pub struct KazuMIDIParserHeader {
     format: u16,
@@ -11,25 +88,71 @@ pub struct KazuMIDIParserHeader {
     ppqn: u16,
 }
 
+/// A copy of one event. `sysex_data` is the payload of a SysEx (0xF0) or
+/// escape (0xF7) event and NULL for everything else.
 #[repr(C)]
 pub struct KazuMIDIParserMidiEvent {
     absolute_ns: u64,
+    absolute_tick: u64,
     status: u8,
     data1: u8,
     data2: u8,
+    track_index: u16,
     sysex_data: *const u8,
     sysex_len: usize,
 }
 
+/// An event as the parser stores it, laid out like the core `MidiEvent`.
+/// `sysex_ref` is opaque; read payloads with `midiparser_get_event_sysex`.
 #[repr(C)]
-pub struct KazuMIDIParserTrackEventIndices {
-    indices: *const usize,
+pub struct KazuMIDIParserEvent {
+    absolute_ns: u64,
+    absolute_tick: u64,
+    status: u8,
+    data1: u8,
+    data2: u8,
+    track_index: u16,
+    sysex_ref: [u32; 2],
+}
+
+// `midiparser_events_view` casts the core events to `KazuMIDIParserEvent`.
+const _: () = {
+    use std::mem::{align_of, offset_of, size_of};
+    assert!(size_of::<KazuMIDIParserEvent>() == size_of::<MidiEvent>());
+    assert!(align_of::<KazuMIDIParserEvent>() == align_of::<MidiEvent>());
+    assert!(offset_of!(KazuMIDIParserEvent, absolute_ns) == offset_of!(MidiEvent, absolute_ns));
+    assert!(offset_of!(KazuMIDIParserEvent, absolute_tick) == offset_of!(MidiEvent, absolute_tick));
+    assert!(offset_of!(KazuMIDIParserEvent, status) == offset_of!(MidiEvent, status));
+    assert!(offset_of!(KazuMIDIParserEvent, data1) == offset_of!(MidiEvent, data1));
+    assert!(offset_of!(KazuMIDIParserEvent, data2) == offset_of!(MidiEvent, data2));
+    assert!(offset_of!(KazuMIDIParserEvent, track_index) == offset_of!(MidiEvent, track_index));
+    assert!(offset_of!(KazuMIDIParserEvent, sysex_ref) == offset_of!(MidiEvent, sysex_index));
+};
+
+#[repr(C)]
+pub struct KazuMIDIParserEventsView {
+    events: *const KazuMIDIParserEvent,
     len: usize,
 }
 
+#[repr(C)]
+pub struct KazuMIDIParserMetaEvent {
+    absolute_ns: u64,
+    absolute_tick: u64,
+    track_index: u16,
+    meta_type: u8,
+    data: *const u8,
+    data_len: usize,
+}
+
+/// The event indices of every track, flattened: track `t` owns
+/// `indices[offsets[t]..offsets[t + 1]]`. `offsets` has `len + 1` entries and
+/// `indices` directly follows it in the same allocation, released as a whole
+/// by `midiparser_all_track_events_free`.
 #[repr(C)]
 pub struct KazuMIDIParserAllTrackEventIndices {
-    tracks: *const KazuMIDIParserTrackEventIndices,
+    offsets: *const usize,
+    indices: *const usize,
     len: usize,
 }
 
@@ -45,10 +168,227 @@ pub struct KazuMIDIParserAllTrackEvents {
     len: usize,
 }
 
+#[repr(C)]
+pub struct KazuMIDIParserEventArrays {
+    timestamps: *const u64,
+    ticks: *const u64,
+    status: *const u8,
+    data1: *const u8,
+    data2: *const u8,
+    track: *const u16,
+    len: usize,
+}
+
+// The object behind a `KazuMIDIParserPtr`. Calls borrow only `parser`, so
+// `midiparser_cancel` can read `cancel_token` from another thread while a
+// parse holds `&mut Parser`.
+struct Handle {
+    // A clone of `Parser::cancel_token`; never replaced after creation.
+    cancel_token: CancelToken,
+    parser: UnsafeCell<Parser>,
+}
+
+struct Parser {
+    parser: MidiParser,
+    track_event_indices: OnceLock<Vec<Vec<usize>>>,
+    // Backs `midiparser_get_event_arrays`; built on first use.
+    event_columns: OnceLock<EventColumns>,
+    cancel_token: CancelToken,
+    job: Option<JoinHandle<(MidiParser, Result<(), ParseError>)>>,
+    status: KazuMIDIParserParseStatus,
+    // Set by the last parse call that failed, cleared by one that succeeds.
+    last_error: Option<(KazuMIDIParserErrorCode, CString)>,
+}
+
+impl Parser {
+    fn track_event_indices(&self) -> &[Vec<usize>] {
+        self.track_event_indices
+            .get_or_init(|| self.parser.get_track_event_indices())
+    }
+
+    // The payload of a SysEx event, owned by the parsed sequence.
+    fn sysex(&self, event: &MidiEvent) -> Option<&[u8]> {
+        self.parser.sequence()?.sysex(event)
+    }
+
+    fn event_columns(&self) -> &EventColumns {
+        self.event_columns
+            .get_or_init(|| EventColumns::from_events(self.parser.get_events()))
+    }
+
+    // Checks a parse can start and readies the cancel token. On failure the
+    // reason is recorded and false returned.
+    fn begin(&mut self) -> bool {
+        if self.job.is_some() {
+            return self.fail(KazuMIDIParserErrorCode::Busy, "A parse is already running");
+        }
+        self.cancel_token.reset();
+        true
+    }
+
+    // `begin`, for a parse of the file at `midi_path`.
+    unsafe fn start<'a>(&mut self, midi_path: *const c_char) -> Option<&'a str> {
+        if midi_path.is_null() {
+            self.fail(KazuMIDIParserErrorCode::InvalidArgument, "The path is NULL");
+            return None;
+        }
+        let Ok(path) = unsafe { CStr::from_ptr(midi_path) }.to_str() else {
+            self.fail(
+                KazuMIDIParserErrorCode::InvalidPath,
+                "The path is not valid UTF-8",
+            );
+            return None;
+        };
+        self.begin().then_some(path)
+    }
+
+    // Records why the call failed and marks the parse Failed, unless a
+    // background parse is still running and owns the status.
+    fn fail(&mut self, code: KazuMIDIParserErrorCode, message: &str) -> bool {
+        let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+        self.last_error = Some((code, message));
+        if self.job.is_none() {
+            self.status = KazuMIDIParserParseStatus::Failed;
+        }
+        false
+    }
+
+    fn finish(&mut self, result: Result<(), ParseError>) -> bool {
+        self.track_event_indices = OnceLock::new();
+        self.event_columns = OnceLock::new();
+        match result {
+            Ok(()) => {
+                self.status = KazuMIDIParserParseStatus::Succeeded;
+                self.last_error = None;
+                true
+            }
+            Err(error) => {
+                self.fail(KazuMIDIParserErrorCode::of(&error), &error.to_string());
+                if self.cancel_token.is_cancelled() {
+                    self.status = KazuMIDIParserParseStatus::Cancelled;
+                }
+                false
+            }
+        }
+    }
+
+    fn join(&mut self) {
+        if let Some(job) = self.job.take() {
+            match job.join() {
+                Ok((parser, result)) => {
+                    self.parser = parser;
+                    self.finish(result);
+                }
+                // The caches were already dropped when the parse started.
+                Err(_) => {
+                    self.fail(KazuMIDIParserErrorCode::Internal, "Parse thread panicked");
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Parser {
+    fn drop(&mut self) {
+        self.cancel_token.cancel();
+        self.join();
+    }
+}
+
+unsafe fn parser_ref<'a>(midiparser_ptr: *mut KazuMIDIParserPtr) -> Option<&'a Parser> {
+    let handle = midiparser_ptr as *const Handle;
+    if handle.is_null() {
+        return None;
+    }
+    Some(unsafe { &*(*handle).parser.get() })
+}
+
+unsafe fn parser_mut<'a>(midiparser_ptr: *mut KazuMIDIParserPtr) -> Option<&'a mut Parser> {
+    let handle = midiparser_ptr as *const Handle;
+    if handle.is_null() {
+        return None;
+    }
+    Some(unsafe { &mut *(*handle).parser.get() })
+}
+
+// `sysex_data` must outlive the returned event; see `Parser::sysex`.
+fn to_c_event(event: &MidiEvent, sysex_data: Option<&[u8]>) -> KazuMIDIParserMidiEvent {
+    let (sysex_data, sysex_len) = if let Some(data) = sysex_data {
+        (data.as_ptr(), data.len())
+    } else {
+        (std::ptr::null(), 0)
+    };
+
+    KazuMIDIParserMidiEvent {
+        absolute_ns: event.absolute_ns,
+        absolute_tick: event.absolute_tick,
+        status: event.status,
+        data1: event.data1,
+        data2: event.data2,
+        track_index: event.track_index,
+        sysex_data,
+        sysex_len,
+    }
+}
+
+struct LogUserData(*mut c_void);
+
+// The host owns `user_data` and promises it can be used from any thread.
+unsafe impl Send for LogUserData {}
+unsafe impl Sync for LogUserData {}
+
+impl LogUserData {
+    fn get(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+/// Sends the parser's log messages to `callback`, passing `user_data` back
+/// unchanged. `message` is only valid during the call. The callback may run
+/// on several parser threads at once. Without a callback (or after passing
+/// NULL) the parser logs nothing.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn kazumidiparser_set_log_callback(
+    callback: KazuMIDIParserLogCallback,
+    user_data: *mut c_void,
+) {
+    let Some(callback) = callback else {
+        logging::clear_log_callback();
+        return;
+    };
+    let user_data = LogUserData(user_data);
+    logging::set_log_callback(move |level, message| {
+        let level = match level {
+            LogLevel::Error => KazuMIDIParserLogLevel::Error,
+            LogLevel::Warn => KazuMIDIParserLogLevel::Warn,
+            LogLevel::Info => KazuMIDIParserLogLevel::Info,
+            LogLevel::Debug => KazuMIDIParserLogLevel::Debug,
+        };
+        let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+        unsafe { callback(level, message.as_ptr(), user_data.get()) };
+    });
+}
+
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn midiparser_new() -> *mut KazuMIDIParserPtr {
-    let midi_parser = Box::new(MidiParser::new());
-    Box::into_raw(midi_parser) as *mut KazuMIDIParserPtr
+    let cancel_token = CancelToken::new();
+    let handle = Box::new(Handle {
+        cancel_token: cancel_token.clone(),
+        parser: UnsafeCell::new(Parser {
+            parser: MidiParser::with_options(ParseOptions {
+                cancel_token: Some(cancel_token.clone()),
+                ..Default::default()
+            })
+            .without_events_copy(),
+            track_event_indices: OnceLock::new(),
+            event_columns: OnceLock::new(),
+            cancel_token,
+            job: None,
+            status: KazuMIDIParserParseStatus::Idle,
+            last_error: None,
+        }),
+    });
+    Box::into_raw(handle) as *mut KazuMIDIParserPtr
 }
 
 #[unsafe(no_mangle)]
@@ -56,21 +396,138 @@ pub unsafe extern "C" fn midiparser_parse_midi_file(
     midiparser_ptr: *mut KazuMIDIParserPtr,
     midi_path: *const c_char,
 ) -> bool {
-    if midiparser_ptr.is_null() || midi_path.is_null() {
+    let Some(midiparser) = (unsafe { parser_mut(midiparser_ptr) }) else {
+        return false;
+    };
+    let Some(rust_path) = (unsafe { midiparser.start(midi_path) }) else {
+        return false;
+    };
+
+    let result = midiparser.parser.parse_file(rust_path);
+    midiparser.finish(result)
+}
+
+/// Parses a file the host already holds in memory. `data` is only read during
+/// the call; the parser keeps its own copy of everything it needs.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn midiparser_parse_midi_data(
+    midiparser_ptr: *mut KazuMIDIParserPtr,
+    data: *const u8,
+    len: usize,
+) -> bool {
+    let Some(midiparser) = (unsafe { parser_mut(midiparser_ptr) }) else {
+        return false;
+    };
+    if data.is_null() && len > 0 {
+        return midiparser.fail(KazuMIDIParserErrorCode::InvalidArgument, "The data is NULL");
+    }
+    if !midiparser.begin() {
         return false;
     }
 
-    let midiparser = unsafe { &mut *(midiparser_ptr as *mut MidiParser) };
+    let data = if len == 0 {
+        &[][..]
+    } else {
+        unsafe { std::slice::from_raw_parts(data, len) }
+    };
+    let result = midiparser.parser.parse_bytes(data);
+    midiparser.finish(result)
+}
 
-    let c_str = unsafe { CStr::from_ptr(midi_path) };
-    let rust_path = match c_str.to_str() {
-        Ok(s) => s,
-        Err(_) => return false,
+/// Starts parsing on a background thread and returns immediately. While the
+/// parse runs the parser reports no data; poll `midiparser_parse_status` or
+/// block in `midiparser_wait`. Returns false if a parse is already running.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn midiparser_parse_midi_file_async(
+    midiparser_ptr: *mut KazuMIDIParserPtr,
+    midi_path: *const c_char,
+) -> bool {
+    let Some(midiparser) = (unsafe { parser_mut(midiparser_ptr) }) else {
+        return false;
+    };
+    let Some(rust_path) = (unsafe { midiparser.start(midi_path) }) else {
+        return false;
     };
 
-    match midiparser.parse_file(rust_path) {
-        Ok(_) => true,
-        Err(_) => false,
+    let rust_path = rust_path.to_owned();
+    let options = midiparser.parser.options().clone();
+    let mut parser = std::mem::replace(
+        &mut midiparser.parser,
+        MidiParser::with_options(options).without_events_copy(),
+    );
+    midiparser.track_event_indices = OnceLock::new();
+    midiparser.event_columns = OnceLock::new();
+    midiparser.status = KazuMIDIParserParseStatus::Running;
+    midiparser.job = Some(std::thread::spawn(move || {
+        let result = parser.parse_file(&rust_path);
+        (parser, result)
+    }));
+    true
+}
+
+/// How the last parse call went. A call rejected before parsing (a NULL or
+/// non-UTF-8 path, NULL data) reports `Failed` too, except while a background
+/// parse is still `Running`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn midiparser_parse_status(
+    midiparser_ptr: *mut KazuMIDIParserPtr,
+) -> KazuMIDIParserParseStatus {
+    let Some(midiparser) = (unsafe { parser_mut(midiparser_ptr) }) else {
+        return KazuMIDIParserParseStatus::Idle;
+    };
+    if midiparser.job.as_ref().is_some_and(|job| job.is_finished()) {
+        midiparser.join();
+    }
+    midiparser.status
+}
+
+/// Blocks until a parse started with `midiparser_parse_midi_file_async`
+/// finishes. Returns true if the most recent parse succeeded.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn midiparser_wait(midiparser_ptr: *mut KazuMIDIParserPtr) -> bool {
+    let Some(midiparser) = (unsafe { parser_mut(midiparser_ptr) }) else {
+        return false;
+    };
+    midiparser.join();
+    midiparser.status == KazuMIDIParserParseStatus::Succeeded
+}
+
+/// Why the last parse call failed, or `None` if it succeeded or none has run.
+/// A background parse reports here once `midiparser_parse_status` or
+/// `midiparser_wait` sees it finish.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn midiparser_last_error_code(
+    midiparser_ptr: *mut KazuMIDIParserPtr,
+) -> KazuMIDIParserErrorCode {
+    (unsafe { parser_ref(midiparser_ptr) })
+        .and_then(|p| p.last_error.as_ref())
+        .map_or(KazuMIDIParserErrorCode::None, |(code, _)| *code)
+}
+
+/// A readable, NUL-terminated UTF-8 description of the last parse failure, or
+/// NULL when `midiparser_last_error_code` is `None`. It belongs to the parser
+/// and stays valid until a parse is started or finishes, or
+/// `midiparser_free`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn midiparser_last_error_message(
+    midiparser_ptr: *mut KazuMIDIParserPtr,
+) -> *const c_char {
+    (unsafe { parser_ref(midiparser_ptr) })
+        .and_then(|p| p.last_error.as_ref())
+        .map_or(std::ptr::null(), |(_, message)| message.as_ptr())
+}
+
+/// Asks the running parse to stop. Safe to call from any thread while
+/// another thread uses the parser, but not concurrently with
+/// `midiparser_free`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn midiparser_cancel(midiparser_ptr: *mut KazuMIDIParserPtr) {
+    let handle = midiparser_ptr as *const Handle;
+    if !handle.is_null() {
+        // Only the token field is borrowed, never the parser a parse may
+        // hold mutably.
+        let cancel_token = unsafe { &*std::ptr::addr_of!((*handle).cancel_token) };
+        cancel_token.cancel();
     }
 }
 
@@ -78,12 +535,11 @@ pub unsafe extern "C" fn midiparser_parse_midi_file(
 pub unsafe extern "C" fn midiparser_get_header(
     midiparser_ptr: *mut KazuMIDIParserPtr,
 ) -> *mut KazuMIDIParserHeader {
-    if midiparser_ptr.is_null() {
+    let Some(midiparser) = (unsafe { parser_ref(midiparser_ptr) }) else {
         return std::ptr::null_mut();
-    }
-    let midiparser: &MidiParser = unsafe { &*(midiparser_ptr as *mut MidiParser) };
+    };
 
-    match midiparser.get_header() {
+    match midiparser.parser.get_header() {
         Some(header) => {
             let c_header = KazuMIDIParserHeader {
                 format: header.format,
@@ -100,31 +556,15 @@ pub unsafe extern "C" fn midiparser_get_header(
 pub unsafe extern "C" fn midiparser_get_events(
     midiparser_ptr: *mut KazuMIDIParserPtr,
 ) -> *mut KazuMIDIParserMidiEvent {
-    if midiparser_ptr.is_null() {
+    let Some(midiparser) = (unsafe { parser_ref(midiparser_ptr) }) else {
         return std::ptr::null_mut();
-    }
-    let midiparser: &MidiParser = unsafe { &*(midiparser_ptr as *mut MidiParser) };
+    };
 
-    let rust_events = midiparser.get_events();
+    let rust_events = midiparser.parser.get_events();
 
     let mut c_events: Vec<KazuMIDIParserMidiEvent> = rust_events
         .iter()
-        .map(|event| {
-            let (sysex_data, sysex_len) = if let Some(ref data) = event.sysex_data {
-                (data.as_ptr(), data.len())
-            } else {
-                (std::ptr::null(), 0)
-            };
-
-            KazuMIDIParserMidiEvent {
-                absolute_ns: event.absolute_ns,
-                status: event.status,
-                data1: event.data1,
-                data2: event.data2,
-                sysex_data,
-                sysex_len,
-            }
-        })
+        .map(|event| to_c_event(event, midiparser.sysex(event)))
         .collect();
     c_events.shrink_to_fit();
 
@@ -138,53 +578,272 @@ pub unsafe extern "C" fn midiparser_get_events(
 pub unsafe extern "C" fn midiparser_get_events_len(
     midiparser_ptr: *mut KazuMIDIParserPtr,
 ) -> usize {
-    if midiparser_ptr.is_null() {
+    let Some(midiparser) = (unsafe { parser_ref(midiparser_ptr) }) else {
+        return 0;
+    };
+    midiparser.parser.get_events().len()
+}
+
+/// The song's length in nanoseconds, up to the last End of Track; 0 when
+/// nothing has been parsed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn midiparser_get_duration_ns(midiparser_ptr: *mut KazuMIDIParserPtr) -> u64 {
+    let Some(midiparser) = (unsafe { parser_ref(midiparser_ptr) }) else {
+        return 0;
+    };
+    midiparser
+        .parser
+        .sequence()
+        .map_or(0, |s| s.total_duration_ns())
+}
+
+/// The parser's own event list, without copying: `len` events in time order,
+/// valid until the next parse or `midiparser_free`. Do not free it. Empty
+/// (NULL, 0) when nothing has been parsed or a parse is running.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn midiparser_events_view(
+    midiparser_ptr: *mut KazuMIDIParserPtr,
+) -> KazuMIDIParserEventsView {
+    let events = (unsafe { parser_ref(midiparser_ptr) }).map_or(&[][..], |p| p.parser.get_events());
+    KazuMIDIParserEventsView {
+        events: if events.is_empty() {
+            std::ptr::null()
+        } else {
+            events.as_ptr() as *const KazuMIDIParserEvent
+        },
+        len: events.len(),
+    }
+}
+
+/// Points `out_data` and `out_len` at the payload of the `index`th event of
+/// `midiparser_events_view`. Returns false when there is no such event or it
+/// is not a SysEx (0xF0) or escape (0xF7) event. The payload stays valid
+/// until the next parse or `midiparser_free`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn midiparser_get_event_sysex(
+    midiparser_ptr: *mut KazuMIDIParserPtr,
+    index: usize,
+    out_data: *mut *const u8,
+    out_len: *mut usize,
+) -> bool {
+    if out_data.is_null() || out_len.is_null() {
+        return false;
+    }
+    let Some(midiparser) = (unsafe { parser_ref(midiparser_ptr) }) else {
+        return false;
+    };
+    let Some(data) = midiparser
+        .parser
+        .get_events()
+        .get(index)
+        .and_then(|event| midiparser.sysex(event))
+    else {
+        return false;
+    };
+
+    unsafe {
+        out_data.write(data.as_ptr());
+        out_len.write(data.len());
+    }
+    true
+}
+
+/// Fills `out_arrays` with one contiguous array per event field, all `len`
+/// long and in event order, ready to memcpy or upload as-is. The arrays are
+/// built on first use, belong to the parser and stay valid until the next
+/// parse or `midiparser_free`. SysEx payloads are only available through
+/// `midiparser_get_events`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn midiparser_get_event_arrays(
+    midiparser_ptr: *mut KazuMIDIParserPtr,
+    out_arrays: *mut KazuMIDIParserEventArrays,
+) -> bool {
+    if out_arrays.is_null() {
+        return false;
+    }
+    let Some(midiparser) = (unsafe { parser_ref(midiparser_ptr) }) else {
+        return false;
+    };
+    if midiparser.parser.sequence().is_none() {
+        return false;
+    }
+
+    let columns = midiparser.event_columns();
+    unsafe {
+        out_arrays.write(KazuMIDIParserEventArrays {
+            timestamps: columns.timestamps.as_ptr(),
+            ticks: columns.ticks.as_ptr(),
+            status: columns.status.as_ptr(),
+            data1: columns.data1.as_ptr(),
+            data2: columns.data2.as_ptr(),
+            track: columns.track.as_ptr(),
+            len: columns.timestamps.len(),
+        })
+    };
+    true
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn midiparser_track_event_count(
+    midiparser_ptr: *mut KazuMIDIParserPtr,
+    track_index: u16,
+) -> usize {
+    let Some(midiparser) = (unsafe { parser_ref(midiparser_ptr) }) else {
         return 0;
+    };
+    midiparser
+        .track_event_indices()
+        .get(track_index as usize)
+        .map_or(0, |indices| indices.len())
+}
+
+/// Copies the `nth` event of a track into `out_event`. Returns false when the
+/// track or event does not exist. `sysex_data` points into the parser and
+/// stays valid until the next parse or `midiparser_free`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn midiparser_get_track_event(
+    midiparser_ptr: *mut KazuMIDIParserPtr,
+    track_index: u16,
+    nth: usize,
+    out_event: *mut KazuMIDIParserMidiEvent,
+) -> bool {
+    if out_event.is_null() {
+        return false;
+    }
+    let Some(midiparser) = (unsafe { parser_ref(midiparser_ptr) }) else {
+        return false;
+    };
+    let Some(&index) = midiparser
+        .track_event_indices()
+        .get(track_index as usize)
+        .and_then(|indices| indices.get(nth))
+    else {
+        return false;
+    };
+
+    let event = &midiparser.parser.get_events()[index];
+    unsafe { out_event.write(to_c_event(event, midiparser.sysex(event))) };
+    true
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn midiparser_get_meta_count(
+    midiparser_ptr: *mut KazuMIDIParserPtr,
+) -> usize {
+    let Some(midiparser) = (unsafe { parser_ref(midiparser_ptr) }) else {
+        return 0;
+    };
+    midiparser.parser.sequence().map_or(0, |s| s.metas().len())
+}
+
+/// Copies the `index`th meta event (in time order) into `out_meta`. `data`
+/// points at the raw payload inside the parser and stays valid until the next
+/// parse or `midiparser_free`; it is not NUL-terminated.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn midiparser_get_meta(
+    midiparser_ptr: *mut KazuMIDIParserPtr,
+    index: usize,
+    out_meta: *mut KazuMIDIParserMetaEvent,
+) -> bool {
+    if out_meta.is_null() {
+        return false;
+    }
+    let Some(meta) = (unsafe { parser_ref(midiparser_ptr) })
+        .and_then(|p| p.parser.sequence())
+        .and_then(|s| s.metas().get(index))
+    else {
+        return false;
+    };
+
+    unsafe {
+        out_meta.write(KazuMIDIParserMetaEvent {
+            absolute_ns: meta.absolute_ns,
+            absolute_tick: meta.absolute_tick,
+            track_index: meta.track_index,
+            meta_type: meta.meta_type,
+            data: meta.data.as_ptr(),
+            data_len: meta.data.len(),
+        })
+    };
+    true
+}
+
+/// Returns the payload of the `index`th meta event as a newly allocated,
+/// NUL-terminated UTF-8 string (invalid sequences replaced, NUL bytes
+/// dropped), or NULL if there is no such event or it is not a text meta
+/// (0x01-0x0F). Release it with `midiparser_string_free`.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn midiparser_get_meta_text(
+    midiparser_ptr: *mut KazuMIDIParserPtr,
+    index: usize,
+) -> *mut c_char {
+    let Some(sequence) = (unsafe { parser_ref(midiparser_ptr) }).and_then(|p| p.parser.sequence())
+    else {
+        return std::ptr::null_mut();
+    };
+
+    let Some(text) = sequence
+        .metas()
+        .get(index)
+        .and_then(|meta| sequence.meta_text(meta))
+    else {
+        return std::ptr::null_mut();
+    };
+    CString::new(text.replace('\0', "")).map_or(std::ptr::null_mut(), CString::into_raw)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn midiparser_string_free(string_ptr: *mut c_char) {
+    if !string_ptr.is_null() {
+        drop(unsafe { CString::from_raw(string_ptr) });
     }
-    let midiparser: &MidiParser = unsafe { &*(midiparser_ptr as *mut MidiParser) };
-    midiparser.get_events().len()
 }
 
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn midiparser_get_track_events(
     midiparser_ptr: *mut KazuMIDIParserPtr,
 ) -> KazuMIDIParserAllTrackEventIndices {
-    if midiparser_ptr.is_null() {
+    let Some(midiparser) = (unsafe { parser_ref(midiparser_ptr) }) else {
         return KazuMIDIParserAllTrackEventIndices {
-            tracks: std::ptr::null(),
+            offsets: std::ptr::null(),
+            indices: std::ptr::null(),
             len: 0,
         };
-    }
-    let midiparser: &MidiParser = unsafe { &*(midiparser_ptr as *mut MidiParser) };
+    };
 
-    let rust_track_indices = midiparser.get_track_event_indices();
+    let tracks = midiparser.track_event_indices();
+    let total: usize = tracks.iter().map(Vec::len).sum();
+    let mut flat = Vec::with_capacity(tracks.len() + 1 + total);
+    flat.push(0);
+    for track in tracks {
+        flat.push(flat.last().unwrap() + track.len());
+    }
+    for track in tracks {
+        flat.extend_from_slice(track);
+    }
 
-    let mut c_track_indices: Vec<KazuMIDIParserTrackEventIndices> = Vec::new();
-    for track_vec in rust_track_indices {
-        let ptr = track_vec.as_ptr();
-        let len = track_vec.len();
-        std::mem::forget(track_vec);
-        c_track_indices.push(KazuMIDIParserTrackEventIndices { indices: ptr, len });
+    let len = tracks.len();
+    let offsets = Box::into_raw(flat.into_boxed_slice()) as *const usize;
+    KazuMIDIParserAllTrackEventIndices {
+        offsets,
+        indices: unsafe { offsets.add(len + 1) },
+        len,
     }
-    c_track_indices.shrink_to_fit();
-    let ptr = c_track_indices.as_ptr();
-    let len = c_track_indices.len();
-    std::mem::forget(c_track_indices);
-    KazuMIDIParserAllTrackEventIndices { tracks: ptr, len }
 }
 
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn midiparser_all_track_events_free(
     all_track_events: KazuMIDIParserAllTrackEventIndices,
 ) {
-    if !all_track_events.tracks.is_null() {
-        let _ = unsafe {
-            Vec::from_raw_parts(
-                all_track_events.tracks as *mut KazuMIDIParserTrackEventIndices,
-                all_track_events.len,
-                all_track_events.len,
-            )
-        };
+    if !all_track_events.offsets.is_null() {
+        unsafe {
+            let total =
+                all_track_events.len + 1 + *all_track_events.offsets.add(all_track_events.len);
+            drop(Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+                all_track_events.offsets as *mut usize,
+                total,
+            )));
+        }
     }
 }
 
@@ -210,6 +869,6 @@ pub unsafe extern "C" fn midiparser_header_free(header_ptr: *mut KazuMIDIParserH
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn midiparser_free(midiparser_ptr: *mut KazuMIDIParserPtr) {
     if !midiparser_ptr.is_null() {
-        drop(unsafe { Box::from_raw(midiparser_ptr as *mut MidiParser) });
+        drop(unsafe { Box::from_raw(midiparser_ptr as *mut Handle) });
     }
 }
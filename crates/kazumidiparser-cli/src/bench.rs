@@ -0,0 +1,105 @@
+use std::error::Error as StdError;
+use std::path::Path;
+use std::time::Duration;
+
+use kazumidiparser_core::{MidiParser, ParseMetrics, ParseOptions};
+
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit + 1 < UNITS.len() {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+fn format_rate(per_second: f64) -> String {
+    if per_second >= 1e6 {
+        format!("{:.2} M", per_second / 1e6)
+    } else if per_second >= 1e3 {
+        format!("{:.2} K", per_second / 1e3)
+    } else {
+        format!("{:.0} ", per_second)
+    }
+}
+
+fn ms(duration: Duration) -> String {
+    format!("{:.2} ms", duration.as_secs_f64() * 1000.0)
+}
+
+// Peak resident set size of this process, where the OS reports it.
+fn peak_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|l| l.starts_with("VmHWM:"))?;
+    let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
+
+pub fn run(path: &Path, iterations: u32, options: ParseOptions) -> Result<(), Box<dyn StdError>> {
+    let iterations = iterations.max(1);
+    let mut runs: Vec<ParseMetrics> = Vec::with_capacity(iterations as usize);
+
+    for i in 0..iterations {
+        let mut parser = MidiParser::with_options(options.clone()).without_events_copy();
+        parser.parse_file(&path.to_string_lossy())?;
+        let metrics = parser
+            .metrics()
+            .ok_or("Parser recorded no metrics")?
+            .clone();
+        println!(
+            "iteration {:>3}: {} ({} events)",
+            i + 1,
+            ms(metrics.total),
+            metrics.event_count
+        );
+        runs.push(metrics);
+    }
+
+    let first = &runs[0];
+    println!();
+    println!(
+        "{}: {}, {} tracks, {} events, {} metas",
+        path.display(),
+        format_bytes(first.file_bytes as f64),
+        first.track_count,
+        first.event_count,
+        first.meta_count
+    );
+    println!();
+    println!("{:<14} {:>12} {:>12} {:>12}", "phase", "min", "mean", "max");
+
+    let summarize = |name: &str, pick: &dyn Fn(&ParseMetrics) -> Duration| {
+        let values: Vec<Duration> = runs.iter().map(pick).collect();
+        let min = values.iter().min().copied().unwrap_or_default();
+        let max = values.iter().max().copied().unwrap_or_default();
+        let mean = values.iter().sum::<Duration>() / values.len() as u32;
+        println!(
+            "{:<14} {:>12} {:>12} {:>12}",
+            name,
+            ms(min),
+            ms(mean),
+            ms(max)
+        );
+        mean
+    };
+    for (index, (name, _)) in first.phases().iter().enumerate() {
+        summarize(name, &|m| m.phases()[index].1);
+    }
+    let mean_total = summarize("total", &|m| m.total);
+
+    let seconds = mean_total.as_secs_f64().max(f64::MIN_POSITIVE);
+    println!();
+    println!(
+        "throughput: {}/s, {}events/s (mean of {} runs)",
+        format_bytes(first.file_bytes as f64 / seconds),
+        format_rate(first.event_count as f64 / seconds),
+        runs.len()
+    );
+    match peak_memory() {
+        Some(bytes) => println!("peak memory: {}", format_bytes(bytes as f64)),
+        None => println!("peak memory: not available on this platform"),
+    }
+    Ok(())
+}
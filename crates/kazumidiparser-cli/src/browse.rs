@@ -0,0 +1,473 @@
+use std::error::Error as StdError;
+use std::path::Path;
+
+use kazumidiparser_core::pitch::{self, OctaveConvention};
+use kazumidiparser_core::{Division, MidiEvent, MidiSequence, gm};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KindFilter {
+    All,
+    Notes,
+    ControlChange,
+    ProgramChange,
+    PitchBend,
+    Aftertouch,
+    SysEx,
+}
+
+impl KindFilter {
+    const CYCLE: [KindFilter; 7] = [
+        KindFilter::All,
+        KindFilter::Notes,
+        KindFilter::ControlChange,
+        KindFilter::ProgramChange,
+        KindFilter::PitchBend,
+        KindFilter::Aftertouch,
+        KindFilter::SysEx,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            KindFilter::All => "all",
+            KindFilter::Notes => "notes",
+            KindFilter::ControlChange => "cc",
+            KindFilter::ProgramChange => "program",
+            KindFilter::PitchBend => "pitch bend",
+            KindFilter::Aftertouch => "aftertouch",
+            KindFilter::SysEx => "sysex",
+        }
+    }
+
+    fn next(self) -> KindFilter {
+        let i = Self::CYCLE.iter().position(|&k| k == self).unwrap_or(0);
+        Self::CYCLE[(i + 1) % Self::CYCLE.len()]
+    }
+
+    fn matches(self, status: u8) -> bool {
+        match self {
+            KindFilter::All => true,
+            KindFilter::Notes => matches!(status & 0xF0, 0x80 | 0x90),
+            KindFilter::ControlChange => status & 0xF0 == 0xB0,
+            KindFilter::ProgramChange => status & 0xF0 == 0xC0,
+            KindFilter::PitchBend => status & 0xF0 == 0xE0,
+            KindFilter::Aftertouch => matches!(status & 0xF0, 0xA0 | 0xD0),
+            KindFilter::SysEx => status >= 0xF0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Prompt {
+    Track,
+    Channel,
+    Time,
+}
+
+impl Prompt {
+    fn label(self) -> &'static str {
+        match self {
+            Prompt::Track => "Track (empty = all)",
+            Prompt::Channel => "Channel 1-16 (empty = all)",
+            Prompt::Time => "Jump to time (seconds or m:ss.mmm)",
+        }
+    }
+}
+
+struct Browser<'a> {
+    title: String,
+    sequence: &'a MidiSequence,
+    track: Option<u16>,
+    channel: Option<u8>,
+    kind: KindFilter,
+    // Indices into the event list matching the filter; `None` when nothing
+    // is filtered, so unfiltered browsing costs no memory.
+    visible: Option<Vec<usize>>,
+    selected: usize,
+    offset: usize,
+    page_size: usize,
+    prompt: Option<(Prompt, String)>,
+    status: String,
+}
+
+fn format_ns(ns: u64) -> String {
+    let ms = ns / 1_000_000;
+    format!("{}:{:02}.{:03}", ms / 60_000, (ms / 1000) % 60, ms % 1000)
+}
+
+// Accepts "12.5" (seconds) or "1:02.5" (minutes and seconds).
+fn parse_time(input: &str) -> Option<u64> {
+    let (minutes, seconds) = match input.split_once(':') {
+        Some((m, s)) => (m.trim().parse::<u64>().ok()?, s),
+        None => (0, input),
+    };
+    let seconds: f64 = seconds.trim().parse().ok()?;
+    let seconds_ns = seconds * 1e9;
+    // `as` would saturate rather than fail on a time past u64.
+    if !seconds_ns.is_finite() || seconds_ns < 0.0 || seconds_ns >= u64::MAX as f64 {
+        return None;
+    }
+    minutes
+        .checked_mul(60_000_000_000)?
+        .checked_add(seconds_ns as u64)
+}
+
+fn describe(event: &MidiEvent, sysex_data: Option<&[u8]>) -> (String, String) {
+    let key = |k: u8| {
+        format!(
+            "{} ({})",
+            pitch::note_name(k, OctaveConvention::MiddleC4),
+            k
+        )
+    };
+    match event.status & 0xF0 {
+        0x90 if event.data2 > 0 => (
+            "Note On".into(),
+            format!("{} vel {}", key(event.data1), event.data2),
+        ),
+        0x80 | 0x90 => (
+            "Note Off".into(),
+            format!("{} vel {}", key(event.data1), event.data2),
+        ),
+        0xA0 => (
+            "Poly Aftertouch".into(),
+            format!("{} = {}", key(event.data1), event.data2),
+        ),
+        0xB0 => (
+            "Control Change".into(),
+            format!("CC {} = {}", event.data1, event.data2),
+        ),
+        0xC0 => (
+            "Program Change".into(),
+            format!("{} {}", event.data1, gm::gm_program_name(event.data1)),
+        ),
+        0xD0 => ("Channel Aftertouch".into(), event.data1.to_string()),
+        0xE0 => (
+            "Pitch Bend".into(),
+            (((event.data2 as i32) << 7 | event.data1 as i32) - 8192).to_string(),
+        ),
+        _ => {
+            let data = sysex_data.unwrap_or_default();
+            let mut hex: Vec<String> = data.iter().take(16).map(|b| format!("{:02X}", b)).collect();
+            if data.len() > 16 {
+                hex.push("..".into());
+            }
+            // F7 escape packets carry no manufacturer ID.
+            let manufacturer = gm::ManufacturerId::of(data).and_then(|id| id.name());
+            let kind = match (event.status, gm::classify_sysex(data), manufacturer) {
+                (0xF0, gm::SysExMessage::Other, Some(name)) => format!("SysEx ({})", name),
+                (0xF0, message, _) => message.name().into(),
+                _ => "SysEx".into(),
+            };
+            (kind, format!("{} bytes: {}", data.len(), hex.join(" ")))
+        }
+    }
+}
+
+impl<'a> Browser<'a> {
+    fn new(title: String, sequence: &'a MidiSequence) -> Browser<'a> {
+        Browser {
+            title,
+            sequence,
+            track: None,
+            channel: None,
+            kind: KindFilter::All,
+            visible: None,
+            selected: 0,
+            offset: 0,
+            page_size: 20,
+            prompt: None,
+            status: String::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match &self.visible {
+            Some(indices) => indices.len(),
+            None => self.sequence.events().len(),
+        }
+    }
+
+    fn event_index(&self, row: usize) -> usize {
+        match &self.visible {
+            Some(indices) => indices[row],
+            None => row,
+        }
+    }
+
+    fn matches(&self, event: &MidiEvent) -> bool {
+        self.track.is_none_or(|t| event.track_index == t)
+            && self
+                .channel
+                .is_none_or(|c| event.status < 0xF0 && event.status & 0x0F == c)
+            && self.kind.matches(event.status)
+    }
+
+    // Rebuilds the filtered view, keeping the selection on the same event
+    // (or the next one that still matches).
+    fn refilter(&mut self) {
+        let current = (self.len() > 0).then(|| self.event_index(self.selected));
+        self.visible =
+            if self.track.is_none() && self.channel.is_none() && self.kind == KindFilter::All {
+                None
+            } else {
+                Some(
+                    self.sequence
+                        .events()
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, e)| self.matches(e))
+                        .map(|(i, _)| i)
+                        .collect(),
+                )
+            };
+        self.selected = match (current, &self.visible) {
+            (Some(index), Some(indices)) => indices.partition_point(|&i| i < index),
+            (Some(index), None) => index,
+            (None, _) => 0,
+        };
+        self.select(self.selected);
+        self.status = format!("{} events shown", self.len());
+    }
+
+    fn select(&mut self, row: usize) {
+        self.selected = row.min(self.len().saturating_sub(1));
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset + self.page_size {
+            self.offset = self.selected + 1 - self.page_size;
+        }
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        self.select(self.selected.saturating_add_signed(delta));
+    }
+
+    fn jump_to(&mut self, ns: u64) {
+        let events = self.sequence.events();
+        let row = match &self.visible {
+            Some(indices) => indices.partition_point(|&i| events[i].absolute_ns < ns),
+            None => events.partition_point(|e| e.absolute_ns < ns),
+        };
+        self.select(row);
+        self.offset = self.selected;
+        self.status = format!("Jumped to {}", format_ns(ns));
+    }
+
+    fn submit(&mut self, prompt: Prompt, input: &str) {
+        let input = input.trim();
+        match prompt {
+            Prompt::Track if input.is_empty() => self.track = None,
+            Prompt::Track => match input.parse::<u16>() {
+                Ok(track) => self.track = Some(track),
+                Err(_) => {
+                    self.status = format!("Invalid track: {}", input);
+                    return;
+                }
+            },
+            Prompt::Channel if input.is_empty() => self.channel = None,
+            Prompt::Channel => match input.parse::<u8>() {
+                Ok(channel @ 1..=16) => self.channel = Some(channel - 1),
+                _ => {
+                    self.status = format!("Invalid channel: {}", input);
+                    return;
+                }
+            },
+            Prompt::Time => {
+                match parse_time(input) {
+                    Some(ns) => self.jump_to(ns),
+                    None => self.status = format!("Invalid time: {}", input),
+                }
+                return;
+            }
+        }
+        self.refilter();
+    }
+
+    // Returns false when the browser should close.
+    fn handle_key(&mut self, code: KeyCode) -> bool {
+        if let Some((prompt, input)) = &mut self.prompt {
+            match code {
+                KeyCode::Enter => {
+                    let (prompt, input) = (*prompt, std::mem::take(input));
+                    self.prompt = None;
+                    self.submit(prompt, &input);
+                }
+                KeyCode::Esc => self.prompt = None,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            }
+            return true;
+        }
+
+        let page = self.page_size as isize;
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Down | KeyCode::Char('j') => self.move_by(1),
+            KeyCode::Up | KeyCode::Char('k') => self.move_by(-1),
+            KeyCode::PageDown | KeyCode::Char(' ') => self.move_by(page),
+            KeyCode::PageUp | KeyCode::Char('b') => self.move_by(-page),
+            KeyCode::Home | KeyCode::Char('g') => self.select(0),
+            KeyCode::End | KeyCode::Char('G') => self.select(usize::MAX),
+            KeyCode::Char('t') => self.prompt = Some((Prompt::Track, String::new())),
+            KeyCode::Char('c') => self.prompt = Some((Prompt::Channel, String::new())),
+            KeyCode::Char(':') | KeyCode::Char('J') => {
+                self.prompt = Some((Prompt::Time, String::new()))
+            }
+            KeyCode::Char('f') => {
+                self.kind = self.kind.next();
+                self.refilter();
+            }
+            KeyCode::Char('r') => {
+                self.track = None;
+                self.channel = None;
+                self.kind = KindFilter::All;
+                self.refilter();
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header_area, table_area, footer_area] = Layout::vertical([
+            Constraint::Length(2),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let header = self.sequence.header();
+        let filter = format!(
+            "track {} | channel {} | type {}",
+            self.track.map_or("all".to_string(), |t| t.to_string()),
+            self.channel
+                .map_or("all".to_string(), |c| (c + 1).to_string()),
+            self.kind.label()
+        );
+        frame.render_widget(
+            Paragraph::new(vec![
+                Line::from(format!(
+                    "{}  format {}, {} tracks, {}, {} events, {}",
+                    self.title,
+                    header.format,
+                    header.tracks,
+                    match header.division() {
+                        Division::TicksPerQuarter(ppqn) => format!("{} ppqn", ppqn),
+                        Division::Smpte {
+                            frames_per_second,
+                            ticks_per_frame,
+                        } => format!("SMPTE {} fps x {}", frames_per_second, ticks_per_frame),
+                    },
+                    self.sequence.events().len(),
+                    format_ns(self.sequence.total_duration_ns())
+                )),
+                Line::from(filter),
+            ]),
+            header_area,
+        );
+
+        // Borders plus the column header row.
+        self.page_size = (table_area.height as usize).saturating_sub(3).max(1);
+        self.select(self.selected);
+
+        let events = self.sequence.events();
+        let end = (self.offset + self.page_size).min(self.len());
+        let rows = (self.offset..end).map(|row| {
+            let index = self.event_index(row);
+            let event = &events[index];
+            let (kind, data) = describe(event, self.sequence.sysex(event));
+            let channel = if event.status < 0xF0 {
+                ((event.status & 0x0F) + 1).to_string()
+            } else {
+                "-".to_string()
+            };
+            let style = if row == self.selected {
+                Style::default().add_modifier(Modifier::REVERSED)
+            } else {
+                Style::default()
+            };
+            Row::new(vec![
+                index.to_string(),
+                format_ns(event.absolute_ns),
+                event.absolute_tick.to_string(),
+                event.track_index.to_string(),
+                channel,
+                kind,
+                data,
+            ])
+            .style(style)
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(10),
+                Constraint::Length(11),
+                Constraint::Length(10),
+                Constraint::Length(5),
+                Constraint::Length(3),
+                Constraint::Length(18),
+                Constraint::Min(10),
+            ],
+        )
+        .header(
+            Row::new(["#", "time", "tick", "track", "ch", "type", "data"])
+                .style(Style::default().fg(Color::Yellow)),
+        )
+        .block(Block::default().borders(Borders::ALL).title(format!(
+            " {}/{} ",
+            if self.len() == 0 {
+                0
+            } else {
+                self.selected + 1
+            },
+            self.len()
+        )));
+        frame.render_widget(table, table_area);
+
+        let footer = match &self.prompt {
+            Some((prompt, input)) => format!("{}: {}", prompt.label(), input),
+            None if !self.status.is_empty() => self.status.clone(),
+            None => "q quit  j/k move  g/G start/end  t track  c channel  f type  r reset  : jump to time".to_string(),
+        };
+        frame.render_widget(Paragraph::new(footer), footer_area);
+    }
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, browser: &mut Browser) -> std::io::Result<()> {
+    loop {
+        terminal.draw(|frame| browser.draw(frame))?;
+        if let Event::Key(key) = event::read()?
+            && key.kind == KeyEventKind::Press
+        {
+            // The status line shows until the next key.
+            if browser.prompt.is_none() {
+                browser.status.clear();
+            }
+            if !browser.handle_key(key.code) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+pub fn run(path: &Path, sequence: MidiSequence) -> Result<(), Box<dyn StdError>> {
+    let title = path.file_name().map_or_else(
+        || path.display().to_string(),
+        |n| n.to_string_lossy().into_owned(),
+    );
+    let mut browser = Browser::new(title, &sequence);
+
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut browser);
+    ratatui::restore();
+    Ok(result?)
+}
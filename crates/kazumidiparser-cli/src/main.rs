@@ -0,0 +1,244 @@
+use std::error::Error as StdError;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use kazumidiparser_core::export::smf::MidiWriter;
+use kazumidiparser_core::logging::{self, LogLevel};
+use kazumidiparser_core::playback::PlaybackTarget;
+use kazumidiparser_core::render::{
+    self, PianoRollOptions, RenderOptions, SimpleSynth, SoundFontSynth, Synthesizer,
+};
+use kazumidiparser_core::validator::{self, Severity};
+use kazumidiparser_core::{MidiParser, MidiSequence, ParseOptions};
+
+mod bench;
+mod browse;
+
+#[derive(Parser)]
+#[command(
+    name = "kazumidi",
+    version,
+    about = "Inspect and convert Standard MIDI Files"
+)]
+struct Cli {
+    /// Print parser progress to stderr.
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Measure parse throughput and per-phase times.
+    Bench {
+        file: PathBuf,
+        #[arg(short = 'n', long, default_value_t = 5)]
+        iterations: u32,
+        /// Ignore tempo events and time at a fixed BPM.
+        #[arg(long)]
+        bpm: Option<f64>,
+        /// Memory-map the file instead of reading it.
+        #[arg(long)]
+        mmap: bool,
+    },
+    /// Browse events interactively.
+    Browse { file: PathBuf },
+    /// Rewrite as format 0 (one track) or format 1 (a track per channel).
+    Convert {
+        file: PathBuf,
+        #[arg(short, long)]
+        output: PathBuf,
+        #[arg(long, value_parser = clap::value_parser!(u16).range(0..=1))]
+        format: u16,
+        /// Rescale to this many ticks per quarter note first.
+        #[arg(long, value_parser = clap::value_parser!(u16).range(1..=0x7FFF))]
+        ppqn: Option<u16>,
+    },
+    /// Check a file against the SMF spec.
+    Validate {
+        file: PathBuf,
+        /// Also fail on warnings.
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Render to audio (.wav) or a piano-roll image (.png).
+    Render {
+        file: PathBuf,
+        #[arg(short, long)]
+        output: PathBuf,
+        #[arg(long, default_value_t = 44100, value_parser = clap::value_parser!(u32).range(1..))]
+        sample_rate: u32,
+        /// Play .wav output with this SoundFont (.sf2) instead of sine tones.
+        #[arg(long)]
+        soundfont: Option<PathBuf>,
+        #[arg(long, default_value_t = 1280)]
+        width: u32,
+        #[arg(long, default_value_t = 720)]
+        height: u32,
+        /// Render only this track (format 2: with its own tempo).
+        #[arg(long, conflicts_with = "sequence")]
+        track: Option<u16>,
+        /// Render only the track holding this sequence number.
+        #[arg(long)]
+        sequence: Option<u16>,
+    },
+}
+
+fn parse(path: &Path) -> Result<MidiSequence, Box<dyn StdError>> {
+    let mut parser = MidiParser::new().without_events_copy();
+    parser.parse_file(&path.to_string_lossy())?;
+    Ok(parser
+        .into_sequence()
+        .ok_or("Parser produced no sequence")?)
+}
+
+fn convert(
+    file: &Path,
+    output: &Path,
+    format: u16,
+    ppqn: Option<u16>,
+) -> Result<(), Box<dyn StdError>> {
+    let mut sequence = parse(file)?;
+    if let Some(ppqn) = ppqn
+        && !sequence.resample_ppqn(ppqn)
+    {
+        return Err("SMPTE-timed files have no PPQN to resample".into());
+    }
+    let writer = if format == 0 {
+        MidiWriter::to_format_0(&sequence)
+    } else {
+        MidiWriter::split_by_channel(&sequence)
+    };
+    writer.save(output)?;
+    Ok(())
+}
+
+fn validate(file: &Path, strict: bool) -> Result<ExitCode, Box<dyn StdError>> {
+    let findings = validator::validate_file(file)?;
+    for finding in &findings {
+        println!("{}", finding);
+    }
+    let failing = findings
+        .iter()
+        .any(|f| f.severity == Severity::Error || (strict && f.severity == Severity::Warning));
+    if findings.is_empty() {
+        println!("{}: ok", file.display());
+    }
+    Ok(if failing {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+fn render(
+    file: &Path,
+    output: &Path,
+    sample_rate: u32,
+    soundfont: Option<&Path>,
+    width: u32,
+    height: u32,
+    target: PlaybackTarget,
+) -> Result<(), Box<dyn StdError>> {
+    let sequence = parse(file)?
+        .select(target)
+        .ok_or("No such track or sequence number")?;
+    let extension = output
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("wav") => {
+            let mut synth: Box<dyn Synthesizer> = match soundfont {
+                Some(path) => Box::new(SoundFontSynth::from_file(path, sample_rate)?),
+                None => Box::new(SimpleSynth::new(sample_rate)),
+            };
+            render::render_wav_file(&sequence, &mut *synth, &RenderOptions::default(), output)
+        }
+        Some("png") => {
+            let options = PianoRollOptions {
+                width,
+                height,
+                ..Default::default()
+            };
+            render::render_piano_roll(&sequence, &options).save_png(output)
+        }
+        _ => Err("Output must end in .wav or .png".into()),
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    // Warnings go to stderr, so they can neither corrupt piped output nor
+    // the browser screen.
+    let verbose = cli.verbose;
+    logging::set_log_callback(move |level, message| {
+        if verbose || level <= LogLevel::Warn {
+            eprintln!("[{}] {}", level, message);
+        }
+    });
+
+    let result = match &cli.command {
+        Command::Bench {
+            file,
+            iterations,
+            bpm,
+            mmap,
+        } => bench::run(
+            file,
+            *iterations,
+            ParseOptions {
+                fixed_bpm: *bpm,
+                memory_map: *mmap,
+                ..Default::default()
+            },
+        ),
+        Command::Browse { file } => parse(file).and_then(|sequence| {
+            logging::set_log_callback(|_, _| {});
+            browse::run(file, sequence)
+        }),
+        Command::Convert {
+            file,
+            output,
+            format,
+            ppqn,
+        } => convert(file, output, *format, *ppqn),
+        Command::Validate { file, strict } => return exit_on_error(validate(file, *strict)),
+        Command::Render {
+            file,
+            output,
+            sample_rate,
+            soundfont,
+            width,
+            height,
+            track,
+            sequence,
+        } => {
+            let target = match (track, sequence) {
+                (Some(track), _) => PlaybackTarget::Track(*track),
+                (_, Some(number)) => PlaybackTarget::SequenceNumber(*number),
+                _ => PlaybackTarget::All,
+            };
+            render(
+                file,
+                output,
+                *sample_rate,
+                soundfont.as_deref(),
+                *width,
+                *height,
+                target,
+            )
+        }
+    };
+    exit_on_error(result.map(|()| ExitCode::SUCCESS))
+}
+
+fn exit_on_error(result: Result<ExitCode, Box<dyn StdError>>) -> ExitCode {
+    result.unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        ExitCode::FAILURE
+    })
+}
@@ -0,0 +1,108 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::MidiSequence;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PressurePoint {
+    pub absolute_ns: u64,
+    pub absolute_tick: u64,
+    pub pressure: u8,
+}
+
+/// One note with the polyphonic key pressure it received while sounding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NotePressure {
+    pub track_index: u16,
+    pub channel: u8,
+    pub key: u8,
+    pub velocity: u8,
+    pub start_ns: u64,
+    pub start_tick: u64,
+    pub end_ns: u64,
+    pub end_tick: u64,
+    // Pressure changes in time order, all within the note.
+    pub curve: Vec<PressurePoint>,
+}
+
+/// Pairs notes per track, channel and key (first on, first off) and hands
+/// each poly-pressure (0xA0) event to the newest sounding note it addresses.
+///
+/// Only notes that received pressure are returned, ordered by note-on.
+/// Pressure for a key that is not sounding is dropped, and unterminated notes
+/// run to the end of the song.
+pub fn poly_pressure_curves(sequence: &MidiSequence) -> Vec<NotePressure> {
+    // Notes only get an entry in `curves` once their first pressure arrives,
+    // so a file with millions of notes and little pressure stays cheap.
+    struct OpenNote {
+        order: usize,
+        velocity: u8,
+        start_ns: u64,
+        start_tick: u64,
+        curve: Option<usize>,
+    }
+
+    let mut curves: Vec<(usize, NotePressure)> = Vec::new();
+    let mut open: HashMap<(u16, u8, u8), VecDeque<OpenNote>> = HashMap::new();
+    let mut note_count = 0;
+
+    for event in sequence.events() {
+        let slot = (event.track_index, event.status & 0x0F, event.data1);
+        match event.status & 0xF0 {
+            0x90 if event.data2 > 0 => {
+                open.entry(slot).or_default().push_back(OpenNote {
+                    order: note_count,
+                    velocity: event.data2,
+                    start_ns: event.absolute_ns,
+                    start_tick: event.absolute_tick,
+                    curve: None,
+                });
+                note_count += 1;
+            }
+            0x80 | 0x90 => {
+                let Some(note) = open.get_mut(&slot).and_then(|q| q.pop_front()) else {
+                    continue;
+                };
+                if let Some(index) = note.curve {
+                    curves[index].1.end_ns = event.absolute_ns;
+                    curves[index].1.end_tick = event.absolute_tick;
+                }
+            }
+            0xA0 => {
+                let Some(note) = open.get_mut(&slot).and_then(|q| q.back_mut()) else {
+                    continue;
+                };
+                let index = *note.curve.get_or_insert_with(|| {
+                    curves.push((
+                        note.order,
+                        NotePressure {
+                            track_index: slot.0,
+                            channel: slot.1,
+                            key: slot.2,
+                            velocity: note.velocity,
+                            start_ns: note.start_ns,
+                            start_tick: note.start_tick,
+                            end_ns: note.start_ns,
+                            end_tick: note.start_tick,
+                            curve: Vec::new(),
+                        },
+                    ));
+                    curves.len() - 1
+                });
+                curves[index].1.curve.push(PressurePoint {
+                    absolute_ns: event.absolute_ns,
+                    absolute_tick: event.absolute_tick,
+                    pressure: event.data2,
+                });
+            }
+            _ => {}
+        }
+    }
+
+    let (end_ns, end_tick) = (sequence.end_ns(), sequence.end_tick());
+    for index in open.into_values().flatten().filter_map(|note| note.curve) {
+        curves[index].1.end_ns = end_ns;
+        curves[index].1.end_tick = end_tick;
+    }
+    curves.sort_unstable_by_key(|(order, _)| *order);
+    curves.into_iter().map(|(_, note)| note).collect()
+}
@@ -0,0 +1,185 @@
+use crate::MidiSequence;
+use crate::pitch;
+
+// Channel 10, whose keys pick drums rather than pitches.
+const DRUM_CHANNEL: u8 = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChordQuality {
+    Major,
+    Minor,
+    Diminished,
+    Augmented,
+    Sus2,
+    Sus4,
+    // Root and fifth only.
+    Power,
+    Dominant7,
+    Major7,
+    Minor7,
+    HalfDiminished7,
+    Diminished7,
+    Major6,
+    Minor6,
+}
+
+impl ChordQuality {
+    // Each quality's pitch classes above the root, bit 0 being the root.
+    // Where two share a set (C6 and Am7), the root that is also the bass
+    // decides, since it is tried first.
+    const TEMPLATES: [(ChordQuality, u16); 14] = [
+        (ChordQuality::Major, 1 | 1 << 4 | 1 << 7),
+        (ChordQuality::Minor, 1 | 1 << 3 | 1 << 7),
+        (ChordQuality::Diminished, 1 | 1 << 3 | 1 << 6),
+        (ChordQuality::Augmented, 1 | 1 << 4 | 1 << 8),
+        (ChordQuality::Sus2, 1 | 1 << 2 | 1 << 7),
+        (ChordQuality::Sus4, 1 | 1 << 5 | 1 << 7),
+        (ChordQuality::Power, 1 | 1 << 7),
+        (ChordQuality::Dominant7, 1 | 1 << 4 | 1 << 7 | 1 << 10),
+        (ChordQuality::Major7, 1 | 1 << 4 | 1 << 7 | 1 << 11),
+        (ChordQuality::Minor7, 1 | 1 << 3 | 1 << 7 | 1 << 10),
+        (ChordQuality::HalfDiminished7, 1 | 1 << 3 | 1 << 6 | 1 << 10),
+        (ChordQuality::Diminished7, 1 | 1 << 3 | 1 << 6 | 1 << 9),
+        (ChordQuality::Major6, 1 | 1 << 4 | 1 << 7 | 1 << 9),
+        (ChordQuality::Minor6, 1 | 1 << 3 | 1 << 7 | 1 << 9),
+    ];
+
+    pub fn suffix(self) -> &'static str {
+        match self {
+            ChordQuality::Major => "",
+            ChordQuality::Minor => "m",
+            ChordQuality::Diminished => "dim",
+            ChordQuality::Augmented => "aug",
+            ChordQuality::Sus2 => "sus2",
+            ChordQuality::Sus4 => "sus4",
+            ChordQuality::Power => "5",
+            ChordQuality::Dominant7 => "7",
+            ChordQuality::Major7 => "maj7",
+            ChordQuality::Minor7 => "m7",
+            ChordQuality::HalfDiminished7 => "m7b5",
+            ChordQuality::Diminished7 => "dim7",
+            ChordQuality::Major6 => "6",
+            ChordQuality::Minor6 => "m6",
+        }
+    }
+
+    // The chord the pitch classes in `mask` (bit 0 = C) spell, trying
+    // `bass` as the root first.
+    fn identify(mask: u16, bass: u8) -> Option<(u8, ChordQuality)> {
+        let roots = std::iter::once(bass).chain((0..12).filter(move |&pc| pc != bass));
+        for root in roots.filter(|&pc| mask & 1 << pc != 0) {
+            let intervals = (mask >> root | mask << (12 - root)) & 0xFFF;
+            if let Some(&(quality, _)) = Self::TEMPLATES.iter().find(|(_, t)| *t == intervals) {
+                return Some((root, quality));
+            }
+        }
+        None
+    }
+}
+
+/// A stretch of time over which the sounding notes spell one chord.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chord {
+    pub start_ns: u64,
+    pub end_ns: u64,
+    pub start_tick: u64,
+    pub end_tick: u64,
+    // Pitch classes, 0 = C.
+    pub root: u8,
+    pub bass: u8,
+    pub quality: ChordQuality,
+    // Every key that sounded during the chord, ascending.
+    pub keys: Vec<u8>,
+}
+
+impl Chord {
+    /// e.g. "Am7" or "C/E", spelled for a key signature in fifths (see
+    /// `pitch::spell`).
+    pub fn name(&self, fifths: i8) -> String {
+        let spell = |pitch_class: u8| {
+            let spelled = pitch::spell(pitch_class, fifths);
+            let accidental = if spelled.alter >= 0 { "#" } else { "b" };
+            format!(
+                "{}{}",
+                spelled.letter,
+                accidental.repeat(spelled.alter.unsigned_abs() as usize)
+            )
+        };
+        let mut name = spell(self.root) + self.quality.suffix();
+        if self.bass != self.root {
+            name = name + "/" + &spell(self.bass);
+        }
+        name
+    }
+}
+
+/// Walks the song's notes (paired by `MidiSequence::notes`, channel 10
+/// left out) and labels every stretch where the sounding pitch classes
+/// form a known chord. Stretches shorter than `min_duration_ns`, such as
+/// the overlap of legato notes, are skipped; neighbouring stretches with
+/// the same chord are joined. Anything that is not a chord, single notes
+/// included, leaves a gap.
+pub fn detect_chords(sequence: &MidiSequence, min_duration_ns: u64) -> Vec<Chord> {
+    // (ns, tick, key, +1 at a note start or -1 at its end); ends sort
+    // before starts on the same time.
+    let mut edges: Vec<(u64, u64, i32, u8)> = Vec::new();
+    for note in sequence.notes() {
+        if note.channel != DRUM_CHANNEL && note.duration_ns > 0 {
+            edges.push((note.start_ns, note.start_tick, 1, note.key));
+            edges.push((note.end_ns(), note.end_tick(), -1, note.key));
+        }
+    }
+    edges.sort_unstable();
+
+    let mut sounding = [0u32; 128];
+    let mut chords: Vec<Chord> = Vec::new();
+    let mut i = 0;
+    while i < edges.len() {
+        let (start_ns, start_tick) = (edges[i].0, edges[i].1);
+        while i < edges.len() && edges[i].0 == start_ns {
+            let (_, _, change, key) = edges[i];
+            sounding[key as usize] = sounding[key as usize].saturating_add_signed(change);
+            i += 1;
+        }
+        let Some(&(end_ns, end_tick, _, _)) = edges.get(i) else {
+            break;
+        };
+        if end_ns - start_ns < min_duration_ns {
+            continue;
+        }
+
+        let keys: Vec<u8> = (0..128u8).filter(|&k| sounding[k as usize] > 0).collect();
+        let mask = keys.iter().fold(0u16, |mask, &k| mask | 1 << (k % 12));
+        let Some(&lowest) = keys.first() else {
+            continue;
+        };
+        let bass = lowest % 12;
+        let Some((root, quality)) = ChordQuality::identify(mask, bass) else {
+            continue;
+        };
+
+        match chords.last_mut() {
+            Some(last)
+                if last.end_ns == start_ns
+                    && (last.root, last.bass, last.quality) == (root, bass, quality) =>
+            {
+                last.end_ns = end_ns;
+                last.end_tick = end_tick;
+                last.keys.extend(keys);
+                last.keys.sort_unstable();
+                last.keys.dedup();
+            }
+            _ => chords.push(Chord {
+                start_ns,
+                end_ns,
+                start_tick,
+                end_tick,
+                root,
+                bass,
+                quality,
+                keys,
+            }),
+        }
+    }
+    chords
+}
@@ -0,0 +1,213 @@
+use std::collections::BTreeMap;
+
+use crate::{MidiEvent, MidiSequence};
+
+/// The continuous value a curve follows on one channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CurveSource {
+    // 0..=127.
+    Controller(u8),
+    // 14-bit, 8192 is centered.
+    PitchBend,
+    // 0..=127.
+    ChannelPressure,
+}
+
+impl CurveSource {
+    fn of(event: &MidiEvent) -> Option<(CurveSource, u16)> {
+        match event.status & 0xF0 {
+            0xB0 => Some((CurveSource::Controller(event.data1), event.data2 as u16)),
+            0xD0 => Some((CurveSource::ChannelPressure, event.data1 as u16)),
+            0xE0 => Some((
+                CurveSource::PitchBend,
+                (event.data2 as u16) << 7 | event.data1 as u16,
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Where curve samples are taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleGrid {
+    /// Every this many nanoseconds, starting at 0.
+    Interval { ns: u64 },
+    /// At the first sample of every audio block, matching `AudioBlocks`.
+    AudioBlocks { sample_rate: u32, block_size: u32 },
+}
+
+impl SampleGrid {
+    pub fn interval_ms(ms: u64) -> SampleGrid {
+        SampleGrid::Interval { ns: ms * 1_000_000 }
+    }
+
+    /// Time of grid point `index`.
+    pub fn time_ns(&self, index: u64) -> u64 {
+        match *self {
+            SampleGrid::Interval { ns } => index * ns,
+            SampleGrid::AudioBlocks {
+                sample_rate,
+                block_size,
+            } => (index as u128 * block_size as u128 * 1_000_000_000 / sample_rate as u128) as u64,
+        }
+    }
+
+    // First grid point at or after `ns`, which is where a change at `ns`
+    // first shows up.
+    fn first_index_from(&self, ns: u64) -> u64 {
+        match *self {
+            SampleGrid::Interval { ns: interval } => ns.div_ceil(interval),
+            SampleGrid::AudioBlocks {
+                sample_rate,
+                block_size,
+            } => {
+                let sample = (ns as u128 * sample_rate as u128 / 1_000_000_000) as u64;
+                sample.div_ceil(block_size as u64)
+            }
+        }
+    }
+
+    // Last grid point at or before `ns`.
+    fn last_index_until(&self, ns: u64) -> u64 {
+        match *self {
+            SampleGrid::Interval { ns: interval } => ns / interval,
+            SampleGrid::AudioBlocks {
+                sample_rate,
+                block_size,
+            } => (ns as u128 * sample_rate as u128 / 1_000_000_000) as u64 / block_size as u64,
+        }
+    }
+
+    fn validate(&self) {
+        match *self {
+            SampleGrid::Interval { ns } => assert!(ns > 0, "sample interval must be non-zero"),
+            SampleGrid::AudioBlocks {
+                sample_rate,
+                block_size,
+            } => {
+                assert!(sample_rate > 0, "sample rate must be non-zero");
+                assert!(block_size > 0, "block size must be non-zero");
+            }
+        }
+    }
+}
+
+/// One controller, pitch bend or pressure value sampled on a uniform grid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SampledCurve {
+    pub channel: u8,
+    pub source: CurveSource,
+    pub grid: SampleGrid,
+    // Grid index of `values[0]`: the first grid point after the first event.
+    // Nothing is known about the value before it.
+    pub first_index: u64,
+    pub values: Vec<u16>,
+}
+
+impl SampledCurve {
+    /// The value in effect at `ns`, or `None` before the curve starts.
+    pub fn value_at(&self, ns: u64) -> Option<u16> {
+        let index = self
+            .grid
+            .last_index_until(ns)
+            .checked_sub(self.first_index)?;
+        self.values
+            .get(index as usize)
+            .or(self.values.last())
+            .copied()
+    }
+
+    /// `(time_ns, value)` for every sample.
+    pub fn points(&self) -> impl Iterator<Item = (u64, u16)> + '_ {
+        self.values
+            .iter()
+            .enumerate()
+            .map(|(i, value)| (self.grid.time_ns(self.first_index + i as u64), *value))
+    }
+}
+
+struct CurveBuilder {
+    first_index: u64,
+    // Grid index where the latest change shows up.
+    latest_index: u64,
+    current: u16,
+    values: Vec<u16>,
+}
+
+impl CurveBuilder {
+    // Holds the current value on every grid point before `index`.
+    fn hold_until(&mut self, index: u64) {
+        let len = index.saturating_sub(self.first_index) as usize;
+        if len > self.values.len() {
+            self.values.resize(len, self.current);
+        }
+    }
+}
+
+/// Samples every controller, pitch bend and channel pressure curve in
+/// `sequence` on `grid`, holding the last value between events.
+///
+/// A grid point takes the value of the last event at or before it, so
+/// several changes between two points collapse into the last one. All
+/// curves end on the grid point at the end of the song, or after the last
+/// change if that is later, and are ordered by channel, then source.
+pub fn sample_curves(sequence: &MidiSequence, grid: SampleGrid) -> Vec<SampledCurve> {
+    sample_matching(sequence, grid, |_, _| true)
+}
+
+/// Samples a single curve; `None` when the channel never sends it.
+pub fn sample_curve(
+    sequence: &MidiSequence,
+    channel: u8,
+    source: CurveSource,
+    grid: SampleGrid,
+) -> Option<SampledCurve> {
+    sample_matching(sequence, grid, |c, s| c == channel && s == source).pop()
+}
+
+fn sample_matching<F>(sequence: &MidiSequence, grid: SampleGrid, wanted: F) -> Vec<SampledCurve>
+where
+    F: Fn(u8, CurveSource) -> bool,
+{
+    grid.validate();
+    let mut builders: BTreeMap<(u8, CurveSource), CurveBuilder> = BTreeMap::new();
+    for event in sequence.events() {
+        let Some((source, value)) = CurveSource::of(event) else {
+            continue;
+        };
+        let channel = event.status & 0x0F;
+        if !wanted(channel, source) {
+            continue;
+        }
+        let index = grid.first_index_from(event.absolute_ns);
+        let builder = builders.entry((channel, source)).or_insert(CurveBuilder {
+            first_index: index,
+            latest_index: index,
+            current: value,
+            values: Vec::new(),
+        });
+        builder.hold_until(index);
+        builder.latest_index = index;
+        builder.current = value;
+    }
+
+    // A change in the last moments of the song still gets one sample, and
+    // every curve ends on the same grid point.
+    let last_index = builders
+        .values()
+        .map(|builder| builder.latest_index)
+        .fold(grid.last_index_until(sequence.end_ns()), u64::max);
+    builders
+        .into_iter()
+        .map(|((channel, source), mut builder)| {
+            builder.hold_until(last_index + 1);
+            SampledCurve {
+                channel,
+                source,
+                grid,
+                first_index: builder.first_index,
+                values: builder.values,
+            }
+        })
+        .collect()
+}
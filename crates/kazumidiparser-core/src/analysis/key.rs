@@ -0,0 +1,112 @@
+use crate::MidiSequence;
+use crate::pitch;
+
+// Krumhansl and Kessler's probe-tone ratings, from the tonic up.
+const MAJOR_PROFILE: [f64; 12] = [
+    6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88,
+];
+const MINOR_PROFILE: [f64; 12] = [
+    6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17,
+];
+
+// Channel 10, whose keys pick drums rather than pitches.
+const DRUM_CHANNEL: u8 = 9;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyEstimate {
+    // Pitch class of the tonic, 0 = C.
+    pub tonic: u8,
+    pub minor: bool,
+    /// Correlation between the song's pitch classes and the key's profile,
+    /// -1.0 to 1.0. Above about 0.8 the key is clear; close runners-up
+    /// (often the relative key) show in `runner_up`.
+    pub confidence: f64,
+    /// The second best key as `(tonic, minor, confidence)`.
+    pub runner_up: (u8, bool, f64),
+}
+
+impl KeyEstimate {
+    /// The key signature in fifths (-6..=5, negative for flats), as a Key
+    /// Signature meta would give it.
+    pub fn fifths(&self) -> i8 {
+        let major_tonic = if self.minor {
+            (self.tonic + 3) % 12
+        } else {
+            self.tonic
+        };
+        (((major_tonic as i32 * 7) + 6).rem_euclid(12) - 6) as i8
+    }
+
+    /// e.g. "Eb major" or "F# minor", spelled for `fifths`.
+    pub fn name(&self) -> String {
+        let spelled = pitch::spell(self.tonic, self.fifths());
+        let accidental = if spelled.alter >= 0 { "#" } else { "b" };
+        format!(
+            "{}{} {}",
+            spelled.letter,
+            accidental.repeat(spelled.alter.unsigned_abs() as usize),
+            if self.minor { "minor" } else { "major" }
+        )
+    }
+}
+
+/// Seconds each pitch class sounds for, summed over every note outside
+/// channel 10, indexed from C.
+pub fn pitch_class_histogram(sequence: &MidiSequence) -> [f64; 12] {
+    let mut histogram = [0.0; 12];
+    for note in sequence.notes() {
+        if note.channel != DRUM_CHANNEL {
+            histogram[(note.key % 12) as usize] += note.duration_ns as f64 / 1e9;
+        }
+    }
+    histogram
+}
+
+fn correlation(histogram: &[f64; 12], profile: &[f64; 12], tonic: usize) -> f64 {
+    let mean_h = histogram.iter().sum::<f64>() / 12.0;
+    let mean_p = profile.iter().sum::<f64>() / 12.0;
+    let (mut covariance, mut var_h, mut var_p) = (0.0, 0.0, 0.0);
+    for pitch_class in 0..12 {
+        let h = histogram[pitch_class] - mean_h;
+        let p = profile[(pitch_class + 12 - tonic) % 12] - mean_p;
+        covariance += h * p;
+        var_h += h * h;
+        var_p += p * p;
+    }
+    covariance / (var_h * var_p).sqrt()
+}
+
+/// Picks the major or minor key whose Krumhansl-Schmuckler profile best
+/// matches `histogram`. None when the histogram is flat (or empty), as no
+/// key stands out.
+pub fn key_from_histogram(histogram: &[f64; 12]) -> Option<KeyEstimate> {
+    let mut scores: Vec<(u8, bool, f64)> = (0..12u8)
+        .flat_map(|tonic| {
+            [(false, &MAJOR_PROFILE), (true, &MINOR_PROFILE)].map(|(minor, profile)| {
+                (
+                    tonic,
+                    minor,
+                    correlation(histogram, profile, tonic as usize),
+                )
+            })
+        })
+        .collect();
+    if scores.iter().any(|&(_, _, score)| score.is_nan()) {
+        return None;
+    }
+    scores.sort_by(|a, b| b.2.total_cmp(&a.2));
+    let (tonic, minor, confidence) = scores[0];
+    Some(KeyEstimate {
+        tonic,
+        minor,
+        confidence,
+        runner_up: scores[1],
+    })
+}
+
+/// Estimates the song's key from how long each pitch class sounds; see
+/// `pitch_class_histogram` and `key_from_histogram`. A single estimate for
+/// the whole song, so modulations blur it.
+pub fn estimate_key(sequence: &MidiSequence) -> Option<KeyEstimate> {
+    key_from_histogram(&pitch_class_histogram(sequence))
+}
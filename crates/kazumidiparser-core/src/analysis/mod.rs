@@ -0,0 +1,13 @@
+mod aftertouch;
+mod chords;
+mod curves;
+mod key;
+mod nps;
+mod stats;
+
+pub use aftertouch::{NotePressure, PressurePoint, poly_pressure_curves};
+pub use chords::{Chord, ChordQuality, detect_chords};
+pub use curves::{CurveSource, SampleGrid, SampledCurve, sample_curve, sample_curves};
+pub use key::{KeyEstimate, estimate_key, key_from_histogram, pitch_class_histogram};
+pub use nps::{DensestWindow, NpsProfile, nps_profile};
+pub use stats::{EventStats, Statistics, statistics};
@@ -0,0 +1,97 @@
+use crate::MidiSequence;
+
+const SECOND_NS: u64 = 1_000_000_000;
+
+/// Note ons (velocity above 0) per second over a song.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NpsProfile {
+    pub bucket_ns: u64,
+    // Note ons in each bucket; bucket `i` covers
+    // `i * bucket_ns <= absolute_ns < (i + 1) * bucket_ns`.
+    pub counts: Vec<u32>,
+    pub note_count: usize,
+    /// The busiest bucket, scaled to notes per second.
+    pub peak_nps: f64,
+    /// All notes over the song's length (`MidiSequence::end_ns`).
+    pub average_nps: f64,
+    /// The one-second span holding the most note ons, wherever it starts.
+    pub densest_window: Option<DensestWindow>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DensestWindow {
+    // Time of the first note on in the window, which runs one second from
+    // there.
+    pub start_ns: u64,
+    pub note_count: usize,
+}
+
+impl NpsProfile {
+    /// Notes per second in bucket `index`.
+    pub fn nps(&self, index: usize) -> f64 {
+        self.counts.get(index).map_or(0.0, |&count| {
+            count as f64 * SECOND_NS as f64 / self.bucket_ns as f64
+        })
+    }
+
+    /// `(bucket start ns, notes per second)` for every bucket.
+    pub fn points(&self) -> impl Iterator<Item = (u64, f64)> + '_ {
+        (0..self.counts.len()).map(|i| (i as u64 * self.bucket_ns, self.nps(i)))
+    }
+}
+
+/// Bins note ons into buckets of `bucket_ns` and finds the densest
+/// one-second window. One-second buckets give the figure usually quoted;
+/// shorter ones show bursts inside a second.
+///
+/// # Panics
+///
+/// If `bucket_ns` is 0.
+pub fn nps_profile(sequence: &MidiSequence, bucket_ns: u64) -> NpsProfile {
+    assert!(bucket_ns > 0, "bucket length must be non-zero");
+
+    let note_times: Vec<u64> = sequence
+        .events()
+        .iter()
+        .filter(|e| e.status & 0xF0 == 0x90 && e.data2 > 0)
+        .map(|e| e.absolute_ns)
+        .collect();
+
+    let mut counts = Vec::new();
+    if let Some(&last) = note_times.last() {
+        counts = vec![0u32; (last / bucket_ns) as usize + 1];
+        for &ns in &note_times {
+            counts[(ns / bucket_ns) as usize] += 1;
+        }
+    }
+
+    // Note times are in order, so a window is a run between two indices.
+    let mut densest_window: Option<DensestWindow> = None;
+    let mut end = 0;
+    for (start, &start_ns) in note_times.iter().enumerate() {
+        while end < note_times.len() && note_times[end] - start_ns < SECOND_NS {
+            end += 1;
+        }
+        if densest_window.is_none_or(|w| end - start > w.note_count) {
+            densest_window = Some(DensestWindow {
+                start_ns,
+                note_count: end - start,
+            });
+        }
+    }
+
+    let peak = counts.iter().copied().max().unwrap_or(0);
+    let end_ns = sequence.end_ns();
+    NpsProfile {
+        bucket_ns,
+        note_count: note_times.len(),
+        peak_nps: peak as f64 * SECOND_NS as f64 / bucket_ns as f64,
+        average_nps: if end_ns > 0 {
+            note_times.len() as f64 * SECOND_NS as f64 / end_ns as f64
+        } else {
+            0.0
+        },
+        densest_window,
+        counts,
+    }
+}
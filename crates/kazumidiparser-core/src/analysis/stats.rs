@@ -0,0 +1,111 @@
+use crate::{MidiEvent, MidiSequence};
+
+/// Counts and ranges over one slice of a song's events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventStats {
+    pub event_count: usize,
+    // Note ons with a velocity above 0.
+    pub note_count: usize,
+    // Control changes, by controller number.
+    pub cc_counts: [usize; 128],
+    pub lowest_key: Option<u8>,
+    pub highest_key: Option<u8>,
+    pub min_velocity: Option<u8>,
+    pub max_velocity: Option<u8>,
+    // Of every counted note on, for `average_velocity`.
+    pub velocity_sum: u64,
+    pub first_event_ns: Option<u64>,
+    pub last_event_ns: Option<u64>,
+    pub first_event_tick: Option<u64>,
+    pub last_event_tick: Option<u64>,
+}
+
+impl Default for EventStats {
+    fn default() -> Self {
+        EventStats {
+            event_count: 0,
+            note_count: 0,
+            cc_counts: [0; 128],
+            lowest_key: None,
+            highest_key: None,
+            min_velocity: None,
+            max_velocity: None,
+            velocity_sum: 0,
+            first_event_ns: None,
+            last_event_ns: None,
+            first_event_tick: None,
+            last_event_tick: None,
+        }
+    }
+}
+
+impl EventStats {
+    pub fn control_change_count(&self) -> usize {
+        self.cc_counts.iter().sum()
+    }
+
+    pub fn average_velocity(&self) -> Option<f64> {
+        (self.note_count > 0).then(|| self.velocity_sum as f64 / self.note_count as f64)
+    }
+
+    // Events arrive in time order, so the first one seen stays first.
+    fn observe(&mut self, event: &MidiEvent) {
+        self.event_count += 1;
+        self.first_event_ns.get_or_insert(event.absolute_ns);
+        self.first_event_tick.get_or_insert(event.absolute_tick);
+        self.last_event_ns = Some(event.absolute_ns);
+        self.last_event_tick = Some(event.absolute_tick);
+
+        match event.status & 0xF0 {
+            0x90 if event.data2 > 0 => {
+                let (key, velocity) = (event.data1, event.data2);
+                self.note_count += 1;
+                self.velocity_sum += velocity as u64;
+                self.lowest_key = Some(self.lowest_key.map_or(key, |k| k.min(key)));
+                self.highest_key = Some(self.highest_key.map_or(key, |k| k.max(key)));
+                self.min_velocity = Some(self.min_velocity.map_or(velocity, |v| v.min(velocity)));
+                self.max_velocity = Some(self.max_velocity.map_or(velocity, |v| v.max(velocity)));
+            }
+            0xB0 => self.cc_counts[(event.data1 & 0x7F) as usize] += 1,
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statistics {
+    // Every event, SysEx included.
+    pub total: EventStats,
+    // By track index; SysEx counts toward its track.
+    pub tracks: Vec<EventStats>,
+    // By 0-based channel; only channel messages.
+    pub channels: [EventStats; 16],
+}
+
+impl Statistics {
+    /// The channels that carry at least one event, 0-based and ascending.
+    pub fn used_channels(&self) -> impl Iterator<Item = u8> + '_ {
+        (0..16u8).filter(|&channel| self.channels[channel as usize].event_count > 0)
+    }
+}
+
+/// Counts notes and controllers and finds key, velocity and time ranges for
+/// the whole song, each track and each channel, in one pass over the events.
+/// Metas are not events here and are not counted.
+pub fn statistics(sequence: &MidiSequence) -> Statistics {
+    let mut statistics = Statistics {
+        total: EventStats::default(),
+        tracks: vec![EventStats::default(); sequence.header().tracks as usize],
+        channels: std::array::from_fn(|_| EventStats::default()),
+    };
+    for event in sequence.events() {
+        statistics.total.observe(event);
+        if let Some(track) = statistics.tracks.get_mut(event.track_index as usize) {
+            track.observe(event);
+        }
+        if event.status < 0xF0 {
+            statistics.channels[(event.status & 0x0F) as usize].observe(event);
+        }
+    }
+    statistics
+}
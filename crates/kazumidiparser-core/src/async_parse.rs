@@ -0,0 +1,82 @@
+use std::io::{self, Read};
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::runtime::Handle;
+
+use crate::{MidiParser, ParseError, ParsedMidi, ParserBuilder};
+
+// Lets the blocking parse read an async stream, waiting on the runtime for
+// each read.
+struct BlockingReader<R> {
+    reader: R,
+    handle: Handle,
+}
+
+impl<R: AsyncRead + Unpin> Read for BlockingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.handle.block_on(self.reader.read(buf))
+    }
+}
+
+impl MidiParser {
+    /// `parse_file` for async code: the file is read with tokio and parsed on
+    /// the blocking pool, so no runtime worker waits on the parse. Must be
+    /// awaited inside a tokio runtime. `memory_map` is ignored.
+    ///
+    /// Dropping the future before it finishes leaves the parser empty.
+    pub async fn parse_file_async(&mut self, file_path: &str) -> Result<(), ParseError> {
+        let data = tokio::fs::read(file_path).await?;
+        self.parse_blocking(move |parser| parser.parse_bytes(&data))
+            .await
+    }
+
+    /// `parse_reader` for async code. The stream is read as the parse goes, so
+    /// as with `parse_reader` nothing after the last track chunk is read.
+    pub async fn parse_reader_async<R>(&mut self, reader: R) -> Result<(), ParseError>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        let handle = Handle::current();
+        self.parse_blocking(move |parser| parser.parse_reader(BlockingReader { reader, handle }))
+            .await
+    }
+
+    // Moves the parser onto the blocking pool for `parse` and back.
+    async fn parse_blocking<F>(&mut self, parse: F) -> Result<(), ParseError>
+    where
+        F: FnOnce(&mut MidiParser) -> Result<(), ParseError> + Send + 'static,
+    {
+        let empty = MidiParser::with_options(self.options().clone());
+        let mut parser = std::mem::replace(self, empty);
+        let job = tokio::task::spawn_blocking(move || {
+            let result = parse(&mut parser);
+            (parser, result)
+        });
+        match job.await {
+            Ok((parser, result)) => {
+                *self = parser;
+                result
+            }
+            Err(error) if error.is_panic() => std::panic::resume_unwind(error.into_panic()),
+            // The runtime shut down before the parse ran.
+            Err(_) => Err(ParseError::Cancelled),
+        }
+    }
+}
+
+impl ParserBuilder {
+    pub async fn parse_file_async(self, file_path: &str) -> Result<ParsedMidi, ParseError> {
+        let mut parser = self.build_one_shot();
+        parser.parse_file_async(file_path).await?;
+        Ok(parser.into_parsed())
+    }
+
+    pub async fn parse_reader_async<R>(self, reader: R) -> Result<ParsedMidi, ParseError>
+    where
+        R: AsyncRead + Unpin + Send + 'static,
+    {
+        let mut parser = self.build_one_shot();
+        parser.parse_reader_async(reader).await?;
+        Ok(parser.into_parsed())
+    }
+}
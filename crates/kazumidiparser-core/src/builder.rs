@@ -0,0 +1,151 @@
+use std::io::Read;
+use std::ops::{Deref, RangeInclusive};
+
+use crate::{
+    CancelToken, EventMask, EventOrder, MidiParser, MidiSequence, ParseError, ParseMetrics,
+    ParseOptions, TextEncoding,
+};
+
+/// Sets up `ParseOptions` one at a time and runs a parse that returns its
+/// result, rather than leaving it in a `MidiParser`:
+/// `MidiParser::builder().strict(false).parse_file(path)`.
+#[derive(Debug, Clone, Default)]
+pub struct ParserBuilder {
+    options: ParseOptions,
+}
+
+impl ParserBuilder {
+    pub fn fixed_bpm(mut self, bpm: f64) -> ParserBuilder {
+        self.options.fixed_bpm = Some(bpm);
+        self
+    }
+
+    pub fn cancel_token(mut self, token: CancelToken) -> ParserBuilder {
+        self.options.cancel_token = Some(token);
+        self
+    }
+
+    pub fn lenient_running_status(mut self, lenient: bool) -> ParserBuilder {
+        self.options.lenient_running_status = lenient;
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> ParserBuilder {
+        self.options.strict = strict;
+        self
+    }
+
+    pub fn track_filter(mut self, tracks: RangeInclusive<u16>) -> ParserBuilder {
+        self.options.track_filter = Some(tracks);
+        self
+    }
+
+    pub fn channel_filter(mut self, channels: u16) -> ParserBuilder {
+        self.options.channel_filter = Some(channels);
+        self
+    }
+
+    pub fn event_mask(mut self, mask: EventMask) -> ParserBuilder {
+        self.options.event_mask = mask;
+        self
+    }
+
+    pub fn event_order(mut self, order: EventOrder) -> ParserBuilder {
+        self.options.event_order = order;
+        self
+    }
+
+    pub fn exact_timing(mut self, exact: bool) -> ParserBuilder {
+        self.options.exact_timing = exact;
+        self
+    }
+
+    pub fn text_encoding(mut self, encoding: TextEncoding) -> ParserBuilder {
+        self.options.text_encoding = encoding;
+        self
+    }
+
+    #[cfg(feature = "mmap")]
+    pub fn memory_map(mut self, memory_map: bool) -> ParserBuilder {
+        self.options.memory_map = memory_map;
+        self
+    }
+
+    pub fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+
+    /// A reusable parser with these options, for the stateful API.
+    pub fn build(self) -> MidiParser {
+        MidiParser::with_options(self.options)
+    }
+
+    // A parser whose sequence is taken with `into_parsed` as soon as it is
+    // parsed, so it skips the deprecated `events` copy.
+    pub(crate) fn build_one_shot(self) -> MidiParser {
+        self.build().without_events_copy()
+    }
+
+    pub fn parse_file(self, file_path: &str) -> Result<ParsedMidi, ParseError> {
+        let mut parser = self.build_one_shot();
+        parser.parse_file(file_path)?;
+        Ok(parser.into_parsed())
+    }
+
+    pub fn parse_bytes(self, data: &[u8]) -> Result<ParsedMidi, ParseError> {
+        let mut parser = self.build_one_shot();
+        parser.parse_bytes(data)?;
+        Ok(parser.into_parsed())
+    }
+
+    pub fn parse_reader<R: Read + Send>(self, reader: R) -> Result<ParsedMidi, ParseError> {
+        let mut parser = self.build_one_shot();
+        parser.parse_reader(reader)?;
+        Ok(parser.into_parsed())
+    }
+}
+
+/// A finished parse: the sequence and how long it took. Derefs to the
+/// `MidiSequence`, which cannot be changed through it.
+#[derive(Debug, Clone)]
+pub struct ParsedMidi {
+    sequence: MidiSequence,
+    metrics: ParseMetrics,
+}
+
+impl ParsedMidi {
+    pub fn sequence(&self) -> &MidiSequence {
+        &self.sequence
+    }
+
+    pub fn metrics(&self) -> &ParseMetrics {
+        &self.metrics
+    }
+
+    pub fn into_sequence(self) -> MidiSequence {
+        self.sequence
+    }
+}
+
+impl Deref for ParsedMidi {
+    type Target = MidiSequence;
+
+    fn deref(&self) -> &MidiSequence {
+        &self.sequence
+    }
+}
+
+impl MidiParser {
+    pub fn builder() -> ParserBuilder {
+        ParserBuilder::default()
+    }
+
+    // Only called after a successful parse.
+    pub(crate) fn into_parsed(self) -> ParsedMidi {
+        let metrics = self.metrics().cloned().unwrap_or_default();
+        ParsedMidi {
+            sequence: self.into_sequence().expect("the parse succeeded"),
+            metrics,
+        }
+    }
+}
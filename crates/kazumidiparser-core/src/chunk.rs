@@ -0,0 +1,330 @@
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+use crate::decode::TrackDecoder;
+use crate::{MidiHeader, ParseError, rmid};
+
+// Also reads through the RIFF wrapper of an RMID file to the SMF in it.
+pub(crate) fn read_header<R: Read>(reader: &mut R) -> Result<MidiHeader, ParseError> {
+    let mut buffer32 = [0; 4];
+
+    reader.read_exact(&mut buffer32)?;
+    if buffer32 == rmid::RIFF {
+        rmid::skip_to_smf(reader)?;
+        reader.read_exact(&mut buffer32)?;
+    }
+    if buffer32 != *b"MThd" {
+        return Err(ParseError::InvalidHeader);
+    }
+
+    reader.read_exact(&mut buffer32)?;
+    let header_length = u32::from_be_bytes(buffer32);
+    if header_length != 6 {
+        return Err(ParseError::UnexpectedHeaderLength(header_length));
+    }
+
+    let mut header_data = [0; 6];
+    reader.read_exact(&mut header_data)?;
+
+    Ok(MidiHeader {
+        format: u16::from_be_bytes([header_data[0], header_data[1]]),
+        tracks: u16::from_be_bytes([header_data[2], header_data[3]]),
+        ppqn: u16::from_be_bytes([header_data[4], header_data[5]]),
+    })
+}
+
+// Reads the `MTrk` chunk header found at file offset `offset` and returns the
+// declared body length.
+pub(crate) fn read_track_header<R: Read>(
+    reader: &mut R,
+    track_index: u16,
+    offset: u64,
+) -> Result<u32, ParseError> {
+    let mut buffer32 = [0; 4];
+    reader
+        .read_exact(&mut buffer32)
+        .map_err(|e| ParseError::in_track(e, track_index, offset))?;
+    if buffer32 != *b"MTrk" {
+        return Err(ParseError::UnexpectedChunk {
+            track: track_index,
+            offset,
+            found: buffer32,
+        });
+    }
+
+    reader
+        .read_exact(&mut buffer32)
+        .map_err(|e| ParseError::in_track(e, track_index, offset))?;
+    Ok(u32::from_be_bytes(buffer32))
+}
+
+pub(crate) fn read_track_chunk<R: Read>(
+    reader: &mut R,
+    track_index: u16,
+    offset: u64,
+    track_data: &mut Vec<u8>,
+) -> Result<(), ParseError> {
+    let track_length = read_track_header(reader, track_index, offset)?;
+    track_data.clear();
+    track_data.resize(track_length as usize, 0);
+    reader
+        .read_exact(track_data)
+        .map_err(|e| ParseError::in_track(e, track_index, offset))?;
+    Ok(())
+}
+
+/// A track whose `MTrk` length field disagrees with where its data ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TrackLengthMismatch {
+    // File offset of the `MTrk` magic.
+    pub chunk_offset: u64,
+    pub declared_length: u32,
+    pub actual_length: u32,
+    // The following chunk was found by scanning for the next `MTrk` magic.
+    pub resynced: bool,
+}
+
+pub(crate) struct TrackChunk {
+    pub(crate) start: usize,
+    pub(crate) end: usize,
+    pub(crate) mismatch: Option<TrackLengthMismatch>,
+}
+
+fn find_mtrk(data: &[u8], from: usize) -> Option<usize> {
+    data.get(from..)?
+        .windows(4)
+        .position(|w| w == b"MTrk")
+        .map(|p| from + p)
+}
+
+fn end_of_track_at(data: &[u8]) -> Option<usize> {
+    // Lenient, so a sloppy track is still measured up to its real end.
+    let mut decoder = TrackDecoder::new(data).lenient_running_status(true);
+    while let Some(Ok(event)) = decoder.next() {
+        if event.kind.is_end_of_track() {
+            return Some(decoder.position());
+        }
+    }
+    None
+}
+
+/// A track body read out of a file by `TrackChunkReader`.
+pub(crate) struct OwnedTrackChunk {
+    pub(crate) track_index: u16,
+    pub(crate) data: Vec<u8>,
+    // File offset of `data`.
+    pub(crate) data_offset: u64,
+    pub(crate) mismatch: Option<TrackLengthMismatch>,
+}
+
+/// Reads track chunks one at a time, so each can be parsed while the next
+/// one is still coming off the disk.
+///
+/// Only as much of the file as the current chunk needs is buffered. A chunk
+/// whose length field cannot be trusted makes the reader pull in the rest of
+/// the file and locate it exactly like `locate_track_chunk` on the whole
+/// image.
+pub(crate) struct TrackChunkReader<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    start: usize,
+    // File offset of `buffer[start]`.
+    offset: u64,
+    eof: bool,
+    next_track: u16,
+    tracks: u16,
+    pub(crate) read_time: Duration,
+    pub(crate) locate_time: Duration,
+    // Everything pulled out of `reader` so far.
+    pub(crate) bytes_read: u64,
+}
+
+impl<R: Read> TrackChunkReader<R> {
+    /// `offset` is the file position `reader` is at, right after the header
+    /// chunk.
+    pub(crate) fn new(reader: R, offset: u64, tracks: u16) -> TrackChunkReader<R> {
+        TrackChunkReader {
+            reader,
+            buffer: Vec::new(),
+            start: 0,
+            offset,
+            eof: false,
+            next_track: 0,
+            tracks,
+            read_time: Duration::ZERO,
+            locate_time: Duration::ZERO,
+            bytes_read: 0,
+        }
+    }
+
+    // Buffers at least `len` bytes past the current chunk, or everything up
+    // to the end of the file.
+    fn fill(&mut self, len: usize) -> std::io::Result<()> {
+        let buffered = self.buffer.len() - self.start;
+        if self.eof || buffered >= len {
+            return Ok(());
+        }
+        let started = Instant::now();
+        self.buffer.drain(..self.start);
+        self.start = 0;
+        let want = (len - buffered) as u64;
+        let read = (&mut self.reader)
+            .take(want)
+            .read_to_end(&mut self.buffer)?;
+        self.eof = (read as u64) < want;
+        self.bytes_read += read as u64;
+        self.read_time += started.elapsed();
+        Ok(())
+    }
+
+    fn fill_to_end(&mut self) -> std::io::Result<()> {
+        self.fill(usize::MAX)
+    }
+
+    fn locate(&mut self, track_index: u16) -> Result<(TrackChunk, usize), ParseError> {
+        let started = Instant::now();
+        let is_last = track_index + 1 == self.tracks;
+        let located = locate_track_chunk(&self.buffer[self.start..], 0, track_index, is_last);
+        self.locate_time += started.elapsed();
+        // The buffer starts at `self.offset`, not at the start of the file.
+        located.map_err(|error| match error {
+            ParseError::UnexpectedChunk {
+                track,
+                offset,
+                found,
+            } => ParseError::UnexpectedChunk {
+                track,
+                offset: offset + self.offset,
+                found,
+            },
+            ParseError::TruncatedTrack { track, offset } => ParseError::TruncatedTrack {
+                track,
+                offset: offset + self.offset,
+            },
+            error => error,
+        })
+    }
+
+    pub(crate) fn next_chunk(&mut self) -> Result<Option<OwnedTrackChunk>, ParseError> {
+        let track_index = self.next_track;
+        if track_index == self.tracks {
+            return Ok(None);
+        }
+
+        self.fill(8)?;
+        let declared_length = self.buffer[self.start..]
+            .get(4..8)
+            .map_or(0, |b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+        // The header, the body and the next chunk's magic, which is what
+        // `locate_track_chunk` looks at to trust the declared length.
+        self.fill(8 + declared_length as usize + 4)?;
+        let (mut chunk, mut next) = self.locate(track_index)?;
+        if chunk.mismatch.is_some() && !self.eof {
+            self.fill_to_end()?;
+            (chunk, next) = self.locate(track_index)?;
+        }
+
+        let mut mismatch = chunk.mismatch;
+        if let Some(mismatch) = &mut mismatch {
+            mismatch.chunk_offset += self.offset;
+        }
+        let data_offset = self.offset + chunk.start as u64;
+        let (body_start, body_end, rest) = (
+            self.start + chunk.start,
+            self.start + chunk.end,
+            self.start + next,
+        );
+        // Hand the buffer itself over as the track and keep what follows it,
+        // unless that is the bigger part (after `fill_to_end`).
+        let data = if self.buffer.len() - rest < body_end - body_start {
+            let following = self.buffer.split_off(rest);
+            let mut data = std::mem::replace(&mut self.buffer, following);
+            data.truncate(body_end);
+            data.drain(..body_start);
+            self.start = 0;
+            data
+        } else {
+            let data = self.buffer[body_start..body_end].to_vec();
+            self.start = rest;
+            data
+        };
+        self.offset += next as u64;
+        self.next_track += 1;
+        Ok(Some(OwnedTrackChunk {
+            track_index,
+            data,
+            data_offset,
+            mismatch,
+        }))
+    }
+}
+
+/// Locates the track chunk at `pos` and returns it with the offset of the
+/// chunk that follows.
+///
+/// The declared chunk length is trusted when it ends on an End of Track
+/// event followed by the next `MTrk` (or the end of the file). Otherwise the
+/// End of Track marker decides, and when neither lines up with an `MTrk`
+/// the next chunk is found by scanning for its magic.
+pub(crate) fn locate_track_chunk(
+    data: &[u8],
+    pos: usize,
+    track_index: u16,
+    is_last: bool,
+) -> Result<(TrackChunk, usize), ParseError> {
+    let next_ok = |p: usize| {
+        p <= data.len() && (is_last || p == data.len() || data[p..].starts_with(b"MTrk"))
+    };
+
+    let declared_length =
+        read_track_header(&mut data.get(pos..).unwrap_or(&[]), track_index, pos as u64)?;
+    let start = pos + 8;
+    let declared_end = start + declared_length as usize;
+
+    let body = data.get(start..declared_end).unwrap_or(&[]);
+    if declared_end <= data.len() && body.ends_with(&[0xFF, 0x2F, 0x00]) && next_ok(declared_end) {
+        let chunk = TrackChunk {
+            start,
+            end: declared_end,
+            mismatch: None,
+        };
+        return Ok((chunk, declared_end));
+    }
+
+    let (end, next, resynced) = match end_of_track_at(&data[start..]) {
+        Some(actual) => {
+            let actual_end = start + actual;
+            if next_ok(declared_end) {
+                (declared_end.min(data.len()), declared_end, false)
+            } else if next_ok(actual_end) {
+                (actual_end, actual_end, false)
+            } else {
+                let next = find_mtrk(data, actual_end).unwrap_or(data.len());
+                (actual_end, next, true)
+            }
+        }
+        None if next_ok(declared_end) => (declared_end, declared_end, false),
+        None if declared_end > data.len() => (data.len(), data.len(), false),
+        None => {
+            let next = find_mtrk(data, start).unwrap_or(data.len());
+            (next, next, true)
+        }
+    };
+
+    let actual_length = end_of_track_at(&data[start..end]).unwrap_or(end - start) as u32;
+    let mismatch = (actual_length != declared_length || resynced).then_some(TrackLengthMismatch {
+        chunk_offset: pos as u64,
+        declared_length,
+        actual_length,
+        resynced,
+    });
+    Ok((
+        TrackChunk {
+            start,
+            end,
+            mismatch,
+        },
+        next,
+    ))
+}
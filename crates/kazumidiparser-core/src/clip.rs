@@ -0,0 +1,548 @@
+//! MIDI 2.0 Clip Files (`SMF2CLIP`): a stream of Universal MIDI Packets
+//! timed by Delta Clockstamps.
+//!
+//! A clip parses into the same `MidiSequence` as a Standard MIDI File, with
+//! each UMP group as a track. MIDI 1.0 channel voice messages are kept as
+//! they are and MIDI 2.0 ones are translated to MIDI 1.0: velocities and
+//! controller values cut down to 7 bits, pitch bend to 14, and bank selects,
+//! RPNs and NRPNs spelled out as control changes. SysEx7 is reassembled, and
+//! tempo, time signature, key signature and text Flex Data become meta
+//! events. The MIDI 2.0 messages themselves are kept at full resolution as
+//! `UmpEvent`s, including the per-note ones MIDI 1.0 has no equivalent for.
+//!
+//! System messages, SysEx8 and Mixed Data Sets are skipped, as is a packet
+//! cut off by the end of the file.
+
+use std::fs;
+
+use crate::tempo::TempoMap;
+use crate::{
+    EventOrder, MetaEvent, MidiEvent, MidiHeader, MidiSequence, ParseError, TextEncoding, TrackMeta,
+};
+
+const MAGIC: &[u8; 8] = b"SMF2CLIP";
+
+// Packet length in 32-bit words, by message type.
+const UMP_WORDS: [usize; 16] = [1, 1, 1, 2, 2, 4, 1, 1, 2, 2, 2, 3, 3, 4, 4, 4];
+
+/// A MIDI 2.0 channel voice message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UmpMessage {
+    NoteOff {
+        note: u8,
+        velocity: u16,
+        attribute_type: u8,
+        attribute: u16,
+    },
+    NoteOn {
+        note: u8,
+        velocity: u16,
+        attribute_type: u8,
+        attribute: u16,
+    },
+    PolyPressure {
+        note: u8,
+        value: u32,
+    },
+    RegisteredPerNoteController {
+        note: u8,
+        index: u8,
+        value: u32,
+    },
+    AssignablePerNoteController {
+        note: u8,
+        index: u8,
+        value: u32,
+    },
+    PerNoteManagement {
+        note: u8,
+        detach: bool,
+        reset: bool,
+    },
+    ControlChange {
+        index: u8,
+        value: u32,
+    },
+    RegisteredController {
+        bank: u8,
+        index: u8,
+        value: u32,
+    },
+    AssignableController {
+        bank: u8,
+        index: u8,
+        value: u32,
+    },
+    RelativeRegisteredController {
+        bank: u8,
+        index: u8,
+        value: i32,
+    },
+    RelativeAssignableController {
+        bank: u8,
+        index: u8,
+        value: i32,
+    },
+    ProgramChange {
+        program: u8,
+        // Bank select MSB and LSB, when the message sets them.
+        bank: Option<(u8, u8)>,
+    },
+    ChannelPressure {
+        value: u32,
+    },
+    PitchBend {
+        value: u32,
+    },
+    PerNotePitchBend {
+        note: u8,
+        value: u32,
+    },
+}
+
+/// A MIDI 2.0 channel voice message from a clip, with its time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UmpEvent {
+    pub absolute_ns: u64,
+    pub absolute_tick: u64,
+    pub group: u8,
+    pub channel: u8,
+    pub message: UmpMessage,
+    // The first of the events in the sequence it was translated to, or None
+    // if MIDI 1.0 has no equivalent.
+    pub event_index: Option<usize>,
+}
+
+/// A parsed clip: the translated sequence and the original MIDI 2.0
+/// messages.
+#[derive(Debug, Clone)]
+pub struct MidiClip {
+    sequence: MidiSequence,
+    ump_events: Vec<UmpEvent>,
+}
+
+impl MidiClip {
+    pub fn sequence(&self) -> &MidiSequence {
+        &self.sequence
+    }
+
+    /// The MIDI 2.0 channel voice messages, in clip order.
+    pub fn ump_events(&self) -> &[UmpEvent] {
+        &self.ump_events
+    }
+
+    pub fn into_sequence(self) -> MidiSequence {
+        self.sequence
+    }
+}
+
+pub fn parse_clip_file(file_path: &str) -> Result<MidiClip, ParseError> {
+    parse_clip(&fs::read(file_path)?)
+}
+
+/// Parses a complete clip file image. Fails with `InvalidHeader` if it does
+/// not start with `SMF2CLIP` or has no usable ticks per quarter note.
+pub fn parse_clip(data: &[u8]) -> Result<MidiClip, ParseError> {
+    let body = data.strip_prefix(MAGIC).ok_or(ParseError::InvalidHeader)?;
+    let mut builder = ClipBuilder::default();
+    let mut pos = 0;
+    while let Some(&first) = body.get(pos) {
+        let length = UMP_WORDS[(first >> 4) as usize] * 4;
+        let Some(packet) = body.get(pos..pos + length) else {
+            break;
+        };
+        let mut words = [0u32; 4];
+        for (word, bytes) in words.iter_mut().zip(packet.chunks_exact(4)) {
+            *word = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+        if !builder.push(words) {
+            break;
+        }
+        pos += length;
+    }
+    builder.finish()
+}
+
+#[derive(Default)]
+struct ClipBuilder {
+    ticks_per_quarter: Option<u16>,
+    // Set at Start of Clip; Delta Clockstamps before it are header data.
+    started: bool,
+    tick: u64,
+    // Bit per group that has something in it.
+    groups: u16,
+    events: Vec<MidiEvent>,
+    sysex: Vec<Vec<u8>>,
+    metas: Vec<MetaEvent>,
+    tempo_changes: Vec<(u64, u32)>,
+    ump_events: Vec<UmpEvent>,
+    // SysEx7 and Flex Data text messages still waiting for their end, per
+    // group.
+    pending_sysex: [Option<Vec<u8>>; 16],
+    pending_text: [Option<(u8, Vec<u8>)>; 16],
+}
+
+impl ClipBuilder {
+    // Returns false at End of Clip.
+    fn push(&mut self, words: [u32; 4]) -> bool {
+        let group = ((words[0] >> 24) & 0x0F) as u8;
+        match words[0] >> 28 {
+            0x0 => self.push_utility(words[0]),
+            0x2 => {
+                let [_, status, data1, data2] = words[0].to_be_bytes();
+                if (0x80..0xF0).contains(&status) {
+                    self.push_event(group, status, data1 & 0x7F, data2 & 0x7F, None);
+                }
+            }
+            0x3 => self.push_sysex7(group, words),
+            0x4 => self.push_midi2(group, words),
+            0xD => self.push_flex_data(group, words),
+            0xF => match (words[0] >> 16) & 0x3FF {
+                0x20 => self.started = true,
+                0x21 => return false,
+                _ => {}
+            },
+            _ => {}
+        }
+        true
+    }
+
+    fn push_utility(&mut self, word: u32) {
+        match (word >> 20) & 0x0F {
+            // Delta Clockstamp Ticks Per Quarter Note.
+            0x3 => {
+                self.ticks_per_quarter.get_or_insert(word as u16);
+            }
+            // Delta Clockstamp.
+            0x4 if self.started => self.tick += (word & 0xF_FFFF) as u64,
+            _ => {}
+        }
+    }
+
+    fn push_event(
+        &mut self,
+        group: u8,
+        status: u8,
+        data1: u8,
+        data2: u8,
+        sysex_index: Option<u32>,
+    ) -> usize {
+        self.groups |= 1 << group;
+        self.events.push(MidiEvent {
+            absolute_ns: 0,
+            absolute_tick: self.tick,
+            status,
+            data1,
+            data2,
+            track_index: group as u16,
+            sysex_index,
+        });
+        self.events.len() - 1
+    }
+
+    fn push_meta(&mut self, group: u8, meta_type: u8, data: Vec<u8>) {
+        self.groups |= 1 << group;
+        self.metas.push(MetaEvent {
+            absolute_ns: 0,
+            absolute_tick: self.tick,
+            track_index: group as u16,
+            meta_type,
+            data,
+        });
+    }
+
+    fn push_sysex7(&mut self, group: u8, words: [u32; 4]) {
+        let [_, kind_count, b0, b1] = words[0].to_be_bytes();
+        let [b2, b3, b4, b5] = words[1].to_be_bytes();
+        let bytes = &[b0, b1, b2, b3, b4, b5][..(kind_count & 0x0F).min(6) as usize];
+        let pending = &mut self.pending_sysex[group as usize];
+        let complete = match kind_count >> 4 {
+            0x0 => Some(bytes.to_vec()),
+            0x1 => {
+                *pending = Some(bytes.to_vec());
+                None
+            }
+            0x2 => {
+                if let Some(data) = pending {
+                    data.extend_from_slice(bytes);
+                }
+                None
+            }
+            0x3 => pending.take().map(|mut data| {
+                data.extend_from_slice(bytes);
+                data
+            }),
+            _ => None,
+        };
+        if let Some(mut data) = complete {
+            // Stored like an SMF SysEx: the bytes after F0, ending in F7.
+            data.push(0xF7);
+            let index = self.sysex.len() as u32;
+            self.sysex.push(data);
+            self.push_event(group, 0xF0, 0, 0, Some(index));
+        }
+    }
+
+    fn push_midi2(&mut self, group: u8, words: [u32; 4]) {
+        let [_, opcode_channel, b1, b0] = words[0].to_be_bytes();
+        let (opcode, channel) = (opcode_channel >> 4, opcode_channel & 0x0F);
+        let value = words[1];
+        let note = b1 & 0x7F;
+        let (msb7, v14) = ((value >> 25) as u8, value >> 18);
+        let message = match opcode {
+            0x0 => UmpMessage::RegisteredPerNoteController {
+                note,
+                index: b0,
+                value,
+            },
+            0x1 => UmpMessage::AssignablePerNoteController {
+                note,
+                index: b0,
+                value,
+            },
+            0x2 => UmpMessage::RegisteredController {
+                bank: b1 & 0x7F,
+                index: b0 & 0x7F,
+                value,
+            },
+            0x3 => UmpMessage::AssignableController {
+                bank: b1 & 0x7F,
+                index: b0 & 0x7F,
+                value,
+            },
+            0x4 => UmpMessage::RelativeRegisteredController {
+                bank: b1 & 0x7F,
+                index: b0 & 0x7F,
+                value: value as i32,
+            },
+            0x5 => UmpMessage::RelativeAssignableController {
+                bank: b1 & 0x7F,
+                index: b0 & 0x7F,
+                value: value as i32,
+            },
+            0x6 => UmpMessage::PerNotePitchBend { note, value },
+            0x8 => UmpMessage::NoteOff {
+                note,
+                velocity: (value >> 16) as u16,
+                attribute_type: b0,
+                attribute: value as u16,
+            },
+            0x9 => UmpMessage::NoteOn {
+                note,
+                velocity: (value >> 16) as u16,
+                attribute_type: b0,
+                attribute: value as u16,
+            },
+            0xA => UmpMessage::PolyPressure { note, value },
+            0xB => UmpMessage::ControlChange {
+                index: b1 & 0x7F,
+                value,
+            },
+            0xC => UmpMessage::ProgramChange {
+                program: (value >> 24) as u8 & 0x7F,
+                bank: (b0 & 1 == 1).then_some(((value >> 8) as u8 & 0x7F, value as u8 & 0x7F)),
+            },
+            0xD => UmpMessage::ChannelPressure { value },
+            0xE => UmpMessage::PitchBend { value },
+            0xF => UmpMessage::PerNoteManagement {
+                note,
+                detach: b0 & 0x02 != 0,
+                reset: b0 & 0x01 != 0,
+            },
+            _ => return,
+        };
+
+        // The MIDI 1.0 translation.
+        let cc = 0xB0 | channel;
+        let mut translated: Vec<(u8, u8, u8)> = Vec::new();
+        match message {
+            UmpMessage::NoteOff { velocity, .. } => {
+                translated.push((0x80 | channel, note, (velocity >> 9) as u8));
+            }
+            // A velocity 0 note on is still a note on in MIDI 2.0.
+            UmpMessage::NoteOn { velocity, .. } => {
+                translated.push((0x90 | channel, note, ((velocity >> 9) as u8).max(1)));
+            }
+            UmpMessage::PolyPressure { .. } => translated.push((0xA0 | channel, note, msb7)),
+            UmpMessage::ControlChange { index, .. } => translated.push((cc, index, msb7)),
+            UmpMessage::RegisteredController { bank, index, .. } => translated.extend([
+                (cc, 101, bank),
+                (cc, 100, index),
+                (cc, 6, (v14 >> 7) as u8),
+                (cc, 38, v14 as u8 & 0x7F),
+            ]),
+            UmpMessage::AssignableController { bank, index, .. } => translated.extend([
+                (cc, 99, bank),
+                (cc, 98, index),
+                (cc, 6, (v14 >> 7) as u8),
+                (cc, 38, v14 as u8 & 0x7F),
+            ]),
+            UmpMessage::ProgramChange { program, bank } => {
+                if let Some((msb, lsb)) = bank {
+                    translated.extend([(cc, 0, msb), (cc, 32, lsb)]);
+                }
+                translated.push((0xC0 | channel, program, 0));
+            }
+            UmpMessage::ChannelPressure { .. } => translated.push((0xD0 | channel, msb7, 0)),
+            UmpMessage::PitchBend { .. } => {
+                translated.push((0xE0 | channel, v14 as u8 & 0x7F, (v14 >> 7) as u8));
+            }
+            _ => {}
+        }
+
+        let mut event_index = None;
+        for (status, data1, data2) in translated {
+            let index = self.push_event(group, status, data1, data2, None);
+            event_index.get_or_insert(index);
+        }
+        self.groups |= 1 << group;
+        self.ump_events.push(UmpEvent {
+            absolute_ns: 0,
+            absolute_tick: self.tick,
+            group,
+            channel,
+            message,
+            event_index,
+        });
+    }
+
+    fn push_flex_data(&mut self, group: u8, words: [u32; 4]) {
+        let form = (words[0] >> 22) & 0x03;
+        let [_, _, bank, status] = words[0].to_be_bytes();
+        let value = words[1];
+        match (bank, status) {
+            // Set Tempo, in 10 ns units per quarter note.
+            (0x00, 0x00) => {
+                let tempo_us = (value / 100).clamp(1, 0xFF_FFFF);
+                self.tempo_changes.push((self.tick, tempo_us));
+                self.push_meta(group, 0x51, tempo_us.to_be_bytes()[1..].to_vec());
+            }
+            // Set Time Signature: numerator, denominator as a power of 2 and
+            // 32nd notes per beat, as in the SMF meta.
+            (0x00, 0x01) => {
+                let [numerator, denominator_log2, thirty_seconds, _] = value.to_be_bytes();
+                let thirty_seconds = if thirty_seconds == 0 {
+                    8
+                } else {
+                    thirty_seconds
+                };
+                self.push_meta(
+                    group,
+                    0x58,
+                    vec![numerator, denominator_log2, 24, thirty_seconds],
+                );
+            }
+            // Set Key Signature: signed sharps or flats, then the tonic.
+            (0x00, 0x05) => {
+                let sharps_flats = (value >> 24) as u8 as i8 >> 4;
+                let tonic = (value >> 24) as u8 & 0x0F;
+                // -8 means the key is unknown or not a standard one.
+                if sharps_flats != -8 {
+                    let minor = u8::from(tonic != 0 && tonic == minor_tonic(sharps_flats));
+                    self.push_meta(group, 0x59, vec![sharps_flats as u8, minor]);
+                }
+            }
+            // Metadata and performance text.
+            (0x01 | 0x02, _) => {
+                let meta_type = match (bank, status) {
+                    (0x01, 0x02 | 0x03) => 0x03,
+                    (0x01, 0x04) => 0x02,
+                    (0x02, 0x01) => 0x05,
+                    _ => 0x01,
+                };
+                let mut bytes: Vec<u8> = words[1..].iter().flat_map(|w| w.to_be_bytes()).collect();
+                while bytes.last() == Some(&0) {
+                    bytes.pop();
+                }
+                let pending = &mut self.pending_text[group as usize];
+                let complete = match form {
+                    0x0 => Some(bytes),
+                    0x1 => {
+                        *pending = Some((meta_type, bytes));
+                        None
+                    }
+                    0x2 => {
+                        if let Some((_, text)) = pending {
+                            text.extend(bytes);
+                        }
+                        None
+                    }
+                    _ => pending.take().map(|(_, mut text)| {
+                        text.extend(bytes);
+                        text
+                    }),
+                };
+                if let Some(text) = complete {
+                    self.push_meta(group, meta_type, text);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(self) -> Result<MidiClip, ParseError> {
+        let ppqn = match self.ticks_per_quarter {
+            // Larger values would read as an SMPTE division.
+            Some(ppqn) if ppqn > 0 && ppqn < 0x8000 => ppqn,
+            _ => return Err(ParseError::InvalidHeader),
+        };
+        let tracks = (16 - self.groups.leading_zeros() as u16).max(1);
+        let header = MidiHeader {
+            format: if tracks == 1 { 0 } else { 1 },
+            tracks,
+            ppqn,
+        };
+        let tempo_map = TempoMap::build(ppqn, self.tempo_changes, false);
+
+        let mut events = self.events;
+        for event in &mut events {
+            event.absolute_ns = tempo_map.tick_to_ns(event.absolute_tick);
+        }
+        let mut metas = self.metas;
+        for meta in &mut metas {
+            meta.absolute_ns = tempo_map.tick_to_ns(meta.absolute_tick);
+        }
+        let mut ump_events = self.ump_events;
+        for event in &mut ump_events {
+            event.absolute_ns = tempo_map.tick_to_ns(event.absolute_tick);
+        }
+        let track_metas = (0..tracks)
+            .map(|track_index| TrackMeta {
+                name: metas
+                    .iter()
+                    .find(|m| m.track_index == track_index && m.meta_type == 0x03)
+                    .map(|m| m.data.clone()),
+                // Every group ends with the clip.
+                end_tick: self.tick,
+                ..Default::default()
+            })
+            .collect();
+
+        Ok(MidiClip {
+            sequence: MidiSequence {
+                header,
+                events,
+                sysex: self.sysex,
+                metas,
+                tempo_map,
+                track_tempo_maps: Vec::new(),
+                track_metas,
+                warnings: Vec::new(),
+                sound_bank: None,
+                // Clip text is UTF-8.
+                text_encoding: TextEncoding::Utf8,
+                event_order: EventOrder::Track,
+            },
+            ump_events,
+        })
+    }
+}
+
+// The tonic of the minor key with this many sharps (or flats, if negative),
+// numbered as Flex Data does: A = 1 to G = 7.
+fn minor_tonic(sharps_flats: i8) -> u8 {
+    // C major's relative minor is A; each sharp moves up a fifth.
+    (sharps_flats as i32 * 4).rem_euclid(7) as u8 + 1
+}
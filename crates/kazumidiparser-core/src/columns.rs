@@ -0,0 +1,52 @@
+use crate::{MidiEvent, MidiSequence};
+
+/// The event list as one array per field, for loops that only read a few
+/// of them: a piano roll touching timestamps and keys streams through 9
+/// bytes per event instead of the whole `MidiEvent`. SysEx payloads are not
+/// included; index `events()` for those.
+#[derive(Debug, Clone, Default)]
+pub struct EventColumns {
+    pub timestamps: Vec<u64>,
+    pub ticks: Vec<u64>,
+    pub status: Vec<u8>,
+    pub data1: Vec<u8>,
+    pub data2: Vec<u8>,
+    pub track: Vec<u16>,
+}
+
+impl EventColumns {
+    pub fn from_events(events: &[MidiEvent]) -> EventColumns {
+        let mut columns = EventColumns {
+            timestamps: Vec::with_capacity(events.len()),
+            ticks: Vec::with_capacity(events.len()),
+            status: Vec::with_capacity(events.len()),
+            data1: Vec::with_capacity(events.len()),
+            data2: Vec::with_capacity(events.len()),
+            track: Vec::with_capacity(events.len()),
+        };
+        for event in events {
+            columns.timestamps.push(event.absolute_ns);
+            columns.ticks.push(event.absolute_tick);
+            columns.status.push(event.status);
+            columns.data1.push(event.data1);
+            columns.data2.push(event.data2);
+            columns.track.push(event.track_index);
+        }
+        columns
+    }
+
+    pub fn len(&self) -> usize {
+        self.timestamps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.timestamps.is_empty()
+    }
+}
+
+impl MidiSequence {
+    /// A column copy of `events()`, in the same order.
+    pub fn columns(&self) -> EventColumns {
+        EventColumns::from_events(&self.events)
+    }
+}
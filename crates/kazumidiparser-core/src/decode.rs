@@ -0,0 +1,400 @@
+use std::fmt;
+
+/// One event as it appears in an `MTrk` chunk, before any tempo conversion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrackEvent<'a> {
+    pub track_index: u16,
+    pub delta_ticks: u32,
+    pub absolute_tick: u64,
+    // Byte offset of the event (its delta time) inside the track data.
+    pub offset: usize,
+    pub kind: TrackEventKind<'a>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackEventKind<'a> {
+    Channel { status: u8, data1: u8, data2: u8 },
+    Meta { meta_type: u8, data: &'a [u8] },
+    // An F0 packet: the message after the F0, ending in F7 unless F7
+    // packets continue it.
+    SysEx { data: &'a [u8] },
+    // An F7 packet continuing the last SysEx.
+    SysExContinuation { data: &'a [u8] },
+    // Any other F7 packet, escaping bytes to send as they are.
+    Escape { data: &'a [u8] },
+    // System common/real-time bytes, which have no meaning inside an SMF.
+    Other { status: u8 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    RunningStatusWithoutStatus { offset: usize },
+    // A data byte follows a meta, SysEx or system message, all of which
+    // cancel running status.
+    RunningStatusCancelled { offset: usize },
+}
+
+impl DecodeError {
+    pub fn offset(&self) -> usize {
+        match self {
+            DecodeError::RunningStatusWithoutStatus { offset }
+            | DecodeError::RunningStatusCancelled { offset } => *offset,
+        }
+    }
+
+    fn shifted(self, by: usize) -> DecodeError {
+        match self {
+            DecodeError::RunningStatusWithoutStatus { offset } => {
+                DecodeError::RunningStatusWithoutStatus {
+                    offset: offset + by,
+                }
+            }
+            DecodeError::RunningStatusCancelled { offset } => DecodeError::RunningStatusCancelled {
+                offset: offset + by,
+            },
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::RunningStatusWithoutStatus { offset } => {
+                write!(
+                    f,
+                    "Running status without previous status at byte {}",
+                    offset
+                )
+            }
+            DecodeError::RunningStatusCancelled { offset } => {
+                write!(
+                    f,
+                    "Running status after a meta or SysEx event at byte {}",
+                    offset
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Running status carried from one event to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) struct RunningStatus {
+    // Status byte of the last channel message.
+    last: Option<u8>,
+    // A meta, SysEx or system message came after `last`. The SMF spec says
+    // this cancels running status; lenient decoding keeps using `last`.
+    cancelled: bool,
+    // The last event was a SysEx packet not yet ended by F7, so an F7
+    // packet continues it.
+    sysex_open: bool,
+}
+
+pub(crate) enum Step<'a> {
+    Event {
+        len: usize,
+        delta_ticks: u32,
+        kind: TrackEventKind<'a>,
+        running_status: RunningStatus,
+        // Lenient decoding reused a cancelled running status.
+        fallback: bool,
+        // (offset, length) of the data bytes resyncing skipped.
+        skipped: Option<(usize, usize)>,
+    },
+    // The event continues past the end of `data`, but more bytes may follow.
+    NeedMore,
+    // No further complete event (end of data, or a truncated event at EOF).
+    End,
+    Error(DecodeError),
+}
+
+impl TrackEventKind<'_> {
+    pub fn is_end_of_track(&self) -> bool {
+        matches!(self, TrackEventKind::Meta { meta_type: 0x2F, data } if data.is_empty())
+    }
+}
+
+fn read_vlq(data: &[u8], pos: &mut usize) -> Option<u32> {
+    let mut value = 0u32;
+    while let Some(&byte) = data.get(*pos) {
+        *pos += 1;
+        value = (value << 7) | (byte & 0x7F) as u32;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Decodes the event at the start of `data`.
+///
+/// Running status is only reported back once an event is complete, so a
+/// caller receiving `NeedMore` can retry with a longer buffer. With `eof` set,
+/// a truncated event ends the track instead. With `lenient` set, a data byte
+/// right after a meta or SysEx event reuses the last channel status instead
+/// of failing. With `resync` set, data bytes with no status at all to run on
+/// are skipped up to the next status byte.
+pub(crate) fn decode_step(
+    data: &[u8],
+    running_status: RunningStatus,
+    lenient: bool,
+    resync: bool,
+    eof: bool,
+) -> Step<'_> {
+    let truncated = if eof { Step::End } else { Step::NeedMore };
+    let mut pos = 0;
+
+    let Some(delta_ticks) = read_vlq(data, &mut pos) else {
+        return truncated;
+    };
+    if pos >= data.len() {
+        return truncated;
+    }
+
+    // Status byte and running status
+    let mut status = data[pos];
+    let mut fallback = false;
+    let mut skipped = None;
+    if status & 0x80 != 0 {
+        pos += 1;
+    } else if let Some(last) = running_status.last {
+        if running_status.cancelled {
+            if !lenient {
+                return Step::Error(DecodeError::RunningStatusCancelled { offset: pos });
+            }
+            fallback = true;
+        }
+        status = last;
+    } else if resync {
+        let Some(next) = data[pos..].iter().position(|b| b & 0x80 != 0) else {
+            return truncated;
+        };
+        skipped = Some((pos, next));
+        pos += next;
+        status = data[pos];
+        pos += 1;
+    } else {
+        return Step::Error(DecodeError::RunningStatusWithoutStatus { offset: pos });
+    }
+    let mut new_running_status = if status & 0xF0 == 0xF0 {
+        RunningStatus {
+            cancelled: true,
+            sysex_open: false,
+            ..running_status
+        }
+    } else {
+        RunningStatus {
+            last: Some(status),
+            cancelled: false,
+            sysex_open: false,
+        }
+    };
+
+    let kind = if status == 0xFF {
+        // Meta Event
+        let Some(&meta_type) = data.get(pos) else {
+            return truncated;
+        };
+        pos += 1;
+        let Some(length) = read_vlq(data, &mut pos) else {
+            return truncated;
+        };
+        let length = length as usize;
+        if data.len() - pos < length {
+            return truncated;
+        }
+        let payload = &data[pos..pos + length];
+        pos += length;
+        TrackEventKind::Meta {
+            meta_type,
+            data: payload,
+        }
+    } else if status == 0xF0 || status == 0xF7 {
+        // System Exclusive (SysEx) packet: a length, then that many bytes
+        let Some(length) = read_vlq(data, &mut pos) else {
+            return truncated;
+        };
+        let length = length as usize;
+        if data.len() - pos < length {
+            return truncated;
+        }
+        let payload = &data[pos..pos + length];
+        pos += length;
+        let continues = status == 0xF7 && running_status.sysex_open;
+        new_running_status.sysex_open =
+            (status == 0xF0 || continues) && payload.last() != Some(&0xF7);
+        if status == 0xF0 {
+            TrackEventKind::SysEx { data: payload }
+        } else if continues {
+            TrackEventKind::SysExContinuation { data: payload }
+        } else {
+            TrackEventKind::Escape { data: payload }
+        }
+    } else if status & 0xF0 != 0xF0 {
+        // MIDI channel message
+        let Some(&data1) = data.get(pos) else {
+            return truncated;
+        };
+        pos += 1;
+        let data2 = if status & 0xF0 != 0xC0 && status & 0xF0 != 0xD0 {
+            let Some(&data2) = data.get(pos) else {
+                return truncated;
+            };
+            pos += 1;
+            data2
+        } else {
+            0
+        };
+        TrackEventKind::Channel {
+            status,
+            data1,
+            data2,
+        }
+    } else {
+        if pos < data.len() {
+            pos += 1;
+        } else if !eof {
+            return Step::NeedMore;
+        }
+        TrackEventKind::Other { status }
+    };
+
+    Step::Event {
+        len: pos,
+        delta_ticks,
+        kind,
+        running_status: new_running_status,
+        fallback,
+        skipped,
+    }
+}
+
+/// Iterates the events of one complete `MTrk` chunk body, stopping after the
+/// End of Track meta event or at the first truncated event.
+///
+/// Meta and SysEx events cancel running status in the SMF spec, but files
+/// that reuse it anyway are accepted unless `lenient_running_status` is
+/// turned off; `running_status_fallbacks` counts them.
+pub struct TrackDecoder<'a> {
+    track_index: u16,
+    data: &'a [u8],
+    position: usize,
+    running_status: RunningStatus,
+    lenient: bool,
+    resync: bool,
+    fallbacks: usize,
+    // (offset, length) of each run of bytes skipped by resyncing.
+    skipped: Vec<(usize, usize)>,
+    absolute_tick: u64,
+    finished: bool,
+    reached_end: bool,
+}
+
+impl<'a> TrackDecoder<'a> {
+    pub fn new(data: &'a [u8]) -> TrackDecoder<'a> {
+        Self::for_track(0, data)
+    }
+
+    pub fn for_track(track_index: u16, data: &'a [u8]) -> TrackDecoder<'a> {
+        TrackDecoder {
+            track_index,
+            data,
+            position: 0,
+            running_status: RunningStatus::default(),
+            lenient: true,
+            resync: false,
+            fallbacks: 0,
+            skipped: Vec::new(),
+            absolute_tick: 0,
+            finished: false,
+            reached_end: false,
+        }
+    }
+
+    pub fn lenient_running_status(mut self, lenient: bool) -> TrackDecoder<'a> {
+        self.lenient = lenient;
+        self
+    }
+
+    /// Skip data bytes that have no status to belong to, up to the next
+    /// status byte, instead of failing with `RunningStatusWithoutStatus`.
+    pub fn resync(mut self, resync: bool) -> TrackDecoder<'a> {
+        self.resync = resync;
+        self
+    }
+
+    /// Events so far that only decoded by reusing a cancelled running status.
+    pub fn running_status_fallbacks(&self) -> usize {
+        self.fallbacks
+    }
+
+    /// The `(offset, length)` of every run of bytes skipped by `resync` so
+    /// far.
+    pub fn skipped_bytes(&self) -> &[(usize, usize)] {
+        &self.skipped
+    }
+
+    /// Whether decoding stopped at an End of Track event rather than at the
+    /// end of the data.
+    pub fn reached_end_of_track(&self) -> bool {
+        self.reached_end
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn absolute_tick(&self) -> u64 {
+        self.absolute_tick
+    }
+}
+
+impl<'a> Iterator for TrackDecoder<'a> {
+    type Item = Result<TrackEvent<'a>, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        let data = &self.data[self.position..];
+        match decode_step(data, self.running_status, self.lenient, self.resync, true) {
+            Step::Event {
+                len,
+                delta_ticks,
+                kind,
+                running_status,
+                fallback,
+                skipped,
+            } => {
+                let offset = self.position;
+                if let Some((start, skipped_len)) = skipped {
+                    self.skipped.push((offset + start, skipped_len));
+                }
+                self.position += len;
+                self.absolute_tick += delta_ticks as u64;
+                self.running_status = running_status;
+                self.fallbacks += fallback as usize;
+                self.finished = kind.is_end_of_track();
+                self.reached_end = self.finished;
+                Some(Ok(TrackEvent {
+                    track_index: self.track_index,
+                    delta_ticks,
+                    absolute_tick: self.absolute_tick,
+                    offset,
+                    kind,
+                }))
+            }
+            Step::Error(e) => {
+                self.finished = true;
+                Some(Err(e.shifted(self.position)))
+            }
+            Step::End | Step::NeedMore => {
+                self.finished = true;
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use crate::{MetaEvent, MidiEvent, MidiSequence, TempoMap, TrackMeta};
+
+// Events and metas are kept in tick order, ties in track order (time order
+// for format 2, see `sort_by_time`). A new one goes after everything already
+// on its tick and track.
+fn insert_position<T>(items: &[T], key: impl Fn(&T) -> (u64, u16), at: (u64, u16)) -> usize {
+    items.partition_point(|item| key(item) <= at)
+}
+
+fn tempo_of(meta: &MetaEvent) -> Option<u32> {
+    match (meta.meta_type, meta.data.as_slice()) {
+        (0x51, &[a, b, c]) => Some(u32::from_be_bytes([0, a, b, c])),
+        _ => None,
+    }
+}
+
+impl MidiSequence {
+    // Makes room for `track_index` if it is past the last track.
+    fn ensure_track(&mut self, track_index: u16) {
+        if track_index >= self.header.tracks {
+            self.header.tracks = track_index + 1;
+        }
+        if self.track_metas.len() < self.header.tracks as usize {
+            self.track_metas
+                .resize_with(self.header.tracks as usize, TrackMeta::default);
+        }
+        // A new format 2 track starts without tempo changes.
+        if self.header.format == 2 && self.header.tracks > 1 {
+            let map = TempoMap::for_header(&self.header, [], self.tempo_map.is_exact());
+            self.track_tempo_maps
+                .resize(self.header.tracks as usize - 1, map);
+        }
+    }
+
+    // What the lists are ordered by, with `track_index` breaking ties.
+    fn order_key(&self, tick: u64, ns: u64) -> u64 {
+        if self.header.format == 2 { ns } else { tick }
+    }
+
+    fn event_position(&self, event: &MidiEvent) -> usize {
+        insert_position(
+            &self.events,
+            |e| {
+                (
+                    self.order_key(e.absolute_tick, e.absolute_ns),
+                    e.track_index,
+                )
+            },
+            (
+                self.order_key(event.absolute_tick, event.absolute_ns),
+                event.track_index,
+            ),
+        )
+    }
+
+    /// Inserts `event` at its tick (in format 2, its time), after any events
+    /// already there on its track, and returns its index. `absolute_ns` is
+    /// computed from the track's tempo map; a track past the last one is
+    /// added. For a SysEx, use `insert_sysex` so its bytes go into the SysEx
+    /// table.
+    pub fn insert_event(&mut self, mut event: MidiEvent) -> usize {
+        self.ensure_track(event.track_index);
+        event.absolute_ns = self
+            .track_map(event.track_index)
+            .tick_to_ns(event.absolute_tick);
+        let index = self.event_position(&event);
+        self.events.insert(index, event);
+        index
+    }
+
+    /// Inserts a SysEx (`data` without the leading F0) at `tick` and returns
+    /// its event index.
+    pub fn insert_sysex(&mut self, tick: u64, track_index: u16, data: Vec<u8>) -> usize {
+        let sysex_index = self.sysex.len() as u32;
+        self.sysex.push(data);
+        self.insert_event(MidiEvent {
+            absolute_ns: 0,
+            absolute_tick: tick,
+            status: 0xF0,
+            data1: 0,
+            data2: 0,
+            track_index,
+            sysex_index: Some(sysex_index),
+        })
+    }
+
+    /// Removes and returns the event at `index`. A SysEx's bytes stay in the
+    /// SysEx table, so other events' `sysex_index` stays valid.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of bounds.
+    pub fn remove_event(&mut self, index: usize) -> MidiEvent {
+        self.events.remove(index)
+    }
+
+    /// Removes the events `keep` returns false for, keeping the order of the
+    /// rest. Returns the number removed.
+    pub fn retain_events<F: FnMut(&MidiEvent) -> bool>(&mut self, keep: F) -> usize {
+        let before = self.events.len();
+        self.events.retain(keep);
+        before - self.events.len()
+    }
+
+    /// Edits the event at `index` in place and returns where it ends up: if
+    /// its tick or track changed it is moved as `insert_event` would place
+    /// it, and `absolute_ns` is recomputed either way.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of bounds.
+    pub fn modify_event<F: FnOnce(&mut MidiEvent)>(&mut self, index: usize, edit: F) -> usize {
+        let before = self.events[index];
+        edit(&mut self.events[index]);
+        let after = self.events[index];
+        if (after.absolute_tick, after.track_index) == (before.absolute_tick, before.track_index) {
+            self.events[index].absolute_ns = self
+                .track_map(after.track_index)
+                .tick_to_ns(after.absolute_tick);
+            return index;
+        }
+        self.events.remove(index);
+        self.insert_event(after)
+    }
+
+    /// Inserts `meta` in tick order, as `insert_event` does, and returns its
+    /// index in `metas()`. A tempo meta changes the tempo map (in format 2,
+    /// its track's) on metrical files, so every event is retimed; a name or
+    /// sequence number meta updates what `track_info` reports for its track.
+    pub fn insert_meta(&mut self, mut meta: MetaEvent) -> usize {
+        self.ensure_track(meta.track_index);
+        meta.absolute_ns = self
+            .track_map(meta.track_index)
+            .tick_to_ns(meta.absolute_tick);
+        let index = insert_position(
+            &self.metas,
+            |m| {
+                (
+                    self.order_key(m.absolute_tick, m.absolute_ns),
+                    m.track_index,
+                )
+            },
+            (
+                self.order_key(meta.absolute_tick, meta.absolute_ns),
+                meta.track_index,
+            ),
+        );
+        let (tick, track_index, meta_type) = (meta.absolute_tick, meta.track_index, meta.meta_type);
+        let tempo = tempo_of(&meta);
+        self.metas.insert(index, meta);
+        if let Some(tempo_us) = tempo {
+            self.set_tempo_change(track_index, tick, Some(tempo_us));
+        }
+        self.refresh_track_meta(track_index, meta_type);
+        index
+    }
+
+    /// Removes and returns the meta at `index` in `metas()`, undoing its
+    /// effect on the tempo map and track names as `insert_meta` describes.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is out of bounds.
+    pub fn remove_meta(&mut self, index: usize) -> MetaEvent {
+        let meta = self.metas.remove(index);
+        if let Some(tempo_us) = tempo_of(&meta)
+            && self
+                .track_map(meta.track_index)
+                .tempo_at_tick(meta.absolute_tick)
+                == tempo_us
+        {
+            // Another tempo meta on the same tick takes over, as the last
+            // one on a tick wins when parsing; in format 2, only one on the
+            // same track.
+            let remaining = self
+                .metas
+                .iter()
+                .rev()
+                .filter(|m| m.absolute_tick == meta.absolute_tick)
+                .filter(|m| self.header.format != 2 || m.track_index == meta.track_index)
+                .find_map(tempo_of);
+            self.set_tempo_change(meta.track_index, meta.absolute_tick, remaining);
+        }
+        self.refresh_track_meta(meta.track_index, meta.meta_type);
+        meta
+    }
+
+    // Sets (or with `None`, drops) the tempo change at `tick` in the map of
+    // `track_index` and retimes. SMPTE files keep their fixed rate.
+    fn set_tempo_change(&mut self, track_index: u16, tick: u64, tempo_us: Option<u32>) {
+        if self.header.tempo_timing().1.is_some() {
+            return;
+        }
+        let map = self.track_map_mut(track_index);
+        match tempo_us {
+            Some(tempo_us) => map.set_tempo(tick, tempo_us),
+            None => {
+                map.remove_tempo(tick);
+            }
+        }
+        self.retime();
+    }
+
+    // Re-reads the first name and instrument name and the last sequence
+    // number of a track from its metas, as the parser picks them.
+    fn refresh_track_meta(&mut self, track_index: u16, meta_type: u8) {
+        if !matches!(meta_type, 0x00 | 0x03 | 0x04) {
+            return;
+        }
+        let mut on_track = self.metas.iter().filter(|m| m.track_index == track_index);
+        let Some(track_meta) = self.track_metas.get_mut(track_index as usize) else {
+            return;
+        };
+        match meta_type {
+            0x00 => {
+                track_meta.sequence_number =
+                    on_track
+                        .rev()
+                        .find_map(|m| match (m.meta_type, m.data.as_slice()) {
+                            (0x00, &[high, low]) => Some(u16::from_be_bytes([high, low])),
+                            _ => None,
+                        });
+            }
+            _ => {
+                let found = on_track
+                    .find(|m| m.meta_type == meta_type)
+                    .map(|m| m.data.clone());
+                if meta_type == 0x03 {
+                    track_meta.name = found;
+                } else {
+                    track_meta.instrument_name = found;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::fmt;
+use std::io;
+
+use crate::decode::DecodeError;
+use crate::{PoolError, TrackLengthMismatch};
+
+/// Why a file could not be parsed.
+///
+/// Byte offsets in chunk errors are from the start of the file; offsets in
+/// `Decode` are from the start of the track's data, as in `DecodeError`.
+#[derive(Debug)]
+pub enum ParseError {
+    Io(io::Error),
+    /// The file does not start with an `MThd` chunk, or an RMID wrapping one.
+    InvalidHeader,
+    /// The `MThd` chunk declares a length other than 6.
+    UnexpectedHeaderLength(u32),
+    /// Something other than `MTrk` where a track chunk should start.
+    UnexpectedChunk {
+        track: u16,
+        offset: u64,
+        found: [u8; 4],
+    },
+    /// The file ends before the track chunk at `offset` does.
+    TruncatedTrack {
+        track: u16,
+        offset: u64,
+    },
+    Decode {
+        track: u16,
+        error: DecodeError,
+    },
+    /// The parse was stopped through its `CancelToken`.
+    Cancelled,
+    /// A single track was asked for that the file does not have.
+    TrackOutOfRange {
+        track: u16,
+        tracks: u16,
+    },
+    /// The file `TailParser` follows is now shorter than what it already
+    /// read.
+    FileShrank {
+        read: u64,
+        length: u64,
+    },
+    /// `ParserPool` could not fit the parse in its memory budget.
+    Pool(PoolError),
+}
+
+impl ParseError {
+    pub fn track(&self) -> Option<u16> {
+        match self {
+            ParseError::UnexpectedChunk { track, .. }
+            | ParseError::TruncatedTrack { track, .. }
+            | ParseError::Decode { track, .. }
+            | ParseError::TrackOutOfRange { track, .. } => Some(*track),
+            _ => None,
+        }
+    }
+
+    /// Whether the same file can still parse: after a cancel or a full
+    /// pool, or with `ParseOptions::strict` off for bad track data.
+    /// Everything else is a problem with the header, the reader or the
+    /// request.
+    pub fn is_recoverable(&self) -> bool {
+        matches!(
+            self,
+            ParseError::Cancelled
+                | ParseError::Pool(PoolError::OverBudget { .. })
+                | ParseError::UnexpectedChunk { .. }
+                | ParseError::TruncatedTrack { .. }
+                | ParseError::Decode { .. }
+        )
+    }
+
+    // Names the track whose chunk ran out, when the reader hit the end of
+    // the file.
+    pub(crate) fn in_track(error: io::Error, track: u16, offset: u64) -> ParseError {
+        match error.kind() {
+            io::ErrorKind::UnexpectedEof => ParseError::TruncatedTrack { track, offset },
+            _ => ParseError::Io(error),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Io(error) => write!(f, "{}", error),
+            ParseError::InvalidHeader => write!(f, "Invalid header: no MThd"),
+            ParseError::UnexpectedHeaderLength(length) => {
+                write!(f, "Unexpected MThd chunk length: {}", length)
+            }
+            ParseError::UnexpectedChunk {
+                track,
+                offset,
+                found,
+            } => write!(
+                f,
+                "Expected MTrk, found {:?} at track {} (byte {})",
+                found, track, offset
+            ),
+            ParseError::TruncatedTrack { track, offset } => write!(
+                f,
+                "Track {} chunk at byte {} is cut off by the end of the file",
+                track, offset
+            ),
+            ParseError::Decode { track, error } => write!(f, "{} on track {}", error, track),
+            ParseError::Cancelled => write!(f, "Parse cancelled"),
+            ParseError::TrackOutOfRange { track, tracks } => {
+                write!(f, "Track {} out of range ({} tracks)", track, tracks)
+            }
+            ParseError::FileShrank { read, length } => write!(
+                f,
+                "File shrank from {} to {} bytes while tailing",
+                read, length
+            ),
+            ParseError::Pool(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Io(error) => Some(error),
+            ParseError::Decode { error, .. } => Some(error),
+            ParseError::Pool(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(error: io::Error) -> ParseError {
+        ParseError::Io(error)
+    }
+}
+
+impl From<PoolError> for ParseError {
+    fn from(error: PoolError) -> ParseError {
+        ParseError::Pool(error)
+    }
+}
+
+/// Something wrong with the file that parsing worked around.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseWarning {
+    /// `None` when the warning is about the file rather than one track.
+    pub track: Option<u16>,
+    // Byte offset from the start of the file.
+    pub offset: u64,
+    pub kind: WarningKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WarningKind {
+    /// Data bytes with no status to belong to, skipped up to the next status
+    /// byte. Only with `ParseOptions::strict` off.
+    SkippedBytes(usize),
+    /// Events that reused a running status cancelled by a meta or SysEx
+    /// event; `offset` is the start of the track data.
+    RunningStatusFallbacks(usize),
+    /// The track data ends without an End of Track event; `offset` is where
+    /// it ends.
+    MissingEndOfTrack,
+    /// The `MTrk` length field disagrees with the track data; `offset` is the
+    /// chunk.
+    LengthMismatch(TrackLengthMismatch),
+    /// Only `found` of the `declared` track chunks could be read, the next
+    /// one failing at `offset`. With `ParseOptions::strict` off the rest of
+    /// the file is ignored.
+    MissingTracks { declared: u16, found: u16 },
+}
+
+impl ParseWarning {
+    // The warning a lenient parse records instead of failing on a chunk
+    // error, or `None` for errors it cannot recover from.
+    pub(crate) fn missing_tracks(error: &ParseError, declared: u16) -> Option<ParseWarning> {
+        let (found, offset) = match *error {
+            ParseError::UnexpectedChunk { track, offset, .. }
+            | ParseError::TruncatedTrack { track, offset } => (track, offset),
+            _ => return None,
+        };
+        Some(ParseWarning {
+            track: None,
+            offset,
+            kind: WarningKind::MissingTracks { declared, found },
+        })
+    }
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            WarningKind::SkippedBytes(count) => write!(f, "Skipped {} stray data bytes", count)?,
+            WarningKind::RunningStatusFallbacks(count) => write!(
+                f,
+                "Reused running status after meta/SysEx events {} times",
+                count
+            )?,
+            WarningKind::MissingEndOfTrack => write!(f, "Track data ends without End of Track")?,
+            WarningKind::LengthMismatch(mismatch) => write!(
+                f,
+                "Length mismatch: declared {} bytes, found {}",
+                mismatch.declared_length, mismatch.actual_length
+            )?,
+            WarningKind::MissingTracks { declared, found } => write!(
+                f,
+                "Only {} of {} declared tracks could be read",
+                found, declared
+            )?,
+        }
+        if let Some(track) = self.track {
+            write!(f, " on track {}", track)?;
+        }
+        write!(f, " (byte {})", self.offset)
+    }
+}
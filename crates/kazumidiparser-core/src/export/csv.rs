@@ -0,0 +1,414 @@
+use std::error::Error as StdError;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+use crate::export::smf::MidiWriter;
+use crate::{MetaEvent, MetaKind, MidiEvent, MidiSequence, TextKind};
+
+// A record on one track: channel events and SysEx, or a meta event.
+enum Record<'a> {
+    Event(&'a MidiEvent),
+    Meta(&'a MetaEvent),
+}
+
+impl Record<'_> {
+    fn tick(&self) -> u64 {
+        match self {
+            Record::Event(event) => event.absolute_tick,
+            Record::Meta(meta) => meta.absolute_tick,
+        }
+    }
+}
+
+// Quotes `data` the way midicsv does: quotes doubled, backslashes doubled
+// and anything outside printable ASCII as a three-digit octal escape.
+fn quote(out: &mut String, data: &[u8]) {
+    out.push('"');
+    for &byte in data {
+        match byte {
+            b'"' => out.push_str("\"\""),
+            b'\\' => out.push_str("\\\\"),
+            0x20..=0x7E => out.push(byte as char),
+            _ => {
+                let _ = write!(out, "\\{:03o}", byte);
+            }
+        }
+    }
+    out.push('"');
+}
+
+fn push_bytes(out: &mut String, data: &[u8]) {
+    let _ = write!(out, ", {}", data.len());
+    for byte in data {
+        let _ = write!(out, ", {}", byte);
+    }
+}
+
+fn write_event(out: &mut String, event: &MidiEvent, sysex_data: Option<&[u8]>) {
+    let channel = event.status & 0x0F;
+    let (data1, data2) = (event.data1, event.data2);
+    let _ = match event.status {
+        0xF0 | 0xF7 => {
+            out.push_str(if event.status == 0xF0 {
+                "System_exclusive"
+            } else {
+                "System_exclusive_packet"
+            });
+            push_bytes(out, sysex_data.unwrap_or_default());
+            Ok(())
+        }
+        status => match status & 0xF0 {
+            0x80 => write!(out, "Note_off_c, {}, {}, {}", channel, data1, data2),
+            0x90 => write!(out, "Note_on_c, {}, {}, {}", channel, data1, data2),
+            0xA0 => write!(out, "Poly_aftertouch_c, {}, {}, {}", channel, data1, data2),
+            0xB0 => write!(out, "Control_c, {}, {}, {}", channel, data1, data2),
+            0xC0 => write!(out, "Program_c, {}, {}", channel, data1),
+            0xD0 => write!(out, "Channel_aftertouch_c, {}, {}", channel, data1),
+            _ => write!(
+                out,
+                "Pitch_bend_c, {}, {}",
+                channel,
+                (data2 as u16 & 0x7F) << 7 | (data1 as u16 & 0x7F)
+            ),
+        },
+    };
+}
+
+fn write_meta(out: &mut String, meta: &MetaEvent) {
+    let text_record = |kind| match kind {
+        TextKind::Text => Some("Text_t"),
+        TextKind::Copyright => Some("Copyright_t"),
+        TextKind::TrackName => Some("Title_t"),
+        TextKind::InstrumentName => Some("Instrument_name_t"),
+        TextKind::Lyric => Some("Lyric_t"),
+        TextKind::Marker => Some("Marker_t"),
+        TextKind::CuePoint => Some("Cue_point_t"),
+        _ => None,
+    };
+    let _ = match meta.kind() {
+        MetaKind::SequenceNumber(Some(number)) => write!(out, "Sequence_number, {}", number),
+        MetaKind::Text { kind, data } if text_record(kind).is_some() => {
+            out.push_str(text_record(kind).unwrap());
+            out.push_str(", ");
+            quote(out, data);
+            Ok(())
+        }
+        MetaKind::ChannelPrefix(channel) => write!(out, "Channel_prefix, {}", channel),
+        MetaKind::Port(port) => write!(out, "MIDI_port, {}", port),
+        MetaKind::Tempo { us_per_quarter } => write!(out, "Tempo, {}", us_per_quarter),
+        MetaKind::SmpteOffset { .. } => {
+            // The hour byte is written whole, frame rate bits included.
+            let data = &meta.data;
+            write!(
+                out,
+                "SMPTE_offset, {}, {}, {}, {}, {}",
+                data[0], data[1], data[2], data[3], data[4]
+            )
+        }
+        MetaKind::TimeSignature {
+            numerator,
+            denominator_log2,
+            clocks_per_click,
+            notated_32nds_per_quarter,
+        } => write!(
+            out,
+            "Time_signature, {}, {}, {}, {}",
+            numerator, denominator_log2, clocks_per_click, notated_32nds_per_quarter
+        ),
+        MetaKind::KeySignature { sharps, minor } => write!(
+            out,
+            "Key_signature, {}, \"{}\"",
+            sharps,
+            if minor { "minor" } else { "major" }
+        ),
+        MetaKind::SequencerSpecific(data) => {
+            out.push_str("Sequencer_specific");
+            push_bytes(out, data);
+            Ok(())
+        }
+        _ => {
+            let _ = write!(out, "Unknown_meta_event, {}", meta.meta_type);
+            push_bytes(out, &meta.data);
+            Ok(())
+        }
+    };
+}
+
+/// The sequence in the text format of `midicsv`, one record per line.
+///
+/// Tracks are numbered from 1 as midicsv does. Events keep the order they
+/// have in the sequence, with a track's meta events ahead of its channel
+/// events on the same tick, and each track ends on the tick of its last
+/// record.
+pub fn to_csv(sequence: &MidiSequence) -> String {
+    let header = sequence.header();
+    let highest = sequence
+        .events()
+        .iter()
+        .map(|event| event.track_index)
+        .chain(sequence.metas().iter().map(|meta| meta.track_index))
+        .max()
+        .map_or(0, |index| index as usize + 1);
+    let track_count = highest.max(header.tracks as usize);
+
+    let mut tracks: Vec<Vec<Record>> = (0..track_count).map(|_| Vec::new()).collect();
+    for meta in sequence.metas() {
+        tracks[meta.track_index as usize].push(Record::Meta(meta));
+    }
+    for event in sequence.events() {
+        tracks[event.track_index as usize].push(Record::Event(event));
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "0, 0, Header, {}, {}, {}",
+        header.format, track_count, header.ppqn
+    );
+    for (index, records) in tracks.iter_mut().enumerate() {
+        let track = index + 1;
+        records.sort_by_key(Record::tick);
+        let _ = writeln!(out, "{}, 0, Start_track", track);
+        for record in records.iter() {
+            let _ = write!(out, "{}, {}, ", track, record.tick());
+            match record {
+                Record::Event(event) => write_event(&mut out, event, sequence.sysex(event)),
+                Record::Meta(meta) => write_meta(&mut out, meta),
+            }
+            out.push('\n');
+        }
+        let end = records.last().map_or(0, Record::tick);
+        let _ = writeln!(out, "{}, {}, End_track", track, end);
+    }
+    out.push_str("0, 0, End_of_file\n");
+    out
+}
+
+pub fn write_csv<W: Write>(sequence: &MidiSequence, mut writer: W) -> io::Result<()> {
+    writer.write_all(to_csv(sequence).as_bytes())
+}
+
+// Splits a record into fields, unquoting strings and expanding their
+// escapes to raw bytes.
+fn split_fields(line: &str) -> Result<Vec<Vec<u8>>, String> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let mut field = Vec::new();
+        if chars.next_if_eq(&'"').is_some() {
+            loop {
+                match chars.next().ok_or("unterminated string")? {
+                    '"' if chars.next_if_eq(&'"').is_some() => field.push(b'"'),
+                    '"' => break,
+                    '\\' if chars.peek().is_some_and(|c| c.is_digit(8)) => {
+                        let mut value = 0u32;
+                        for _ in 0..3 {
+                            match chars.next_if(|c| c.is_digit(8)) {
+                                Some(digit) => value = value * 8 + digit.to_digit(8).unwrap(),
+                                None => break,
+                            }
+                        }
+                        field.push(value as u8);
+                    }
+                    '\\' => {
+                        let c = chars.next().ok_or("unterminated string")?;
+                        field.extend(c.encode_utf8(&mut [0; 4]).as_bytes());
+                    }
+                    c => field.extend(c.encode_utf8(&mut [0; 4]).as_bytes()),
+                }
+            }
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        } else {
+            while let Some(c) = chars.next_if(|&c| c != ',') {
+                field.extend(c.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+            field.truncate(field.trim_ascii_end().len());
+        }
+        fields.push(field);
+        match chars.next() {
+            Some(',') => continue,
+            None => return Ok(fields),
+            Some(c) => return Err(format!("unexpected {:?} after string", c)),
+        }
+    }
+}
+
+fn number<T: std::str::FromStr>(fields: &[Vec<u8>], index: usize) -> Result<T, String> {
+    let field = fields
+        .get(index)
+        .ok_or_else(|| format!("missing field {}", index + 1))?;
+    std::str::from_utf8(field)
+        .ok()
+        .and_then(|text| text.parse().ok())
+        .ok_or_else(|| format!("bad number {:?}", String::from_utf8_lossy(field)))
+}
+
+// A length followed by that many bytes, from field `index` on.
+fn bytes(fields: &[Vec<u8>], index: usize) -> Result<Vec<u8>, String> {
+    let len: usize = number(fields, index)?;
+    (0..len).map(|i| number(fields, index + 1 + i)).collect()
+}
+
+/// Reads text in the format of `midicsv` back into a writer, ready to be
+/// saved as a Standard MIDI File or parsed again.
+///
+/// Blank lines and lines starting with `#` or `;` are skipped, and record
+/// names are matched without regard to case, as `csvmidi` does. A track
+/// always ends on its last event; the tick given by `End_track` is not
+/// kept.
+pub fn from_csv(text: &str) -> Result<MidiWriter, Box<dyn StdError>> {
+    let mut writer = None;
+    for (number_in_file, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        parse_record(line, &mut writer)
+            .map_err(|message| format!("line {}: {}", number_in_file + 1, message))?;
+    }
+    writer.ok_or_else(|| "no Header record".into())
+}
+
+fn parse_record(line: &str, writer: &mut Option<MidiWriter>) -> Result<(), String> {
+    let fields = split_fields(line)?;
+    let track: u16 = number(&fields, 0)?;
+    let tick: u64 = number(&fields, 1)?;
+    let record = fields
+        .get(2)
+        .map(|name| String::from_utf8_lossy(name).to_ascii_lowercase())
+        .ok_or("missing record type")?;
+
+    if record == "header" {
+        *writer = Some(MidiWriter::new(number(&fields, 3)?, number(&fields, 5)?));
+        return Ok(());
+    }
+    let writer = writer.as_mut().ok_or("record before the Header")?;
+    if matches!(record.as_str(), "start_track" | "end_track" | "end_of_file") {
+        return Ok(());
+    }
+    let track_index = track
+        .checked_sub(1)
+        .ok_or("track 0 holds only the header")?;
+
+    let channel_event = |status: u8, data1: u8, data2: u8| -> Result<MidiEvent, String> {
+        let channel: u8 = number(&fields, 3)?;
+        if channel > 15 {
+            return Err(format!("bad channel {}", channel));
+        }
+        Ok(MidiEvent {
+            absolute_ns: 0,
+            absolute_tick: tick,
+            status: status | channel,
+            data1,
+            data2,
+            track_index,
+            sysex_index: None,
+        })
+    };
+    let mut sysex_data = None;
+    let event = match record.as_str() {
+        "note_off_c" => Some(channel_event(
+            0x80,
+            number(&fields, 4)?,
+            number(&fields, 5)?,
+        )?),
+        "note_on_c" => Some(channel_event(
+            0x90,
+            number(&fields, 4)?,
+            number(&fields, 5)?,
+        )?),
+        "poly_aftertouch_c" => Some(channel_event(
+            0xA0,
+            number(&fields, 4)?,
+            number(&fields, 5)?,
+        )?),
+        "control_c" => Some(channel_event(
+            0xB0,
+            number(&fields, 4)?,
+            number(&fields, 5)?,
+        )?),
+        "program_c" => Some(channel_event(0xC0, number(&fields, 4)?, 0)?),
+        "channel_aftertouch_c" => Some(channel_event(0xD0, number(&fields, 4)?, 0)?),
+        "pitch_bend_c" => {
+            let value: u16 = number(&fields, 4)?;
+            Some(channel_event(
+                0xE0,
+                (value & 0x7F) as u8,
+                (value >> 7 & 0x7F) as u8,
+            )?)
+        }
+        "system_exclusive" | "system_exclusive_packet" => {
+            sysex_data = Some(bytes(&fields, 3)?);
+            Some(MidiEvent {
+                absolute_ns: 0,
+                absolute_tick: tick,
+                status: if record == "system_exclusive" {
+                    0xF0
+                } else {
+                    0xF7
+                },
+                data1: 0,
+                data2: 0,
+                track_index,
+                sysex_index: None,
+            })
+        }
+        _ => None,
+    };
+    if let Some(event) = event {
+        writer.push_event(&event, sysex_data.as_deref());
+        return Ok(());
+    }
+
+    let text = || -> Result<Vec<u8>, String> {
+        fields.get(3).cloned().ok_or_else(|| "missing text".into())
+    };
+    let (meta_type, data) = match record.as_str() {
+        "sequence_number" => (0x00, number::<u16>(&fields, 3)?.to_be_bytes().to_vec()),
+        "text_t" => (0x01, text()?),
+        "copyright_t" => (0x02, text()?),
+        "title_t" => (0x03, text()?),
+        "instrument_name_t" => (0x04, text()?),
+        "lyric_t" => (0x05, text()?),
+        "marker_t" => (0x06, text()?),
+        "cue_point_t" => (0x07, text()?),
+        "channel_prefix" => (0x20, vec![number(&fields, 3)?]),
+        "midi_port" => (0x21, vec![number(&fields, 3)?]),
+        "tempo" => (0x51, number::<u32>(&fields, 3)?.to_be_bytes()[1..].to_vec()),
+        "smpte_offset" => (
+            0x54,
+            (3..8)
+                .map(|i| number(&fields, i))
+                .collect::<Result<_, _>>()?,
+        ),
+        "time_signature" => (
+            0x58,
+            (3..7)
+                .map(|i| number(&fields, i))
+                .collect::<Result<_, _>>()?,
+        ),
+        "key_signature" => {
+            let sharps: i8 = number(&fields, 3)?;
+            let minor = text_field_eq(&fields, 4, "minor");
+            (0x59, vec![sharps as u8, minor as u8])
+        }
+        "sequencer_specific" => (0x7F, bytes(&fields, 3)?),
+        "unknown_meta_event" => (number(&fields, 3)?, bytes(&fields, 4)?),
+        _ => return Err(format!("unknown record type {:?}", record)),
+    };
+    writer.push_meta(&MetaEvent {
+        absolute_ns: 0,
+        absolute_tick: tick,
+        track_index,
+        meta_type,
+        data,
+    });
+    Ok(())
+}
+
+fn text_field_eq(fields: &[Vec<u8>], index: usize, expected: &str) -> bool {
+    fields
+        .get(index)
+        .is_some_and(|field| field.eq_ignore_ascii_case(expected.as_bytes()))
+}
@@ -0,0 +1,37 @@
+use std::io::{self, Write};
+
+use serde::Serialize;
+
+use crate::{MetaEvent, MidiEvent, MidiHeader, MidiSequence, TempoMap};
+
+#[derive(Serialize)]
+struct SequenceJson<'a> {
+    header: &'a MidiHeader,
+    tempo_map: &'a TempoMap,
+    events: &'a [MidiEvent],
+    sysex: &'a [Vec<u8>],
+    metas: &'a [MetaEvent],
+}
+
+impl<'a> SequenceJson<'a> {
+    fn new(sequence: &'a MidiSequence) -> SequenceJson<'a> {
+        SequenceJson {
+            header: sequence.header(),
+            tempo_map: sequence.tempo_map(),
+            events: sequence.events(),
+            sysex: sequence.sysex_table(),
+            metas: sequence.metas(),
+        }
+    }
+}
+
+/// The header, tempo map, events, SysEx table and meta events of `sequence`
+/// as one JSON object.
+pub fn to_json(sequence: &MidiSequence) -> String {
+    serde_json::to_string(&SequenceJson::new(sequence)).expect("sequences always serialize")
+}
+
+/// Like `to_json`, streamed into `writer` instead of built up in memory.
+pub fn write_json<W: Write>(sequence: &MidiSequence, writer: W) -> io::Result<()> {
+    serde_json::to_writer(writer, &SequenceJson::new(sequence)).map_err(io::Error::from)
+}
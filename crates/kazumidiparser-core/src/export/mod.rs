@@ -0,0 +1,10 @@
+pub mod csv;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod musicxml;
+#[cfg(feature = "osc")]
+pub mod osc;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+pub mod smf;
+pub mod ump;
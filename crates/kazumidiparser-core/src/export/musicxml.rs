@@ -0,0 +1,470 @@
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+use crate::{MetaKind, MidiSequence, pitch};
+
+#[derive(Debug, Clone)]
+pub struct ScoreNote {
+    pub start_tick: u64,
+    pub duration_ticks: u64,
+    pub key: u8,
+    pub velocity: u8,
+}
+
+#[derive(Debug, Clone)]
+pub struct ScorePart {
+    pub name: String,
+    pub notes: Vec<ScoreNote>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TimeSignature {
+    pub tick: u64,
+    pub numerator: u8,
+    pub denominator: u8,
+}
+
+/// Key signatures outside MusicXML's -7..=7 `fifths` range are left out.
+#[derive(Debug, Clone, Copy)]
+pub struct KeySignature {
+    pub tick: u64,
+    pub fifths: i8,
+    pub minor: bool,
+}
+
+/// Minimal MusicXML (partwise) exporter.
+///
+/// Each part is written as a single voice: notes starting on the same tick
+/// become a chord, overlapping notes are cut at the next onset, gaps become
+/// rests and anything crossing a barline is split and tied. Every measure up
+/// to the last note is written, so a score longer than `MAX_MEASURES` is
+/// refused rather than written out.
+#[derive(Debug, Clone)]
+pub struct MusicXmlExport {
+    pub divisions: u16,
+    pub title: Option<String>,
+    pub parts: Vec<ScorePart>,
+    pub time_signatures: Vec<TimeSignature>,
+    pub key_signatures: Vec<KeySignature>,
+}
+
+pub const MAX_MEASURES: usize = 100_000;
+
+#[derive(Debug)]
+enum Item {
+    Rest {
+        start: u64,
+        duration: u64,
+    },
+    Chord {
+        start: u64,
+        duration: u64,
+        keys: Vec<(u8, u8)>,
+    },
+}
+
+struct Measure {
+    start: u64,
+    length: u64,
+    time: Option<TimeSignature>,
+    key: Option<KeySignature>,
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn note_type(duration: u64, divisions: u64) -> Option<(&'static str, bool)> {
+    const TYPES: [(&str, u64, u64); 7] = [
+        ("whole", 4, 1),
+        ("half", 2, 1),
+        ("quarter", 1, 1),
+        ("eighth", 1, 2),
+        ("16th", 1, 4),
+        ("32nd", 1, 8),
+        ("64th", 1, 16),
+    ];
+    for (name, num, den) in TYPES {
+        if duration * den == divisions * num {
+            return Some((name, false));
+        }
+        if duration * den * 2 == divisions * num * 3 {
+            return Some((name, true));
+        }
+    }
+    None
+}
+
+impl MusicXmlExport {
+    pub fn new(divisions: u16) -> MusicXmlExport {
+        MusicXmlExport {
+            divisions,
+            title: None,
+            parts: Vec::new(),
+            time_signatures: Vec::new(),
+            key_signatures: Vec::new(),
+        }
+    }
+
+    /// An export of a parsed song: one part per track that has notes, named
+    /// after the track, with the song's time and key signatures. Divisions
+    /// are the sequence's ticks per quarter note.
+    pub fn from_sequence(sequence: &MidiSequence) -> MusicXmlExport {
+        let mut export = MusicXmlExport::new(sequence.tempo_map().ppqn().max(1));
+        let mut parts: Vec<ScorePart> = sequence
+            .track_info()
+            .into_iter()
+            .map(|info| ScorePart {
+                name: info
+                    .name
+                    .unwrap_or_else(|| format!("Track {}", info.index + 1)),
+                notes: Vec::new(),
+            })
+            .collect();
+        for note in sequence.notes() {
+            if let Some(part) = parts.get_mut(note.track_index as usize) {
+                part.notes.push(ScoreNote {
+                    start_tick: note.start_tick,
+                    duration_ticks: note.duration_ticks,
+                    key: note.key,
+                    velocity: note.velocity,
+                });
+            }
+        }
+        export.parts = parts.into_iter().filter(|p| !p.notes.is_empty()).collect();
+
+        for meta in sequence.metas() {
+            match meta.kind() {
+                MetaKind::TimeSignature {
+                    numerator,
+                    denominator_log2,
+                    ..
+                } => {
+                    // Denominators past 128 cannot be written.
+                    let Some(denominator) = 1u8.checked_shl(denominator_log2 as u32) else {
+                        continue;
+                    };
+                    export.time_signatures.push(TimeSignature {
+                        tick: meta.absolute_tick,
+                        numerator,
+                        denominator,
+                    });
+                }
+                MetaKind::KeySignature { sharps, minor } => {
+                    export.key_signatures.push(KeySignature {
+                        tick: meta.absolute_tick,
+                        fifths: sharps,
+                        minor,
+                    });
+                }
+                _ => {}
+            }
+        }
+        export
+    }
+
+    fn part_items(&self, part: &ScorePart) -> Vec<Item> {
+        let mut notes: Vec<&ScoreNote> =
+            part.notes.iter().filter(|n| n.duration_ticks > 0).collect();
+        notes.sort_by_key(|n| (n.start_tick, n.key));
+
+        let mut items = Vec::new();
+        let mut cursor = 0u64;
+        let mut i = 0;
+        while i < notes.len() {
+            let start = notes[i].start_tick;
+            let mut end = start;
+            let mut keys = Vec::new();
+            while i < notes.len() && notes[i].start_tick == start {
+                end = end.max(start.saturating_add(notes[i].duration_ticks));
+                if !keys.iter().any(|(k, _)| *k == notes[i].key) {
+                    keys.push((notes[i].key, notes[i].velocity));
+                }
+                i += 1;
+            }
+            if let Some(next) = notes.get(i) {
+                end = end.min(next.start_tick);
+            }
+
+            if start < cursor {
+                continue;
+            }
+            if start > cursor {
+                items.push(Item::Rest {
+                    start: cursor,
+                    duration: start - cursor,
+                });
+            }
+            items.push(Item::Chord {
+                start,
+                duration: end - start,
+                keys,
+            });
+            cursor = end;
+        }
+        items
+    }
+
+    fn measures(&self, end_tick: u64) -> io::Result<Vec<Measure>> {
+        let mut time_signatures = self.time_signatures.clone();
+        time_signatures.sort_by_key(|t| t.tick);
+        let mut key_signatures = self.key_signatures.clone();
+        key_signatures.retain(|k| (-7..=7).contains(&k.fifths));
+        key_signatures.sort_by_key(|k| k.tick);
+
+        let divisions = self.divisions.max(1) as u64;
+        let mut current_time = TimeSignature {
+            tick: 0,
+            numerator: 4,
+            denominator: 4,
+        };
+        let mut next_time = 0;
+        let mut next_key = 0;
+        let mut measures: Vec<Measure> = Vec::new();
+        let mut start = 0u64;
+
+        loop {
+            let mut time = None;
+            while next_time < time_signatures.len() && time_signatures[next_time].tick <= start {
+                current_time = time_signatures[next_time];
+                time = Some(current_time);
+                next_time += 1;
+            }
+            let mut key = None;
+            while next_key < key_signatures.len() && key_signatures[next_key].tick <= start {
+                key = Some(key_signatures[next_key]);
+                next_key += 1;
+            }
+            if measures.is_empty() {
+                time = Some(time.unwrap_or(current_time));
+                key = Some(key.unwrap_or(KeySignature {
+                    tick: 0,
+                    fifths: 0,
+                    minor: false,
+                }));
+            }
+
+            let mut length = divisions * 4 * current_time.numerator.max(1) as u64
+                / current_time.denominator.max(1) as u64;
+            // A time signature change mid-measure ends the measure early.
+            if let Some(next) = time_signatures.get(next_time)
+                && next.tick > start
+                && next.tick < start + length
+            {
+                length = next.tick - start;
+            }
+            let length = length.max(1);
+
+            if measures.len() == MAX_MEASURES {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Score needs more than {} measures", MAX_MEASURES),
+                ));
+            }
+            measures.push(Measure {
+                start,
+                length,
+                time,
+                key,
+            });
+            start = start.saturating_add(length);
+            if start >= end_tick {
+                break;
+            }
+        }
+        Ok(measures)
+    }
+
+    fn write_note(
+        &self,
+        out: &mut String,
+        keys: Option<&[(u8, u8)]>,
+        duration: u64,
+        tie_stop: bool,
+        tie_start: bool,
+        fifths: i8,
+    ) {
+        let divisions = self.divisions.max(1) as u64;
+        let note_type = note_type(duration, divisions);
+        let chord_keys: Vec<Option<(u8, u8)>> = match keys {
+            Some(keys) => keys.iter().map(|k| Some(*k)).collect(),
+            None => vec![None],
+        };
+
+        for (index, key) in chord_keys.iter().enumerate() {
+            match key {
+                // MusicXML dynamics are a percentage of forte (velocity 90).
+                Some((_, velocity)) => {
+                    let _ = writeln!(
+                        out,
+                        "      <note dynamics=\"{}\">",
+                        *velocity as u32 * 100 / 90
+                    );
+                }
+                None => out.push_str("      <note>\n"),
+            }
+            if index > 0 {
+                out.push_str("        <chord/>\n");
+            }
+            match key {
+                Some((key, _)) => {
+                    let spelled = pitch::spell(*key, fifths);
+                    out.push_str("        <pitch>\n");
+                    let _ = writeln!(out, "          <step>{}</step>", spelled.letter);
+                    if spelled.alter != 0 {
+                        let _ = writeln!(out, "          <alter>{}</alter>", spelled.alter);
+                    }
+                    let _ = writeln!(out, "          <octave>{}</octave>", spelled.octave);
+                    out.push_str("        </pitch>\n");
+                }
+                None => out.push_str("        <rest/>\n"),
+            }
+            let _ = writeln!(out, "        <duration>{}</duration>", duration);
+            if key.is_some() {
+                if tie_stop {
+                    out.push_str("        <tie type=\"stop\"/>\n");
+                }
+                if tie_start {
+                    out.push_str("        <tie type=\"start\"/>\n");
+                }
+            }
+            out.push_str("        <voice>1</voice>\n");
+            if let Some((name, dotted)) = note_type {
+                let _ = writeln!(out, "        <type>{}</type>", name);
+                if dotted {
+                    out.push_str("        <dot/>\n");
+                }
+            }
+            if key.is_some() && (tie_stop || tie_start) {
+                out.push_str("        <notations>\n");
+                if tie_stop {
+                    out.push_str("          <tied type=\"stop\"/>\n");
+                }
+                if tie_start {
+                    out.push_str("          <tied type=\"start\"/>\n");
+                }
+                out.push_str("        </notations>\n");
+            }
+            out.push_str("      </note>\n");
+        }
+    }
+
+    fn write_part(&self, out: &mut String, part_id: &str, part: &ScorePart, measures: &[Measure]) {
+        let items = self.part_items(part);
+        let mut fifths = 0i8;
+
+        let _ = writeln!(out, "  <part id=\"{}\">", part_id);
+        let mut item_index = 0;
+        for (number, measure) in measures.iter().enumerate() {
+            let _ = writeln!(out, "    <measure number=\"{}\">", number + 1);
+            if measure.time.is_some() || measure.key.is_some() {
+                out.push_str("      <attributes>\n");
+                if number == 0 {
+                    let _ = writeln!(out, "        <divisions>{}</divisions>", self.divisions);
+                }
+                if let Some(key) = measure.key {
+                    fifths = key.fifths;
+                    out.push_str("        <key>\n");
+                    let _ = writeln!(out, "          <fifths>{}</fifths>", key.fifths);
+                    let _ = writeln!(
+                        out,
+                        "          <mode>{}</mode>",
+                        if key.minor { "minor" } else { "major" }
+                    );
+                    out.push_str("        </key>\n");
+                }
+                if let Some(time) = measure.time {
+                    out.push_str("        <time>\n");
+                    let _ = writeln!(out, "          <beats>{}</beats>", time.numerator);
+                    let _ = writeln!(out, "          <beat-type>{}</beat-type>", time.denominator);
+                    out.push_str("        </time>\n");
+                }
+                if number == 0 {
+                    out.push_str("        <clef>\n          <sign>G</sign>\n          <line>2</line>\n        </clef>\n");
+                }
+                out.push_str("      </attributes>\n");
+            }
+
+            let measure_end = measure.start + measure.length;
+            let mut filled = measure.start;
+            while let Some(item) = items.get(item_index) {
+                let (start, duration, keys) = match item {
+                    Item::Rest { start, duration } => (*start, *duration, None),
+                    Item::Chord {
+                        start,
+                        duration,
+                        keys,
+                    } => (*start, *duration, Some(keys.as_slice())),
+                };
+                let end = start + duration;
+                if start >= measure_end {
+                    break;
+                }
+                let clipped_start = start.max(measure.start);
+                let clipped_end = end.min(measure_end);
+                if clipped_end > clipped_start {
+                    self.write_note(
+                        out,
+                        keys,
+                        clipped_end - clipped_start,
+                        clipped_start > start,
+                        clipped_end < end,
+                        fifths,
+                    );
+                    filled = clipped_end;
+                }
+                if end > measure_end {
+                    break;
+                }
+                item_index += 1;
+            }
+            if filled < measure_end {
+                self.write_note(out, None, measure_end - filled, false, false, fifths);
+            }
+            out.push_str("    </measure>\n");
+        }
+        out.push_str("  </part>\n");
+    }
+
+    pub fn to_xml_string(&self) -> io::Result<String> {
+        let end_tick = self
+            .parts
+            .iter()
+            .flat_map(|p| p.notes.iter())
+            .map(|n| n.start_tick.saturating_add(n.duration_ticks))
+            .max()
+            .unwrap_or(0);
+        let measures = self.measures(end_tick)?;
+
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n");
+        out.push_str("<!DOCTYPE score-partwise PUBLIC \"-//Recordare//DTD MusicXML 4.0 Partwise//EN\" \"http://www.musicxml.org/dtds/partwise.dtd\">\n");
+        out.push_str("<score-partwise version=\"4.0\">\n");
+        if let Some(title) = &self.title {
+            let _ = writeln!(
+                out,
+                "  <work>\n    <work-title>{}</work-title>\n  </work>",
+                escape(title)
+            );
+        }
+        out.push_str("  <part-list>\n");
+        for (index, part) in self.parts.iter().enumerate() {
+            let _ = writeln!(out, "    <score-part id=\"P{}\">", index + 1);
+            let _ = writeln!(out, "      <part-name>{}</part-name>", escape(&part.name));
+            out.push_str("    </score-part>\n");
+        }
+        out.push_str("  </part-list>\n");
+        for (index, part) in self.parts.iter().enumerate() {
+            self.write_part(&mut out, &format!("P{}", index + 1), part, &measures);
+        }
+        out.push_str("</score-partwise>\n");
+        Ok(out)
+    }
+
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(self.to_xml_string()?.as_bytes())
+    }
+}
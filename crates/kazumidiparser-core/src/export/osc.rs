@@ -0,0 +1,202 @@
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+
+use crate::playback::EventSubscriber;
+use crate::{MidiEvent, MidiSequence};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OscArg {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    String(String),
+    Blob(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OscMessage {
+    pub address: String,
+    pub args: Vec<OscArg>,
+}
+
+/// Controls how MIDI events are turned into OSC addresses and arguments.
+///
+/// `address_template` may contain `{kind}`, `{channel}` (1-16) and `{track}`
+/// placeholders, e.g. `/midi/{track}/{kind}` or `/ch{channel}/{kind}`.
+#[derive(Debug, Clone)]
+pub struct OscMapping {
+    pub address_template: String,
+    pub include_channel_arg: bool,
+    pub include_time_arg: bool,
+}
+
+pub struct OscSender {
+    socket: UdpSocket,
+    mapping: OscMapping,
+}
+
+fn pad_to_4(buffer: &mut Vec<u8>) {
+    while !buffer.len().is_multiple_of(4) {
+        buffer.push(0);
+    }
+}
+
+fn write_osc_string(buffer: &mut Vec<u8>, s: &str) {
+    buffer.extend_from_slice(s.as_bytes());
+    buffer.push(0);
+    pad_to_4(buffer);
+}
+
+impl OscMessage {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        write_osc_string(&mut buffer, &self.address);
+
+        let mut type_tags = String::from(",");
+        for arg in &self.args {
+            type_tags.push(match arg {
+                OscArg::Int(_) => 'i',
+                OscArg::Long(_) => 'h',
+                OscArg::Float(_) => 'f',
+                OscArg::String(_) => 's',
+                OscArg::Blob(_) => 'b',
+            });
+        }
+        write_osc_string(&mut buffer, &type_tags);
+
+        for arg in &self.args {
+            match arg {
+                OscArg::Int(v) => buffer.extend_from_slice(&v.to_be_bytes()),
+                OscArg::Long(v) => buffer.extend_from_slice(&v.to_be_bytes()),
+                OscArg::Float(v) => buffer.extend_from_slice(&v.to_be_bytes()),
+                OscArg::String(s) => write_osc_string(&mut buffer, s),
+                OscArg::Blob(data) => {
+                    buffer.extend_from_slice(&(data.len() as i32).to_be_bytes());
+                    buffer.extend_from_slice(data);
+                    pad_to_4(&mut buffer);
+                }
+            }
+        }
+        buffer
+    }
+}
+
+impl Default for OscMapping {
+    fn default() -> Self {
+        OscMapping {
+            address_template: "/midi/{kind}".to_string(),
+            include_channel_arg: true,
+            include_time_arg: false,
+        }
+    }
+}
+
+impl OscMapping {
+    fn address(&self, kind: &str, channel: Option<u8>, track_index: u16) -> String {
+        let channel = match channel {
+            Some(ch) => (ch + 1).to_string(),
+            None => "all".to_string(),
+        };
+        self.address_template
+            .replace("{kind}", kind)
+            .replace("{channel}", &channel)
+            .replace("{track}", &track_index.to_string())
+    }
+
+    /// The message for `event`, if it maps to one. A SysEx is only sent with
+    /// its bytes in `sysex_data`.
+    pub fn message_for(&self, event: &MidiEvent, sysex_data: Option<&[u8]>) -> Option<OscMessage> {
+        let channel = event.status & 0x0F;
+        let pair = || {
+            vec![
+                OscArg::Int(event.data1 as i32),
+                OscArg::Int(event.data2 as i32),
+            ]
+        };
+        let (kind, values): (&str, Vec<OscArg>) = match event.status & 0xF0 {
+            0x80 => ("note_off", pair()),
+            0x90 if event.data2 == 0 => (
+                "note_off",
+                vec![OscArg::Int(event.data1 as i32), OscArg::Int(0)],
+            ),
+            0x90 => ("note_on", pair()),
+            0xA0 => ("poly_pressure", pair()),
+            0xB0 => ("cc", pair()),
+            0xC0 => ("program", vec![OscArg::Int(event.data1 as i32)]),
+            0xD0 => ("channel_pressure", vec![OscArg::Int(event.data1 as i32)]),
+            0xE0 => {
+                let bend = ((event.data2 as i32) << 7 | event.data1 as i32) - 8192;
+                ("pitch_bend", vec![OscArg::Int(bend)])
+            }
+            0xF0 => {
+                let data = sysex_data?.to_vec();
+                let mut args = vec![OscArg::Blob(data)];
+                if self.include_time_arg {
+                    args.push(OscArg::Long(event.absolute_ns as i64));
+                }
+                return Some(OscMessage {
+                    address: self.address("sysex", None, event.track_index),
+                    args,
+                });
+            }
+            _ => return None,
+        };
+
+        let mut args = Vec::with_capacity(values.len() + 2);
+        if self.include_channel_arg {
+            args.push(OscArg::Int(channel as i32 + 1));
+        }
+        args.extend(values);
+        if self.include_time_arg {
+            args.push(OscArg::Long(event.absolute_ns as i64));
+        }
+
+        Some(OscMessage {
+            address: self.address(kind, Some(channel), event.track_index),
+            args,
+        })
+    }
+}
+
+impl OscSender {
+    pub fn connect<A: ToSocketAddrs>(target: A, mapping: OscMapping) -> io::Result<OscSender> {
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket.connect(target)?;
+        Ok(OscSender { socket, mapping })
+    }
+
+    pub fn mapping(&self) -> &OscMapping {
+        &self.mapping
+    }
+
+    pub fn send_message(&self, message: &OscMessage) -> io::Result<()> {
+        self.socket.send(&message.encode())?;
+        Ok(())
+    }
+
+    pub fn send_event(&self, event: &MidiEvent, sysex_data: Option<&[u8]>) -> io::Result<()> {
+        match self.mapping.message_for(event, sysex_data) {
+            Some(message) => self.send_message(&message),
+            None => Ok(()),
+        }
+    }
+
+    // `events` belong to `sequence`, which holds their SysEx payloads.
+    pub fn send_events(&self, sequence: &MidiSequence, events: &[MidiEvent]) -> io::Result<()> {
+        for event in events {
+            self.send_event(event, sequence.sysex(event))?;
+        }
+        Ok(())
+    }
+
+    // Forwards everything currently queued on a playback bus subscriber. The
+    // bus carries no SysEx payloads, so SysEx events are left out.
+    pub fn forward(&self, subscriber: &EventSubscriber) -> io::Result<usize> {
+        let mut sent = 0;
+        for event in subscriber.drain() {
+            self.send_event(&event, None)?;
+            sent += 1;
+        }
+        Ok(sent)
+    }
+}
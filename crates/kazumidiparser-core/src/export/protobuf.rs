@@ -0,0 +1,259 @@
+use std::error::Error as StdError;
+
+use prost::Message;
+
+use crate::{
+    EventOrder, MetaEvent, MetaKind, MidiEvent, MidiHeader, MidiSequence, TempoMap, TextEncoding,
+    TrackMeta,
+};
+
+/// Message types for `proto/kazumidiparser.proto`, written out by hand so
+/// building does not need `protoc`. Keep the tags in sync with the schema.
+pub mod proto {
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Header {
+        #[prost(uint32, tag = "1")]
+        pub format: u32,
+        #[prost(uint32, tag = "2")]
+        pub tracks: u32,
+        #[prost(uint32, tag = "3")]
+        pub ppqn: u32,
+        #[prost(bool, tag = "4")]
+        pub exact_timing: bool,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Event {
+        #[prost(uint64, tag = "1")]
+        pub absolute_ns: u64,
+        #[prost(uint64, tag = "2")]
+        pub absolute_tick: u64,
+        #[prost(uint32, tag = "3")]
+        pub status: u32,
+        #[prost(uint32, tag = "4")]
+        pub data1: u32,
+        #[prost(uint32, tag = "5")]
+        pub data2: u32,
+        #[prost(uint32, tag = "6")]
+        pub track_index: u32,
+        #[prost(bytes = "vec", optional, tag = "7")]
+        pub sysex_data: Option<Vec<u8>>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct TempoChange {
+        #[prost(uint64, tag = "1")]
+        pub absolute_tick: u64,
+        #[prost(uint64, tag = "2")]
+        pub absolute_ns: u64,
+        #[prost(uint32, tag = "3")]
+        pub tempo_us: u32,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct MetaEvent {
+        #[prost(uint64, tag = "1")]
+        pub absolute_ns: u64,
+        #[prost(uint64, tag = "2")]
+        pub absolute_tick: u64,
+        #[prost(uint32, tag = "3")]
+        pub track_index: u32,
+        #[prost(uint32, tag = "4")]
+        pub meta_type: u32,
+        #[prost(bytes = "vec", tag = "5")]
+        pub data: Vec<u8>,
+    }
+
+    #[derive(Clone, PartialEq, prost::Message)]
+    pub struct Sequence {
+        #[prost(message, optional, tag = "1")]
+        pub header: Option<Header>,
+        #[prost(message, repeated, tag = "2")]
+        pub events: Vec<Event>,
+        #[prost(message, repeated, tag = "3")]
+        pub tempo_map: Vec<TempoChange>,
+        #[prost(message, repeated, tag = "4")]
+        pub metas: Vec<MetaEvent>,
+    }
+}
+
+/// The `.proto` schema the messages follow, for shipping alongside exports.
+pub const SCHEMA: &str = include_str!("../../proto/kazumidiparser.proto");
+
+pub fn to_proto(sequence: &MidiSequence) -> proto::Sequence {
+    let header = sequence.header();
+    proto::Sequence {
+        header: Some(proto::Header {
+            format: header.format as u32,
+            tracks: header.tracks as u32,
+            ppqn: header.ppqn as u32,
+            exact_timing: sequence.tempo_map().is_exact(),
+        }),
+        events: sequence
+            .events()
+            .iter()
+            .map(|event| proto::Event {
+                absolute_ns: event.absolute_ns,
+                absolute_tick: event.absolute_tick,
+                status: event.status as u32,
+                data1: event.data1 as u32,
+                data2: event.data2 as u32,
+                track_index: event.track_index as u32,
+                sysex_data: sequence.sysex(event).map(<[u8]>::to_vec),
+            })
+            .collect(),
+        tempo_map: sequence
+            .tempo_map()
+            .points()
+            .iter()
+            .map(|point| proto::TempoChange {
+                absolute_tick: point.absolute_tick,
+                absolute_ns: point.absolute_ns,
+                tempo_us: point.tempo_us,
+            })
+            .collect(),
+        metas: sequence
+            .metas()
+            .iter()
+            .map(|meta| proto::MetaEvent {
+                absolute_ns: meta.absolute_ns,
+                absolute_tick: meta.absolute_tick,
+                track_index: meta.track_index as u32,
+                meta_type: meta.meta_type as u32,
+                data: meta.data.clone(),
+            })
+            .collect(),
+    }
+}
+
+fn narrow<T: TryFrom<u32>>(value: u32, field: &str) -> Result<T, Box<dyn StdError>> {
+    T::try_from(value).map_err(|_| format!("{} out of range: {}", field, value).into())
+}
+
+/// Rebuilds a sequence from a message. Event and meta times are taken as
+/// sent; the tempo map is recomputed from the tempo changes, which must be in
+/// tick order.
+pub fn from_proto(message: proto::Sequence) -> Result<MidiSequence, Box<dyn StdError>> {
+    let header = message.header.ok_or("Sequence has no header")?;
+    let exact = header.exact_timing;
+    let header = MidiHeader {
+        format: narrow(header.format, "format")?,
+        tracks: narrow(header.tracks, "tracks")?,
+        ppqn: narrow(header.ppqn, "ppqn")?,
+    };
+
+    let mut sysex = Vec::new();
+    let events = message
+        .events
+        .into_iter()
+        .map(|event| {
+            let sysex_index = event.sysex_data.map(|data| {
+                sysex.push(data);
+                sysex.len() as u32 - 1
+            });
+            Ok(MidiEvent {
+                absolute_ns: event.absolute_ns,
+                absolute_tick: event.absolute_tick,
+                status: narrow(event.status, "status")?,
+                data1: narrow(event.data1, "data1")?,
+                data2: narrow(event.data2, "data2")?,
+                track_index: narrow(event.track_index, "track_index")?,
+                sysex_index,
+            })
+        })
+        .collect::<Result<Vec<_>, Box<dyn StdError>>>()?;
+
+    let metas = message
+        .metas
+        .into_iter()
+        .map(|meta| {
+            Ok(MetaEvent {
+                absolute_ns: meta.absolute_ns,
+                absolute_tick: meta.absolute_tick,
+                track_index: narrow(meta.track_index, "track_index")?,
+                meta_type: narrow(meta.meta_type, "meta_type")?,
+                data: meta.data,
+            })
+        })
+        .collect::<Result<Vec<_>, Box<dyn StdError>>>()?;
+
+    if message
+        .tempo_map
+        .windows(2)
+        .any(|pair| pair[1].absolute_tick < pair[0].absolute_tick)
+    {
+        return Err("Tempo changes are not in tick order".into());
+    }
+    let tempo_map = TempoMap::for_header(
+        &header,
+        message
+            .tempo_map
+            .iter()
+            .map(|change| (change.absolute_tick, change.tempo_us)),
+        exact,
+    );
+
+    // Track names and sequence numbers live in the metas; recover them the
+    // same way the parser does.
+    let mut track_metas = vec![TrackMeta::default(); header.tracks as usize];
+    for meta in &metas {
+        let Some(track) = track_metas.get_mut(meta.track_index as usize) else {
+            continue;
+        };
+        match meta.meta_type {
+            0x00 if meta.data.len() == 2 => {
+                track.sequence_number = Some(u16::from_be_bytes([meta.data[0], meta.data[1]]));
+            }
+            0x03 if track.name.is_none() => {
+                track.name = Some(meta.data.clone());
+            }
+            0x04 if track.instrument_name.is_none() => {
+                track.instrument_name = Some(meta.data.clone());
+            }
+            _ => {}
+        }
+    }
+
+    // Only the first track's map is sent; in format 2 the others come from
+    // each track's own tempo metas.
+    let track_tempo_maps = match header.format {
+        2 => (1..header.tracks)
+            .map(|track_index| {
+                let changes = metas
+                    .iter()
+                    .filter(|meta| meta.track_index == track_index)
+                    .filter_map(|meta| match meta.kind() {
+                        MetaKind::Tempo { us_per_quarter } => {
+                            Some((meta.absolute_tick, us_per_quarter))
+                        }
+                        _ => None,
+                    });
+                TempoMap::for_header(&header, changes, tempo_map.is_exact())
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    Ok(MidiSequence {
+        header,
+        events,
+        sysex,
+        metas,
+        tempo_map,
+        track_tempo_maps,
+        track_metas,
+        warnings: Vec::new(),
+        sound_bank: None,
+        // Protobuf strings are UTF-8.
+        text_encoding: TextEncoding::Utf8,
+        event_order: EventOrder::Track,
+    })
+}
+
+pub fn encode(sequence: &MidiSequence) -> Vec<u8> {
+    to_proto(sequence).encode_to_vec()
+}
+
+pub fn decode(bytes: &[u8]) -> Result<MidiSequence, Box<dyn StdError>> {
+    from_proto(proto::Sequence::decode(bytes)?)
+}
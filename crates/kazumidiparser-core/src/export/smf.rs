@@ -0,0 +1,300 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::tempo::DEFAULT_TEMPO_US;
+use crate::{MetaEvent, MidiEvent, MidiSequence, TempoMap};
+
+// The largest delta time a VLQ can hold.
+const MAX_DELTA: u64 = 0x0FFF_FFFF;
+
+#[derive(Debug, Clone)]
+enum Item {
+    Channel { status: u8, data1: u8, data2: u8 },
+    // `status` is 0xF0 or 0xF7; `data` follows the length as written.
+    SysEx { status: u8, data: Vec<u8> },
+    Meta { meta_type: u8, data: Vec<u8> },
+}
+
+/// Writes events back out as a Standard MIDI File.
+///
+/// Events are placed by `absolute_tick` and `track_index`; their times in
+/// nanoseconds are ignored, so the tempo has to come from tempo metas or
+/// `push_tempo_map`. Events on the same tick keep the order they were pushed
+/// in. A format 0 file gets every track merged into one; otherwise there is
+/// one `MTrk` chunk per track index up to the highest used. Each track ends
+/// with an End of Track event on its last tick.
+#[derive(Debug, Clone)]
+pub struct MidiWriter {
+    pub format: u16,
+    // The raw division field, as in `MidiHeader::ppqn`.
+    pub ppqn: u16,
+    /// Leave out repeated channel statuses. Meta and SysEx events cancel
+    /// running status, as the SMF spec requires.
+    pub running_status: bool,
+    // Per track, (absolute_tick, item) in push order.
+    tracks: Vec<Vec<(u64, Item)>>,
+}
+
+fn write_vlq(out: &mut Vec<u8>, value: u32) {
+    let mut bytes = [0u8; 4];
+    let mut len = 0;
+    let mut value = value & 0x0FFF_FFFF;
+    loop {
+        bytes[len] = (value & 0x7F) as u8 | if len > 0 { 0x80 } else { 0 };
+        len += 1;
+        value >>= 7;
+        if value == 0 {
+            break;
+        }
+    }
+    out.extend(bytes[..len].iter().rev());
+}
+
+impl MidiWriter {
+    pub fn new(format: u16, ppqn: u16) -> MidiWriter {
+        MidiWriter {
+            format,
+            ppqn,
+            running_status: true,
+            tracks: Vec::new(),
+        }
+    }
+
+    /// A writer holding everything in `sequence`: its events, its meta
+    /// events and its tempo map in place of the tempo metas, so a
+    /// `fixed_bpm` parse writes out at that BPM. Format 2 and SMPTE files
+    /// keep their tempo metas, as the merged map would not fit them.
+    pub fn from_sequence(sequence: &MidiSequence) -> MidiWriter {
+        let header = sequence.header();
+        let mut writer = MidiWriter::new(header.format, header.ppqn);
+        writer.push_sequence(
+            sequence,
+            |meta| Some(meta.track_index),
+            |event| event.track_index,
+        );
+        writer
+    }
+
+    /// `sequence` flattened into a format 0 file, for players that take
+    /// nothing else. Channels are kept as they are. Of the metas that only
+    /// mean something per track (sequence number, names, channel prefix and
+    /// port), only the first track's are kept. A format 2 file's patterns
+    /// would all play at once; convert its `sequences()` one at a time.
+    pub fn to_format_0(sequence: &MidiSequence) -> MidiWriter {
+        let mut writer = MidiWriter::new(0, sequence.header().ppqn);
+        writer.push_sequence(
+            sequence,
+            |meta| {
+                let per_track = matches!(meta.meta_type, 0x00 | 0x03 | 0x04 | 0x20 | 0x21);
+                (meta.track_index == 0 || !per_track).then_some(0)
+            },
+            |_| 0,
+        );
+        writer
+    }
+
+    /// `sequence` as a format 1 file with a track per channel: the first
+    /// track holds every meta event and SysEx, and is followed by one track
+    /// for each channel used, lowest channel first.
+    pub fn split_by_channel(sequence: &MidiSequence) -> MidiWriter {
+        let mut used = 0u16;
+        for event in sequence.events() {
+            if event.status < 0xF0 {
+                used |= 1 << (event.status & 0x0F);
+            }
+        }
+        let mut writer = MidiWriter::new(1, sequence.header().ppqn);
+        writer.push_sequence(
+            sequence,
+            |_| Some(0),
+            |event| {
+                let channel = event.status & 0x0F;
+                if event.status >= 0xF0 {
+                    0
+                } else {
+                    // One past the number of used channels below this one.
+                    (used & ((1 << channel) - 1)).count_ones() as u16 + 1
+                }
+            },
+        );
+        writer
+    }
+
+    // Pushes the tempo map, metas and events of `sequence`, putting each on
+    // the track `meta_track` or `event_track` picks. Metas `meta_track`
+    // gives None for are left out.
+    fn push_sequence<M, E>(&mut self, sequence: &MidiSequence, meta_track: M, event_track: E)
+    where
+        M: Fn(&MetaEvent) -> Option<u16>,
+        E: Fn(&MidiEvent) -> u16,
+    {
+        let header = sequence.header();
+        let keep_tempo_metas = header.format == 2 || header.tempo_timing().1.is_some();
+        if !keep_tempo_metas {
+            self.push_tempo_map(sequence.tempo_map());
+        }
+        for meta in sequence.metas() {
+            if !keep_tempo_metas && meta.meta_type == 0x51 {
+                continue;
+            }
+            if let Some(track_index) = meta_track(meta) {
+                self.push_meta(&MetaEvent {
+                    track_index,
+                    ..meta.clone()
+                });
+            }
+        }
+        for event in sequence.events() {
+            let event = MidiEvent {
+                track_index: event_track(event),
+                ..*event
+            };
+            self.push_event(&event, sequence.sysex(&event));
+        }
+    }
+
+    fn track(&mut self, track_index: u16) -> &mut Vec<(u64, Item)> {
+        let track_index = track_index as usize;
+        if self.tracks.len() <= track_index {
+            self.tracks.resize_with(track_index + 1, Vec::new);
+        }
+        &mut self.tracks[track_index]
+    }
+
+    /// Adds a channel message, or a SysEx (status 0xF0) or escape (0xF7)
+    /// packet with its bytes in `sysex_data`, as `MidiSequence::sysex`
+    /// returns them.
+    pub fn push_event(&mut self, event: &MidiEvent, sysex_data: Option<&[u8]>) {
+        let item = match event.status {
+            0xF0 | 0xF7 => Item::SysEx {
+                status: event.status,
+                data: sysex_data.unwrap_or_default().to_vec(),
+            },
+            status => Item::Channel {
+                status,
+                data1: event.data1,
+                data2: event.data2,
+            },
+        };
+        self.track(event.track_index)
+            .push((event.absolute_tick, item));
+    }
+
+    /// Adds a meta event. End of Track events are skipped, as every track
+    /// gets one when written.
+    pub fn push_meta(&mut self, meta: &MetaEvent) {
+        if meta.meta_type == 0x2F {
+            return;
+        }
+        let item = Item::Meta {
+            meta_type: meta.meta_type,
+            data: meta.data.clone(),
+        };
+        self.track(meta.track_index)
+            .push((meta.absolute_tick, item));
+    }
+
+    /// Adds the tempo changes of `tempo_map` to the first track, leaving out
+    /// the implied 120 BPM at tick 0.
+    pub fn push_tempo_map(&mut self, tempo_map: &TempoMap) {
+        let track = self.track(0);
+        for (tick, tempo_us) in tempo_map.changes() {
+            if tick == 0 && tempo_us == DEFAULT_TEMPO_US {
+                continue;
+            }
+            let item = Item::Meta {
+                meta_type: 0x51,
+                data: tempo_us.to_be_bytes()[1..].to_vec(),
+            };
+            track.push((tick, item));
+        }
+    }
+
+    fn write_track(&self, items: &[&(u64, Item)], out: &mut Vec<u8>) {
+        let mut last_tick = 0;
+        let mut running_status = None;
+        for (tick, item) in items {
+            let mut delta = tick - last_tick;
+            // Longer gaps are bridged with empty text events.
+            while delta > MAX_DELTA {
+                write_vlq(out, MAX_DELTA as u32);
+                out.extend([0xFF, 0x01, 0x00]);
+                delta -= MAX_DELTA;
+            }
+            write_vlq(out, delta as u32);
+            last_tick = *tick;
+
+            match item {
+                Item::Channel {
+                    status,
+                    data1,
+                    data2,
+                } => {
+                    if !self.running_status || running_status != Some(*status) {
+                        out.push(*status);
+                    }
+                    running_status = Some(*status);
+                    out.push(data1 & 0x7F);
+                    if !matches!(status & 0xF0, 0xC0 | 0xD0) {
+                        out.push(data2 & 0x7F);
+                    }
+                }
+                Item::SysEx { status, data } => {
+                    running_status = None;
+                    out.push(*status);
+                    write_vlq(out, data.len() as u32);
+                    out.extend(data);
+                }
+                Item::Meta { meta_type, data } => {
+                    running_status = None;
+                    out.extend([0xFF, *meta_type]);
+                    write_vlq(out, data.len() as u32);
+                    out.extend(data);
+                }
+            }
+        }
+        out.extend([0x00, 0xFF, 0x2F, 0x00]);
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let merged;
+        let tracks: Vec<&[(u64, Item)]> = if self.format == 0 {
+            merged = self.tracks.concat();
+            vec![&merged]
+        } else if self.tracks.is_empty() {
+            vec![&[]]
+        } else {
+            self.tracks.iter().map(Vec::as_slice).collect()
+        };
+
+        let mut out = Vec::new();
+        out.extend(b"MThd");
+        out.extend(6u32.to_be_bytes());
+        out.extend(self.format.to_be_bytes());
+        out.extend((tracks.len() as u16).to_be_bytes());
+        out.extend(self.ppqn.to_be_bytes());
+
+        let mut body = Vec::new();
+        for track in tracks {
+            let mut items: Vec<&(u64, Item)> = track.iter().collect();
+            items.sort_by_key(|(tick, _)| *tick);
+            body.clear();
+            self.write_track(&items, &mut body);
+            out.extend(b"MTrk");
+            out.extend((body.len() as u32).to_be_bytes());
+            out.extend(&body);
+        }
+        out
+    }
+
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.to_bytes())
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()
+    }
+}
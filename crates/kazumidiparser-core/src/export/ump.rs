@@ -0,0 +1,206 @@
+//! Universal MIDI Packets from parsed channel voice events, for hosts that
+//! take MIDI 2.0 input.
+//!
+//! With `UmpProtocol::Midi2` each event is upscaled the way the MIDI 2.0
+//! translation rules describe: values are widened with min-center-max
+//! scaling, bank selects are folded into the next program change, and RPN
+//! and NRPN data entry becomes a registered or assignable controller message.
+
+use crate::{MidiEvent, MidiSequence};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UmpProtocol {
+    // MIDI 1.0 channel voice messages (message type 2), one word each.
+    Midi1,
+    // MIDI 2.0 channel voice messages (message type 4), two words each.
+    Midi2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UmpPacket {
+    pub absolute_ns: u64,
+    pub absolute_tick: u64,
+    words: [u32; 2],
+    len: u8,
+}
+
+impl UmpPacket {
+    fn new(event: &MidiEvent, words: &[u32]) -> UmpPacket {
+        let mut packet = UmpPacket {
+            absolute_ns: event.absolute_ns,
+            absolute_tick: event.absolute_tick,
+            words: [0; 2],
+            len: words.len() as u8,
+        };
+        packet.words[..words.len()].copy_from_slice(words);
+        packet
+    }
+
+    pub fn words(&self) -> &[u32] {
+        &self.words[..self.len as usize]
+    }
+
+    /// The packet in the big-endian byte order UMP streams use.
+    pub fn to_be_bytes(&self) -> Vec<u8> {
+        self.words()
+            .iter()
+            .flat_map(|word| word.to_be_bytes())
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ChannelState {
+    bank_msb: Option<u8>,
+    bank_lsb: Option<u8>,
+    // The parameter number CC 101/100 (registered) or 99/98 (assignable)
+    // last selected.
+    parameter: Option<(bool, u8, u8)>,
+    data_msb: u8,
+}
+
+/// Converts channel voice events one at a time, keeping the per-channel
+/// state that bank select and RPN/NRPN translation need. Feed it the events
+/// of one sequence in order.
+#[derive(Debug, Clone)]
+pub struct UmpConverter {
+    protocol: UmpProtocol,
+    group: u8,
+    channels: [ChannelState; 16],
+}
+
+impl UmpConverter {
+    pub fn new(protocol: UmpProtocol) -> UmpConverter {
+        UmpConverter {
+            protocol,
+            group: 0,
+            channels: [ChannelState::default(); 16],
+        }
+    }
+
+    /// The UMP group (0-15) to send on. Defaults to 0.
+    pub fn group(mut self, group: u8) -> UmpConverter {
+        self.group = group & 0x0F;
+        self
+    }
+
+    /// The packet for `event`, or None if it is not a channel voice event or
+    /// only updates state for a later message (bank select, RPN/NRPN number).
+    pub fn convert(&mut self, event: &MidiEvent) -> Option<UmpPacket> {
+        if !(0x80..0xF0).contains(&event.status) {
+            return None;
+        }
+        let group = (self.group as u32) << 24;
+        let (data1, data2) = (event.data1 & 0x7F, event.data2 & 0x7F);
+        if self.protocol == UmpProtocol::Midi1 {
+            let word = 0x2000_0000
+                | group
+                | (event.status as u32) << 16
+                | (data1 as u32) << 8
+                | data2 as u32;
+            return Some(UmpPacket::new(event, &[word]));
+        }
+
+        let channel = event.status & 0x0F;
+        let state = &mut self.channels[channel as usize];
+        let head = |opcode: u8, index1: u8, index2: u8| {
+            0x4000_0000
+                | group
+                | ((opcode << 4 | channel) as u32) << 16
+                | (index1 as u32) << 8
+                | index2 as u32
+        };
+        let words = match event.status >> 4 {
+            0x8 => [head(0x8, data1, 0), scale_up(data2 as u32, 7, 16) << 16],
+            // Velocity 0 is a note off, and has no velocity of its own.
+            0x9 if data2 == 0 => [head(0x8, data1, 0), 0x8000 << 16],
+            0x9 => [head(0x9, data1, 0), scale_up(data2 as u32, 7, 16) << 16],
+            0xA => [head(0xA, data1, 0), scale_up(data2 as u32, 7, 32)],
+            0xB => match data1 {
+                0 => {
+                    state.bank_msb = Some(data2);
+                    return None;
+                }
+                32 => {
+                    state.bank_lsb = Some(data2);
+                    return None;
+                }
+                101 | 99 => {
+                    let lsb = state.parameter.map_or(0, |(_, _, lsb)| lsb);
+                    state.parameter = Some((data1 == 101, data2, lsb));
+                    return None;
+                }
+                100 | 98 => {
+                    let msb = state.parameter.map_or(0, |(_, msb, _)| msb);
+                    state.parameter = Some((data1 == 100, msb, data2));
+                    return None;
+                }
+                // Data entry for a selected parameter; 127/127 deselects it.
+                6 | 38
+                    if state
+                        .parameter
+                        .is_some_and(|(_, msb, lsb)| (msb, lsb) != (127, 127)) =>
+                {
+                    let (registered, msb, lsb) = state.parameter.unwrap();
+                    let data_lsb = if data1 == 6 {
+                        state.data_msb = data2;
+                        0
+                    } else {
+                        data2
+                    };
+                    let value = (state.data_msb as u32) << 7 | data_lsb as u32;
+                    let opcode = if registered { 0x2 } else { 0x3 };
+                    [head(opcode, msb, lsb), scale_up(value, 14, 32)]
+                }
+                _ => [head(0xB, data1, 0), scale_up(data2 as u32, 7, 32)],
+            },
+            0xC => {
+                let (options, bank) = match (state.bank_msb, state.bank_lsb) {
+                    (None, None) => (0, 0),
+                    (msb, lsb) => (1, (msb.unwrap_or(0) as u32) << 8 | lsb.unwrap_or(0) as u32),
+                };
+                [head(0xC, 0, options), (data1 as u32) << 24 | bank]
+            }
+            0xD => [head(0xD, 0, 0), scale_up(data1 as u32, 7, 32)],
+            _ => [
+                head(0xE, 0, 0),
+                scale_up((data2 as u32) << 7 | data1 as u32, 14, 32),
+            ],
+        };
+        Some(UmpPacket::new(event, &words))
+    }
+}
+
+/// Every channel voice event of `sequence` as a UMP packet, on group 0.
+pub fn to_ump(sequence: &MidiSequence, protocol: UmpProtocol) -> Vec<UmpPacket> {
+    let mut converter = UmpConverter::new(protocol);
+    sequence
+        .events()
+        .iter()
+        .filter_map(|event| converter.convert(event))
+        .collect()
+}
+
+// Widens `value` from `from` to `to` bits so that 0, the center and the
+// maximum map to 0, the center and the maximum, with the bits above the
+// center repeated to fill the gap evenly.
+fn scale_up(value: u32, from: u32, to: u32) -> u32 {
+    let shift = to - from;
+    let shifted = value << shift;
+    if value <= 1 << (from - 1) {
+        return shifted;
+    }
+    let repeat_bits = from - 1;
+    let mut repeat = value & ((1 << repeat_bits) - 1);
+    repeat = if shift > repeat_bits {
+        repeat << (shift - repeat_bits)
+    } else {
+        repeat >> (repeat_bits - shift)
+    };
+    let mut result = shifted;
+    while repeat != 0 {
+        result |= repeat;
+        repeat >>= repeat_bits;
+    }
+    result
+}
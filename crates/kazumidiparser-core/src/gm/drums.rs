@@ -0,0 +1,135 @@
+use crate::MidiEvent;
+
+// GM2 percussion map; keys 35-81 are the original GM1 set.
+const DRUM_NAMES: [&str; 61] = [
+    "High Q",
+    "Slap",
+    "Scratch Push",
+    "Scratch Pull",
+    "Sticks",
+    "Square Click",
+    "Metronome Click",
+    "Metronome Bell",
+    "Acoustic Bass Drum",
+    "Bass Drum 1",
+    "Side Stick",
+    "Acoustic Snare",
+    "Hand Clap",
+    "Electric Snare",
+    "Low Floor Tom",
+    "Closed Hi-Hat",
+    "High Floor Tom",
+    "Pedal Hi-Hat",
+    "Low Tom",
+    "Open Hi-Hat",
+    "Low-Mid Tom",
+    "Hi-Mid Tom",
+    "Crash Cymbal 1",
+    "High Tom",
+    "Ride Cymbal 1",
+    "Chinese Cymbal",
+    "Ride Bell",
+    "Tambourine",
+    "Splash Cymbal",
+    "Cowbell",
+    "Crash Cymbal 2",
+    "Vibraslap",
+    "Ride Cymbal 2",
+    "Hi Bongo",
+    "Low Bongo",
+    "Mute Hi Conga",
+    "Open Hi Conga",
+    "Low Conga",
+    "High Timbale",
+    "Low Timbale",
+    "High Agogo",
+    "Low Agogo",
+    "Cabasa",
+    "Maracas",
+    "Short Whistle",
+    "Long Whistle",
+    "Short Guiro",
+    "Long Guiro",
+    "Claves",
+    "Hi Wood Block",
+    "Low Wood Block",
+    "Mute Cuica",
+    "Open Cuica",
+    "Mute Triangle",
+    "Open Triangle",
+    "Shaker",
+    "Jingle Bell",
+    "Bell Tree",
+    "Castanets",
+    "Mute Surdo",
+    "Open Surdo",
+];
+
+const FIRST_DRUM_KEY: u8 = 27;
+const GM_DRUM_CHANNEL: u8 = 9;
+const GM2_RHYTHM_BANK_MSB: u8 = 0x78;
+const GM2_MELODY_BANK_MSB: u8 = 0x79;
+
+pub fn drum_name(key: u8) -> Option<&'static str> {
+    key.checked_sub(FIRST_DRUM_KEY)
+        .and_then(|i| DRUM_NAMES.get(i as usize))
+        .copied()
+}
+
+// Stateless check: only MIDI channel 10 is treated as a rhythm channel.
+pub fn is_percussion(event: &MidiEvent) -> bool {
+    event.status < 0xF0 && event.status & 0x0F == GM_DRUM_CHANNEL
+}
+
+/// Tracks GM2 bank selects (CC#0 0x78 / 0x79) so that any channel can be
+/// switched between rhythm and melody parts while walking an event list.
+#[derive(Debug, Clone)]
+pub struct PercussionTracker {
+    rhythm_channels: [bool; 16],
+}
+
+impl PercussionTracker {
+    pub fn new() -> PercussionTracker {
+        let mut rhythm_channels = [false; 16];
+        rhythm_channels[GM_DRUM_CHANNEL as usize] = true;
+        PercussionTracker { rhythm_channels }
+    }
+
+    pub fn observe(&mut self, event: &MidiEvent) {
+        if event.status & 0xF0 == 0xB0 && event.data1 == 0 {
+            let channel = (event.status & 0x0F) as usize;
+            match event.data2 {
+                GM2_RHYTHM_BANK_MSB => self.rhythm_channels[channel] = true,
+                GM2_MELODY_BANK_MSB => self.rhythm_channels[channel] = false,
+                _ => {}
+            }
+        }
+    }
+
+    pub fn is_rhythm_channel(&self, channel: u8) -> bool {
+        self.rhythm_channels
+            .get(channel as usize)
+            .copied()
+            .unwrap_or(false)
+    }
+
+    pub fn is_percussion(&self, event: &MidiEvent) -> bool {
+        event.status < 0xF0 && self.is_rhythm_channel(event.status & 0x0F)
+    }
+
+    // Observes the event first, then returns the drum name for note-ons and
+    // note-offs that land on a rhythm channel.
+    pub fn label(&mut self, event: &MidiEvent) -> Option<&'static str> {
+        self.observe(event);
+        match event.status & 0xF0 {
+            0x80 | 0x90 if self.is_percussion(event) => drum_name(event.data1),
+            _ => None,
+        }
+    }
+}
+
+impl Default for PercussionTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,7 @@
+mod drums;
+mod programs;
+mod sysex;
+
+pub use drums::{PercussionTracker, drum_name, is_percussion};
+pub use programs::{drum_kit_name, gm_program_name, program_name};
+pub use sysex::{ManufacturerId, SysExInfo, SysExMessage, classify_sysex};
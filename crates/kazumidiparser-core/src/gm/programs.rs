@@ -0,0 +1,379 @@
+const GM_PROGRAM_NAMES: [&str; 128] = [
+    // Piano
+    "Acoustic Grand Piano",
+    "Bright Acoustic Piano",
+    "Electric Grand Piano",
+    "Honky-tonk Piano",
+    "Electric Piano 1",
+    "Electric Piano 2",
+    "Harpsichord",
+    "Clavi",
+    // Chromatic Percussion
+    "Celesta",
+    "Glockenspiel",
+    "Music Box",
+    "Vibraphone",
+    "Marimba",
+    "Xylophone",
+    "Tubular Bells",
+    "Dulcimer",
+    // Organ
+    "Drawbar Organ",
+    "Percussive Organ",
+    "Rock Organ",
+    "Church Organ",
+    "Reed Organ",
+    "Accordion",
+    "Harmonica",
+    "Tango Accordion",
+    // Guitar
+    "Acoustic Guitar (nylon)",
+    "Acoustic Guitar (steel)",
+    "Electric Guitar (jazz)",
+    "Electric Guitar (clean)",
+    "Electric Guitar (muted)",
+    "Overdriven Guitar",
+    "Distortion Guitar",
+    "Guitar Harmonics",
+    // Bass
+    "Acoustic Bass",
+    "Electric Bass (finger)",
+    "Electric Bass (pick)",
+    "Fretless Bass",
+    "Slap Bass 1",
+    "Slap Bass 2",
+    "Synth Bass 1",
+    "Synth Bass 2",
+    // Strings
+    "Violin",
+    "Viola",
+    "Cello",
+    "Contrabass",
+    "Tremolo Strings",
+    "Pizzicato Strings",
+    "Orchestral Harp",
+    "Timpani",
+    // Ensemble
+    "String Ensemble 1",
+    "String Ensemble 2",
+    "SynthStrings 1",
+    "SynthStrings 2",
+    "Choir Aahs",
+    "Voice Oohs",
+    "Synth Voice",
+    "Orchestra Hit",
+    // Brass
+    "Trumpet",
+    "Trombone",
+    "Tuba",
+    "Muted Trumpet",
+    "French Horn",
+    "Brass Section",
+    "SynthBrass 1",
+    "SynthBrass 2",
+    // Reed
+    "Soprano Sax",
+    "Alto Sax",
+    "Tenor Sax",
+    "Baritone Sax",
+    "Oboe",
+    "English Horn",
+    "Bassoon",
+    "Clarinet",
+    // Pipe
+    "Piccolo",
+    "Flute",
+    "Recorder",
+    "Pan Flute",
+    "Blown Bottle",
+    "Shakuhachi",
+    "Whistle",
+    "Ocarina",
+    // Synth Lead
+    "Lead 1 (square)",
+    "Lead 2 (sawtooth)",
+    "Lead 3 (calliope)",
+    "Lead 4 (chiff)",
+    "Lead 5 (charang)",
+    "Lead 6 (voice)",
+    "Lead 7 (fifths)",
+    "Lead 8 (bass + lead)",
+    // Synth Pad
+    "Pad 1 (new age)",
+    "Pad 2 (warm)",
+    "Pad 3 (polysynth)",
+    "Pad 4 (choir)",
+    "Pad 5 (bowed)",
+    "Pad 6 (metallic)",
+    "Pad 7 (halo)",
+    "Pad 8 (sweep)",
+    // Synth Effects
+    "FX 1 (rain)",
+    "FX 2 (soundtrack)",
+    "FX 3 (crystal)",
+    "FX 4 (atmosphere)",
+    "FX 5 (brightness)",
+    "FX 6 (goblins)",
+    "FX 7 (echoes)",
+    "FX 8 (sci-fi)",
+    // Ethnic
+    "Sitar",
+    "Banjo",
+    "Shamisen",
+    "Koto",
+    "Kalimba",
+    "Bag pipe",
+    "Fiddle",
+    "Shanai",
+    // Percussive
+    "Tinkle Bell",
+    "Agogo",
+    "Steel Drums",
+    "Woodblock",
+    "Taiko Drum",
+    "Melodic Tom",
+    "Synth Drum",
+    "Reverse Cymbal",
+    // Sound Effects
+    "Guitar Fret Noise",
+    "Breath Noise",
+    "Seashore",
+    "Bird Tweet",
+    "Telephone Ring",
+    "Helicopter",
+    "Applause",
+    "Gunshot",
+];
+
+// (bank MSB, program, name) variation tones from the Roland GS (SC-55) map.
+const GS_VARIATIONS: &[(u8, u8, &str)] = &[
+    (8, 0, "Piano 1w"),
+    (16, 0, "Piano 1d"),
+    (8, 1, "Piano 2w"),
+    (8, 2, "Piano 3w"),
+    (8, 3, "Honky-tonk w"),
+    (8, 4, "Detuned EP 1"),
+    (16, 4, "E.Piano 1v"),
+    (24, 4, "60's E.Piano"),
+    (8, 5, "Detuned EP 2"),
+    (16, 5, "E.Piano 2v"),
+    (8, 6, "Coupled Hps."),
+    (8, 11, "Vib.w"),
+    (8, 12, "Marimba w"),
+    (8, 14, "Church Bell"),
+    (9, 14, "Carillon"),
+    (8, 16, "Detuned Or.1"),
+    (16, 16, "60's Organ 1"),
+    (32, 16, "Organ 4"),
+    (8, 17, "Detuned Or.2"),
+    (32, 17, "Organ 5"),
+    (8, 19, "Church Org.2"),
+    (16, 19, "Church Org.3"),
+    (8, 21, "Italian Accordion"),
+    (8, 24, "Ukulele"),
+    (16, 24, "Nylon Gt.o"),
+    (32, 24, "Nylon Gt.2"),
+    (8, 25, "12-str.Gt"),
+    (16, 25, "Mandolin"),
+    (8, 26, "Hawaiian Gt."),
+    (8, 27, "Chorus Gt."),
+    (8, 28, "Funk Gt."),
+    (8, 30, "Feedback Gt."),
+    (8, 31, "Gt. Feedback"),
+    (8, 38, "Synth Bass 3"),
+    (8, 39, "Synth Bass 4"),
+    (8, 48, "Strings"),
+    (16, 48, "Orchestra"),
+    (8, 50, "Syn.Strings 3"),
+    (8, 61, "Brass 2"),
+    (8, 62, "Synth Brass 3"),
+    (8, 63, "Synth Brass 4"),
+    (1, 80, "Square"),
+    (8, 80, "Sine Wave"),
+    (1, 81, "Saw"),
+    (8, 81, "Doctor Solo"),
+    (8, 107, "Taisho Koto"),
+    (8, 115, "Castanets"),
+    (8, 116, "Concert BD"),
+    (8, 117, "Melo. Tom 2"),
+    (8, 118, "808 Tom"),
+    (1, 120, "Gt.Cut Noise"),
+    (2, 120, "String Slap"),
+    (1, 121, "Fl.Key Click"),
+    (1, 122, "Rain"),
+    (2, 122, "Thunder"),
+    (3, 122, "Wind"),
+    (4, 122, "Stream"),
+    (5, 122, "Bubble"),
+    (1, 123, "Dog"),
+    (2, 123, "Horse-Gallop"),
+    (3, 123, "Bird 2"),
+    (1, 124, "Telephone 2"),
+    (2, 124, "DoorCreaking"),
+    (3, 124, "Door"),
+    (4, 124, "Scratch"),
+    (5, 124, "Wind Chimes"),
+    (1, 125, "Car-Engine"),
+    (2, 125, "Car-Stop"),
+    (3, 125, "Car-Pass"),
+    (4, 125, "Car-Crash"),
+    (5, 125, "Siren"),
+    (6, 125, "Train"),
+    (7, 125, "Jetplane"),
+    (8, 125, "Starship"),
+    (9, 125, "Burst Noise"),
+    (1, 126, "Laughing"),
+    (2, 126, "Screaming"),
+    (3, 126, "Punch"),
+    (4, 126, "Heart Beat"),
+    (5, 126, "Footsteps"),
+    (1, 127, "Machine Gun"),
+    (2, 127, "Lasergun"),
+    (3, 127, "Explosion"),
+];
+
+// (bank LSB, program, name) normal-voice variations from the Yamaha XG map.
+const XG_VARIATIONS: &[(u8, u8, &str)] = &[
+    (1, 0, "Grand Piano KSP"),
+    (40, 0, "Piano Strings"),
+    (41, 0, "Dream"),
+    (1, 1, "Bright Piano KSP"),
+    (1, 2, "Electric Grand KSP"),
+    (32, 2, "Detuned CP80"),
+    (1, 3, "Honky-tonk KSP"),
+    (1, 4, "Electric Piano 1 KSP"),
+    (32, 4, "Chorus Electric Piano 1"),
+    (1, 5, "Electric Piano 2 KSP"),
+    (32, 5, "Chorus Electric Piano 2"),
+    (1, 6, "Harpsichord KSP"),
+    (35, 6, "Harpsichord 2"),
+    (1, 7, "Clavi KSP"),
+    (32, 16, "Detuned Drawbar Organ"),
+    (32, 17, "Detuned Percussive Organ"),
+    (32, 19, "Church Organ 3"),
+    (35, 19, "Church Organ 2"),
+    (32, 24, "Nylon Guitar 2"),
+    (35, 25, "12-String Guitar"),
+    (32, 27, "Chorus Guitar"),
+    (40, 33, "Finger Slap Bass"),
+    (32, 48, "Orchestra"),
+    (33, 50, "Synth Strings 3"),
+    (32, 61, "Brass Section 2"),
+    (8, 80, "Square Lead 2"),
+    (8, 81, "Sawtooth Lead 2"),
+];
+
+const XG_SFX_VOICES: &[(u8, &str)] = &[
+    (0, "Cutting Noise"),
+    (1, "Cutting Noise 2"),
+    (3, "String Slap"),
+    (16, "Flute Key Click"),
+    (32, "Shower"),
+    (33, "Thunder"),
+    (34, "Wind"),
+    (35, "Stream"),
+    (36, "Bubble"),
+    (37, "Feed"),
+    (48, "Dog"),
+    (49, "Horse"),
+    (50, "Bird Tweet 2"),
+    (52, "Ghost"),
+    (53, "Maou"),
+    (64, "Phone Call"),
+    (65, "Door Squeak"),
+    (66, "Door Slam"),
+    (67, "Scratch Cut"),
+    (68, "Scratch Split"),
+    (69, "Wind Chime"),
+    (70, "Telephone Ring 2"),
+    (80, "Car Engine Ignition"),
+    (81, "Car Tires Squeal"),
+    (82, "Car Passing"),
+    (83, "Car Crash"),
+    (84, "Siren"),
+    (85, "Train"),
+    (86, "Jet Plane"),
+    (87, "Starship"),
+    (88, "Burst"),
+    (89, "Roller Coaster"),
+    (90, "Submarine"),
+    (96, "Laugh"),
+    (97, "Scream"),
+    (98, "Punch"),
+    (99, "Heartbeat"),
+    (100, "Footsteps"),
+    (112, "Machine Gun"),
+    (113, "Laser Gun"),
+    (114, "Explosion"),
+    (115, "Firework"),
+];
+
+// Drum kits shared by the GS and XG maps (selected by program change on a
+// rhythm channel).
+const DRUM_KITS: &[(u8, &str)] = &[
+    (0, "Standard Kit"),
+    (8, "Room Kit"),
+    (16, "Power Kit"),
+    (24, "Electronic Kit"),
+    (25, "TR-808 Kit"),
+    (32, "Jazz Kit"),
+    (40, "Brush Kit"),
+    (48, "Orchestra Kit"),
+    (56, "SFX Kit"),
+];
+
+const XG_SFX_BANK_MSB: u8 = 64;
+const XG_SFX_KIT_BANK_MSB: u8 = 126;
+const XG_DRUM_BANK_MSB: u8 = 127;
+
+pub fn gm_program_name(program: u8) -> &'static str {
+    GM_PROGRAM_NAMES[(program & 0x7F) as usize]
+}
+
+pub fn drum_kit_name(program: u8) -> Option<&'static str> {
+    DRUM_KITS
+        .iter()
+        .find(|(p, _)| *p == program)
+        .map(|(_, name)| *name)
+}
+
+/// Resolves an instrument name for a 14-bit bank number (`CC#0 << 7 | CC#32`)
+/// and a 0-based program number.
+///
+/// XG variations are selected through the bank LSB and GS variations through
+/// the MSB, so both maps can be consulted without knowing which one a file
+/// targets. Unknown variations fall back to the GM capital tone, the same way
+/// GS and XG modules do.
+pub fn program_name(bank: u16, program: u8) -> &'static str {
+    let program = program & 0x7F;
+    let msb = ((bank >> 7) & 0x7F) as u8;
+    let lsb = (bank & 0x7F) as u8;
+
+    match msb {
+        XG_DRUM_BANK_MSB => return drum_kit_name(program).unwrap_or("Drum Kit"),
+        XG_SFX_KIT_BANK_MSB => return "SFX Kit",
+        XG_SFX_BANK_MSB => {
+            return XG_SFX_VOICES
+                .iter()
+                .find(|(p, _)| *p == program)
+                .map(|(_, name)| *name)
+                .unwrap_or(gm_program_name(program));
+        }
+        _ => {}
+    }
+
+    let variation = if msb == 0 {
+        XG_VARIATIONS
+            .iter()
+            .find(|(l, p, _)| *l == lsb && *p == program)
+    } else {
+        GS_VARIATIONS
+            .iter()
+            .find(|(m, p, _)| *m == msb && *p == program)
+    };
+
+    match variation {
+        Some((_, _, name)) => name,
+        None => gm_program_name(program),
+    }
+}
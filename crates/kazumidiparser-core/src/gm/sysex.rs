@@ -0,0 +1,167 @@
+use crate::{MidiEvent, MidiSequence};
+
+/// The ID a SysEx message starts with: one byte, or 0x00 and two more.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ManufacturerId {
+    Single(u8),
+    Extended(u8, u8),
+}
+
+// Names from the MMA's manufacturer ID list, for the IDs seen most in files.
+const SINGLE_IDS: [(u8, &str); 31] = [
+    (0x01, "Sequential Circuits"),
+    (0x02, "Big Briar"),
+    (0x03, "Octave / Plateau"),
+    (0x04, "Moog"),
+    (0x05, "Passport Designs"),
+    (0x06, "Lexicon"),
+    (0x07, "Kurzweil"),
+    (0x08, "Fender"),
+    (0x0A, "AKG"),
+    (0x0F, "Ensoniq"),
+    (0x10, "Oberheim"),
+    (0x11, "Apple"),
+    (0x13, "Digidesign"),
+    (0x18, "E-mu"),
+    (0x1A, "ART"),
+    (0x1C, "Eventide"),
+    (0x40, "Kawai"),
+    (0x41, "Roland"),
+    (0x42, "Korg"),
+    (0x43, "Yamaha"),
+    (0x44, "Casio"),
+    (0x46, "Kamiya"),
+    (0x47, "Akai"),
+    (0x48, "Victor"),
+    (0x4C, "Sony"),
+    (0x4E, "Teac"),
+    (0x51, "Fostex"),
+    (0x52, "Zoom"),
+    (0x7D, "Non-Commercial"),
+    (0x7E, "Universal Non-Real Time"),
+    (0x7F, "Universal Real Time"),
+];
+
+const EXTENDED_IDS: [((u8, u8), &str); 10] = [
+    ((0x00, 0x0E), "Alesis"),
+    ((0x00, 0x3B), "MOTU"),
+    ((0x00, 0x66), "Mackie"),
+    ((0x20, 0x29), "Focusrite / Novation"),
+    ((0x20, 0x32), "Behringer"),
+    ((0x20, 0x33), "Access Music"),
+    ((0x20, 0x3C), "Elektron"),
+    ((0x20, 0x6B), "Arturia"),
+    ((0x21, 0x09), "Native Instruments"),
+    ((0x21, 0x1D), "Ableton"),
+];
+
+impl ManufacturerId {
+    /// The ID at the start of a SysEx payload (the bytes after F0).
+    pub fn of(data: &[u8]) -> Option<ManufacturerId> {
+        match *data {
+            [0x00, high, low, ..] => Some(ManufacturerId::Extended(high, low)),
+            [id, ..] if id < 0x80 && id != 0x00 => Some(ManufacturerId::Single(id)),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> Option<&'static str> {
+        match self {
+            ManufacturerId::Single(id) => SINGLE_IDS
+                .iter()
+                .find(|(i, _)| *i == id)
+                .map(|(_, name)| *name),
+            ManufacturerId::Extended(high, low) => EXTENDED_IDS
+                .iter()
+                .find(|(i, _)| *i == (high, low))
+                .map(|(_, name)| *name),
+        }
+    }
+}
+
+/// What a SysEx payload does, for the common messages a player or
+/// synthesizer has to act on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SysExMessage {
+    GmSystemOn,
+    Gm2SystemOn,
+    GmSystemOff,
+    // Roland GS Reset.
+    GsReset,
+    // Yamaha XG System On.
+    XgSystemOn,
+    // Universal real-time master volume, 14-bit (16383 is full volume).
+    MasterVolume(u16),
+    Other,
+}
+
+impl SysExMessage {
+    pub fn name(self) -> &'static str {
+        match self {
+            SysExMessage::GmSystemOn => "GM System On",
+            SysExMessage::Gm2SystemOn => "GM2 System On",
+            SysExMessage::GmSystemOff => "GM System Off",
+            SysExMessage::GsReset => "GS Reset",
+            SysExMessage::XgSystemOn => "XG System On",
+            SysExMessage::MasterVolume(_) => "Master Volume",
+            SysExMessage::Other => "SysEx",
+        }
+    }
+
+    /// Whether the message resets the receiving synthesizer.
+    pub fn is_reset(self) -> bool {
+        matches!(
+            self,
+            SysExMessage::GmSystemOn
+                | SysExMessage::Gm2SystemOn
+                | SysExMessage::GmSystemOff
+                | SysExMessage::GsReset
+                | SysExMessage::XgSystemOn
+        )
+    }
+}
+
+/// Recognises a SysEx payload (the bytes after F0, with or without the
+/// closing F7). Any device ID is accepted.
+pub fn classify_sysex(data: &[u8]) -> SysExMessage {
+    let data = data.strip_suffix(&[0xF7]).unwrap_or(data);
+    match *data {
+        [0x7E, _, 0x09, 0x01] => SysExMessage::GmSystemOn,
+        [0x7E, _, 0x09, 0x02] => SysExMessage::GmSystemOff,
+        [0x7E, _, 0x09, 0x03] => SysExMessage::Gm2SystemOn,
+        [0x7F, _, 0x04, 0x01, lsb, msb] => {
+            SysExMessage::MasterVolume(((msb & 0x7F) as u16) << 7 | (lsb & 0x7F) as u16)
+        }
+        // Model ID 0x42 (GS), DT1, address 40 00 7F, data 00, checksum.
+        [0x41, _, 0x42, 0x12, 0x40, 0x00, 0x7F, 0x00, 0x41] => SysExMessage::GsReset,
+        // Parameter change (1n), model ID 0x4C (XG), address 00 00 7E.
+        [0x43, device, 0x4C, 0x00, 0x00, 0x7E, 0x00] if device & 0xF0 == 0x10 => {
+            SysExMessage::XgSystemOn
+        }
+        _ => SysExMessage::Other,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SysExInfo<'a> {
+    pub manufacturer: Option<ManufacturerId>,
+    pub message: SysExMessage,
+    // The payload after F0, closing F7 included.
+    pub data: &'a [u8],
+}
+
+impl MidiSequence {
+    /// The manufacturer and meaning of a SysEx event (status 0xF0). None for
+    /// other events, including F7 escape packets, which carry no ID.
+    pub fn sysex_info(&self, event: &MidiEvent) -> Option<SysExInfo<'_>> {
+        if event.status != 0xF0 {
+            return None;
+        }
+        let data = self.sysex(event)?;
+        Some(SysExInfo {
+            manufacturer: ManufacturerId::of(data),
+            message: classify_sysex(data),
+            data,
+        })
+    }
+}
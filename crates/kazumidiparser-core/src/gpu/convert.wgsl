@@ -0,0 +1,82 @@
+// Tick -> nanosecond conversion. WGSL has no 64-bit integers, so every u64
+// is a vec2<u32> of (low, high) words.
+
+struct TempoPoint {
+    tick: vec2<u32>,
+    ns: vec2<u32>,
+    tick_ns: vec2<u32>,
+    pad: vec2<u32>,
+};
+
+struct Params {
+    count: u32,
+    point_count: u32,
+    pad0: u32,
+    pad1: u32,
+};
+
+@group(0) @binding(0) var<storage, read> points: array<TempoPoint>;
+@group(0) @binding(1) var<storage, read> ticks: array<vec2<u32>>;
+@group(0) @binding(2) var<storage, read_write> out_ns: array<vec2<u32>>;
+@group(0) @binding(3) var<uniform> params: Params;
+
+fn le64(a: vec2<u32>, b: vec2<u32>) -> bool {
+    return a.y < b.y || (a.y == b.y && a.x <= b.x);
+}
+
+fn add64(a: vec2<u32>, b: vec2<u32>) -> vec2<u32> {
+    let lo = a.x + b.x;
+    let carry = select(0u, 1u, lo < a.x);
+    return vec2<u32>(lo, a.y + b.y + carry);
+}
+
+fn sub64(a: vec2<u32>, b: vec2<u32>) -> vec2<u32> {
+    let borrow = select(0u, 1u, a.x < b.x);
+    return vec2<u32>(a.x - b.x, a.y - b.y - borrow);
+}
+
+// Full 32x32 -> 64-bit product from 16-bit halves.
+fn mul32(a: u32, b: u32) -> vec2<u32> {
+    let a0 = a & 0xFFFFu;
+    let a1 = a >> 16u;
+    let b0 = b & 0xFFFFu;
+    let b1 = b >> 16u;
+    let p00 = a0 * b0;
+    let p01 = a0 * b1;
+    let p10 = a1 * b0;
+    let p11 = a1 * b1;
+    let mid = (p00 >> 16u) + (p01 & 0xFFFFu) + (p10 & 0xFFFFu);
+    let lo = (p00 & 0xFFFFu) | (mid << 16u);
+    let hi = p11 + (p01 >> 16u) + (p10 >> 16u) + (mid >> 16u);
+    return vec2<u32>(lo, hi);
+}
+
+// Wrapping 64x64 -> 64-bit product, like u64 multiplication on the CPU.
+fn mul64(a: vec2<u32>, b: vec2<u32>) -> vec2<u32> {
+    let low = mul32(a.x, b.x);
+    return vec2<u32>(low.x, low.y + a.x * b.y + a.y * b.x);
+}
+
+@compute @workgroup_size(256)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if (i >= params.count) {
+        return;
+    }
+    let tick = ticks[i];
+
+    // Last point at or before `tick`; points[0] is always at tick 0.
+    var lo = 0u;
+    var hi = params.point_count;
+    while (lo + 1u < hi) {
+        let mid = (lo + hi) / 2u;
+        if (le64(points[mid].tick, tick)) {
+            lo = mid;
+        } else {
+            hi = mid;
+        }
+    }
+
+    let point = points[lo];
+    out_ns[i] = add64(point.ns, mul64(sub64(tick, point.tick), point.tick_ns));
+}
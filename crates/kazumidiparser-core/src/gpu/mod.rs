@@ -0,0 +1,224 @@
+use std::fmt;
+
+use wgpu::util::DeviceExt;
+
+use crate::{MidiSequence, TempoMap};
+
+const WORKGROUP_SIZE: u32 = 256;
+// Ticks per dispatch; keeps each storage buffer at 64 MiB, well under the
+// default binding size limit.
+const MAX_CHUNK: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct GpuError(String);
+
+impl fmt::Display for GpuError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "GPU conversion failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for GpuError {}
+
+fn split_u64(value: u64) -> [u32; 2] {
+    [value as u32, (value >> 32) as u32]
+}
+
+/// Experimental tick -> nanosecond conversion in a wgpu compute shader.
+///
+/// Produces exactly the same values as `TempoMap::tick_to_ns`; it only pays
+/// off for very large event counts, where the upload is cheaper than the
+/// CPU conversion.
+pub struct GpuTimeConverter {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    chunk_len: usize,
+}
+
+impl GpuTimeConverter {
+    pub fn new() -> Result<GpuTimeConverter, GpuError> {
+        pollster::block_on(Self::new_async())
+    }
+
+    pub async fn new_async() -> Result<GpuTimeConverter, GpuError> {
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor::default());
+        let adapter = instance
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: wgpu::PowerPreference::HighPerformance,
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| GpuError(e.to_string()))?;
+        let (device, queue) = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: Some("kazumidiparser"),
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| GpuError(e.to_string()))?;
+
+        let shader = device.create_shader_module(wgpu::include_wgsl!("convert.wgsl"));
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("tick_to_ns"),
+            layout: None,
+            module: &shader,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        let max_binding = device.limits().max_storage_buffer_binding_size as usize;
+        Ok(GpuTimeConverter {
+            device,
+            queue,
+            pipeline,
+            chunk_len: MAX_CHUNK.min(max_binding / 8),
+        })
+    }
+
+    pub fn ticks_to_ns(&self, tempo_map: &TempoMap, ticks: &[u64]) -> Result<Vec<u64>, GpuError> {
+        // The shader only does 64-bit truncated per-tick math.
+        if tempo_map.is_exact() {
+            return Err(GpuError("exact tempo maps are not supported".to_string()));
+        }
+        let mut points: Vec<u32> = Vec::with_capacity(tempo_map.points().len() * 8);
+        for point in tempo_map.points() {
+            points.extend(split_u64(point.absolute_tick));
+            points.extend(split_u64(point.absolute_ns));
+            points.extend(split_u64(point.tick_ns));
+            points.extend([0, 0]);
+        }
+        let points_buffer = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("tempo points"),
+                contents: bytemuck::cast_slice(&points),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+
+        let mut result = Vec::with_capacity(ticks.len());
+        for chunk in ticks.chunks(self.chunk_len) {
+            result.extend(self.convert_chunk(&points_buffer, tempo_map.points().len(), chunk)?);
+        }
+        Ok(result)
+    }
+
+    fn convert_chunk(
+        &self,
+        points_buffer: &wgpu::Buffer,
+        point_count: usize,
+        ticks: &[u64],
+    ) -> Result<Vec<u64>, GpuError> {
+        let words: Vec<u32> = ticks.iter().flat_map(|&t| split_u64(t)).collect();
+        let size = (words.len() * 4) as wgpu::BufferAddress;
+
+        let ticks_buffer = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("ticks"),
+                contents: bytemuck::cast_slice(&words),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let output_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("ns"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("ns readback"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let params: [u32; 4] = [ticks.len() as u32, point_count as u32, 0, 0];
+        let params_buffer = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("params"),
+                contents: bytemuck::cast_slice(&params),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: points_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: ticks_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: output_buffer.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: params_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor::default());
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups((ticks.len() as u32).div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&output_buffer, 0, &readback_buffer, 0, size);
+        self.queue.submit([encoder.finish()]);
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        self.device
+            .poll(wgpu::PollType::Wait)
+            .map_err(|e| GpuError(e.to_string()))?;
+        receiver
+            .recv()
+            .map_err(|e| GpuError(e.to_string()))?
+            .map_err(|e| GpuError(e.to_string()))?;
+
+        let mapped = readback_buffer.slice(..).get_mapped_range();
+        let words: &[u32] = bytemuck::cast_slice(&mapped);
+        let ns = words
+            .chunks_exact(2)
+            .map(|w| w[0] as u64 | (w[1] as u64) << 32)
+            .collect();
+        drop(mapped);
+        readback_buffer.unmap();
+        Ok(ns)
+    }
+}
+
+impl MidiSequence {
+    /// Recomputes every event's `absolute_ns` on the GPU. Meta events are
+    /// few and stay on the CPU, as does a format 2 file whose tracks have
+    /// maps of their own.
+    pub fn retime_gpu(&mut self, converter: &GpuTimeConverter) -> Result<(), GpuError> {
+        if !self.track_tempo_maps.is_empty() {
+            self.retime();
+            return Ok(());
+        }
+        let ticks: Vec<u64> = self.events.iter().map(|e| e.absolute_tick).collect();
+        let ns = converter.ticks_to_ns(&self.tempo_map, &ticks)?;
+        for (event, ns) in self.events.iter_mut().zip(ns) {
+            event.absolute_ns = ns;
+        }
+        for meta in &mut self.metas {
+            meta.absolute_ns = self.tempo_map.tick_to_ns(meta.absolute_tick);
+        }
+        Ok(())
+    }
+}
@@ -1,40 +1,213 @@
-use std::error::Error as StdError;
 use std::fs::File;
 use std::io::Read;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
 
 use rayon::prelude::*;
 use rayon::slice::ParallelSliceMut;
 
-#[derive(Debug)]
+pub mod analysis;
+#[cfg(feature = "tokio")]
+mod async_parse;
+mod builder;
+mod cancel;
+mod chunk;
+pub mod clip;
+mod columns;
+pub mod decode;
+mod edit;
+mod error;
+pub mod export;
+pub mod gm;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod logging;
+mod looping;
+#[cfg(feature = "lua")]
+pub mod lua;
+pub mod lyrics;
+mod markers;
+pub mod meta;
+mod metrics;
+mod musical_time;
+mod notes;
+mod options;
+pub mod pitch;
+pub mod playback;
+mod pool;
+mod reader;
+pub mod render;
+mod rmid;
+pub mod sequence;
+#[cfg(feature = "shm")]
+pub mod shared;
+pub mod state;
+mod stream;
+mod tail;
+pub mod tempo;
+mod text;
+mod time_signature;
+pub mod track_info;
+pub mod transform;
+pub mod validator;
+mod visitor;
+
+pub use builder::{ParsedMidi, ParserBuilder};
+pub use cancel::CancelToken;
+pub use chunk::TrackLengthMismatch;
+pub use columns::EventColumns;
+pub use error::{ParseError, ParseWarning, WarningKind};
+pub use looping::{LoopPoints, LoopSource};
+pub use markers::{Marker, MarkerKind, MarkerList};
+pub use meta::{MetaEvent, MetaKind, TextKind};
+pub use metrics::ParseMetrics;
+pub use musical_time::{BarLine, MusicalTime};
+pub use notes::Note;
+pub use options::{EventMask, EventOrder, ParseOptions};
+pub use pool::{BudgetPolicy, ParserPool, PoolError, estimate_parse_memory};
+pub use reader::EventReader;
+pub use sequence::MidiSequence;
+pub use stream::EventStream;
+pub use tail::TailParser;
+pub use tempo::{TempoMap, TempoPoint};
+pub use text::TextEncoding;
+pub use time_signature::{BarBeat, TimeSignatureMap, TimeSignaturePoint};
+pub use visitor::{MidiVisitor, parse_with_visitor};
+
+use chunk::{OwnedTrackChunk, TrackChunkReader, locate_track_chunk, read_header};
+use decode::{TrackDecoder, TrackEventKind};
+use logging::log_at;
+
+// Events converted between cancellation checks.
+const CONVERT_BLOCK: usize = 1 << 20;
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MidiHeader {
     pub format: u16,
     pub tracks: u16,
+    // The raw division word: ticks per quarter note unless the top bit is
+    // set. See `division`.
     pub ppqn: u16,
 }
 
-#[derive(Debug, Clone)]
+/// How a file divides time into ticks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Division {
+    TicksPerQuarter(u16),
+    /// Ticks are fractions of an SMPTE frame and ignore tempo changes.
+    /// `frames_per_second` is 24, 25, 29 (30 drop-frame, i.e. 29.97) or 30.
+    Smpte {
+        frames_per_second: u8,
+        ticks_per_frame: u8,
+    },
+}
+
+impl MidiHeader {
+    pub fn division(&self) -> Division {
+        if self.ppqn & 0x8000 == 0 {
+            return Division::TicksPerQuarter(self.ppqn);
+        }
+        let [frames, ticks_per_frame] = self.ppqn.to_be_bytes();
+        Division::Smpte {
+            frames_per_second: (frames as i8).unsigned_abs(),
+            ticks_per_frame,
+        }
+    }
+
+    // The PPQN a tempo map for this file is built with and, for SMPTE files,
+    // the fixed tempo that makes one "quarter note" last 30 frames (or one
+    // second at 24 and 25 fps).
+    pub(crate) fn tempo_timing(&self) -> (u16, Option<u32>) {
+        match self.division() {
+            Division::TicksPerQuarter(ppqn) => (ppqn, None),
+            Division::Smpte {
+                frames_per_second: 29,
+                ticks_per_frame,
+            } => (30 * ticks_per_frame as u16, Some(1_001_000)),
+            Division::Smpte {
+                frames_per_second,
+                ticks_per_frame,
+            } => (
+                frames_per_second as u16 * ticks_per_frame as u16,
+                Some(1_000_000),
+            ),
+        }
+    }
+}
+
+// `repr(C)` so the C API can hand out the event list as it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
 pub struct MidiEvent {
     pub absolute_ns: u64,
+    pub absolute_tick: u64,
     pub status: u8,
     pub data1: u8,
     pub data2: u8,
     pub track_index: u16,
-    pub sysex_data: Option<Vec<u8>>,
+    // For a SysEx (status 0xF0) or F7 packet (status 0xF7), where its bytes
+    // are in the SysEx table of the sequence or stream it came from; see
+    // `MidiSequence::sysex`. Kept out of line so the event stays 32 bytes.
+    pub sysex_index: Option<u32>,
 }
 
 pub struct MidiParser {
     is_parsed: bool,
-    header: MidiHeader,
+    options: ParseOptions,
+    sequence: MidiSequence,
+    metrics: ParseMetrics,
+    // A copy of `sequence.events`, kept for callers of the old field until it
+    // is removed. One-shot parses that hand the sequence straight over skip it.
+    mirror_events: bool,
+    #[deprecated(note = "use `get_events()` or `sequence().events()` instead")]
     pub events: Vec<MidiEvent>,
 }
 
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TrackMeta {
+    // Raw bytes, decoded with the sequence's text encoding.
+    pub(crate) name: Option<Vec<u8>>,
+    pub(crate) instrument_name: Option<Vec<u8>>,
+    pub(crate) sequence_number: Option<u16>,
+    // Tick of the End of Track event, or of the last event if there is none.
+    pub(crate) end_tick: u64,
+    pub(crate) length_mismatch: Option<TrackLengthMismatch>,
+    pub(crate) running_status_fallbacks: usize,
+}
+
 #[derive(Debug)]
 enum TempEventData {
     Midi { status: u8, data1: u8, data2: u8 },
-    TempoChange { new_tempo_us: u32 },
-    SysEx { data: Vec<u8> },
+    // Index into the track's SysEx table. A SysEx is reassembled there from
+    // all of its packets.
+    SysEx { index: u32 },
+    Escape { index: u32 },
 }
 
+#[derive(Default)]
+struct ParsedTrack {
+    events: Vec<TempEvent>,
+    // (absolute_tick, tempo_us), kept apart so the event list holds only
+    // events that end up in the sequence.
+    tempo_changes: Vec<(u64, u32)>,
+    metas: Vec<MetaEvent>,
+    // Payloads of the SysEx and escape events.
+    sysex: Vec<Vec<u8>>,
+    meta: TrackMeta,
+    warnings: Vec<ParseWarning>,
+}
+
+// A track's index, parse outcome and length mismatch, if any.
+type TrackResult = (
+    u16,
+    Result<ParsedTrack, ParseError>,
+    Option<TrackLengthMismatch>,
+);
+
 #[derive(Debug)]
 struct TempEvent {
     absolute_tick: u64,
@@ -42,347 +215,700 @@ struct TempEvent {
     data: TempEventData,
 }
 
+// Decodes a prefix of the track and extrapolates its average event size to
+// the whole chunk. Black MIDI tracks are long and uniform, so this lands
+// close enough to reserve the event vector once instead of doubling it
+// through several gigabytes.
+fn estimate_event_count(track_index: u16, track_data: &[u8]) -> usize {
+    const SAMPLE_EVENTS: usize = 4096;
+    let mut decoder = TrackDecoder::for_track(track_index, track_data).lenient_running_status(true);
+    let mut sampled = 0;
+    while sampled < SAMPLE_EVENTS {
+        match decoder.next() {
+            Some(Ok(_)) => sampled += 1,
+            _ => return sampled,
+        }
+    }
+    let estimate = sampled * track_data.len() / decoder.position().max(1);
+    estimate + estimate / 16
+}
+
+impl Default for MidiParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MidiParser {
     pub fn new() -> MidiParser {
+        Self::with_options(ParseOptions::default())
+    }
+
+    pub fn with_options(options: ParseOptions) -> MidiParser {
+        #[allow(deprecated)]
         MidiParser {
             is_parsed: false,
-            header: MidiHeader {
-                format: 0,
-                tracks: 0,
-                ppqn: 0,
-            },
+            options,
+            sequence: MidiSequence::empty(),
+            metrics: ParseMetrics::default(),
+            mirror_events: true,
             events: Vec::new(),
         }
     }
 
+    /// Stops filling the deprecated `events` field after each parse, so the
+    /// events are held once, in the sequence. Callers that read them through
+    /// `get_events()` or `sequence()` should always turn the copy off.
+    pub fn without_events_copy(mut self) -> MidiParser {
+        self.mirror_events = false;
+        self
+    }
+
+    pub fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+
+    pub fn set_options(&mut self, options: ParseOptions) {
+        self.options = options;
+    }
+
     pub fn get_header(&self) -> Option<&MidiHeader> {
         if self.is_parsed {
-            Some(&self.header)
+            Some(&self.sequence.header)
         } else {
             None
         }
     }
 
-    fn tempo_to_tick_ns(tempo_us: u32, ppqn: u16) -> u64 {
-        (tempo_us as u64 * 1000) / ppqn as u64
-    }
-
+    // `data_offset` is the file offset of `track_data`, for warnings.
     fn parse_track(
         track_index: u16,
         track_data: &[u8],
-        total_tracks: u16,
-    ) -> Result<Vec<TempEvent>, Box<dyn StdError + Send + Sync>> {
-        let mut track_events = Vec::new();
-        let mut index = 0;
-        let mut last_status: Option<u8> = None;
-        let mut absolute_tick = 0u64;
-
-        while index < track_data.len() {
-            // Read delta time using VLQ
-            let mut delta_ticks = 0u32;
-            loop {
-                if index >= track_data.len() {
-                    break;
-                }
-                let byte = track_data[index];
-                index += 1;
-                delta_ticks = (delta_ticks << 7) | (byte & 0x7F) as u32;
-                if byte & 0x80 == 0 {
-                    break;
-                }
-            }
-            absolute_tick += delta_ticks as u64;
-
-            if index >= track_data.len() {
-                break;
+        data_offset: u64,
+        header: &MidiHeader,
+        options: &ParseOptions,
+    ) -> Result<ParsedTrack, ParseError> {
+        // A track left out by `track_filter` still keeps its meta events,
+        // which time the others, except in format 2 where every track has its
+        // own.
+        let keep_track = options.keeps_track(track_index);
+        if !keep_track && header.format == 2 {
+            return Ok(ParsedTrack::default());
+        }
+        let mut track_events = if keep_track {
+            Vec::with_capacity(estimate_event_count(track_index, track_data))
+        } else {
+            Vec::new()
+        };
+        let mut track_metas = Vec::new();
+        let mut tempo_changes = Vec::new();
+        let mut sysex = Vec::new();
+        let mut track_meta = TrackMeta::default();
+        let mut warnings = Vec::new();
+        let mut decoder = TrackDecoder::for_track(track_index, track_data)
+            .lenient_running_status(options.lenient_running_status || !options.strict)
+            .resync(!options.strict);
+        for event in decoder.by_ref() {
+            let event = event.map_err(|error| ParseError::Decode {
+                track: track_index,
+                error,
+            })?;
+            if options.is_cancelled() {
+                return Err(ParseError::Cancelled);
             }
-
-            // Status byte and running status
-            let mut status = track_data[index];
-            if status & 0x80 != 0 {
-                index += 1;
-                last_status = Some(status);
-            } else if let Some(last) = last_status {
-                status = last;
-            } else {
-                return Err(format!(
-                    "Running status without previous status on track {}",
-                    track_index
-                )
-                .into());
+            let absolute_tick = event.absolute_tick;
+            track_meta.end_tick = absolute_tick;
+
+            if let TrackEventKind::Meta { meta_type, data } = event.kind
+                && !event.kind.is_end_of_track()
+            {
+                track_metas.push(MetaEvent {
+                    absolute_ns: 0,
+                    absolute_tick,
+                    track_index,
+                    meta_type,
+                    data: data.to_vec(),
+                });
             }
 
-            if status == 0xFF {
-                // Meta Event
-                if index + 1 > track_data.len() {
-                    break;
-                }
-                let meta_type = track_data[index];
-                index += 1;
-
-                let mut length = 0usize;
-                loop {
-                    if index >= track_data.len() {
-                        break;
+            match event.kind {
+                TrackEventKind::Meta { meta_type, data } => match meta_type {
+                    0x00 if data.len() == 2 => {
+                        // Sequence number
+                        track_meta.sequence_number = Some(u16::from_be_bytes([data[0], data[1]]));
                     }
-                    let byte = track_data[index];
-                    index += 1;
-                    length = (length << 7) | (byte & 0x7F) as usize;
-                    if byte & 0x80 == 0 {
-                        break;
-                    }
-                }
-
-                if index + length > track_data.len() {
-                    break;
-                }
-
-                match meta_type {
-                    0x51 if length == 3 => {
+                    0x51 if data.len() == 3 => {
                         // Tempo change
-                        let new_tempo_us = ((track_data[index] as u32) << 16)
-                            | ((track_data[index + 1] as u32) << 8)
-                            | (track_data[index + 2] as u32);
-                        track_events.push(TempEvent {
-                            absolute_tick,
-                            track_index,
-                            data: TempEventData::TempoChange { new_tempo_us },
-                        });
+                        let new_tempo_us =
+                            ((data[0] as u32) << 16) | ((data[1] as u32) << 8) | (data[2] as u32);
+                        tempo_changes.push((absolute_tick, new_tempo_us));
                     }
-                    0x2F if length == 0 => {
-                        // End of track
-                        break;
+                    0x03 if track_meta.name.is_none() => {
+                        // Sequence/Track name
+                        track_meta.name = Some(data.to_vec());
+                    }
+                    0x04 if track_meta.instrument_name.is_none() => {
+                        // Instrument name
+                        track_meta.instrument_name = Some(data.to_vec());
                     }
                     _ => { /* Ignore other meta event */ }
+                },
+                TrackEventKind::SysEx { data }
+                    if keep_track && options.event_mask.contains(EventMask::SYSEX) =>
+                {
+                    track_events.push(TempEvent {
+                        absolute_tick,
+                        track_index,
+                        data: TempEventData::SysEx {
+                            index: sysex.len() as u32,
+                        },
+                    });
+                    sysex.push(data.to_vec());
                 }
-                index += length;
-            } else if status == 0xF0 {
-                // System Exclusive (SysEx) message
-                let mut sysex_data = Vec::new();
-
-                while index < track_data.len() {
-                    let byte = track_data[index];
-                    index += 1;
-                    sysex_data.push(byte);
-                    if byte == 0xF7 {
-                        break; // End of SysEx
+                TrackEventKind::SysExContinuation { data } => {
+                    // Continuations directly follow their SysEx, so it is the
+                    // last event kept, if it was kept at all.
+                    if let Some(TempEvent {
+                        data: TempEventData::SysEx { index },
+                        ..
+                    }) = track_events.last()
+                    {
+                        sysex[*index as usize].extend_from_slice(data);
                     }
                 }
-
-                track_events.push(TempEvent {
-                    absolute_tick,
-                    track_index,
-                    data: TempEventData::SysEx { data: sysex_data },
-                });
-            } else if status & 0xF0 != 0xF0 {
-                // MIDI channel message
-                if index >= track_data.len() {
-                    break;
+                TrackEventKind::Escape { data }
+                    if keep_track && options.event_mask.contains(EventMask::SYSEX) =>
+                {
+                    track_events.push(TempEvent {
+                        absolute_tick,
+                        track_index,
+                        data: TempEventData::Escape {
+                            index: sysex.len() as u32,
+                        },
+                    });
+                    sysex.push(data.to_vec());
                 }
-                let data1 = track_data[index];
-                index += 1;
-
-                let data2 = if status & 0xF0 != 0xC0 && status & 0xF0 != 0xD0 {
-                    if index >= track_data.len() {
-                        break;
-                    }
-                    let d = track_data[index];
-                    index += 1;
-                    d
-                } else {
-                    0
-                };
-
-                track_events.push(TempEvent {
-                    absolute_tick,
-                    track_index,
-                    data: TempEventData::Midi {
-                        status,
-                        data1,
-                        data2,
-                    },
-                });
-            } else {
-                if index < track_data.len() {
-                    index += 1;
+                TrackEventKind::Channel {
+                    status,
+                    data1,
+                    data2,
+                } if keep_track
+                    && options.keeps_channel(status & 0x0F)
+                    && options.event_mask.keeps_status(status) =>
+                {
+                    track_events.push(TempEvent {
+                        absolute_tick,
+                        track_index,
+                        data: TempEventData::Midi {
+                            status,
+                            data1,
+                            data2,
+                        },
+                    });
                 }
+                _ => {}
             }
         }
 
+        track_meta.running_status_fallbacks = decoder.running_status_fallbacks();
+        if track_meta.running_status_fallbacks > 0 {
+            log_at!(
+                Warn,
+                "Track {} reuses running status after meta/SysEx events {} times",
+                track_index + 1,
+                track_meta.running_status_fallbacks
+            );
+            warnings.push(ParseWarning {
+                track: Some(track_index),
+                offset: data_offset,
+                kind: WarningKind::RunningStatusFallbacks(track_meta.running_status_fallbacks),
+            });
+        }
+        for &(offset, len) in decoder.skipped_bytes() {
+            log_at!(
+                Warn,
+                "Track {} skips {} stray data bytes at byte {}",
+                track_index + 1,
+                len,
+                data_offset + offset as u64
+            );
+            warnings.push(ParseWarning {
+                track: Some(track_index),
+                offset: data_offset + offset as u64,
+                kind: WarningKind::SkippedBytes(len),
+            });
+        }
+        if !decoder.reached_end_of_track() {
+            log_at!(Warn, "Track {} has no End of Track event", track_index + 1);
+            warnings.push(ParseWarning {
+                track: Some(track_index),
+                offset: data_offset + track_data.len() as u64,
+                kind: WarningKind::MissingEndOfTrack,
+            });
+        }
+
         let thread_id_str = match rayon::current_thread_index() {
             Some(id) => id.to_string(),
             None => "N/A".to_string(),
         };
 
-        println!(
+        log_at!(
+            Debug,
             "[Thread {}] Track {:>2}/{} parsed ({} bytes), collected {} temp events",
             thread_id_str,
             track_index + 1,
-            total_tracks,
+            header.tracks,
             track_data.len(),
             track_events.len()
         );
 
-        Ok(track_events)
+        Ok(ParsedTrack {
+            events: track_events,
+            tempo_changes,
+            metas: track_metas,
+            sysex,
+            meta: track_meta,
+            warnings,
+        })
     }
 
-    pub fn parse_file(&mut self, file_path: &str) -> Result<(), Box<dyn StdError>> {
+    pub fn parse_file(&mut self, file_path: &str) -> Result<(), ParseError> {
         let mut file = File::open(file_path)?;
-        let mut buffer32 = [0; 4];
-
-        file.read_exact(&mut buffer32)?;
-        if buffer32 != *b"MThd" {
-            return Err("Invalid header: no MThd".into());
+        #[cfg(feature = "mmap")]
+        if self.options.memory_map {
+            // The file must not be truncated or rewritten while it is being
+            // parsed.
+            let map = unsafe { memmap2::Mmap::map(&file)? };
+            return self.parse_image(&map);
         }
-
-        file.read_exact(&mut buffer32)?;
-        let header_length = u32::from_be_bytes(buffer32);
-        if header_length != 6 {
-            return Err(format!("Unexpected MThd chunk length: {}", header_length).into());
+        if rmid::is_riff(&mut file)? {
+            // Read whole, so the sound bank after the SMF is kept.
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+            return self.parse_image(&data);
         }
+        let file_bytes = file.metadata()?.len();
+        self.parse_source(file, Some(file_bytes))
+    }
 
-        let mut header_data = [0; 6];
-        file.read_exact(&mut header_data)?;
+    /// Parses a complete file image already in memory.
+    pub fn parse_bytes(&mut self, data: &[u8]) -> Result<(), ParseError> {
+        self.parse_image(data)
+    }
 
-        self.header = MidiHeader {
-            format: u16::from_be_bytes([header_data[0], header_data[1]]),
-            tracks: u16::from_be_bytes([header_data[2], header_data[3]]),
-            ppqn: u16::from_be_bytes([header_data[4], header_data[5]]),
-        };
+    /// Parses a file from any byte stream, reading it front to back once.
+    /// Nothing after the last track chunk is read, so `metrics().file_bytes`
+    /// only counts up to there, and the sound bank of an RMID file is
+    /// skipped.
+    pub fn parse_reader<R: Read + Send>(&mut self, reader: R) -> Result<(), ParseError> {
+        self.parse_source(reader, None)
+    }
 
-        let mut all_track_data = Vec::with_capacity(self.header.tracks as usize);
-        for i in 0..self.header.tracks {
-            file.read_exact(&mut buffer32)?;
-            if buffer32 != *b"MTrk" {
-                return Err(format!("Expected MTrk, found {:?} at track {}", buffer32, i).into());
-            }
+    fn parse_source<R: Read + Send>(
+        &mut self,
+        mut reader: R,
+        file_bytes: Option<u64>,
+    ) -> Result<(), ParseError> {
+        let started = Instant::now();
+        let mut metrics = ParseMetrics::default();
+
+        let header = read_header(&mut reader)?;
+        metrics.track_count = header.tracks;
+        self.options.check_cancelled()?;
+
+        log_at!(Info, "Parsing {} tracks...", header.tracks);
+        let phase = Instant::now();
+        let mut chunk_reader = TrackChunkReader::new(reader, 14, header.tracks);
+        // Each track is handed to the pool as soon as it is read. The bound
+        // keeps a fast disk from buffering the whole file ahead of parsing.
+        let (sender, receiver) = mpsc::sync_channel(rayon::current_num_threads() * 2);
+        let options = &self.options;
+        let (mut parsing_results, read_result) = thread::scope(|scope| {
+            let reader = scope.spawn(move || -> Result<_, ParseError> {
+                let mut missing = None;
+                loop {
+                    let chunk = match chunk_reader.next_chunk() {
+                        Ok(Some(chunk)) => chunk,
+                        Ok(None) => break,
+                        Err(error) if !options.strict => {
+                            missing = ParseWarning::missing_tracks(&error, header.tracks);
+                            if missing.is_none() {
+                                return Err(error);
+                            }
+                            break;
+                        }
+                        Err(error) => return Err(error),
+                    };
+                    if options.is_cancelled() || sender.send(chunk).is_err() {
+                        break;
+                    }
+                }
+                Ok((
+                    chunk_reader.read_time,
+                    chunk_reader.locate_time,
+                    chunk_reader.bytes_read,
+                    missing,
+                ))
+            });
+            let results: Vec<_> = receiver
+                .into_iter()
+                .par_bridge()
+                .map(|chunk: OwnedTrackChunk| {
+                    let result = Self::parse_track(
+                        chunk.track_index,
+                        &chunk.data,
+                        chunk.data_offset,
+                        &header,
+                        options,
+                    );
+                    (chunk.track_index, result, chunk.mismatch)
+                })
+                .collect();
+            (
+                results,
+                reader.join().expect("track reader thread panicked"),
+            )
+        });
+        let (bytes_read, missing);
+        (metrics.read, metrics.locate_chunks, bytes_read, missing) = read_result?;
+        metrics.file_bytes = file_bytes.unwrap_or(14 + bytes_read);
+        self.options.check_cancelled()?;
+        parsing_results.sort_unstable_by_key(|(track_index, _, _)| *track_index);
+        self.merge_tracks(header, parsing_results, missing, metrics, started, phase)
+    }
+
+    // Unwraps an RMID image before parsing the SMF in it.
+    fn parse_image(&mut self, data: &[u8]) -> Result<(), ParseError> {
+        if !data.starts_with(&rmid::RIFF) {
+            return self.parse_smf_image(data);
+        }
+        let rmid = rmid::split_rmid(data)?;
+        self.parse_smf_image(rmid.smf)?;
+        self.sequence.sound_bank = rmid.sound_bank;
+        self.metrics.file_bytes = data.len() as u64;
+        Ok(())
+    }
 
-            file.read_exact(&mut buffer32)?;
-            let track_length = u32::from_be_bytes(buffer32);
-            let mut track_data = vec![0; track_length as usize];
-            file.read_exact(&mut track_data)?;
-            all_track_data.push(track_data);
+    // Parses the tracks straight out of a complete file image, without
+    // copying them.
+    fn parse_smf_image(&mut self, data: &[u8]) -> Result<(), ParseError> {
+        let started = Instant::now();
+        let mut metrics = ParseMetrics {
+            file_bytes: data.len() as u64,
+            ..Default::default()
+        };
+
+        let header = read_header(&mut &data[..])?;
+        metrics.track_count = header.tracks;
+        self.options.check_cancelled()?;
+
+        log_at!(Info, "Parsing {} tracks...", header.tracks);
+        let phase = Instant::now();
+        let mut chunks = Vec::with_capacity(header.tracks as usize);
+        let mut missing = None;
+        let mut pos = 14;
+        for track_index in 0..header.tracks {
+            let is_last = track_index + 1 == header.tracks;
+            let (chunk, next) = match locate_track_chunk(data, pos, track_index, is_last) {
+                Ok(located) => located,
+                Err(error) if !self.options.strict => {
+                    missing = ParseWarning::missing_tracks(&error, header.tracks);
+                    if missing.is_none() {
+                        return Err(error);
+                    }
+                    break;
+                }
+                Err(error) => return Err(error),
+            };
+            chunks.push((track_index, chunk));
+            pos = next;
         }
+        metrics.locate_chunks = phase.elapsed();
 
-        println!("[KazuMIDIParser] Parsing {} tracks...", self.header.tracks);
-        let parsing_results: Vec<Result<Vec<TempEvent>, _>> = all_track_data
+        let options = &self.options;
+        let parsing_results: Vec<TrackResult> = chunks
             .into_par_iter()
-            .enumerate()
-            .map(|(i, data)| Self::parse_track(i as u16, &data, self.header.tracks))
+            .map(|(track_index, chunk)| {
+                let track_data = &data[chunk.start..chunk.end];
+                let result = Self::parse_track(
+                    track_index,
+                    track_data,
+                    chunk.start as u64,
+                    &header,
+                    options,
+                );
+                (track_index, result, chunk.mismatch)
+            })
             .collect();
+        self.options.check_cancelled()?;
+        self.merge_tracks(header, parsing_results, missing, metrics, started, phase)
+    }
 
-        let mut temp_events: Vec<TempEvent> = Vec::new();
-        for result in parsing_results {
+    // `missing` is set when a lenient parse stopped at a bad chunk, and
+    // shrinks the header to the tracks actually read.
+    fn merge_tracks(
+        &mut self,
+        mut header: MidiHeader,
+        parsing_results: Vec<TrackResult>,
+        missing: Option<ParseWarning>,
+        mut metrics: ParseMetrics,
+        started: Instant,
+        phase: Instant,
+    ) -> Result<(), ParseError> {
+        let mut warnings = Vec::new();
+        if let Some(missing) = missing {
+            if let WarningKind::MissingTracks { declared, found } = missing.kind {
+                log_at!(
+                    Warn,
+                    "Only {} of {} tracks could be read, ignoring the rest of the file",
+                    found,
+                    declared
+                );
+                header.tracks = found;
+                metrics.track_count = found;
+            }
+            warnings.push(missing);
+        }
+        for (track_index, _, mismatch) in &parsing_results {
+            if let Some(mismatch) = mismatch {
+                log_at!(
+                    Warn,
+                    "Track {} length mismatch: declared {} bytes, found {}{}",
+                    track_index + 1,
+                    mismatch.declared_length,
+                    mismatch.actual_length,
+                    if mismatch.resynced {
+                        " (resynchronized on next MTrk)"
+                    } else {
+                        ""
+                    }
+                );
+                warnings.push(ParseWarning {
+                    track: Some(*track_index),
+                    offset: mismatch.chunk_offset,
+                    kind: WarningKind::LengthMismatch(*mismatch),
+                });
+            }
+        }
+
+        let (event_total, meta_total) = parsing_results
+            .iter()
+            .filter_map(|(_, result, _)| result.as_ref().ok())
+            .fold((0, 0), |(e, m), t| (e + t.events.len(), m + t.metas.len()));
+        let mut temp_events: Vec<TempEvent> = Vec::with_capacity(event_total);
+        let mut metas: Vec<MetaEvent> = Vec::with_capacity(meta_total);
+        let mut tempo_changes = Vec::new();
+        // Format 2 only: every track's own changes, in track order.
+        let mut track_tempo_changes = Vec::new();
+        let mut track_metas = Vec::with_capacity(header.tracks as usize);
+        // The tracks' SysEx tables joined into one, and where each track's
+        // starts in it.
+        let mut sysex = Vec::new();
+        let mut sysex_base = vec![0u32; parsing_results.len()];
+        for (track_index, result, mismatch) in parsing_results {
             match result {
-                Ok(track_events) => {
-                    temp_events.extend(track_events);
+                Ok(mut track) => {
+                    if let Some(base) = sysex_base.get_mut(track_index as usize) {
+                        *base = sysex.len() as u32;
+                    }
+                    sysex.extend(track.sysex);
+                    temp_events.extend(track.events);
+                    if header.format == 2 {
+                        track_tempo_changes.push(track.tempo_changes);
+                    } else {
+                        tempo_changes.extend(track.tempo_changes);
+                    }
+                    metas.extend(track.metas);
+                    warnings.extend(track.warnings);
+                    track.meta.length_mismatch = mismatch;
+                    track_metas.push(track.meta);
                 }
                 Err(e) => {
-                    return Err(e.to_string().into());
+                    return Err(e);
                 }
             }
         }
 
-        println!(
-            "[KazuMIDIParser] All tracks parsed, total {} temp events collected.",
+        metrics.parse_tracks = phase.elapsed();
+
+        log_at!(
+            Info,
+            "All tracks parsed, total {} temp events collected.",
             temp_events.len()
         );
 
-        println!("[KazuMIDIParser] Sorting merged events...");
-        temp_events.par_sort_by_key(|e| e.absolute_tick);
-
-        self.events.clear();
-
-        println!("[KazuMIDIParser] Pre-calculating tempo map...");
-
-        #[derive(Debug, Clone, Copy)]
-        struct TempoPoint {
-            absolute_tick: u64,
-            absolute_ns: u64,
-            tick_ns: u64,
+        log_at!(Info, "Sorting merged events...");
+        let phase = Instant::now();
+        self.options.check_cancelled()?;
+        // Stable, and the tracks were appended in order, so ties keep their
+        // order in the file.
+        let order = self.options.event_order;
+        if order == EventOrder::Track {
+            temp_events.par_sort_by_key(|e| e.absolute_tick);
+        } else {
+            temp_events.par_sort_by_key(|e| {
+                let rank = match e.data {
+                    TempEventData::Midi { status, data2, .. } => order.rank(status, data2),
+                    _ => order.rank(0xF0, 0),
+                };
+                (e.absolute_tick, e.track_index, rank)
+            });
         }
+        metrics.sort = phase.elapsed();
+        self.options.check_cancelled()?;
+
+        log_at!(Info, "Pre-calculating tempo map...");
+        let phase = Instant::now();
+        let exact = self.options.exact_timing;
+        let mut track_tempo_maps: Vec<TempoMap> = match self.options.fixed_tempo_us() {
+            Some(tempo_us) => track_tempo_changes
+                .iter()
+                .map(|_| TempoMap::for_header(&header, [(0, tempo_us)], exact))
+                .collect(),
+            // Each track's changes are already in tick order.
+            None => track_tempo_changes
+                .into_iter()
+                .map(|changes| TempoMap::for_header(&header, changes, exact))
+                .collect(),
+        };
+        let tempo_map = if track_tempo_maps.is_empty() {
+            let changes = match self.options.fixed_tempo_us() {
+                Some(tempo_us) => vec![(0, tempo_us)],
+                None => {
+                    // Stable, so same-tick changes keep track order, as they
+                    // would in the merged event list.
+                    tempo_changes.sort_by_key(|&(tick, _)| tick);
+                    tempo_changes
+                }
+            };
+            TempoMap::for_header(&header, changes, exact)
+        } else {
+            track_tempo_maps.remove(0)
+        };
 
-        let mut tempo_timeline: Vec<TempoPoint> = Vec::new();
-        let mut last_tick = 0u64;
-        let mut elapsed_ns = 0u64;
-        let mut current_tempo_us = 500_000u32;
-        let mut tick_ns = Self::tempo_to_tick_ns(current_tempo_us, self.header.ppqn);
-
-        tempo_timeline.push(TempoPoint {
-            absolute_tick: 0,
-            absolute_ns: 0,
-            tick_ns,
-        });
-
-        for event in &temp_events {
-            if let TempEventData::TempoChange { new_tempo_us } = event.data {
-                let delta_ticks = event.absolute_tick - last_tick;
-                elapsed_ns += delta_ticks * tick_ns;
-
-                current_tempo_us = new_tempo_us;
-                tick_ns = Self::tempo_to_tick_ns(current_tempo_us, self.header.ppqn);
-
-                tempo_timeline.push(TempoPoint {
-                    absolute_tick: event.absolute_tick,
-                    absolute_ns: elapsed_ns,
-                    tick_ns,
-                });
-
-                last_tick = event.absolute_tick;
-            }
-        }
+        metrics.tempo_map = phase.elapsed();
 
-        println!("[KazuMIDIParser] Converting ticks to absolute time in parallel...");
+        log_at!(Info, "Converting ticks to absolute time in parallel...");
+        let phase = Instant::now();
 
-        self.events = temp_events
-            .into_par_iter()
-            .map(|event| {
-                let tempo_point_index =
-                    tempo_timeline.partition_point(|p| p.absolute_tick <= event.absolute_tick) - 1;
-                let base_tempo_point = tempo_timeline[tempo_point_index];
-                let delta_ticks_from_base = event.absolute_tick - base_tempo_point.absolute_tick;
-                let final_ns = base_tempo_point.absolute_ns
-                    + (delta_ticks_from_base * base_tempo_point.tick_ns);
-
-                match event.data {
+        // Converted a block at a time so a cancel is noticed part way
+        // through. Each block is an indexed map, so rayon writes straight
+        // into the one exact-size allocation.
+        let mut events: Vec<MidiEvent> = Vec::with_capacity(temp_events.len());
+        for block in temp_events.chunks(CONVERT_BLOCK) {
+            self.options.check_cancelled()?;
+            events.par_extend(block.par_iter().map(|event| {
+                let (status, data1, data2, sysex_index) = match event.data {
                     TempEventData::Midi {
                         status,
                         data1,
                         data2,
-                    } => Some(MidiEvent {
-                        absolute_ns: final_ns,
-                        status,
-                        data1,
-                        data2,
-                        track_index: event.track_index,
-                        sysex_data: None,
-                    }),
-                    TempEventData::SysEx { data } => Some(MidiEvent {
-                        absolute_ns: final_ns,
-                        status: 0xF0,
-                        data1: 0,
-                        data2: 0,
-                        track_index: event.track_index,
-                        sysex_data: Some(data),
-                    }),
-                    TempEventData::TempoChange { .. } => None,
+                    } => (status, data1, data2, None),
+                    TempEventData::SysEx { index } => (
+                        0xF0,
+                        0,
+                        0,
+                        Some(sysex_base[event.track_index as usize] + index),
+                    ),
+                    TempEventData::Escape { index } => (
+                        0xF7,
+                        0,
+                        0,
+                        Some(sysex_base[event.track_index as usize] + index),
+                    ),
+                };
+                let map = sequence::track_map(&tempo_map, &track_tempo_maps, event.track_index);
+                MidiEvent {
+                    absolute_ns: map.tick_to_ns(event.absolute_tick),
+                    absolute_tick: event.absolute_tick,
+                    status,
+                    data1,
+                    data2,
+                    track_index: event.track_index,
+                    sysex_index,
                 }
-            })
-            .filter_map(|e| e)
-            .collect();
+            }));
+        }
+        drop(temp_events);
+
+        self.options.check_cancelled()?;
 
+        metas.sort_by_key(|m| m.absolute_tick);
+        for meta in &mut metas {
+            let map = sequence::track_map(&tempo_map, &track_tempo_maps, meta.track_index);
+            meta.absolute_ns = map.tick_to_ns(meta.absolute_tick);
+        }
+        if !track_tempo_maps.is_empty() {
+            sequence::sort_by_time(&mut events, &mut metas);
+        }
+        warnings.sort_by_key(|w| w.offset);
+        metrics.convert = phase.elapsed();
+        metrics.event_count = events.len() as u64;
+        metrics.meta_count = metas.len() as u64;
+        metrics.total = started.elapsed();
+
+        let text_encoding = self.options.text_encoding.resolve(
+            metas
+                .iter()
+                .filter(|m| m.is_text())
+                .map(|m| m.data.as_slice()),
+        );
+        self.sequence = MidiSequence {
+            header,
+            events,
+            sysex,
+            metas,
+            tempo_map,
+            track_tempo_maps,
+            track_metas,
+            warnings,
+            sound_bank: None,
+            text_encoding,
+            event_order: order,
+        };
+        self.metrics = metrics;
+        if self.mirror_events {
+            #[allow(deprecated)]
+            {
+                self.events = self.sequence.events.clone();
+            }
+        }
         self.is_parsed = true;
         Ok(())
     }
 
+    pub fn metrics(&self) -> Option<&ParseMetrics> {
+        if self.is_parsed {
+            Some(&self.metrics)
+        } else {
+            None
+        }
+    }
+
     pub fn get_events(&self) -> &Vec<MidiEvent> {
-        &self.events
+        &self.sequence.events
+    }
+
+    pub fn sequence(&self) -> Option<&MidiSequence> {
+        if self.is_parsed {
+            Some(&self.sequence)
+        } else {
+            None
+        }
+    }
+
+    pub fn into_sequence(self) -> Option<MidiSequence> {
+        if self.is_parsed {
+            Some(self.sequence)
+        } else {
+            None
+        }
     }
 
     pub fn get_track_event_indices(&self) -> Vec<Vec<usize>> {
         let mut track_event_indices: Vec<Vec<usize>> =
-            vec![Vec::new(); self.header.tracks as usize];
-        for (index, event) in self.events.iter().enumerate() {
+            vec![Vec::new(); self.sequence.header.tracks as usize];
+        for (index, event) in self.sequence.events.iter().enumerate() {
             if (event.track_index as usize) < track_event_indices.len() {
                 track_event_indices[event.track_index as usize].push(index);
             }
@@ -0,0 +1,72 @@
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+        })
+    }
+}
+
+type LogCallback = Arc<dyn Fn(LogLevel, &str) + Send + Sync>;
+
+static LOG_CALLBACK: RwLock<Option<LogCallback>> = RwLock::new(None);
+
+/// Routes the parser's progress and diagnostic messages to `callback`. The
+/// callback can be invoked from several worker threads at once.
+///
+/// Without a callback the messages go to the `log` crate (target
+/// `kazumidiparser`) when the `log` feature is on, and nowhere otherwise.
+pub fn set_log_callback<F>(callback: F)
+where
+    F: Fn(LogLevel, &str) + Send + Sync + 'static,
+{
+    *LOG_CALLBACK.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(callback));
+}
+
+// Restores the default of forwarding to `log`, or staying silent.
+pub fn clear_log_callback() {
+    *LOG_CALLBACK.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+pub(crate) fn emit(level: LogLevel, args: fmt::Arguments<'_>) {
+    let callback = LOG_CALLBACK
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    match callback {
+        Some(callback) => callback(level, &args.to_string()),
+        #[cfg(feature = "log")]
+        None => {
+            let level = match level {
+                LogLevel::Error => log::Level::Error,
+                LogLevel::Warn => log::Level::Warn,
+                LogLevel::Info => log::Level::Info,
+                LogLevel::Debug => log::Level::Debug,
+            };
+            log::log!(target: "kazumidiparser", level, "{}", args);
+        }
+        #[cfg(not(feature = "log"))]
+        None => {}
+    }
+}
+
+macro_rules! log_at {
+    ($level:ident, $($arg:tt)*) => {
+        $crate::logging::emit($crate::logging::LogLevel::$level, format_args!($($arg)*))
+    };
+}
+
+pub(crate) use log_at;
@@ -0,0 +1,90 @@
+use crate::{MarkerKind, MidiSequence};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopSource {
+    // "loopStart" / "loopEnd" markers, in any case.
+    Markers,
+    // EMIDI (Apogee) CC 118 and 119, looping the whole song.
+    EmidiGlobal,
+    // RPG Maker's CC 111, marking the loop start; the loop ends with the song.
+    Cc111,
+    // EMIDI CC 116 and 117, looping a single track; the first track with a
+    // loop start is used.
+    EmidiTrack,
+}
+
+/// The part of a song a game player repeats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopPoints {
+    pub start_ns: u64,
+    pub end_ns: u64,
+    pub start_tick: u64,
+    pub end_tick: u64,
+    pub source: LoopSource,
+}
+
+impl MidiSequence {
+    /// The loop a game player should repeat, by the first convention that
+    /// matches, in `LoopSource` order. A loop without an end runs to the end
+    /// of the song (`total_duration_tick`), one without a start from 0.
+    pub fn loop_points(&self) -> Option<LoopPoints> {
+        let song_end = self.total_duration_tick();
+        let points = |start_tick: Option<u64>, end_tick: Option<u64>, source| {
+            let start_tick = start_tick.unwrap_or(0);
+            let end_tick = end_tick.unwrap_or(song_end);
+            (start_tick < end_tick).then(|| LoopPoints {
+                start_ns: self.tempo_map.tick_to_ns(start_tick),
+                end_ns: self.tempo_map.tick_to_ns(end_tick),
+                start_tick,
+                end_tick,
+                source,
+            })
+        };
+
+        let markers = self.markers();
+        let marker_tick = |name: &str| {
+            markers
+                .of_kind(MarkerKind::Marker)
+                .find(|m| m.name.trim().eq_ignore_ascii_case(name))
+                .map(|m| m.absolute_tick)
+        };
+        let (start, end) = (marker_tick("loopStart"), marker_tick("loopEnd"));
+        if start.is_some() || end.is_some() {
+            return points(start, end, LoopSource::Markers);
+        }
+
+        let controller_tick = |controller: u8, track: Option<u16>| {
+            self.events
+                .iter()
+                .find(|e| {
+                    e.status & 0xF0 == 0xB0
+                        && e.data1 == controller
+                        && track.is_none_or(|t| e.track_index == t)
+                })
+                .map(|e| e.absolute_tick)
+        };
+        let start = controller_tick(118, None);
+        if start.is_some() {
+            return points(start, controller_tick(119, None), LoopSource::EmidiGlobal);
+        }
+        let start = controller_tick(111, None);
+        if start.is_some() {
+            return points(start, None, LoopSource::Cc111);
+        }
+        let track = self
+            .events
+            .iter()
+            .find(|e| e.status & 0xF0 == 0xB0 && e.data1 == 116)?
+            .track_index;
+        points(
+            controller_tick(116, Some(track)),
+            controller_tick(117, Some(track)),
+            LoopSource::EmidiTrack,
+        )
+    }
+
+    /// `(start_ns, end_ns)` of `loop_points`.
+    pub fn loop_region(&self) -> Option<(u64, u64)> {
+        self.loop_points().map(|l| (l.start_ns, l.end_ns))
+    }
+}
@@ -0,0 +1,156 @@
+use mlua::{Function, Lua, MetaMethod, Table, UserData, UserDataMethods, UserDataRef, Value};
+
+use crate::{MidiEvent, MidiParser, MidiSequence, ParseOptions};
+
+/// A parsed sequence as Lua userdata.
+pub struct LuaSequence(pub MidiSequence);
+
+fn event_table<'lua>(
+    lua: &'lua Lua,
+    sequence: &MidiSequence,
+    event: &MidiEvent,
+) -> mlua::Result<Table<'lua>> {
+    let table = lua.create_table()?;
+    table.set("ns", event.absolute_ns)?;
+    table.set("tick", event.absolute_tick)?;
+    table.set("status", event.status)?;
+    table.set("data1", event.data1)?;
+    table.set("data2", event.data2)?;
+    table.set("track", event.track_index)?;
+    if let Some(data) = sequence.sysex(event) {
+        table.set("sysex", lua.create_string(data)?)?;
+    }
+    Ok(table)
+}
+
+impl UserData for LuaSequence {
+    fn add_methods<'lua, M: UserDataMethods<'lua, Self>>(methods: &mut M) {
+        methods.add_method("header", |lua, this, ()| {
+            let header = this.0.header();
+            let table = lua.create_table()?;
+            table.set("format", header.format)?;
+            table.set("tracks", header.tracks)?;
+            table.set("ppqn", header.ppqn)?;
+            Ok(table)
+        });
+        methods.add_method("event_count", |_, this, ()| Ok(this.0.events().len()));
+        methods.add_meta_method(MetaMethod::Len, |_, this, ()| Ok(this.0.events().len()));
+
+        // Events are 1-based, as usual in Lua.
+        methods.add_method("event", |lua, this, index: usize| {
+            match index.checked_sub(1).and_then(|i| this.0.events().get(i)) {
+                Some(event) => Ok(Some(event_table(lua, &this.0, event)?)),
+                None => Ok(None),
+            }
+        });
+        methods.add_method("events", |lua, this, ()| {
+            let table = lua.create_table_with_capacity(this.0.events().len(), 0)?;
+            for event in this.0.events() {
+                table.raw_push(event_table(lua, &this.0, event)?)?;
+            }
+            Ok(table)
+        });
+        // Calls `f(event)` for every event; returning false stops early.
+        methods.add_method("each_event", |lua, this, f: Function| {
+            for event in this.0.events() {
+                if let Value::Boolean(false) =
+                    f.call::<_, Value>(event_table(lua, &this.0, event)?)?
+                {
+                    break;
+                }
+            }
+            Ok(())
+        });
+
+        methods.add_method("duration_ns", |_, this, ()| Ok(this.0.total_duration_ns()));
+        methods.add_method("tick_to_ns", |_, this, tick: u64| {
+            Ok(this.0.tempo_map().tick_to_ns(tick))
+        });
+        methods.add_method("ns_to_tick", |_, this, ns: u64| {
+            Ok(this.0.tempo_map().ns_to_tick(ns))
+        });
+        methods.add_method("tempo_changes", |lua, this, ()| {
+            let table = lua.create_table()?;
+            for (tick, tempo_us) in this.0.tempo_map().changes() {
+                let change = lua.create_table()?;
+                change.set("tick", tick)?;
+                change.set("tempo_us", tempo_us)?;
+                table.raw_push(change)?;
+            }
+            Ok(table)
+        });
+        methods.add_method("track_info", |lua, this, ()| {
+            let table = lua.create_table()?;
+            for info in this.0.track_info() {
+                let track = lua.create_table()?;
+                track.set("index", info.index)?;
+                track.set("name", info.name)?;
+                track.set("instrument_name", info.instrument_name)?;
+                track.set("program", info.program)?;
+                track.set("program_name", info.program_name)?;
+                track.set("event_count", info.event_count)?;
+                track.set("note_count", info.note_count)?;
+                table.raw_push(track)?;
+            }
+            Ok(table)
+        });
+
+        methods.add_method("clone", |_, this, ()| Ok(LuaSequence(this.0.clone())));
+        methods.add_method_mut("resample_ppqn", |_, this, ppqn: u16| {
+            Ok(this.0.resample_ppqn(ppqn))
+        });
+        methods.add_method_mut("set_tempo", |_, this, (tick, tempo_us): (u64, u32)| {
+            this.0.edit_tempo_map(|map| map.set_tempo(tick, tempo_us));
+            Ok(())
+        });
+        methods.add_method_mut("remove_tempo", |_, this, tick: u64| {
+            let mut removed = false;
+            this.0
+                .edit_tempo_map(|map| removed = map.remove_tempo(tick));
+            Ok(removed)
+        });
+        methods.add_method_mut(
+            "append",
+            |_, this, (other, gap_ns): (UserDataRef<LuaSequence>, Option<u64>)| {
+                this.0.append(&other.0, gap_ns.unwrap_or(0));
+                Ok(())
+            },
+        );
+    }
+}
+
+fn parse<'lua>(
+    _: &'lua Lua,
+    (path, options): (String, Option<Table<'lua>>),
+) -> mlua::Result<LuaSequence> {
+    let fixed_bpm = match options {
+        Some(options) => options.get("bpm")?,
+        None => None,
+    };
+    let mut parser = MidiParser::with_options(ParseOptions {
+        fixed_bpm,
+        ..Default::default()
+    })
+    .without_events_copy();
+    parser
+        .parse_file(&path)
+        .map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+    Ok(LuaSequence(parser.into_sequence().unwrap()))
+}
+
+/// Builds the `kazumidiparser` module table and registers it in
+/// `package.loaded`, so scripts can `require("kazumidiparser")`.
+///
+/// ```lua
+/// local kmp = require("kazumidiparser")
+/// local seq = kmp.parse("song.mid", { bpm = 120 })
+/// seq:each_event(function(e) print(e.ns, e.status, e.data1, e.data2) end)
+/// ```
+pub fn register(lua: &Lua) -> mlua::Result<Table<'_>> {
+    let module = lua.create_table()?;
+    module.set("parse", lua.create_function(parse)?)?;
+
+    let loaded: Table = lua.globals().get::<_, Table>("package")?.get("loaded")?;
+    loaded.set("kazumidiparser", module.clone())?;
+    Ok(module)
+}
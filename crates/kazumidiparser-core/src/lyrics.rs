@@ -0,0 +1,153 @@
+//! Timed lyrics for karaoke display, from lyric metas or from the text metas
+//! of Soft Karaoke (`.kar`) files.
+//!
+//! Soft Karaoke keeps its lyrics in text (0x01) events: `@`-prefixed ones are
+//! headers (`@T` title lines, `@I` information, `@L` language), and a syllable
+//! starting with `\` begins a new paragraph and one starting with `/` a new
+//! line. Other files use lyric (0x05) events, which often end lines with a
+//! CR or LF instead. Both conventions are honoured whichever kind is used.
+//! Text is decoded with the sequence's text encoding.
+
+use crate::{MetaEvent, MidiSequence};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LyricSyllable {
+    pub absolute_ns: u64,
+    pub absolute_tick: u64,
+    // As written, spaces included, without the line break markers.
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LyricLine {
+    // Whether the line starts a new paragraph (a cleared screen in Soft
+    // Karaoke). The first line always does.
+    pub paragraph_start: bool,
+    pub syllables: Vec<LyricSyllable>,
+}
+
+impl LyricLine {
+    pub fn start_ns(&self) -> u64 {
+        self.syllables[0].absolute_ns
+    }
+
+    pub fn text(&self) -> String {
+        self.syllables.iter().map(|s| s.text.as_str()).collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LyricTimeline {
+    // Soft Karaoke `@T` lines: the title, then usually the performer and
+    // copyright.
+    pub titles: Vec<String>,
+    // Soft Karaoke `@I` lines.
+    pub info: Vec<String>,
+    pub language: Option<String>,
+    // Every line has at least one syllable.
+    pub lines: Vec<LyricLine>,
+}
+
+impl LyricTimeline {
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    /// The line and syllable indices of the syllable sung at `ns`: the last
+    /// one starting at or before it. None before the first syllable.
+    pub fn position_at(&self, ns: u64) -> Option<(usize, usize)> {
+        let line = self
+            .lines
+            .partition_point(|l| l.start_ns() <= ns)
+            .checked_sub(1)?;
+        let syllables = &self.lines[line].syllables;
+        let syllable = syllables.partition_point(|s| s.absolute_ns <= ns) - 1;
+        Some((line, syllable))
+    }
+
+    fn push_text(&mut self, meta: &MetaEvent, text: &str, pending_break: &mut Option<bool>) {
+        let mut rest = text;
+        if let Some(stripped) = rest.strip_prefix('\\') {
+            *pending_break = Some(true);
+            rest = stripped;
+        } else if let Some(stripped) = rest.strip_prefix('/') {
+            pending_break.get_or_insert(false);
+            rest = stripped;
+        }
+
+        let mut segments = rest.split(['\r', '\n']).peekable();
+        while let Some(segment) = segments.next() {
+            if !segment.is_empty() {
+                if self.lines.is_empty() || pending_break.is_some() {
+                    self.lines.push(LyricLine {
+                        paragraph_start: self.lines.is_empty() || *pending_break == Some(true),
+                        syllables: Vec::new(),
+                    });
+                    *pending_break = None;
+                }
+                self.lines
+                    .last_mut()
+                    .unwrap()
+                    .syllables
+                    .push(LyricSyllable {
+                        absolute_ns: meta.absolute_ns,
+                        absolute_tick: meta.absolute_tick,
+                        text: segment.to_owned(),
+                    });
+            }
+            if segments.peek().is_some() {
+                pending_break.get_or_insert(false);
+            }
+        }
+    }
+}
+
+impl MidiSequence {
+    /// Gathers the lyrics into lines of timed syllables. Soft Karaoke text
+    /// is used when the file has an `@K` header, lyric metas otherwise.
+    pub fn lyric_timeline(&self) -> LyricTimeline {
+        let is_header = |meta: &MetaEvent| meta.data.first() == Some(&b'@');
+        let soft_karaoke = self
+            .metas_of_type(0x01)
+            .any(|meta| meta.data.starts_with(b"@K"));
+
+        let mut timeline = LyricTimeline::default();
+        let lyrics: Vec<&MetaEvent> = if soft_karaoke {
+            for meta in self.metas_of_type(0x01).filter(|meta| is_header(meta)) {
+                let value = self.decode_text(&meta.data[2.min(meta.data.len())..]);
+                match meta.data.get(1) {
+                    Some(b'T') => timeline.titles.push(value),
+                    Some(b'I') => timeline.info.push(value),
+                    Some(b'L') => timeline.language = Some(value),
+                    _ => {}
+                }
+            }
+            // Other tracks may carry stray text; the words are on the track
+            // with the most of it.
+            let mut counts = vec![0usize; self.header.tracks as usize];
+            for meta in self.metas_of_type(0x01).filter(|meta| !is_header(meta)) {
+                if let Some(count) = counts.get_mut(meta.track_index as usize) {
+                    *count += 1;
+                }
+            }
+            // Reversed so a tie goes to the first track.
+            let words_track = counts
+                .iter()
+                .enumerate()
+                .rev()
+                .max_by_key(|&(_, count)| *count)
+                .map(|(i, _)| i);
+            self.metas_of_type(0x01)
+                .filter(|meta| words_track == Some(meta.track_index as usize) && !is_header(meta))
+                .collect()
+        } else {
+            self.metas_of_type(0x05).collect()
+        };
+
+        let mut pending_break = None;
+        for meta in lyrics {
+            timeline.push_text(meta, &self.decode_text(&meta.data), &mut pending_break);
+        }
+        timeline
+    }
+}
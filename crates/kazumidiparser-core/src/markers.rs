@@ -0,0 +1,92 @@
+use crate::MidiSequence;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerKind {
+    // Meta 0x06: a section name such as "Verse" or "Chorus".
+    Marker,
+    // Meta 0x07: something that happens at this point, such as a sound
+    // effect or a curtain opening.
+    CuePoint,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Marker {
+    pub kind: MarkerKind,
+    pub name: String,
+    pub absolute_ns: u64,
+    pub absolute_tick: u64,
+    pub track_index: u16,
+}
+
+/// Time-ordered markers and cue points, for jumping between the sections
+/// of a song.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MarkerList {
+    markers: Vec<Marker>,
+}
+
+impl MarkerList {
+    pub fn markers(&self) -> &[Marker] {
+        &self.markers
+    }
+
+    pub fn len(&self) -> usize {
+        self.markers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.markers.is_empty()
+    }
+
+    pub fn of_kind(&self, kind: MarkerKind) -> impl Iterator<Item = &Marker> {
+        self.markers.iter().filter(move |m| m.kind == kind)
+    }
+
+    /// The section playing at `ns`: the last marker at or before it.
+    pub fn marker_at_or_before(&self, ns: u64) -> Option<&Marker> {
+        let index = self.markers.partition_point(|m| m.absolute_ns <= ns);
+        self.markers[..index].last()
+    }
+
+    /// The first marker strictly after `ns`, for "next section".
+    pub fn marker_after(&self, ns: u64) -> Option<&Marker> {
+        let index = self.markers.partition_point(|m| m.absolute_ns <= ns);
+        self.markers.get(index)
+    }
+
+    /// The last marker strictly before `ns`, for "previous section".
+    pub fn marker_before(&self, ns: u64) -> Option<&Marker> {
+        let index = self.markers.partition_point(|m| m.absolute_ns < ns);
+        self.markers[..index].last()
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Marker> {
+        self.markers.iter().find(|m| m.name == name)
+    }
+}
+
+impl MidiSequence {
+    /// The marker (0x06) and cue point (0x07) events of every track, names
+    /// decoded with the sequence's text encoding.
+    pub fn markers(&self) -> MarkerList {
+        let markers = self
+            .metas
+            .iter()
+            .filter_map(|meta| {
+                let kind = match meta.meta_type {
+                    0x06 => MarkerKind::Marker,
+                    0x07 => MarkerKind::CuePoint,
+                    _ => return None,
+                };
+                Some(Marker {
+                    kind,
+                    name: self.decode_text(&meta.data),
+                    absolute_ns: meta.absolute_ns,
+                    absolute_tick: meta.absolute_tick,
+                    track_index: meta.track_index,
+                })
+            })
+            .collect();
+        MarkerList { markers }
+    }
+}
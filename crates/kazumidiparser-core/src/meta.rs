@@ -0,0 +1,142 @@
+/// A meta event (other than End of Track) kept with its raw payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MetaEvent {
+    pub absolute_ns: u64,
+    pub absolute_tick: u64,
+    pub track_index: u16,
+    pub meta_type: u8,
+    pub data: Vec<u8>,
+}
+
+impl MetaEvent {
+    // Text-type metas (0x01-0x0F) carry free text; the payload is not
+    // guaranteed to be UTF-8.
+    pub fn is_text(&self) -> bool {
+        (0x01..=0x0F).contains(&self.meta_type)
+    }
+
+    // As UTF-8; `MidiSequence::meta_text` uses the file's text encoding.
+    pub fn text(&self) -> Option<String> {
+        self.is_text()
+            .then(|| String::from_utf8_lossy(&self.data).into_owned())
+    }
+
+    /// Decodes the payload by meta type. Payloads of the wrong length come
+    /// back as `MetaKind::Other` rather than being guessed at.
+    pub fn kind(&self) -> MetaKind<'_> {
+        let data = self.data.as_slice();
+        match (self.meta_type, data) {
+            (0x00, []) => MetaKind::SequenceNumber(None),
+            (0x00, &[hi, lo]) => MetaKind::SequenceNumber(Some(u16::from_be_bytes([hi, lo]))),
+            (0x01..=0x0F, _) => MetaKind::Text {
+                kind: TextKind::from_meta_type(self.meta_type),
+                data,
+            },
+            (0x20, &[channel]) => MetaKind::ChannelPrefix(channel),
+            (0x21, &[port]) => MetaKind::Port(port),
+            (0x51, &[a, b, c]) => MetaKind::Tempo {
+                us_per_quarter: u32::from_be_bytes([0, a, b, c]),
+            },
+            (0x54, &[hours, minutes, seconds, frames, subframes]) => MetaKind::SmpteOffset {
+                rate: hours >> 5 & 0x03,
+                hours: hours & 0x1F,
+                minutes,
+                seconds,
+                frames,
+                subframes,
+            },
+            (0x58, &[numerator, denominator_log2, clocks_per_click, notated_32nds]) => {
+                MetaKind::TimeSignature {
+                    numerator,
+                    denominator_log2,
+                    clocks_per_click,
+                    notated_32nds_per_quarter: notated_32nds,
+                }
+            }
+            (0x59, &[sharps, mode]) => MetaKind::KeySignature {
+                sharps: sharps as i8,
+                minor: mode == 1,
+            },
+            (0x7F, _) => MetaKind::SequencerSpecific(data),
+            (meta_type, _) => MetaKind::Other { meta_type, data },
+        }
+    }
+}
+
+/// The flavours of text meta event (0x01-0x0F).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextKind {
+    Text,
+    Copyright,
+    TrackName,
+    InstrumentName,
+    Lyric,
+    Marker,
+    CuePoint,
+    ProgramName,
+    DeviceName,
+    // 0x0A-0x0F are reserved for text but have no assigned meaning.
+    Reserved(u8),
+}
+
+impl TextKind {
+    fn from_meta_type(meta_type: u8) -> TextKind {
+        match meta_type {
+            0x01 => TextKind::Text,
+            0x02 => TextKind::Copyright,
+            0x03 => TextKind::TrackName,
+            0x04 => TextKind::InstrumentName,
+            0x05 => TextKind::Lyric,
+            0x06 => TextKind::Marker,
+            0x07 => TextKind::CuePoint,
+            0x08 => TextKind::ProgramName,
+            0x09 => TextKind::DeviceName,
+            other => TextKind::Reserved(other),
+        }
+    }
+}
+
+/// A meta event's payload decoded by type, borrowing from the `MetaEvent`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetaKind<'a> {
+    // An empty payload means "use the track's position".
+    SequenceNumber(Option<u16>),
+    // Not guaranteed to be UTF-8; see `MetaEvent::text`.
+    Text {
+        kind: TextKind,
+        data: &'a [u8],
+    },
+    ChannelPrefix(u8),
+    Port(u8),
+    Tempo {
+        us_per_quarter: u32,
+    },
+    SmpteOffset {
+        // 0 = 24, 1 = 25, 2 = 29.97 drop-frame, 3 = 30 fps.
+        rate: u8,
+        hours: u8,
+        minutes: u8,
+        seconds: u8,
+        frames: u8,
+        // Hundredths of a frame.
+        subframes: u8,
+    },
+    TimeSignature {
+        numerator: u8,
+        // The denominator is 2 to this power.
+        denominator_log2: u8,
+        clocks_per_click: u8,
+        notated_32nds_per_quarter: u8,
+    },
+    KeySignature {
+        // Negative for flats.
+        sharps: i8,
+        minor: bool,
+    },
+    SequencerSpecific(&'a [u8]),
+    Other {
+        meta_type: u8,
+        data: &'a [u8],
+    },
+}
@@ -0,0 +1,44 @@
+use std::time::Duration;
+
+/// Timings and sizes recorded by the last `MidiParser` parse.
+#[derive(Debug, Clone, Default)]
+pub struct ParseMetrics {
+    pub file_bytes: u64,
+    pub track_count: u16,
+    pub event_count: u64,
+    pub meta_count: u64,
+    // Reading track chunks off the disk. Overlaps `parse_tracks`.
+    pub read: Duration,
+    // MTrk chunk discovery. Overlaps `parse_tracks`.
+    pub locate_chunks: Duration,
+    // Reading and decoding all tracks, from the first chunk read to the last
+    // track parsed.
+    pub parse_tracks: Duration,
+    // Merging the tracks into one tick-ordered list.
+    pub sort: Duration,
+    pub tempo_map: Duration,
+    // Tick -> nanosecond conversion of every event and meta.
+    pub convert: Duration,
+    pub total: Duration,
+}
+
+impl ParseMetrics {
+    pub fn phases(&self) -> [(&'static str, Duration); 6] {
+        [
+            ("read", self.read),
+            ("locate chunks", self.locate_chunks),
+            ("parse tracks", self.parse_tracks),
+            ("sort", self.sort),
+            ("tempo map", self.tempo_map),
+            ("convert", self.convert),
+        ]
+    }
+
+    pub fn bytes_per_second(&self) -> f64 {
+        self.file_bytes as f64 / self.total.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+
+    pub fn events_per_second(&self) -> f64 {
+        self.event_count as f64 / self.total.as_secs_f64().max(f64::MIN_POSITIVE)
+    }
+}
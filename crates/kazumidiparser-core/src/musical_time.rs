@@ -0,0 +1,90 @@
+use std::ops::Range;
+
+use crate::{BarBeat, MidiSequence, TempoMap, TimeSignatureMap};
+
+/// The start of a bar, in both clocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BarLine {
+    // Zero-based.
+    pub bar: u64,
+    pub absolute_tick: u64,
+    pub absolute_ns: u64,
+    pub numerator: u8,
+    // The denominator is 2 to this power.
+    pub denominator_log2: u8,
+}
+
+/// Converts between wall-clock time and bars and beats, going through ticks:
+/// the tempo map for time, the time signature map for bars.
+#[derive(Debug, Clone)]
+pub struct MusicalTime {
+    tempo_map: TempoMap,
+    time_signatures: TimeSignatureMap,
+}
+
+impl MusicalTime {
+    /// Both maps must use the same PPQN.
+    pub fn new(tempo_map: TempoMap, time_signatures: TimeSignatureMap) -> MusicalTime {
+        assert_eq!(
+            tempo_map.ppqn(),
+            time_signatures.ppqn(),
+            "tempo and time signature maps use different PPQNs"
+        );
+        MusicalTime {
+            tempo_map,
+            time_signatures,
+        }
+    }
+
+    pub fn tempo_map(&self) -> &TempoMap {
+        &self.tempo_map
+    }
+
+    pub fn time_signature_map(&self) -> &TimeSignatureMap {
+        &self.time_signatures
+    }
+
+    pub fn ns_to_bar_beat(&self, ns: u64) -> BarBeat {
+        self.time_signatures
+            .tick_to_bar_beat(self.tempo_map.ns_to_tick(ns))
+    }
+
+    pub fn bar_beat_to_ns(&self, position: BarBeat) -> u64 {
+        self.tempo_map
+            .tick_to_ns(self.time_signatures.bar_beat_to_tick(position))
+    }
+
+    pub fn bar_line(&self, bar: u64) -> BarLine {
+        let absolute_tick = self.time_signatures.bar_start(bar);
+        let point = self.time_signatures.at_tick(absolute_tick);
+        BarLine {
+            bar,
+            absolute_tick,
+            absolute_ns: self.tempo_map.tick_to_ns(absolute_tick),
+            numerator: point.numerator,
+            denominator_log2: point.denominator_log2,
+        }
+    }
+
+    /// Every bar line from the first, without end; stop it at the song's
+    /// end (`MidiSequence::total_duration_ns`) or the edge of a view.
+    pub fn bars(&self) -> impl Iterator<Item = BarLine> + '_ {
+        (0..).map(|bar| self.bar_line(bar))
+    }
+
+    /// The bar lines a view of `range.start <= ns < range.end` shows, led by
+    /// the one of the bar `range.start` falls in.
+    pub fn bars_in_range(&self, range: Range<u64>) -> impl Iterator<Item = BarLine> + '_ {
+        let first = self.ns_to_bar_beat(range.start).bar;
+        (first..)
+            .map(|bar| self.bar_line(bar))
+            .take_while(move |line| line.bar == first || line.absolute_ns < range.end)
+    }
+}
+
+impl MidiSequence {
+    /// Bars and beats over the song's tempo map and time signature events.
+    pub fn musical_time(&self) -> MusicalTime {
+        MusicalTime::new(self.tempo_map.clone(), self.time_signature_map())
+    }
+}
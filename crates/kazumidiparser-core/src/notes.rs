@@ -0,0 +1,78 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::MidiSequence;
+
+/// A note on paired with the note off that ends it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Note {
+    pub track_index: u16,
+    pub channel: u8,
+    pub key: u8,
+    pub velocity: u8,
+    pub start_ns: u64,
+    pub duration_ns: u64,
+    pub start_tick: u64,
+    pub duration_ticks: u64,
+    // No note off was found, so the note runs to the end of the song.
+    pub unterminated: bool,
+}
+
+impl Note {
+    pub fn end_ns(&self) -> u64 {
+        self.start_ns + self.duration_ns
+    }
+
+    pub fn end_tick(&self) -> u64 {
+        self.start_tick + self.duration_ticks
+    }
+}
+
+impl MidiSequence {
+    /// Pairs every note on with a note off (or a note on of velocity 0) on
+    /// the same track, channel and key, first on first off, ordered by note
+    /// on.
+    ///
+    /// A note off with no sounding note to end is ignored. Notes still
+    /// sounding at the end are returned as `unterminated`, lasting until
+    /// `end_ns`.
+    pub fn notes(&self) -> Vec<Note> {
+        let mut notes: Vec<Note> = Vec::new();
+        // Indices into `notes` of the sounding notes, per slot.
+        let mut open: HashMap<(u16, u8, u8), VecDeque<usize>> = HashMap::new();
+        for event in &self.events {
+            let kind = event.status & 0xF0;
+            if kind != 0x80 && kind != 0x90 {
+                continue;
+            }
+            let slot = (event.track_index, event.status & 0x0F, event.data1 & 0x7F);
+            if kind == 0x90 && event.data2 > 0 {
+                open.entry(slot).or_default().push_back(notes.len());
+                notes.push(Note {
+                    track_index: slot.0,
+                    channel: slot.1,
+                    key: slot.2,
+                    velocity: event.data2,
+                    start_ns: event.absolute_ns,
+                    duration_ns: 0,
+                    start_tick: event.absolute_tick,
+                    duration_ticks: 0,
+                    unterminated: false,
+                });
+            } else if let Some(index) = open.get_mut(&slot).and_then(|q| q.pop_front()) {
+                let note = &mut notes[index];
+                note.duration_ns = event.absolute_ns - note.start_ns;
+                note.duration_ticks = event.absolute_tick - note.start_tick;
+            }
+        }
+
+        let (end_ns, end_tick) = (self.end_ns(), self.end_tick());
+        for index in open.into_values().flatten() {
+            let note = &mut notes[index];
+            note.duration_ns = end_ns.saturating_sub(note.start_ns);
+            note.duration_ticks = end_tick.saturating_sub(note.start_tick);
+            note.unterminated = true;
+        }
+        notes
+    }
+}
@@ -0,0 +1,175 @@
+use std::ops::{BitOr, BitOrAssign, RangeInclusive};
+
+use crate::{CancelToken, ParseError, TextEncoding};
+
+/// The kinds of event a parse keeps, combined with `|`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EventMask(u8);
+
+impl EventMask {
+    pub const NONE: EventMask = EventMask(0);
+    // One bit per channel message status nibble, 0x8 to 0xE.
+    pub const NOTE_OFF: EventMask = EventMask(1 << 0);
+    pub const NOTE_ON: EventMask = EventMask(1 << 1);
+    pub const POLY_PRESSURE: EventMask = EventMask(1 << 2);
+    pub const CONTROL_CHANGE: EventMask = EventMask(1 << 3);
+    pub const PROGRAM_CHANGE: EventMask = EventMask(1 << 4);
+    pub const CHANNEL_PRESSURE: EventMask = EventMask(1 << 5);
+    pub const PITCH_BEND: EventMask = EventMask(1 << 6);
+    // SysEx messages and F7 escape packets.
+    pub const SYSEX: EventMask = EventMask(1 << 7);
+    pub const NOTES: EventMask = EventMask(Self::NOTE_OFF.0 | Self::NOTE_ON.0);
+    pub const ALL: EventMask = EventMask(u8::MAX);
+
+    pub fn contains(self, other: EventMask) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    // Whether an event with this status byte is kept. Only channel messages
+    // and SysEx end up in the event list, so other statuses never are.
+    pub(crate) fn keeps_status(self, status: u8) -> bool {
+        match status {
+            0x80..=0xEF => self.0 & (1 << ((status >> 4) - 8)) != 0,
+            0xF0 | 0xF7 => self.contains(EventMask::SYSEX),
+            _ => false,
+        }
+    }
+}
+
+impl Default for EventMask {
+    fn default() -> Self {
+        EventMask::ALL
+    }
+}
+
+impl BitOr for EventMask {
+    type Output = EventMask;
+
+    fn bitor(self, other: EventMask) -> EventMask {
+        EventMask(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for EventMask {
+    fn bitor_assign(&mut self, other: EventMask) {
+        self.0 |= other.0;
+    }
+}
+
+/// How events on the same tick are ordered. Either way the order is fixed
+/// by the file, so every parse of it gives the same event list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum EventOrder {
+    /// By track, then in the order they appear in the track.
+    #[default]
+    Track,
+    /// By track, then note offs (and note ons of velocity 0) ahead of the
+    /// track's other events, then in track order. Keeps a note that ends
+    /// and restarts on one tick from being cut off by its own note off.
+    NoteOffsFirst,
+}
+
+impl EventOrder {
+    // Sorts before higher ranks on the same tick and track.
+    pub(crate) fn rank(self, status: u8, data2: u8) -> u8 {
+        let note_off = status & 0xF0 == 0x80 || (status & 0xF0 == 0x90 && data2 == 0);
+        match self {
+            EventOrder::NoteOffsFirst if note_off => 0,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Ignore every tempo meta event and time the whole file at this BPM.
+    pub fixed_bpm: Option<f64>,
+    /// Checked after every decoded event, between stages and between
+    /// blocks of events while converting ticks; once cancelled, the parse
+    /// stops with `ParseError::Cancelled`.
+    pub cancel_token: Option<CancelToken>,
+    /// Keep the last channel status across meta and SysEx events, which the
+    /// SMF spec says cancel it. Many writers rely on this, so it is on by
+    /// default; each use is counted in
+    /// `MidiSequence::running_status_fallbacks` and reported as a warning.
+    /// Turn it off (with `strict`) to reject such files.
+    pub lenient_running_status: bool,
+    /// Fail on the first bad byte in a track or missing track chunk. When
+    /// off, `MidiParser` skips stray data bytes up to the next status byte,
+    /// implies `lenient_running_status`, keeps the tracks read before a bad
+    /// chunk, and reports what it worked around in
+    /// `MidiSequence::warnings`. On by default.
+    pub strict: bool,
+    /// Keep only the events of these tracks. The others are still read for
+    /// their meta events (tempo, time signatures, markers), except in format
+    /// 2 files where they are skipped entirely. Track indices are unchanged.
+    pub track_filter: Option<RangeInclusive<u16>>,
+    /// Keep only the channel messages of the channels whose bit is set, bit
+    /// 0 being channel 1: `Some(1 << 9)` keeps only the drums on channel 10.
+    /// SysEx and meta events are always kept.
+    pub channel_filter: Option<u16>,
+    /// The kinds of event to keep in the event list, all by default. Meta
+    /// events are always kept.
+    pub event_mask: EventMask,
+    /// The order of events on the same tick.
+    pub event_order: EventOrder,
+    /// Build an exact tempo map (see `TempoMap`), for sample-accurate timing
+    /// over long files at the cost of slower tick conversion.
+    pub exact_timing: bool,
+    /// How meta text is decoded; see `MidiSequence::decode_text`. UTF-8 by
+    /// default.
+    pub text_encoding: TextEncoding,
+    /// Have `MidiParser::parse_file` map the file into memory and parse the
+    /// tracks in place instead of reading each into its own buffer.
+    #[cfg(feature = "mmap")]
+    pub memory_map: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            fixed_bpm: None,
+            cancel_token: None,
+            lenient_running_status: true,
+            strict: true,
+            track_filter: None,
+            channel_filter: None,
+            event_mask: EventMask::ALL,
+            event_order: EventOrder::Track,
+            exact_timing: false,
+            text_encoding: TextEncoding::Utf8,
+            #[cfg(feature = "mmap")]
+            memory_map: false,
+        }
+    }
+}
+
+impl ParseOptions {
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancel_token.as_ref().is_some_and(|t| t.is_cancelled())
+    }
+
+    pub(crate) fn check_cancelled(&self) -> Result<(), ParseError> {
+        if self.is_cancelled() {
+            return Err(ParseError::Cancelled);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn keeps_track(&self, track_index: u16) -> bool {
+        self.track_filter
+            .as_ref()
+            .is_none_or(|tracks| tracks.contains(&track_index))
+    }
+
+    pub(crate) fn keeps_channel(&self, channel: u8) -> bool {
+        self.channel_filter
+            .is_none_or(|mask| mask & (1 << channel) != 0)
+    }
+
+    pub(crate) fn fixed_tempo_us(&self) -> Option<u32> {
+        self.fixed_bpm
+            .filter(|bpm| bpm.is_finite() && *bpm > 0.0)
+            .map(|bpm| (60_000_000.0 / bpm).round().clamp(1.0, 0xFF_FFFF as f64) as u32)
+    }
+}
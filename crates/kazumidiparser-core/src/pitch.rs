@@ -0,0 +1,141 @@
+pub const STANDARD_A4_HZ: f64 = 440.0;
+
+const LETTERS: [(char, i32); 7] = [
+    ('C', 0),
+    ('D', 2),
+    ('E', 4),
+    ('F', 5),
+    ('G', 7),
+    ('A', 9),
+    ('B', 11),
+];
+
+// Letter indices in the order sharps (F C G D A E B) are added to a key signature;
+// flats are added in the reverse order.
+const SHARP_ORDER: [usize; 7] = [3, 0, 4, 1, 5, 2, 6];
+
+/// Which octave number middle C (key 60) is written with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OctaveConvention {
+    /// Scientific pitch notation, key 60 = C4.
+    #[default]
+    MiddleC4,
+    /// Yamaha/many DAWs, key 60 = C3.
+    MiddleC3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpelledPitch {
+    pub letter: char,
+    pub alter: i8,
+    /// Scientific octave (key 60 = C4) regardless of display convention.
+    pub octave: i32,
+}
+
+impl OctaveConvention {
+    fn offset(self) -> i32 {
+        match self {
+            OctaveConvention::MiddleC4 => 0,
+            OctaveConvention::MiddleC3 => -1,
+        }
+    }
+}
+
+fn signature_alter(letter_index: usize, fifths: i8) -> i8 {
+    let count = fifths.unsigned_abs().min(7) as usize;
+    if fifths > 0 && SHARP_ORDER[..count].contains(&letter_index) {
+        1
+    } else if fifths < 0 && SHARP_ORDER[7 - count..].contains(&letter_index) {
+        -1
+    } else {
+        0
+    }
+}
+
+fn spelled(key: u8, letter_index: usize, alter: i8) -> SpelledPitch {
+    let (letter, base) = LETTERS[letter_index];
+    SpelledPitch {
+        letter,
+        alter,
+        octave: (key as i32 - alter as i32 - base).div_euclid(12) - 1,
+    }
+}
+
+/// Spells a key number for a key signature given in fifths (-7..=7).
+///
+/// Scale tones use the signature's accidentals (so F# major yields E#), other
+/// tones are naturals where possible, otherwise sharps in sharp keys and flats
+/// in flat keys.
+pub fn spell(key: u8, fifths: i8) -> SpelledPitch {
+    let pitch_class = (key % 12) as i32;
+
+    for (index, (_, base)) in LETTERS.iter().enumerate() {
+        let alter = signature_alter(index, fifths);
+        if (base + alter as i32).rem_euclid(12) == pitch_class {
+            return spelled(key, index, alter);
+        }
+    }
+    if let Some(index) = LETTERS.iter().position(|(_, b)| *b == pitch_class) {
+        return spelled(key, index, 0);
+    }
+
+    let (target, alter) = if fifths < 0 {
+        ((pitch_class + 1) % 12, -1)
+    } else {
+        (pitch_class - 1, 1)
+    };
+    let index = LETTERS
+        .iter()
+        .position(|(_, b)| *b == target)
+        .expect("every black key neighbours a natural");
+    spelled(key, index, alter)
+}
+
+fn format_spelled(pitch: SpelledPitch, convention: OctaveConvention) -> String {
+    let accidental = if pitch.alter >= 0 { "#" } else { "b" };
+    format!(
+        "{}{}{}",
+        pitch.letter,
+        accidental.repeat(pitch.alter.unsigned_abs() as usize),
+        pitch.octave + convention.offset()
+    )
+}
+
+// Names a key using sharps, e.g. 61 -> "C#4".
+pub fn note_name(key: u8, convention: OctaveConvention) -> String {
+    format_spelled(spell(key, 0), convention)
+}
+
+pub fn note_name_in_key(key: u8, fifths: i8, convention: OctaveConvention) -> String {
+    format_spelled(spell(key, fifths), convention)
+}
+
+/// Parses names such as `C4`, `f#3`, `Bb-1`, `E♭5` or `B#3` back to a key number.
+pub fn parse_note_name(name: &str, convention: OctaveConvention) -> Option<u8> {
+    let mut chars = name.trim().chars().peekable();
+    let letter = chars.next()?.to_ascii_uppercase();
+    let (_, base) = LETTERS.iter().find(|(l, _)| *l == letter)?;
+
+    let mut alter = 0i32;
+    while let Some(c) = chars.peek() {
+        match c {
+            '#' | '♯' => alter += 1,
+            'b' | '♭' => alter -= 1,
+            _ => break,
+        }
+        chars.next();
+    }
+
+    let octave: i32 = chars.collect::<String>().parse().ok()?;
+    let key = (octave - convention.offset() + 1) * 12 + base + alter;
+    u8::try_from(key).ok().filter(|k| *k <= 127)
+}
+
+pub fn key_to_frequency(key: u8, a4_hz: f64) -> f64 {
+    a4_hz * 2f64.powf((key as f64 - 69.0) / 12.0)
+}
+
+// Returns a fractional key number; round it to get the nearest key.
+pub fn frequency_to_key(frequency_hz: f64, a4_hz: f64) -> f64 {
+    69.0 + 12.0 * (frequency_hz / a4_hz).log2()
+}
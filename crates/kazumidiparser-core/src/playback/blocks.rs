@@ -0,0 +1,74 @@
+use crate::{MidiEvent, MidiSequence};
+
+pub struct AudioBlock<'a> {
+    pub start_sample: u64,
+    pub events: Vec<(u32, &'a MidiEvent)>,
+}
+
+/// Splits a time-ordered event list into fixed-size audio render blocks.
+///
+/// Every block is yielded, including empty ones, until the block holding the
+/// last event has been produced.
+pub struct AudioBlocks<'a> {
+    events: &'a [MidiEvent],
+    sample_rate: u32,
+    block_size: u32,
+    next_event: usize,
+    block_index: u64,
+}
+
+impl<'a> AudioBlocks<'a> {
+    pub fn new(events: &'a [MidiEvent], sample_rate: u32, block_size: u32) -> AudioBlocks<'a> {
+        assert!(sample_rate > 0, "sample rate must be non-zero");
+        assert!(block_size > 0, "block size must be non-zero");
+        AudioBlocks {
+            events,
+            sample_rate,
+            block_size,
+            next_event: 0,
+            block_index: 0,
+        }
+    }
+
+    pub fn ns_to_sample(ns: u64, sample_rate: u32) -> u64 {
+        (ns as u128 * sample_rate as u128 / 1_000_000_000) as u64
+    }
+}
+
+impl<'a> Iterator for AudioBlocks<'a> {
+    type Item = AudioBlock<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next_event >= self.events.len() {
+            return None;
+        }
+
+        let start_sample = self.block_index * self.block_size as u64;
+        let end_sample = start_sample + self.block_size as u64;
+        let mut block_events = Vec::new();
+
+        while let Some(event) = self.events.get(self.next_event) {
+            let sample = Self::ns_to_sample(event.absolute_ns, self.sample_rate);
+            if sample >= end_sample {
+                break;
+            }
+            // Events are time-ordered, so anything earlier than this block was
+            // already emitted; clamp defensively for unsorted input.
+            let offset = sample.saturating_sub(start_sample) as u32;
+            block_events.push((offset, event));
+            self.next_event += 1;
+        }
+
+        self.block_index += 1;
+        Some(AudioBlock {
+            start_sample,
+            events: block_events,
+        })
+    }
+}
+
+impl MidiSequence {
+    pub fn audio_blocks(&self, sample_rate: u32, block_size: u32) -> AudioBlocks<'_> {
+        AudioBlocks::new(&self.events, sample_rate, block_size)
+    }
+}
@@ -0,0 +1,127 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use crossbeam_queue::ArrayQueue;
+
+use crate::MidiEvent;
+
+struct SubscriberQueue {
+    queue: ArrayQueue<MidiEvent>,
+    dropped: AtomicU64,
+    closed: AtomicBool,
+}
+
+/// Fans a playback event stream out to any number of independent sinks.
+///
+/// Every subscriber owns a bounded lock-free queue, so publishing never blocks:
+/// when a slow sink lets its queue fill up, its oldest event is overwritten and
+/// counted in [`EventSubscriber::dropped_count`].
+///
+/// Share it as an `Arc<EventBus>`: hand one to a `Scheduler`, `Player` or
+/// `PlayOptions` to publish what they play, and subscribe from any thread,
+/// even while playing.
+pub struct EventBus {
+    // Only locked for writing while a subscriber is added.
+    subscribers: RwLock<Vec<Arc<SubscriberQueue>>>,
+}
+
+pub struct EventSubscriber {
+    shared: Arc<SubscriberQueue>,
+}
+
+impl EventBus {
+    pub fn new() -> EventBus {
+        EventBus {
+            subscribers: RwLock::new(Vec::new()),
+        }
+    }
+
+    pub fn subscribe(&self, capacity: usize) -> EventSubscriber {
+        let mut subscribers = self.subscribers.write().unwrap();
+        subscribers.retain(|s| !s.closed.load(Ordering::Acquire));
+
+        let shared = Arc::new(SubscriberQueue {
+            queue: ArrayQueue::new(capacity.max(1)),
+            dropped: AtomicU64::new(0),
+            closed: AtomicBool::new(false),
+        });
+        subscribers.push(Arc::clone(&shared));
+        EventSubscriber { shared }
+    }
+
+    pub fn publish(&self, event: &MidiEvent) {
+        self.publish_all(std::slice::from_ref(event));
+    }
+
+    pub fn publish_all(&self, events: &[MidiEvent]) {
+        if events.is_empty() {
+            return;
+        }
+        for subscriber in self.subscribers.read().unwrap().iter() {
+            if subscriber.closed.load(Ordering::Acquire) {
+                continue;
+            }
+            for event in events {
+                if subscriber.queue.force_push(*event).is_some() {
+                    subscriber.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.subscribers
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|s| !s.closed.load(Ordering::Acquire))
+            .count()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for EventBus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventBus")
+            .field("subscribers", &self.subscriber_count())
+            .finish()
+    }
+}
+
+impl EventSubscriber {
+    pub fn try_recv(&self) -> Option<MidiEvent> {
+        self.shared.queue.pop()
+    }
+
+    pub fn drain(&self) -> impl Iterator<Item = MidiEvent> + '_ {
+        std::iter::from_fn(move || self.shared.queue.pop())
+    }
+
+    pub fn len(&self) -> usize {
+        self.shared.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shared.queue.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.shared.queue.capacity()
+    }
+
+    pub fn dropped_count(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for EventSubscriber {
+    fn drop(&mut self) {
+        self.shared.closed.store(true, Ordering::Release);
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::VecDeque;
+
+use super::player::chase;
+use crate::state::ChannelStateSnapshot;
+use crate::{MidiEvent, MidiSequence};
+
+/// A read position in a sequence for playback code that keeps its own clock:
+/// poll `next_due` with the current time and send what it returns.
+///
+/// After a seek the next events due are the chase: note offs for whatever
+/// was sounding, then the program, controller (sustain included), pitch bend
+/// and pressure changes that put each channel where the sequence has it at
+/// the new position. Notes already under way there are not restarted.
+#[derive(Debug, Clone)]
+pub struct PlaybackCursor<'a> {
+    sequence: &'a MidiSequence,
+    position_ns: u64,
+    next_event: usize,
+    // What the receiving synthesizer holds after everything returned so far.
+    state: ChannelStateSnapshot,
+    chase: VecDeque<MidiEvent>,
+}
+
+impl<'a> PlaybackCursor<'a> {
+    pub fn new(sequence: &'a MidiSequence) -> PlaybackCursor<'a> {
+        PlaybackCursor {
+            sequence,
+            position_ns: 0,
+            next_event: 0,
+            state: ChannelStateSnapshot::default(),
+            chase: VecDeque::new(),
+        }
+    }
+
+    pub fn sequence(&self) -> &'a MidiSequence {
+        self.sequence
+    }
+
+    /// The time of the last seek or `next_due` call, whichever was later.
+    pub fn position_ns(&self) -> u64 {
+        self.position_ns
+    }
+
+    pub fn state(&self) -> &ChannelStateSnapshot {
+        &self.state
+    }
+
+    /// When the next event is due: now if chase events are waiting, None at
+    /// the end of the sequence. For sleeping until there is work to do.
+    pub fn next_due_ns(&self) -> Option<u64> {
+        if !self.chase.is_empty() {
+            return Some(self.position_ns);
+        }
+        self.sequence
+            .events
+            .get(self.next_event)
+            .map(|event| event.absolute_ns)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.chase.is_empty() && self.next_event >= self.sequence.events.len()
+    }
+
+    /// The next event due at or before `now_ns`, or None if there is nothing
+    /// to send yet. Call it until it returns None.
+    pub fn next_due(&mut self, now_ns: u64) -> Option<MidiEvent> {
+        self.position_ns = self.position_ns.max(now_ns);
+        if let Some(event) = self.chase.pop_front() {
+            return Some(event);
+        }
+        let event = *self.sequence.events.get(self.next_event)?;
+        if event.absolute_ns > now_ns {
+            return None;
+        }
+        self.state.apply(&event);
+        self.next_event += 1;
+        Some(event)
+    }
+
+    /// Moves to `ns`, so that events at `ns` are the next due, and queues the
+    /// chase for it, after any chase events an earlier seek left unsent.
+    pub fn seek_to_ns(&mut self, ns: u64) {
+        let earlier = self.sequence.events_in_range(0..ns);
+        let mut target = ChannelStateSnapshot::default();
+        for event in earlier {
+            target.apply(event);
+        }
+
+        let mut chase_events = Vec::new();
+        let tick = self.sequence.tempo_map.ns_to_tick(ns);
+        chase(&self.state, &target, ns, tick, &mut chase_events);
+        self.chase.extend(chase_events);
+
+        for channel in &mut target.channels {
+            channel.notes.clear();
+        }
+        target.time_ns = ns;
+        self.state = target;
+        self.position_ns = ns;
+        self.next_event = earlier.len();
+    }
+
+    pub fn seek_to_tick(&mut self, tick: u64) {
+        self.seek_to_ns(self.sequence.tempo_map.tick_to_ns(tick));
+    }
+}
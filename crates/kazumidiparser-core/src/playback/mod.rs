@@ -0,0 +1,19 @@
+mod blocks;
+mod bus;
+mod cursor;
+mod player;
+#[cfg(feature = "midir")]
+mod port;
+mod scheduler;
+mod select;
+mod view;
+
+pub use blocks::{AudioBlock, AudioBlocks};
+pub use bus::{EventBus, EventSubscriber};
+pub use cursor::PlaybackCursor;
+pub use player::{Player, SequenceSwapper};
+#[cfg(feature = "midir")]
+pub use port::{PlayError, PlayOptions, play_to_port};
+pub use scheduler::Scheduler;
+pub use select::PlaybackTarget;
+pub use view::EventView;
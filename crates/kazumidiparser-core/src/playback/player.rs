@@ -0,0 +1,222 @@
+use std::sync::Arc;
+
+use crossbeam_queue::ArrayQueue;
+
+use super::EventBus;
+use crate::state::ChannelStateSnapshot;
+use crate::{MidiEvent, MidiSequence};
+
+/// Hands a new sequence to a running [`Player`] from another thread.
+///
+/// The player picks it up at the start of its next `advance`. Swaps queued
+/// faster than that replace each other, so only the newest is played.
+#[derive(Clone)]
+pub struct SequenceSwapper {
+    incoming: Arc<ArrayQueue<Arc<MidiSequence>>>,
+}
+
+impl SequenceSwapper {
+    pub fn swap(&self, sequence: Arc<MidiSequence>) {
+        self.incoming.force_push(sequence);
+    }
+}
+
+/// Walks a sequence in real time, handing out the events each stretch of
+/// playback covers, and can swap in a reparsed sequence without stopping.
+pub struct Player {
+    sequence: Arc<MidiSequence>,
+    position_ns: u64,
+    next_event: usize,
+    // What the receiving synthesizer holds after everything emitted so far.
+    state: ChannelStateSnapshot,
+    incoming: Arc<ArrayQueue<Arc<MidiSequence>>>,
+    bus: Option<Arc<EventBus>>,
+}
+
+fn channel_event(
+    status: u8,
+    data1: u8,
+    data2: u8,
+    absolute_ns: u64,
+    absolute_tick: u64,
+) -> MidiEvent {
+    MidiEvent {
+        absolute_ns,
+        absolute_tick,
+        status,
+        data1,
+        data2,
+        track_index: 0,
+        sysex_index: None,
+    }
+}
+
+impl Player {
+    pub fn new(sequence: Arc<MidiSequence>) -> Player {
+        Player {
+            sequence,
+            position_ns: 0,
+            next_event: 0,
+            state: ChannelStateSnapshot::default(),
+            incoming: Arc::new(ArrayQueue::new(1)),
+            bus: None,
+        }
+    }
+
+    /// Publishes on `bus` every event `advance` and `replace_sequence` hand
+    /// out, as they hand them out.
+    pub fn set_event_bus(&mut self, bus: Arc<EventBus>) {
+        self.bus = Some(bus);
+    }
+
+    fn publish(&self, events: &[MidiEvent]) {
+        if let Some(bus) = &self.bus {
+            bus.publish_all(events);
+        }
+    }
+
+    pub fn swapper(&self) -> SequenceSwapper {
+        SequenceSwapper {
+            incoming: Arc::clone(&self.incoming),
+        }
+    }
+
+    pub fn sequence(&self) -> &Arc<MidiSequence> {
+        &self.sequence
+    }
+
+    pub fn position_ns(&self) -> u64 {
+        self.position_ns
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next_event >= self.sequence.events.len()
+    }
+
+    /// Moves playback `duration_ns` forward and appends every event in that
+    /// stretch to `out`. A sequence queued through a [`SequenceSwapper`] is
+    /// switched to first.
+    pub fn advance(&mut self, duration_ns: u64, out: &mut Vec<MidiEvent>) {
+        if let Some(sequence) = self.incoming.pop() {
+            self.replace_sequence(sequence, out);
+        }
+
+        let first = out.len();
+        let end_ns = self.position_ns.saturating_add(duration_ns);
+        while let Some(event) = self.sequence.events.get(self.next_event) {
+            if event.absolute_ns >= end_ns {
+                break;
+            }
+            self.state.apply(event);
+            out.push(*event);
+            self.next_event += 1;
+        }
+        self.position_ns = end_ns;
+        self.publish(&out[first..]);
+    }
+
+    /// Continues playback in `sequence` at the same musical position (to the
+    /// tick, rescaled if the PPQN changed). SMPTE files have no musical
+    /// position, so if either sequence is one, playback continues at the
+    /// same time instead.
+    ///
+    /// Notes still sounding are released rather than carried over, and `out`
+    /// receives the controller, program and pitch bend changes that bring the
+    /// synthesizer to the state the new sequence has at that point. When the
+    /// new sequence has not set a controller by then that the old one had,
+    /// the channel gets a Reset All Controllers first.
+    pub fn replace_sequence(&mut self, sequence: Arc<MidiSequence>, out: &mut Vec<MidiEvent>) {
+        let smpte = self.sequence.header.tempo_timing().1.is_some()
+            || sequence.header.tempo_timing().1.is_some();
+        let (tick, position_ns) = if smpte {
+            let tick = sequence.tempo_map.ns_to_tick(self.position_ns);
+            (tick, self.position_ns)
+        } else {
+            let old_ppqn = self.sequence.tempo_map.ppqn().max(1) as u128;
+            let new_ppqn = sequence.tempo_map.ppqn().max(1) as u128;
+            let old_tick = self.sequence.tempo_map.ns_to_tick(self.position_ns) as u128;
+            let tick = ((old_tick * new_ppqn + old_ppqn / 2) / old_ppqn) as u64;
+            (tick, sequence.tempo_map.tick_to_ns(tick))
+        };
+
+        let earlier = sequence.events_in_range(0..position_ns);
+        let next_event = earlier.len();
+        let mut target = ChannelStateSnapshot::default();
+        for event in earlier {
+            target.apply(event);
+        }
+
+        let first = out.len();
+        chase(&self.state, &target, position_ns, tick, out);
+        self.publish(&out[first..]);
+
+        // Notes already under way in the new sequence are not started.
+        for channel in &mut target.channels {
+            channel.notes.clear();
+        }
+        target.time_ns = position_ns;
+        self.state = target;
+        self.sequence = sequence;
+        self.position_ns = position_ns;
+        self.next_event = next_event;
+    }
+}
+
+/// Appends to `out` what takes a synthesizer in state `current` to `target`
+/// at `position_ns`: note offs for every sounding note, then the controller,
+/// program, pitch bend and pressure changes, with a Reset All Controllers
+/// first on channels where `target` has not set a controller `current` has.
+pub(crate) fn chase(
+    current: &ChannelStateSnapshot,
+    target: &ChannelStateSnapshot,
+    position_ns: u64,
+    tick: u64,
+    out: &mut Vec<MidiEvent>,
+) {
+    let emit = |out: &mut Vec<MidiEvent>, status, data1, data2| {
+        out.push(channel_event(status, data1, data2, position_ns, tick));
+    };
+    for channel in 0..16u8 {
+        let mut current = current.channels[channel as usize].clone();
+        let wanted = &target.channels[channel as usize];
+
+        for note in &current.notes {
+            emit(out, 0x80 | channel, note.key, 0);
+        }
+
+        // Channel mode messages (120 and up) are not state worth restoring.
+        let unset = current.controllers[..120]
+            .iter()
+            .zip(&wanted.controllers)
+            .any(|(now, then)| now.is_some() && then.is_none())
+            || (current.channel_pressure.is_some() && wanted.channel_pressure.is_none());
+        if unset {
+            let reset = channel_event(0xB0 | channel, 121, 0, position_ns, tick);
+            current.apply(&reset);
+            out.push(reset);
+        }
+
+        for controller in 0..120u8 {
+            let value = wanted.controllers[controller as usize];
+            if let Some(value) = value
+                && current.controllers[controller as usize] != Some(value)
+            {
+                emit(out, 0xB0 | channel, controller, value);
+            }
+        }
+        if let Some(program) = wanted.program
+            && (current.program != Some(program) || current.bank() != wanted.bank())
+        {
+            emit(out, 0xC0 | channel, program, 0);
+        }
+        if current.pitch_bend != wanted.pitch_bend {
+            let bend = wanted.pitch_bend;
+            emit(out, 0xE0 | channel, (bend & 0x7F) as u8, (bend >> 7) as u8);
+        }
+        if let Some(pressure) = wanted.channel_pressure
+            && current.channel_pressure != Some(pressure)
+        {
+            emit(out, 0xD0 | channel, pressure, 0);
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use midir::{MidiOutputConnection, SendError};
+
+use super::{EventBus, PlaybackCursor, Scheduler};
+use crate::{CancelToken, MidiEvent, MidiSequence};
+
+// The longest sleep between checks of the cancel token.
+const CANCEL_POLL: Duration = Duration::from_millis(10);
+
+#[derive(Debug, Clone)]
+pub struct PlayOptions {
+    /// Times the file's tempo: 2.0 plays twice as fast. Must be finite and
+    /// above 0.
+    pub speed: f64,
+    /// Where in the song to start. The program and controller state at that
+    /// point is sent first.
+    pub start_ns: u64,
+    /// Checked at least every 10 ms; once cancelled, playback stops with
+    /// `PlayError::Cancelled`.
+    pub cancel_token: Option<CancelToken>,
+    /// Receives every event once it is sent, the chase at `start_ns`
+    /// included.
+    pub event_bus: Option<Arc<EventBus>>,
+}
+
+impl Default for PlayOptions {
+    fn default() -> Self {
+        PlayOptions {
+            speed: 1.0,
+            start_ns: 0,
+            cancel_token: None,
+            event_bus: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PlayError {
+    Send(SendError),
+    /// `PlayOptions::speed` is not a finite number above 0; nothing was
+    /// sent.
+    InvalidSpeed(f64),
+    /// Stopped through `PlayOptions::cancel_token`, after silencing every
+    /// channel.
+    Cancelled,
+}
+
+impl fmt::Display for PlayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlayError::Send(e) => write!(f, "Failed to send to the MIDI port: {}", e),
+            PlayError::InvalidSpeed(speed) => write!(f, "Invalid playback speed: {}", speed),
+            PlayError::Cancelled => write!(f, "Playback was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for PlayError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PlayError::Send(e) => Some(e),
+            PlayError::InvalidSpeed(_) | PlayError::Cancelled => None,
+        }
+    }
+}
+
+impl From<SendError> for PlayError {
+    fn from(e: SendError) -> Self {
+        PlayError::Send(e)
+    }
+}
+
+fn send_event(
+    connection: &mut MidiOutputConnection,
+    sequence: &MidiSequence,
+    event: &MidiEvent,
+    buffer: &mut Vec<u8>,
+) -> Result<(), SendError> {
+    buffer.clear();
+    match event.status {
+        0xF0 => {
+            buffer.push(0xF0);
+            buffer.extend_from_slice(sequence.sysex(event).unwrap_or_default());
+        }
+        // An escape packet is sent as it is.
+        0xF7 => buffer.extend_from_slice(sequence.sysex(event).unwrap_or_default()),
+        status @ (0xC0..=0xDF) => buffer.extend_from_slice(&[status, event.data1]),
+        status => buffer.extend_from_slice(&[status, event.data1, event.data2]),
+    }
+    if buffer.is_empty() {
+        return Ok(());
+    }
+    connection.send(buffer)
+}
+
+// Sleeps for `duration` in slices short enough to notice a cancel.
+fn sleep_unless_cancelled(duration: Duration, cancel_token: Option<&CancelToken>) -> bool {
+    let Some(cancel_token) = cancel_token else {
+        std::thread::sleep(duration);
+        return true;
+    };
+    let deadline = Instant::now() + duration;
+    loop {
+        if cancel_token.is_cancelled() {
+            return false;
+        }
+        let left = deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return true;
+        }
+        std::thread::sleep(left.min(CANCEL_POLL));
+    }
+}
+
+/// Plays `sequence` to a MIDI output in real time, blocking until it ends.
+/// Notes still sounding when playback is cancelled are released with All
+/// Notes Off and a sustain pedal release on every channel.
+pub fn play_to_port(
+    sequence: &MidiSequence,
+    connection: &mut MidiOutputConnection,
+    options: &PlayOptions,
+) -> Result<(), PlayError> {
+    if !(options.speed.is_finite() && options.speed > 0.0) {
+        return Err(PlayError::InvalidSpeed(options.speed));
+    }
+    let cancel_token = options.cancel_token.as_ref();
+    let mut buffer = Vec::new();
+
+    if options.start_ns > 0 {
+        let mut cursor = PlaybackCursor::new(sequence);
+        cursor.seek_to_ns(options.start_ns);
+        // Only the chase is due before the first event at the start point.
+        while let Some(event) = cursor.next_due(options.start_ns.saturating_sub(1)) {
+            send_event(connection, sequence, &event, &mut buffer)?;
+            if let Some(bus) = &options.event_bus {
+                bus.publish(&event);
+            }
+        }
+    }
+
+    let mut scheduler = Scheduler::new(sequence, Instant::now())
+        .speed(options.speed)
+        .starting_at(options.start_ns);
+    if let Some(bus) = &options.event_bus {
+        scheduler = scheduler.event_bus(Arc::clone(bus));
+    }
+    for (sleep, batch) in scheduler {
+        if !sleep_unless_cancelled(sleep, cancel_token) {
+            for channel in 0..16u8 {
+                connection.send(&[0xB0 | channel, 64, 0])?;
+                connection.send(&[0xB0 | channel, 123, 0])?;
+            }
+            return Err(PlayError::Cancelled);
+        }
+        for event in batch {
+            send_event(connection, sequence, event, &mut buffer)?;
+        }
+    }
+    Ok(())
+}
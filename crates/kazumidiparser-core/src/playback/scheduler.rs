@@ -0,0 +1,109 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use super::EventBus;
+use crate::{MidiEvent, MidiSequence};
+
+/// Paces a sequence against the wall clock. Each item is how long to sleep
+/// and the events to send on waking; a player sleeps, sends the batch and
+/// asks for the next one until the iterator ends.
+///
+/// Every sleep is measured from `start` when the item is produced, so a late
+/// wake-up shortens the next sleep instead of pushing the rest of the song
+/// back; drift never builds up. A batch is every event due within the
+/// lookahead of its first one (or of now, when playback has fallen behind),
+/// so a burst of nearly simultaneous events goes out on one wake-up.
+#[derive(Debug, Clone)]
+pub struct Scheduler<'a> {
+    events: &'a [MidiEvent],
+    start: Instant,
+    // Song time at `start`.
+    offset_ns: u64,
+    lookahead_ns: u64,
+    speed: f64,
+    bus: Option<Arc<EventBus>>,
+    // The last batch handed out, published once the next one is asked for.
+    unpublished: &'a [MidiEvent],
+}
+
+impl<'a> Scheduler<'a> {
+    pub fn new(sequence: &'a MidiSequence, start: Instant) -> Scheduler<'a> {
+        Scheduler {
+            events: &sequence.events,
+            start,
+            offset_ns: 0,
+            lookahead_ns: 1_000_000,
+            speed: 1.0,
+            bus: None,
+            unpublished: &[],
+        }
+    }
+
+    /// How far ahead of its time an event may be sent to share a wake-up
+    /// with an earlier one. 1 ms by default.
+    pub fn lookahead(mut self, lookahead: Duration) -> Scheduler<'a> {
+        self.lookahead_ns = lookahead.as_nanos() as u64;
+        self
+    }
+
+    /// Plays at `speed` times the file's tempo: 2.0 is twice as fast. Event
+    /// times are not rescaled, only the sleeps between them.
+    ///
+    /// # Panics
+    ///
+    /// If `speed` is not a finite number above 0.
+    pub fn speed(mut self, speed: f64) -> Scheduler<'a> {
+        assert!(
+            speed.is_finite() && speed > 0.0,
+            "speed must be finite and positive"
+        );
+        self.speed = speed;
+        self
+    }
+
+    /// Publishes every batch on `bus` when it is due: once the next item is
+    /// asked for, which a player does after sleeping and sending it.
+    pub fn event_bus(mut self, bus: Arc<EventBus>) -> Scheduler<'a> {
+        self.bus = Some(bus);
+        self
+    }
+
+    /// Plays from `ns` into the song, `start` being that moment. Earlier
+    /// events are skipped; see `PlaybackCursor` to chase their state.
+    pub fn starting_at(mut self, ns: u64) -> Scheduler<'a> {
+        self.events = &self.events[self.events.partition_point(|e| e.absolute_ns < ns)..];
+        self.offset_ns = ns;
+        self
+    }
+
+    /// The events not yet handed out.
+    pub fn remaining(&self) -> &'a [MidiEvent] {
+        self.events
+    }
+
+    fn now_ns(&self) -> u64 {
+        let elapsed_ns = self.start.elapsed().as_nanos() as f64 * self.speed;
+        self.offset_ns.saturating_add(elapsed_ns as u64)
+    }
+}
+
+impl<'a> Iterator for Scheduler<'a> {
+    type Item = (Duration, &'a [MidiEvent]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let sent = std::mem::take(&mut self.unpublished);
+        if let Some(bus) = &self.bus {
+            bus.publish_all(sent);
+        }
+        let first_ns = self.events.first()?.absolute_ns;
+        let now_ns = self.now_ns();
+        let sleep_ns = first_ns.saturating_sub(now_ns) as f64 / self.speed;
+        let sleep = Duration::from_nanos(sleep_ns as u64);
+        let until_ns = first_ns.max(now_ns).saturating_add(self.lookahead_ns);
+        let len = self.events.partition_point(|e| e.absolute_ns <= until_ns);
+        let (batch, rest) = self.events.split_at(len);
+        self.events = rest;
+        self.unpublished = batch;
+        Some((sleep, batch))
+    }
+}
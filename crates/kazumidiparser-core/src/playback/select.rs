@@ -0,0 +1,90 @@
+use crate::{MetaEvent, MidiEvent, MidiHeader, MidiSequence, ParseWarning};
+
+/// The part of a file to play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlaybackTarget {
+    #[default]
+    All,
+    Track(u16),
+    /// A sequence number as listed by `MidiSequence::sequence_index`.
+    SequenceNumber(u16),
+}
+
+impl MidiSequence {
+    /// Copies the events of `target` into a sequence of their own, ready for
+    /// `audio_blocks` and the other playback helpers. `None` when the track or
+    /// sequence number does not exist.
+    ///
+    /// In a format 2 file every track is an independent pattern, so a single
+    /// track is timed by its own tempo events only; in formats 0 and 1 it
+    /// keeps the song's tempo map. The result is a one-track format 0
+    /// sequence.
+    pub fn select(&self, target: PlaybackTarget) -> Option<MidiSequence> {
+        let track_index = match target {
+            PlaybackTarget::All => return Some(self.clone()),
+            PlaybackTarget::Track(track_index) => track_index,
+            PlaybackTarget::SequenceNumber(number) => self.track_for_sequence_number(number)?,
+        };
+        if track_index >= self.header.tracks {
+            return None;
+        }
+
+        let metas: Vec<_> = self
+            .metas
+            .iter()
+            .filter(|meta| meta.track_index == track_index)
+            .map(|meta| MetaEvent {
+                track_index: 0,
+                ..meta.clone()
+            })
+            .collect();
+        let tempo_map = self.track_tempo_map(track_index)?;
+
+        // Only the track's own SysEx payloads come along, renumbered.
+        let mut sysex = Vec::new();
+        let events = self
+            .events
+            .iter()
+            .filter(|event| event.track_index == track_index)
+            .map(|event| MidiEvent {
+                track_index: 0,
+                sysex_index: self.sysex(event).map(|data| {
+                    sysex.push(data.to_vec());
+                    sysex.len() as u32 - 1
+                }),
+                ..*event
+            })
+            .collect();
+
+        Some(MidiSequence {
+            header: MidiHeader {
+                format: 0,
+                tracks: 1,
+                ppqn: self.header.ppqn,
+            },
+            events,
+            sysex,
+            metas,
+            tempo_map,
+            track_tempo_maps: Vec::new(),
+            track_metas: self
+                .track_metas
+                .get(track_index as usize)
+                .cloned()
+                .into_iter()
+                .collect(),
+            warnings: self
+                .warnings
+                .iter()
+                .filter(|warning| warning.track == Some(track_index))
+                .map(|warning| ParseWarning {
+                    track: Some(0),
+                    ..warning.clone()
+                })
+                .collect(),
+            sound_bank: self.sound_bank.clone(),
+            text_encoding: self.text_encoding,
+            event_order: self.event_order,
+        })
+    }
+}
@@ -0,0 +1,123 @@
+use std::ops::Range;
+
+use crate::{MidiEvent, MidiSequence};
+
+/// A sequence's events with tracks muted or soloed and channels muted,
+/// filtered as they are read rather than copied. Toggling a track is cheap,
+/// so a player can keep one view and check `passes` on each event it sends.
+///
+/// Only channel messages are muted; SysEx always passes. So do note offs
+/// (and note ons of velocity 0), so a note sounding when its track is
+/// muted still ends.
+#[derive(Debug, Clone)]
+pub struct EventView<'a> {
+    sequence: &'a MidiSequence,
+    // By track index.
+    muted_tracks: Vec<bool>,
+    soloed_tracks: Vec<bool>,
+    solo_count: usize,
+    // Bit 0 is channel 1.
+    muted_channels: u16,
+}
+
+impl<'a> EventView<'a> {
+    pub fn new(sequence: &'a MidiSequence) -> EventView<'a> {
+        let tracks = sequence.header.tracks as usize;
+        EventView {
+            sequence,
+            muted_tracks: vec![false; tracks],
+            soloed_tracks: vec![false; tracks],
+            solo_count: 0,
+            muted_channels: 0,
+        }
+    }
+
+    pub fn with_muted_tracks<I: IntoIterator<Item = u16>>(mut self, tracks: I) -> EventView<'a> {
+        for track in tracks {
+            self.set_track_muted(track, true);
+        }
+        self
+    }
+
+    pub fn with_soloed_tracks<I: IntoIterator<Item = u16>>(mut self, tracks: I) -> EventView<'a> {
+        for track in tracks {
+            self.set_track_soloed(track, true);
+        }
+        self
+    }
+
+    /// Mutes the channels whose bit is set, bit 0 being channel 1.
+    pub fn with_muted_channels(mut self, channels: u16) -> EventView<'a> {
+        self.muted_channels = channels;
+        self
+    }
+
+    pub fn sequence(&self) -> &'a MidiSequence {
+        self.sequence
+    }
+
+    pub fn set_track_muted(&mut self, track_index: u16, muted: bool) {
+        if let Some(slot) = self.muted_tracks.get_mut(track_index as usize) {
+            *slot = muted;
+        }
+    }
+
+    /// While any track is soloed, only soloed tracks play.
+    pub fn set_track_soloed(&mut self, track_index: u16, soloed: bool) {
+        if let Some(slot) = self.soloed_tracks.get_mut(track_index as usize)
+            && *slot != soloed
+        {
+            *slot = soloed;
+            if soloed {
+                self.solo_count += 1;
+            } else {
+                self.solo_count -= 1;
+            }
+        }
+    }
+
+    /// `channel` is 0-based.
+    pub fn set_channel_muted(&mut self, channel: u8, muted: bool) {
+        let bit = 1 << (channel & 0x0F);
+        if muted {
+            self.muted_channels |= bit;
+        } else {
+            self.muted_channels &= !bit;
+        }
+    }
+
+    pub fn is_track_audible(&self, track_index: u16) -> bool {
+        let track = track_index as usize;
+        !self.muted_tracks.get(track).copied().unwrap_or(false)
+            && (self.solo_count == 0 || self.soloed_tracks.get(track).copied().unwrap_or(false))
+    }
+
+    pub fn is_channel_audible(&self, channel: u8) -> bool {
+        self.muted_channels & (1 << (channel & 0x0F)) == 0
+    }
+
+    /// Whether `event` is let through.
+    pub fn passes(&self, event: &MidiEvent) -> bool {
+        let note_off =
+            event.status & 0xF0 == 0x80 || (event.status & 0xF0 == 0x90 && event.data2 == 0);
+        event.status >= 0xF0
+            || note_off
+            || (self.is_track_audible(event.track_index)
+                && self.is_channel_audible(event.status & 0x0F))
+    }
+
+    pub fn events(&self) -> impl Iterator<Item = &'a MidiEvent> + '_ {
+        self.sequence
+            .events
+            .iter()
+            .filter(|event| self.passes(event))
+    }
+
+    /// The events with `range.start <= absolute_ns < range.end` that pass.
+    pub fn events_in_range(&self, range: Range<u64>) -> impl Iterator<Item = &'a MidiEvent> + '_ {
+        self.sequence
+            .events_in_range(range)
+            .iter()
+            .filter(|event| self.passes(event))
+    }
+}
@@ -0,0 +1,179 @@
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+
+use rayon::ThreadPoolBuildError;
+
+use crate::{MidiEvent, MidiParser, MidiSequence, ParseError, ParseOptions, TempEvent};
+
+// How often a queued parse wakes up to check its cancel token.
+const CANCEL_POLL: Duration = Duration::from_millis(50);
+
+/// What `ParserPool` does with a parse that does not fit in the remaining
+/// budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BudgetPolicy {
+    /// Block until enough running parses have finished.
+    #[default]
+    Queue,
+    /// Fail straight away with `PoolError::OverBudget` (as `ParseError::Pool`).
+    Reject,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PoolError {
+    /// The parse would not fit next to the ones already running.
+    OverBudget { requested: u64, available: u64 },
+    /// The parse would not fit even in an idle pool.
+    ExceedsBudget { requested: u64, budget: u64 },
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolError::OverBudget {
+                requested,
+                available,
+            } => write!(
+                f,
+                "Parse needs {} bytes but only {} are left in the pool budget",
+                requested, available
+            ),
+            PoolError::ExceedsBudget { requested, budget } => write!(
+                f,
+                "Parse needs {} bytes, more than the whole pool budget of {}",
+                requested, budget
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PoolError {}
+
+/// Upper estimate of the memory one `parse_file` holds at its peak: the track
+/// data in flight plus the temporary and final event lists, assuming the
+/// densest encoding of three bytes per event.
+pub fn estimate_parse_memory(file_bytes: u64) -> u64 {
+    let per_event = (size_of::<TempEvent>() + size_of::<MidiEvent>()) as u64;
+    file_bytes + file_bytes / 3 * per_event
+}
+
+#[derive(Debug)]
+struct Budget {
+    total: u64,
+    in_use: Mutex<u64>,
+    released: Condvar,
+}
+
+struct Reservation<'a> {
+    budget: &'a Budget,
+    bytes: u64,
+}
+
+impl Drop for Reservation<'_> {
+    fn drop(&mut self) {
+        *self.budget.in_use.lock().unwrap() -= self.bytes;
+        self.budget.released.notify_all();
+    }
+}
+
+/// Runs many parses at once on one shared rayon pool, keeping the estimated
+/// memory of the parses in flight under a fixed budget.
+///
+/// The budget only covers parsing; a returned `MidiSequence` belongs to the
+/// caller and no longer counts against it. Clones share the same threads
+/// and budget, so a server can hand one to every request handler.
+#[derive(Debug, Clone)]
+pub struct ParserPool {
+    threads: Arc<rayon::ThreadPool>,
+    budget: Arc<Budget>,
+    policy: BudgetPolicy,
+}
+
+impl ParserPool {
+    /// `threads` of 0 lets rayon pick one per CPU. Fails only when rayon
+    /// cannot start the threads.
+    pub fn new(threads: usize, memory_budget: u64) -> Result<ParserPool, ThreadPoolBuildError> {
+        Self::with_policy(threads, memory_budget, BudgetPolicy::default())
+    }
+
+    pub fn with_policy(
+        threads: usize,
+        memory_budget: u64,
+        policy: BudgetPolicy,
+    ) -> Result<ParserPool, ThreadPoolBuildError> {
+        let threads = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("kazumidi-pool-{}", i))
+            .build()?;
+        Ok(ParserPool {
+            threads: Arc::new(threads),
+            budget: Arc::new(Budget {
+                total: memory_budget,
+                in_use: Mutex::new(0),
+                released: Condvar::new(),
+            }),
+            policy,
+        })
+    }
+
+    pub fn policy(&self) -> BudgetPolicy {
+        self.policy
+    }
+
+    pub fn memory_budget(&self) -> u64 {
+        self.budget.total
+    }
+
+    /// Estimated bytes held by the parses currently running.
+    pub fn memory_in_use(&self) -> u64 {
+        *self.budget.in_use.lock().unwrap()
+    }
+
+    pub fn current_num_threads(&self) -> usize {
+        self.threads.current_num_threads()
+    }
+
+    /// Parses `file_path` on the pool's threads once its estimated memory
+    /// fits in the budget. Safe to call from many threads at once.
+    pub fn parse_file(
+        &self,
+        file_path: &str,
+        options: ParseOptions,
+    ) -> Result<MidiSequence, ParseError> {
+        let file_bytes = std::fs::metadata(file_path)?.len();
+        let _reservation = self.reserve(estimate_parse_memory(file_bytes), &options)?;
+
+        let mut parser = MidiParser::with_options(options).without_events_copy();
+        self.threads.install(|| parser.parse_file(file_path))?;
+        Ok(parser
+            .into_sequence()
+            .expect("parse_file succeeded, so the sequence is set"))
+    }
+
+    fn reserve(&self, bytes: u64, options: &ParseOptions) -> Result<Reservation<'_>, ParseError> {
+        let budget = &*self.budget;
+        if bytes > budget.total {
+            return Err(PoolError::ExceedsBudget {
+                requested: bytes,
+                budget: budget.total,
+            }
+            .into());
+        }
+
+        let mut in_use = budget.in_use.lock().unwrap();
+        while budget.total - *in_use < bytes {
+            if self.policy == BudgetPolicy::Reject {
+                return Err(PoolError::OverBudget {
+                    requested: bytes,
+                    available: budget.total - *in_use,
+                }
+                .into());
+            }
+            options.check_cancelled()?;
+            in_use = budget.released.wait_timeout(in_use, CANCEL_POLL).unwrap().0;
+        }
+        *in_use += bytes;
+        Ok(Reservation { budget, bytes })
+    }
+}
@@ -0,0 +1,270 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::chunk::{read_header, read_track_header};
+use crate::decode::{RunningStatus, Step, TrackEvent, decode_step};
+use crate::{MidiHeader, ParseError};
+
+const DEFAULT_BLOCK_SIZE: usize = 4096;
+
+struct Peeked {
+    len: usize,
+    delta_ticks: u32,
+    running_status: RunningStatus,
+    end_of_track: bool,
+}
+
+struct TrackStream {
+    track_index: u16,
+    // File offset of the `MTrk` magic.
+    chunk_offset: u64,
+    next_read: u64,
+    remaining: u64,
+    buffer: Vec<u8>,
+    start: usize,
+    consumed: usize,
+    running_status: RunningStatus,
+    absolute_tick: u64,
+    finished: bool,
+    peeked: Option<Peeked>,
+}
+
+impl TrackStream {
+    fn refill<R: Read + Seek>(&mut self, reader: &mut R, block_size: usize) -> std::io::Result<()> {
+        self.buffer.drain(..self.start);
+        self.start = 0;
+
+        let n = (block_size as u64).min(self.remaining) as usize;
+        let old_len = self.buffer.len();
+        self.buffer.resize(old_len + n, 0);
+        reader.seek(SeekFrom::Start(self.next_read))?;
+        reader.read_exact(&mut self.buffer[old_len..])?;
+        self.next_read += n as u64;
+        self.remaining -= n as u64;
+        Ok(())
+    }
+
+    fn peek<R: Read + Seek>(
+        &mut self,
+        reader: &mut R,
+        block_size: usize,
+        lenient: bool,
+    ) -> Result<bool, ParseError> {
+        while self.peeked.is_none() && !self.finished {
+            let eof = self.remaining == 0;
+            match decode_step(
+                &self.buffer[self.start..],
+                self.running_status,
+                lenient,
+                false,
+                eof,
+            ) {
+                Step::Event {
+                    len,
+                    delta_ticks,
+                    kind,
+                    running_status,
+                    ..
+                } => {
+                    self.peeked = Some(Peeked {
+                        len,
+                        delta_ticks,
+                        running_status,
+                        end_of_track: kind.is_end_of_track(),
+                    });
+                }
+                Step::NeedMore => self
+                    .refill(reader, block_size)
+                    .map_err(|e| ParseError::in_track(e, self.track_index, self.chunk_offset))?,
+                Step::End => self.finished = true,
+                Step::Error(e) => {
+                    self.finished = true;
+                    return Err(ParseError::Decode {
+                        track: self.track_index,
+                        error: e,
+                    });
+                }
+            }
+        }
+        Ok(self.peeked.is_some())
+    }
+
+    fn next_tick(&self) -> Option<u64> {
+        self.peeked
+            .as_ref()
+            .map(|p| self.absolute_tick + p.delta_ticks as u64)
+    }
+
+    fn consume(&mut self) {
+        if let Some(peeked) = self.peeked.take() {
+            self.start += peeked.len;
+            self.consumed += peeked.len;
+            self.absolute_tick += peeked.delta_ticks as u64;
+            self.running_status = peeked.running_status;
+            self.finished = peeked.end_of_track;
+        }
+    }
+
+    fn event(&self, previous_running_status: RunningStatus, lenient: bool) -> TrackEvent<'_> {
+        match decode_step(
+            &self.buffer[self.start..],
+            previous_running_status,
+            lenient,
+            false,
+            self.remaining == 0,
+        ) {
+            Step::Event {
+                delta_ticks, kind, ..
+            } => TrackEvent {
+                track_index: self.track_index,
+                delta_ticks,
+                absolute_tick: self.absolute_tick + delta_ticks as u64,
+                offset: self.consumed,
+                kind,
+            },
+            _ => unreachable!("peeked event must decode again"),
+        }
+    }
+}
+
+/// Pull parser yielding one event at a time without materialising the file.
+///
+/// Over several tracks the events are merged by absolute tick (ties go to the
+/// lower track index, then file order), matching the order `parse_file`
+/// produces. Memory use is one small read buffer per track, growing only to
+/// fit a single oversized meta or SysEx event.
+pub struct EventReader<R> {
+    reader: R,
+    header: MidiHeader,
+    tracks: Vec<TrackStream>,
+    // Tracks with a peeked event, keyed by its tick. Built on the first
+    // `next`, then only the track just consumed is re-peeked.
+    heads: BinaryHeap<Reverse<(u64, usize)>>,
+    primed: bool,
+    pending: Option<usize>,
+    block_size: usize,
+    lenient_running_status: bool,
+}
+
+impl<R: Read + Seek> EventReader<R> {
+    pub fn new(reader: R) -> Result<EventReader<R>, ParseError> {
+        Self::open(reader, None)
+    }
+
+    pub fn single_track(reader: R, track_index: u16) -> Result<EventReader<R>, ParseError> {
+        Self::open(reader, Some(track_index))
+    }
+
+    fn open(mut reader: R, only_track: Option<u16>) -> Result<EventReader<R>, ParseError> {
+        let header = read_header(&mut reader)?;
+        if let Some(track_index) = only_track
+            && track_index >= header.tracks
+        {
+            return Err(ParseError::TrackOutOfRange {
+                track: track_index,
+                tracks: header.tracks,
+            });
+        }
+
+        let mut tracks = Vec::new();
+        for track_index in 0..header.tracks {
+            let chunk_offset = reader.stream_position()?;
+            let length = read_track_header(&mut reader, track_index, chunk_offset)? as u64;
+            let position = reader.stream_position()?;
+            if only_track.is_none_or(|t| t == track_index) {
+                tracks.push(TrackStream {
+                    track_index,
+                    chunk_offset,
+                    next_read: position,
+                    remaining: length,
+                    buffer: Vec::new(),
+                    start: 0,
+                    consumed: 0,
+                    running_status: RunningStatus::default(),
+                    absolute_tick: 0,
+                    finished: false,
+                    peeked: None,
+                });
+            }
+            if only_track == Some(track_index) {
+                break;
+            }
+            reader.seek(SeekFrom::Start(position + length))?;
+        }
+
+        Ok(EventReader {
+            reader,
+            header,
+            tracks,
+            heads: BinaryHeap::new(),
+            primed: false,
+            pending: None,
+            block_size: DEFAULT_BLOCK_SIZE,
+            lenient_running_status: true,
+        })
+    }
+
+    pub fn header(&self) -> &MidiHeader {
+        &self.header
+    }
+
+    pub fn set_block_size(&mut self, block_size: usize) {
+        self.block_size = block_size.max(16);
+    }
+
+    /// Reuse the last channel status after meta and SysEx events instead of
+    /// failing, as `ParseOptions::lenient_running_status` does. On by
+    /// default.
+    pub fn set_lenient_running_status(&mut self, lenient: bool) {
+        self.lenient_running_status = lenient;
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<Result<TrackEvent<'_>, ParseError>> {
+        if let Some(index) = self.pending.take() {
+            self.tracks[index].consume();
+            if let Err(e) = self.push_head(index) {
+                return Some(Err(e));
+            }
+        }
+        if !self.primed {
+            self.primed = true;
+            // Every track is primed even after an error, so the rest can
+            // still be read.
+            let mut first_error = None;
+            for index in 0..self.tracks.len() {
+                if let Err(e) = self.push_head(index) {
+                    first_error.get_or_insert(e);
+                }
+            }
+            if let Some(e) = first_error {
+                return Some(Err(e));
+            }
+        }
+
+        let Reverse((_, index)) = self.heads.pop()?;
+        self.pending = Some(index);
+        let track = &self.tracks[index];
+        Some(Ok(
+            track.event(track.running_status, self.lenient_running_status)
+        ))
+    }
+
+    fn push_head(&mut self, index: usize) -> Result<(), ParseError> {
+        let track = &mut self.tracks[index];
+        if track.peek(
+            &mut self.reader,
+            self.block_size,
+            self.lenient_running_status,
+        )? {
+            self.heads
+                .push(Reverse((track.next_tick().unwrap(), index)));
+        }
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
@@ -0,0 +1,148 @@
+use std::error::Error as StdError;
+use std::io::{Seek, Write};
+use std::path::Path;
+
+use crate::MidiSequence;
+use crate::playback::AudioBlocks;
+
+use super::synth::Synthesizer;
+
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    pub block_size: u32,
+    // Extra time rendered after the last event, so releases can ring out.
+    pub tail_ns: u64,
+    pub gain: f32,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            block_size: 512,
+            tail_ns: 1_000_000_000,
+            gain: 1.0,
+        }
+    }
+}
+
+/// Plays `sequence` through `synth` block by block, handing each rendered
+/// block (left, right) to `sink`. Events are applied at their exact sample
+/// offset within the block.
+pub fn render_blocks<S, F>(
+    sequence: &MidiSequence,
+    synth: &mut S,
+    options: &RenderOptions,
+    mut sink: F,
+) where
+    S: Synthesizer + ?Sized,
+    F: FnMut(&[f32], &[f32]),
+{
+    let sample_rate = synth.sample_rate();
+    let block_size = options.block_size.max(1);
+    let mut left = vec![0.0f32; block_size as usize];
+    let mut right = vec![0.0f32; block_size as usize];
+
+    let mut rendered_samples = 0u64;
+    for block in AudioBlocks::new(sequence.events(), sample_rate, block_size) {
+        left.fill(0.0);
+        right.fill(0.0);
+        let mut position = 0usize;
+        for (offset, event) in block.events {
+            let offset = (offset as usize).min(left.len());
+            synth.render(&mut left[position..offset], &mut right[position..offset]);
+            synth.process_event(event);
+            position = offset;
+        }
+        synth.render(&mut left[position..], &mut right[position..]);
+        apply_gain(&mut left, &mut right, options.gain);
+        sink(&left, &right);
+        rendered_samples += block_size as u64;
+    }
+
+    let end_sample = AudioBlocks::ns_to_sample(sequence.end_ns() + options.tail_ns, sample_rate);
+    while rendered_samples < end_sample {
+        let len = (end_sample - rendered_samples).min(block_size as u64) as usize;
+        left[..len].fill(0.0);
+        right[..len].fill(0.0);
+        synth.render(&mut left[..len], &mut right[..len]);
+        apply_gain(&mut left[..len], &mut right[..len], options.gain);
+        sink(&left[..len], &right[..len]);
+        rendered_samples += len as u64;
+    }
+}
+
+fn apply_gain(left: &mut [f32], right: &mut [f32], gain: f32) {
+    if gain != 1.0 {
+        left.iter_mut()
+            .chain(right.iter_mut())
+            .for_each(|s| *s *= gain);
+    }
+}
+
+/// Renders the whole sequence into interleaved stereo samples.
+pub fn render_samples<S: Synthesizer + ?Sized>(
+    sequence: &MidiSequence,
+    synth: &mut S,
+    options: &RenderOptions,
+) -> Vec<f32> {
+    let mut samples = Vec::new();
+    render_blocks(sequence, synth, options, |left, right| {
+        for (l, r) in left.iter().zip(right) {
+            samples.push(*l);
+            samples.push(*r);
+        }
+    });
+    samples
+}
+
+/// Renders to 16-bit stereo PCM WAV, streaming blocks to `writer`.
+pub fn render_wav<S, W>(
+    sequence: &MidiSequence,
+    synth: &mut S,
+    options: &RenderOptions,
+    writer: W,
+) -> Result<(), Box<dyn StdError>>
+where
+    S: Synthesizer + ?Sized,
+    W: Write + Seek,
+{
+    let spec = hound::WavSpec {
+        channels: 2,
+        sample_rate: synth.sample_rate(),
+        bits_per_sample: 16,
+        sample_format: hound::SampleFormat::Int,
+    };
+    let mut wav = hound::WavWriter::new(writer, spec)?;
+    let mut result = Ok(());
+    render_blocks(sequence, synth, options, |left, right| {
+        if result.is_err() {
+            return;
+        }
+        for (l, r) in left.iter().zip(right) {
+            for sample in [l, r] {
+                let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+                if let Err(e) = wav.write_sample(value) {
+                    result = Err(e);
+                    return;
+                }
+            }
+        }
+    });
+    result?;
+    wav.finalize()?;
+    Ok(())
+}
+
+pub fn render_wav_file<S, P>(
+    sequence: &MidiSequence,
+    synth: &mut S,
+    options: &RenderOptions,
+    path: P,
+) -> Result<(), Box<dyn StdError>>
+where
+    S: Synthesizer + ?Sized,
+    P: AsRef<Path>,
+{
+    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+    render_wav(sequence, synth, options, file)
+}
@@ -0,0 +1,211 @@
+use rayon::prelude::*;
+
+use crate::MidiSequence;
+
+// Each level's buckets are this many times wider than the previous one.
+const LEVEL_FACTOR: u64 = 4;
+const DEFAULT_FINEST_BUCKET_NS: u64 = 1_000_000;
+
+/// A run of one key: a single note at the exact level, or the union of the
+/// notes falling in a run of buckets at the coarse ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoteBlock {
+    pub start_ns: u64,
+    pub end_ns: u64,
+    pub key: u8,
+    // The highest track involved, which a piano roll draws on top.
+    pub track_index: u16,
+    pub velocity: u8,
+    pub note_count: u32,
+}
+
+#[derive(Debug, Clone)]
+struct LodLevel {
+    bucket_ns: u64,
+    // Per key, disjoint and in time order.
+    keys: Vec<Vec<NoteBlock>>,
+}
+
+/// Multi-resolution note index for zoomable piano-roll viewers.
+///
+/// The exact notes are kept per key, and every coarser level merges them
+/// into blocks snapped to buckets `LEVEL_FACTOR` times wider than the level
+/// below, until one bucket spans the song. A query picks the coarsest level
+/// whose buckets are no wider than a pixel, so what is drawn looks the same
+/// as the exact notes while the number of blocks stays bounded by the screen
+/// size rather than the note count.
+#[derive(Debug, Clone)]
+pub struct NoteLod {
+    // Per key, ordered by start.
+    notes: Vec<Vec<NoteBlock>>,
+    // Per key, the latest end among `notes[key][..=i]`, for binary searching
+    // overlapping notes despite long ones.
+    reach: Vec<Vec<u64>>,
+    levels: Vec<LodLevel>,
+    note_count: usize,
+}
+
+// The notes of each key, unterminated ones lasting at least 1 ns.
+fn paired_notes(sequence: &MidiSequence) -> Vec<Vec<NoteBlock>> {
+    let mut keys: Vec<Vec<NoteBlock>> = vec![Vec::new(); 128];
+    for note in sequence.notes() {
+        keys[note.key as usize].push(NoteBlock {
+            start_ns: note.start_ns,
+            end_ns: if note.unterminated {
+                note.end_ns().max(note.start_ns.saturating_add(1))
+            } else {
+                note.end_ns()
+            },
+            key: note.key,
+            track_index: note.track_index,
+            velocity: note.velocity,
+            note_count: 1,
+        });
+    }
+    keys
+}
+
+fn merge_level(notes: &[NoteBlock], bucket_ns: u64) -> Vec<NoteBlock> {
+    let mut blocks: Vec<NoteBlock> = Vec::new();
+    for note in notes {
+        let start_ns = note.start_ns / bucket_ns * bucket_ns;
+        let end_ns = note
+            .end_ns
+            .div_ceil(bucket_ns)
+            .max((start_ns / bucket_ns).saturating_add(1))
+            .saturating_mul(bucket_ns);
+        match blocks.last_mut() {
+            // Notes are in start order, so only the last block can touch it.
+            Some(block) if start_ns <= block.end_ns => {
+                block.end_ns = block.end_ns.max(end_ns);
+                block.track_index = block.track_index.max(note.track_index);
+                block.velocity = block.velocity.max(note.velocity);
+                block.note_count += 1;
+            }
+            _ => blocks.push(NoteBlock {
+                start_ns,
+                end_ns,
+                ..*note
+            }),
+        }
+    }
+    blocks
+}
+
+// Blocks of one key overlapping `start_ns..end_ns`, given the latest end
+// reached by each prefix.
+fn overlapping(
+    blocks: &[NoteBlock],
+    reach: impl Fn(usize) -> u64,
+    start_ns: u64,
+    end_ns: u64,
+) -> impl Iterator<Item = &NoteBlock> {
+    let mut low = 0;
+    let mut high = blocks.len();
+    while low < high {
+        let mid = (low + high) / 2;
+        if reach(mid) > start_ns {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    blocks[low..]
+        .iter()
+        .take_while(move |block| block.start_ns < end_ns)
+        .filter(move |block| block.end_ns > start_ns)
+}
+
+impl NoteLod {
+    pub fn new(sequence: &MidiSequence) -> NoteLod {
+        Self::with_finest_bucket(sequence, DEFAULT_FINEST_BUCKET_NS)
+    }
+
+    /// Builds levels starting at `finest_bucket_ns`; zooming in further than
+    /// that shows the exact notes.
+    pub fn with_finest_bucket(sequence: &MidiSequence, finest_bucket_ns: u64) -> NoteLod {
+        let mut notes = paired_notes(sequence);
+        notes
+            .par_iter_mut()
+            .for_each(|key| key.sort_by_key(|note| note.start_ns));
+        let reach = notes
+            .iter()
+            .map(|key| {
+                key.iter()
+                    .scan(0, |reach, note| {
+                        *reach = note.end_ns.max(*reach);
+                        Some(*reach)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let song_end = sequence.end_ns().max(1);
+        let mut bucket_sizes = vec![finest_bucket_ns.max(1)];
+        while *bucket_sizes.last().unwrap() < song_end {
+            bucket_sizes.push(bucket_sizes.last().unwrap().saturating_mul(LEVEL_FACTOR));
+        }
+        let levels = bucket_sizes
+            .into_par_iter()
+            .map(|bucket_ns| LodLevel {
+                bucket_ns,
+                keys: notes
+                    .iter()
+                    .map(|key| merge_level(key, bucket_ns))
+                    .collect(),
+            })
+            .collect();
+
+        NoteLod {
+            note_count: notes.iter().map(Vec::len).sum(),
+            notes,
+            reach,
+            levels,
+        }
+    }
+
+    pub fn note_count(&self) -> usize {
+        self.note_count
+    }
+
+    pub fn level_count(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn bucket_ns(&self, level: usize) -> Option<u64> {
+        self.levels.get(level).map(|level| level.bucket_ns)
+    }
+
+    /// The coarsest level whose buckets fit in one pixel, or `None` when
+    /// zoomed in past the finest level and exact notes should be drawn.
+    pub fn level_for(&self, ns_per_pixel: u64) -> Option<usize> {
+        self.levels
+            .iter()
+            .rposition(|level| level.bucket_ns <= ns_per_pixel)
+    }
+
+    /// Blocks visible in `start_ns..end_ns` at a zoom of `ns_per_pixel`,
+    /// ordered by key, then time.
+    pub fn query(&self, start_ns: u64, end_ns: u64, ns_per_pixel: u64) -> Vec<&NoteBlock> {
+        self.query_level(self.level_for(ns_per_pixel), start_ns, end_ns)
+    }
+
+    /// Like `query`, at a fixed level; `None` is the exact notes.
+    pub fn query_level(&self, level: Option<usize>, start_ns: u64, end_ns: u64) -> Vec<&NoteBlock> {
+        let mut visible = Vec::new();
+        match level.and_then(|level| self.levels.get(level)) {
+            Some(level) => {
+                for blocks in &level.keys {
+                    // Disjoint, so each block's end is also the prefix reach.
+                    visible.extend(overlapping(blocks, |i| blocks[i].end_ns, start_ns, end_ns));
+                }
+            }
+            None => {
+                for (notes, reach) in self.notes.iter().zip(&self.reach) {
+                    visible.extend(overlapping(notes, |i| reach[i], start_ns, end_ns));
+                }
+            }
+        }
+        visible
+    }
+}
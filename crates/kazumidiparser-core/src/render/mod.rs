@@ -0,0 +1,21 @@
+#[cfg(feature = "audio")]
+mod audio;
+mod lod;
+#[cfg(feature = "piano-roll")]
+mod piano_roll;
+#[cfg(feature = "soundfont")]
+mod soundfont;
+#[cfg(feature = "audio")]
+mod synth;
+
+#[cfg(feature = "audio")]
+pub use audio::{RenderOptions, render_blocks, render_samples, render_wav, render_wav_file};
+pub use lod::{NoteBlock, NoteLod};
+#[cfg(feature = "piano-roll")]
+pub use piano_roll::{PianoRollOptions, RgbaImage, render_piano_roll};
+#[cfg(feature = "soundfont")]
+pub use rustysynth::SoundFont;
+#[cfg(feature = "soundfont")]
+pub use soundfont::{SoundFontError, SoundFontSynth};
+#[cfg(feature = "audio")]
+pub use synth::{SimpleSynth, Synthesizer};
@@ -0,0 +1,224 @@
+use std::collections::{HashMap, VecDeque};
+use std::error::Error as StdError;
+use std::io::Write;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+use crate::MidiSequence;
+
+// Colors cycle through this list by track index.
+const DEFAULT_PALETTE: [[u8; 4]; 8] = [
+    [0x4C, 0xAF, 0x50, 0xFF],
+    [0x21, 0x96, 0xF3, 0xFF],
+    [0xF4, 0x43, 0x36, 0xFF],
+    [0xFF, 0xC1, 0x07, 0xFF],
+    [0x9C, 0x27, 0xB0, 0xFF],
+    [0x00, 0xBC, 0xD4, 0xFF],
+    [0xFF, 0x57, 0x22, 0xFF],
+    [0x8B, 0xC3, 0x4A, 0xFF],
+];
+
+#[derive(Debug, Clone)]
+pub struct PianoRollOptions {
+    pub width: u32,
+    pub height: u32,
+    // Visible time range; `None` means the start or end of the song.
+    pub start_ns: Option<u64>,
+    pub end_ns: Option<u64>,
+    // Keys shown, lowest at the bottom of the image.
+    pub keys: RangeInclusive<u8>,
+    pub background: [u8; 4],
+    // RGBA per track; tracks past the end of the list cycle through it.
+    // Empty means the built-in palette.
+    pub track_colors: Vec<[u8; 4]>,
+    // Darken the edges of notes large enough to have them, so adjacent
+    // notes stay distinguishable.
+    pub outline: bool,
+}
+
+impl Default for PianoRollOptions {
+    fn default() -> Self {
+        PianoRollOptions {
+            width: 1280,
+            height: 720,
+            start_ns: None,
+            end_ns: None,
+            keys: 0..=127,
+            background: [0x10, 0x10, 0x10, 0xFF],
+            track_colors: Vec::new(),
+            outline: true,
+        }
+    }
+}
+
+impl PianoRollOptions {
+    fn track_color(&self, track_index: u16) -> [u8; 4] {
+        let palette: &[[u8; 4]] = if self.track_colors.is_empty() {
+            &DEFAULT_PALETTE
+        } else {
+            &self.track_colors
+        };
+        palette[track_index as usize % palette.len()]
+    }
+}
+
+/// An 8-bit RGBA image, rows top to bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    fn new(width: u32, height: u32, fill: [u8; 4]) -> RgbaImage {
+        RgbaImage {
+            width,
+            height,
+            pixels: fill.repeat(width as usize * height as usize),
+        }
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        self.pixels[i..i + 4].try_into().ok()
+    }
+
+    fn fill_rect(&mut self, x0: u32, x1: u32, y0: u32, y1: u32, color: [u8; 4]) {
+        for y in y0..y1.min(self.height) {
+            let row = y as usize * self.width as usize;
+            let start = (row + x0 as usize) * 4;
+            let end = (row + x1.min(self.width) as usize) * 4;
+            for pixel in self.pixels[start..end].chunks_exact_mut(4) {
+                pixel.copy_from_slice(&color);
+            }
+        }
+    }
+
+    pub fn write_png<W: Write>(&self, writer: W) -> Result<(), Box<dyn StdError>> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(())
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn StdError>> {
+        self.write_png(std::io::BufWriter::new(std::fs::File::create(path)?))
+    }
+}
+
+struct NoteSpan {
+    start_ns: u64,
+    end_ns: u64,
+    key: u8,
+}
+
+// Pairs note on/off per track, channel and key (first on, first off) and
+// keeps only the notes overlapping the visible window, grouped by track.
+fn visible_notes(
+    sequence: &MidiSequence,
+    start_ns: u64,
+    end_ns: u64,
+    keys: &RangeInclusive<u8>,
+) -> Vec<Vec<NoteSpan>> {
+    let mut tracks: Vec<Vec<NoteSpan>> = Vec::new();
+    let mut open: HashMap<(u16, u8, u8), VecDeque<u64>> = HashMap::new();
+    let mut open_count = 0usize;
+    let mut push = |track: u16, key: u8, note_start: u64, note_end: u64| {
+        if note_start < end_ns && note_end > start_ns {
+            let track = track as usize;
+            if tracks.len() <= track {
+                tracks.resize_with(track + 1, Vec::new);
+            }
+            tracks[track].push(NoteSpan {
+                start_ns: note_start,
+                end_ns: note_end,
+                key,
+            });
+        }
+    };
+
+    for event in sequence.events() {
+        if event.absolute_ns >= end_ns && open_count == 0 {
+            break;
+        }
+        let kind = event.status & 0xF0;
+        if !(kind == 0x80 || kind == 0x90) || !keys.contains(&event.data1) {
+            continue;
+        }
+        let slot = (event.track_index, event.status & 0x0F, event.data1);
+        if kind == 0x90 && event.data2 > 0 {
+            // Notes starting past the window can never be visible.
+            if event.absolute_ns < end_ns {
+                open.entry(slot).or_default().push_back(event.absolute_ns);
+                open_count += 1;
+            }
+        } else if let Some(note_start) = open.get_mut(&slot).and_then(|q| q.pop_front()) {
+            open_count -= 1;
+            push(slot.0, slot.2, note_start, event.absolute_ns);
+        }
+    }
+
+    // Unterminated notes run to the end of the song.
+    let song_end = sequence.end_ns();
+    for ((track, _, key), starts) in open {
+        for note_start in starts {
+            push(track, key, note_start, song_end.max(note_start + 1));
+        }
+    }
+    tracks
+}
+
+fn darken(color: [u8; 4]) -> [u8; 4] {
+    [color[0] / 2, color[1] / 2, color[2] / 2, color[3]]
+}
+
+/// Draws the notes of `sequence` as a piano roll: time left to right, pitch
+/// bottom to top, later tracks drawn over earlier ones.
+pub fn render_piano_roll(sequence: &MidiSequence, options: &PianoRollOptions) -> RgbaImage {
+    let mut image = RgbaImage::new(options.width, options.height, options.background);
+    let start_ns = options.start_ns.unwrap_or(0);
+    let end_ns = options.end_ns.unwrap_or_else(|| sequence.end_ns());
+    if options.width == 0 || options.height == 0 || end_ns <= start_ns || options.keys.is_empty() {
+        return image;
+    }
+
+    let span = (end_ns - start_ns) as f64;
+    let width = options.width as f64;
+    let height = options.height as f64;
+    let lowest = *options.keys.start();
+    let highest = *options.keys.end();
+    let key_count = (highest - lowest) as f64 + 1.0;
+
+    let to_x = |ns: u64| ((ns.saturating_sub(start_ns) as f64 / span) * width).min(width) as u32;
+    let to_y = |row: f64| ((row / key_count) * height) as u32;
+
+    for (track_index, notes) in visible_notes(sequence, start_ns, end_ns, &options.keys)
+        .into_iter()
+        .enumerate()
+    {
+        let color = options.track_color(track_index as u16);
+        let edge = darken(color);
+        for note in notes {
+            let x0 = to_x(note.start_ns).min(options.width - 1);
+            let x1 = to_x(note.end_ns).max(x0 + 1);
+            let row = (highest - note.key) as f64;
+            let y0 = to_y(row).min(options.height - 1);
+            let y1 = to_y(row + 1.0).max(y0 + 1);
+
+            if options.outline && x1 - x0 >= 3 && y1 - y0 >= 3 {
+                image.fill_rect(x0, x1, y0, y1, edge);
+                image.fill_rect(x0 + 1, x1 - 1, y0 + 1, y1 - 1, color);
+            } else {
+                image.fill_rect(x0, x1, y0, y1, color);
+            }
+        }
+    }
+    image
+}
@@ -0,0 +1,121 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+use rustysynth::{SoundFont, SynthesizerSettings};
+
+use super::synth::Synthesizer;
+use crate::MidiEvent;
+
+#[derive(Debug)]
+pub enum SoundFontError {
+    Io(io::Error),
+    // The file is not a SoundFont 2 bank rustysynth can read.
+    Load(rustysynth::SoundFontError),
+    // Most often a sample rate outside 16000..=192000.
+    Synth(rustysynth::SynthesizerError),
+}
+
+impl fmt::Display for SoundFontError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SoundFontError::Io(e) => write!(f, "Failed to read the SoundFont: {}", e),
+            SoundFontError::Load(e) => write!(f, "Invalid SoundFont: {}", e),
+            SoundFontError::Synth(e) => write!(f, "Failed to start the synthesizer: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SoundFontError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SoundFontError::Io(e) => Some(e),
+            SoundFontError::Load(e) => Some(e),
+            SoundFontError::Synth(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for SoundFontError {
+    fn from(e: io::Error) -> Self {
+        SoundFontError::Io(e)
+    }
+}
+
+/// Renders with the instruments of a SoundFont 2 (.sf2) bank through
+/// rustysynth, reverb and chorus included. SysEx is ignored.
+pub struct SoundFontSynth {
+    synth: rustysynth::Synthesizer,
+    // rustysynth overwrites its output; `render` adds, so it goes through
+    // these first.
+    scratch_left: Vec<f32>,
+    scratch_right: Vec<f32>,
+}
+
+impl SoundFontSynth {
+    /// The bank can be shared between synths, e.g. one per render thread.
+    pub fn new(sound_font: Arc<SoundFont>, sample_rate: u32) -> Result<Self, SoundFontError> {
+        let sample_rate = i32::try_from(sample_rate).unwrap_or(i32::MAX);
+        let settings = SynthesizerSettings::new(sample_rate);
+        let synth =
+            rustysynth::Synthesizer::new(&sound_font, &settings).map_err(SoundFontError::Synth)?;
+        Ok(SoundFontSynth {
+            synth,
+            scratch_left: Vec::new(),
+            scratch_right: Vec::new(),
+        })
+    }
+
+    pub fn from_reader<R: Read>(reader: &mut R, sample_rate: u32) -> Result<Self, SoundFontError> {
+        let sound_font = SoundFont::new(reader).map_err(SoundFontError::Load)?;
+        SoundFontSynth::new(Arc::new(sound_font), sample_rate)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P, sample_rate: u32) -> Result<Self, SoundFontError> {
+        let mut reader = BufReader::new(File::open(path)?);
+        SoundFontSynth::from_reader(&mut reader, sample_rate)
+    }
+
+    pub fn sound_font(&self) -> &SoundFont {
+        self.synth.get_sound_font()
+    }
+
+    /// 1.0 is full scale; rustysynth starts at 0.5.
+    pub fn set_master_volume(&mut self, volume: f32) {
+        self.synth.set_master_volume(volume);
+    }
+}
+
+impl Synthesizer for SoundFontSynth {
+    fn sample_rate(&self) -> u32 {
+        self.synth.get_sample_rate() as u32
+    }
+
+    fn process_event(&mut self, event: &MidiEvent) {
+        if event.status >= 0xF0 {
+            return;
+        }
+        self.synth.process_midi_message(
+            (event.status & 0x0F) as i32,
+            (event.status & 0xF0) as i32,
+            event.data1 as i32,
+            event.data2 as i32,
+        );
+    }
+
+    fn render(&mut self, left: &mut [f32], right: &mut [f32]) {
+        let len = left.len();
+        self.scratch_left.resize(len, 0.0);
+        self.scratch_right.resize(len, 0.0);
+        self.synth
+            .render(&mut self.scratch_left, &mut self.scratch_right);
+        for (out, sample) in left.iter_mut().zip(&self.scratch_left) {
+            *out += sample;
+        }
+        for (out, sample) in right.iter_mut().zip(&self.scratch_right) {
+            *out += sample;
+        }
+    }
+}
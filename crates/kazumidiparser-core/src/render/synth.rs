@@ -0,0 +1,255 @@
+use std::f32::consts::TAU;
+
+use crate::MidiEvent;
+
+/// A software synthesizer driven by MIDI events, producing stereo samples.
+///
+/// With the `soundfont` feature, `SoundFontSynth` renders with the
+/// instruments of an .sf2 bank; [`SimpleSynth`] is the built-in fallback.
+pub trait Synthesizer {
+    fn sample_rate(&self) -> u32;
+
+    fn process_event(&mut self, event: &MidiEvent);
+
+    // Adds `left.len()` samples to both buffers; callers pass buffers of
+    // equal length.
+    fn render(&mut self, left: &mut [f32], right: &mut [f32]);
+}
+
+const ATTACK_SECONDS: f32 = 0.005;
+const RELEASE_SECONDS: f32 = 0.12;
+const DRUM_DECAY_SECONDS: f32 = 0.15;
+const VOICE_GAIN: f32 = 0.12;
+const PERCUSSION_CHANNEL: u8 = 9;
+
+#[derive(Clone, Copy)]
+struct ChannelParams {
+    volume: f32,
+    expression: f32,
+    pan: f32,
+    bend_semitones: f32,
+    sustain: bool,
+}
+
+impl Default for ChannelParams {
+    fn default() -> Self {
+        ChannelParams {
+            volume: 100.0 / 127.0,
+            expression: 1.0,
+            pan: 0.5,
+            bend_semitones: 0.0,
+            sustain: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Stage {
+    Attack,
+    Hold,
+    Release,
+}
+
+struct Voice {
+    channel: u8,
+    key: u8,
+    gain: f32,
+    phase: f32,
+    level: f32,
+    stage: Stage,
+    held_by_pedal: bool,
+}
+
+/// A small polyphonic sine synth with an attack/release envelope and noise
+/// percussion on channel 10. Good enough to check timing, pitch and
+/// dynamics by ear; it does not try to sound like General MIDI.
+pub struct SimpleSynth {
+    sample_rate: u32,
+    max_voices: usize,
+    channels: [ChannelParams; 16],
+    voices: Vec<Voice>,
+    noise_state: u32,
+}
+
+impl SimpleSynth {
+    pub fn new(sample_rate: u32) -> SimpleSynth {
+        assert!(sample_rate > 0, "sample rate must be non-zero");
+        SimpleSynth {
+            sample_rate,
+            max_voices: 256,
+            channels: [ChannelParams::default(); 16],
+            voices: Vec::new(),
+            noise_state: 0x9E37_79B9,
+        }
+    }
+
+    /// Caps polyphony; the quietest releasing voice, or else the oldest one,
+    /// is stolen when a new note would exceed it.
+    pub fn set_max_voices(&mut self, max_voices: usize) {
+        self.max_voices = max_voices.max(1);
+    }
+
+    pub fn active_voices(&self) -> usize {
+        self.voices.len()
+    }
+
+    fn note_on(&mut self, channel: u8, key: u8, velocity: u8) {
+        if self.voices.len() >= self.max_voices {
+            let victim = self
+                .voices
+                .iter()
+                .enumerate()
+                .filter(|(_, v)| v.stage == Stage::Release)
+                .min_by(|(_, a), (_, b)| a.level.total_cmp(&b.level))
+                .map_or(0, |(i, _)| i);
+            self.voices.remove(victim);
+        }
+        let velocity = velocity as f32 / 127.0;
+        self.voices.push(Voice {
+            channel,
+            key,
+            gain: velocity * velocity,
+            phase: 0.0,
+            level: 0.0,
+            stage: Stage::Attack,
+            held_by_pedal: false,
+        });
+    }
+
+    fn note_off(&mut self, channel: u8, key: u8) {
+        let sustain = self.channels[channel as usize].sustain;
+        for voice in &mut self.voices {
+            if voice.channel == channel && voice.key == key && voice.stage != Stage::Release {
+                if sustain {
+                    voice.held_by_pedal = true;
+                } else {
+                    voice.stage = Stage::Release;
+                }
+            }
+        }
+    }
+
+    fn controller(&mut self, channel: u8, controller: u8, value: u8) {
+        let params = &mut self.channels[channel as usize];
+        let value_f = value as f32 / 127.0;
+        match controller {
+            7 => params.volume = value_f,
+            10 => params.pan = value_f,
+            11 => params.expression = value_f,
+            64 => {
+                params.sustain = value >= 64;
+                if !params.sustain {
+                    for voice in &mut self.voices {
+                        if voice.channel == channel && voice.held_by_pedal {
+                            voice.held_by_pedal = false;
+                            voice.stage = Stage::Release;
+                        }
+                    }
+                }
+            }
+            // All Sound Off
+            120 => self.voices.retain(|v| v.channel != channel),
+            121 => {
+                params.expression = 1.0;
+                params.bend_semitones = 0.0;
+                params.sustain = false;
+            }
+            // All Notes Off
+            123 => {
+                for voice in &mut self.voices {
+                    if voice.channel == channel {
+                        voice.stage = Stage::Release;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn next_noise(&mut self) -> f32 {
+        // xorshift32
+        let mut x = self.noise_state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.noise_state = x;
+        (x as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+}
+
+impl Synthesizer for SimpleSynth {
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn process_event(&mut self, event: &MidiEvent) {
+        if event.status >= 0xF0 {
+            return;
+        }
+        let channel = event.status & 0x0F;
+        match event.status & 0xF0 {
+            0x90 if event.data2 > 0 => self.note_on(channel, event.data1, event.data2),
+            0x80 | 0x90 => self.note_off(channel, event.data1),
+            0xB0 => self.controller(channel, event.data1, event.data2),
+            0xE0 => {
+                let value = (event.data2 as i32) << 7 | event.data1 as i32;
+                // Default +-2 semitone bend range.
+                self.channels[channel as usize].bend_semitones = (value - 8192) as f32 / 4096.0;
+            }
+            _ => {}
+        }
+    }
+
+    fn render(&mut self, left: &mut [f32], right: &mut [f32]) {
+        let sample_rate = self.sample_rate as f32;
+        let attack_step = 1.0 / (ATTACK_SECONDS * sample_rate);
+        let release_step = 1.0 / (RELEASE_SECONDS * sample_rate);
+        let drum_decay = (-1.0 / (DRUM_DECAY_SECONDS * sample_rate)).exp();
+
+        let mut voices = std::mem::take(&mut self.voices);
+        for voice in &mut voices {
+            let params = self.channels[voice.channel as usize];
+            let amplitude = VOICE_GAIN * voice.gain * params.volume * params.expression;
+            let (pan_left, pan_right) = ((1.0 - params.pan).sqrt(), params.pan.sqrt());
+            let percussion = voice.channel == PERCUSSION_CHANNEL;
+            let frequency =
+                440.0 * ((voice.key as f32 + params.bend_semitones - 69.0) / 12.0).exp2();
+            let phase_step = frequency / sample_rate;
+
+            for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+                match voice.stage {
+                    Stage::Attack if percussion => {
+                        voice.level = 1.0;
+                        voice.stage = Stage::Release;
+                    }
+                    Stage::Attack => {
+                        voice.level += attack_step;
+                        if voice.level >= 1.0 {
+                            voice.level = 1.0;
+                            voice.stage = Stage::Hold;
+                        }
+                    }
+                    Stage::Hold => {}
+                    Stage::Release if percussion => voice.level *= drum_decay,
+                    Stage::Release => voice.level -= release_step,
+                }
+                if voice.level <= 0.001 && voice.stage == Stage::Release {
+                    voice.level = 0.0;
+                    break;
+                }
+
+                let sample = if percussion {
+                    self.next_noise()
+                } else {
+                    voice.phase = (voice.phase + phase_step).fract();
+                    (voice.phase * TAU).sin()
+                };
+                let sample = sample * amplitude * voice.level;
+                *l += sample * pan_left;
+                *r += sample * pan_right;
+            }
+        }
+        voices.retain(|v| v.level > 0.0 || v.stage != Stage::Release);
+        self.voices = voices;
+    }
+}
@@ -0,0 +1,90 @@
+//! RMID files: a Standard MIDI File in the `data` chunk of a RIFF `RMID`
+//! form, sometimes followed by a DLS (or SoundFont) bank for it. Byte offsets
+//! reported for such a file count from the start of the embedded SMF.
+
+use std::fs::File;
+use std::io::{self, Read, Seek};
+
+use crate::ParseError;
+
+pub(crate) const RIFF: [u8; 4] = *b"RIFF";
+
+pub(crate) struct RmidImage<'a> {
+    pub(crate) smf: &'a [u8],
+    // The embedded bank as a standalone RIFF file.
+    pub(crate) sound_bank: Option<Vec<u8>>,
+}
+
+fn le_u32(bytes: &[u8]) -> usize {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize
+}
+
+// Whether a chunk body is a DLS collection or a SoundFont, by its form type.
+fn is_sound_bank(body: &[u8]) -> bool {
+    body.starts_with(b"DLS ") || body.starts_with(b"sfbk")
+}
+
+/// Whether `file` starts with `RIFF`. Leaves it rewound.
+pub(crate) fn is_riff(file: &mut File) -> io::Result<bool> {
+    let mut magic = Vec::with_capacity(4);
+    file.by_ref().take(4).read_to_end(&mut magic)?;
+    file.rewind()?;
+    Ok(magic == RIFF)
+}
+
+/// Finds the SMF and sound bank in a complete RMID image. A RIFF length
+/// that runs past the end of `data` is cut short rather than rejected.
+pub(crate) fn split_rmid(data: &[u8]) -> Result<RmidImage<'_>, ParseError> {
+    if data.len() < 12 || data[..4] != RIFF || data[8..12] != *b"RMID" {
+        return Err(ParseError::InvalidHeader);
+    }
+    let end = (8 + le_u32(&data[4..8])).min(data.len());
+
+    let mut smf = None;
+    let mut sound_bank = None;
+    let mut pos = 12;
+    while pos + 8 <= end {
+        let length = le_u32(&data[pos + 4..pos + 8]);
+        let body = &data[pos + 8..(pos + 8).saturating_add(length).min(end)];
+        match &data[pos..pos + 4] {
+            b"data" if smf.is_none() => smf = Some(body),
+            // Some writers store the bank as a LIST rather than a RIFF chunk.
+            b"RIFF" | b"LIST" if sound_bank.is_none() && is_sound_bank(body) => {
+                let mut bank = data[pos..pos + 8 + body.len()].to_vec();
+                bank[..4].copy_from_slice(&RIFF);
+                sound_bank = Some(bank);
+            }
+            _ => {}
+        }
+        pos = pos.saturating_add(8 + length + (length & 1));
+    }
+
+    let smf = smf.ok_or(ParseError::InvalidHeader)?;
+    Ok(RmidImage { smf, sound_bank })
+}
+
+/// Reads past the RIFF header and any chunks before `data`, leaving `reader`
+/// at the start of the SMF. `RIFF` has already been read.
+pub(crate) fn skip_to_smf<R: Read>(reader: &mut R) -> Result<(), ParseError> {
+    let eof_is_invalid = |error: io::Error| match error.kind() {
+        io::ErrorKind::UnexpectedEof => ParseError::InvalidHeader,
+        _ => ParseError::Io(error),
+    };
+
+    let mut header = [0; 8];
+    reader.read_exact(&mut header).map_err(eof_is_invalid)?;
+    if header[4..] != *b"RMID" {
+        return Err(ParseError::InvalidHeader);
+    }
+    loop {
+        reader.read_exact(&mut header).map_err(eof_is_invalid)?;
+        if header[..4] == *b"data" {
+            return Ok(());
+        }
+        let length = le_u32(&header[4..]) as u64;
+        let padded = length + (length & 1);
+        if io::copy(&mut reader.by_ref().take(padded), &mut io::sink())? < padded {
+            return Err(ParseError::InvalidHeader);
+        }
+    }
+}
@@ -0,0 +1,530 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use rayon::prelude::*;
+
+use crate::playback::PlaybackTarget;
+use crate::tempo::TempoMap;
+use crate::{
+    EventOrder, MetaEvent, MidiEvent, MidiHeader, ParseWarning, TextEncoding, TrackLengthMismatch,
+    TrackMeta,
+};
+
+/// A parsed song: header, time-ordered events and the tempo map they were
+/// timed against.
+///
+/// A format 2 file holds an independent song in every track, each timed by
+/// its own tempo events (see `track_tempo_map`). All of them start at zero,
+/// so the merged lists are in time order there but not in tick order.
+#[derive(Debug, Clone)]
+pub struct MidiSequence {
+    pub(crate) header: MidiHeader,
+    pub(crate) events: Vec<MidiEvent>,
+    // SysEx payloads, by `MidiEvent::sysex_index`.
+    pub(crate) sysex: Vec<Vec<u8>>,
+    pub(crate) metas: Vec<MetaEvent>,
+    pub(crate) tempo_map: TempoMap,
+    // Format 2 only: the maps of the tracks after the first, whose map is
+    // `tempo_map`. Empty for every other format.
+    pub(crate) track_tempo_maps: Vec<TempoMap>,
+    pub(crate) track_metas: Vec<TrackMeta>,
+    pub(crate) warnings: Vec<ParseWarning>,
+    // The DLS or SoundFont bank of an RMID file.
+    pub(crate) sound_bank: Option<Vec<u8>>,
+    // Never `Auto`; that is resolved when the sequence is built.
+    pub(crate) text_encoding: TextEncoding,
+    // How events on one tick and track were ordered, for re-sorts.
+    pub(crate) event_order: EventOrder,
+}
+
+impl MidiSequence {
+    pub(crate) fn empty() -> MidiSequence {
+        MidiSequence {
+            header: MidiHeader {
+                format: 0,
+                tracks: 0,
+                ppqn: 0,
+            },
+            events: Vec::new(),
+            sysex: Vec::new(),
+            metas: Vec::new(),
+            tempo_map: TempoMap::new(0),
+            track_tempo_maps: Vec::new(),
+            track_metas: Vec::new(),
+            warnings: Vec::new(),
+            sound_bank: None,
+            text_encoding: TextEncoding::Utf8,
+            event_order: EventOrder::Track,
+        }
+    }
+
+    pub fn header(&self) -> &MidiHeader {
+        &self.header
+    }
+
+    /// Every event, ordered by time, ties in track order. Outside format 2
+    /// that is also tick order.
+    pub fn events(&self) -> &[MidiEvent] {
+        &self.events
+    }
+
+    /// The bytes of a SysEx or F7 packet event: after the F0 of a SysEx, or
+    /// the escaped bytes of an F7 packet. `None` for channel messages.
+    pub fn sysex(&self, event: &MidiEvent) -> Option<&[u8]> {
+        let index = event.sysex_index? as usize;
+        self.sysex.get(index).map(Vec::as_slice)
+    }
+
+    /// Every SysEx payload, indexed by `MidiEvent::sysex_index`.
+    pub fn sysex_table(&self) -> &[Vec<u8>] {
+        &self.sysex
+    }
+
+    /// The DLS (form `DLS `) or SoundFont (form `sfbk`) bank embedded in an
+    /// RMID file, as a complete RIFF file that can be saved as `.dls` or
+    /// `.sf2`. Only kept when the whole file was read, so not by
+    /// `parse_reader`.
+    pub fn sound_bank(&self) -> Option<&[u8]> {
+        self.sound_bank.as_deref()
+    }
+
+    /// The events with `range.start <= absolute_ns < range.end`, found by
+    /// binary search over the time-ordered event list.
+    pub fn events_in_range(&self, range: Range<u64>) -> &[MidiEvent] {
+        let start = self.events.partition_point(|e| e.absolute_ns < range.start);
+        let end = start + self.events[start..].partition_point(|e| e.absolute_ns < range.end);
+        &self.events[start..end]
+    }
+
+    pub fn first_event_at_or_after(&self, ns: u64) -> Option<&MidiEvent> {
+        let index = self.events.partition_point(|e| e.absolute_ns < ns);
+        self.events.get(index)
+    }
+
+    // Meta events other than End of Track, ordered as `events` is.
+    pub fn metas(&self) -> &[MetaEvent] {
+        &self.metas
+    }
+
+    /// The meta events of one track, ordered by tick.
+    pub fn metas_on_track(&self, track_index: u16) -> impl Iterator<Item = &MetaEvent> {
+        self.metas
+            .iter()
+            .filter(move |meta| meta.track_index == track_index)
+    }
+
+    /// Every meta event of one type across all tracks, ordered as `metas`.
+    pub fn metas_of_type(&self, meta_type: u8) -> impl Iterator<Item = &MetaEvent> {
+        self.metas
+            .iter()
+            .filter(move |meta| meta.meta_type == meta_type)
+    }
+
+    /// How events on the same tick and track are ordered, as chosen by
+    /// `ParseOptions::event_order`.
+    pub fn event_order(&self) -> EventOrder {
+        self.event_order
+    }
+
+    /// The encoding meta text is decoded with, as chosen by
+    /// `ParseOptions::text_encoding`. Never `TextEncoding::Auto`.
+    pub fn text_encoding(&self) -> TextEncoding {
+        self.text_encoding
+    }
+
+    /// Changes how meta text is decoded, detecting it again for `Auto`.
+    pub fn set_text_encoding(&mut self, encoding: TextEncoding) {
+        self.text_encoding = encoding.resolve(
+            self.metas
+                .iter()
+                .filter(|meta| meta.is_text())
+                .map(|meta| meta.data.as_slice()),
+        );
+    }
+
+    pub fn decode_text(&self, bytes: &[u8]) -> String {
+        self.text_encoding.decode(bytes)
+    }
+
+    /// `MetaEvent::text`, decoded with the sequence's text encoding.
+    pub fn meta_text(&self, meta: &MetaEvent) -> Option<String> {
+        meta.is_text().then(|| self.decode_text(&meta.data))
+    }
+
+    /// The map every event is timed against. In a format 2 file that is the
+    /// first track's; see `track_tempo_map` for the others.
+    pub fn tempo_map(&self) -> &TempoMap {
+        &self.tempo_map
+    }
+
+    /// The tempo map a track plays with: in format 2, where every track is an
+    /// independent sequence, one built from that track's tempo events alone;
+    /// otherwise the song's map.
+    pub fn track_tempo_map(&self, track_index: u16) -> Option<TempoMap> {
+        if track_index >= self.header.tracks {
+            return None;
+        }
+        Some(self.track_map(track_index).clone())
+    }
+
+    pub(crate) fn track_map(&self, track_index: u16) -> &TempoMap {
+        track_map(&self.tempo_map, &self.track_tempo_maps, track_index)
+    }
+
+    pub(crate) fn track_map_mut(&mut self, track_index: u16) -> &mut TempoMap {
+        match track_index
+            .checked_sub(1)
+            .and_then(|i| self.track_tempo_maps.get_mut(i as usize))
+        {
+            Some(map) => map,
+            None => &mut self.tempo_map,
+        }
+    }
+
+    // The first track's map, then the other format 2 tracks' maps.
+    fn tempo_maps(&self) -> impl Iterator<Item = &TempoMap> {
+        std::iter::once(&self.tempo_map).chain(&self.track_tempo_maps)
+    }
+
+    /// Splits a format 2 file into its independent sequences, one per track
+    /// in file order and each on its own timeline (see `select`). Formats 0
+    /// and 1 hold a single sequence, returned whole.
+    pub fn sequences(&self) -> Vec<MidiSequence> {
+        if self.header.format != 2 {
+            return vec![self.clone()];
+        }
+        (0..self.header.tracks)
+            .filter_map(|track_index| self.select(PlaybackTarget::Track(track_index)))
+            .collect()
+    }
+
+    /// Tracks whose `MTrk` length field did not match their data, by track
+    /// index.
+    pub fn track_length_mismatches(&self) -> impl Iterator<Item = (u16, &TrackLengthMismatch)> {
+        self.track_metas
+            .iter()
+            .enumerate()
+            .filter_map(|(i, meta)| Some((i as u16, meta.length_mismatch.as_ref()?)))
+    }
+
+    /// Tracks that reuse running status after meta or SysEx events, which
+    /// only parse with `ParseOptions::lenient_running_status` (the default),
+    /// with the number of events that reused a cancelled running status.
+    pub fn running_status_fallbacks(&self) -> impl Iterator<Item = (u16, usize)> {
+        self.track_metas
+            .iter()
+            .enumerate()
+            .filter(|(_, meta)| meta.running_status_fallbacks > 0)
+            .map(|(i, meta)| (i as u16, meta.running_status_fallbacks))
+    }
+
+    /// What the parse worked around, ordered by file offset. Only a
+    /// non-strict parse recovers from bad track data, but length mismatches
+    /// and missing End of Track events are reported either way.
+    pub fn warnings(&self) -> &[ParseWarning] {
+        &self.warnings
+    }
+
+    pub fn end_tick(&self) -> u64 {
+        // Format 2 lists are in time order, so the last tick can be anywhere.
+        let last_event = if self.track_tempo_maps.is_empty() {
+            self.events.last().map_or(0, |e| e.absolute_tick)
+        } else {
+            self.events
+                .iter()
+                .map(|e| e.absolute_tick)
+                .max()
+                .unwrap_or(0)
+        };
+        let last_tempo = self
+            .tempo_maps()
+            .filter_map(|map| map.points().last())
+            .map(|p| p.absolute_tick)
+            .max()
+            .unwrap_or(0);
+        last_event.max(last_tempo)
+    }
+
+    pub fn end_ns(&self) -> u64 {
+        if self.track_tempo_maps.is_empty() {
+            return self.tempo_map.tick_to_ns(self.end_tick());
+        }
+        let last_event = self.events.last().map_or(0, |e| e.absolute_ns);
+        let last_tempo = self
+            .tempo_maps()
+            .filter_map(|map| map.points().last())
+            .map(|p| p.absolute_ns)
+            .max()
+            .unwrap_or(0);
+        last_event.max(last_tempo)
+    }
+
+    /// The song's length in ticks: the latest of `end_tick`, the last meta
+    /// and every track's End of Track, so the silence a track ends with
+    /// counts too.
+    pub fn total_duration_tick(&self) -> u64 {
+        let last_meta = if self.track_tempo_maps.is_empty() {
+            self.metas.last().map_or(0, |m| m.absolute_tick)
+        } else {
+            self.metas
+                .iter()
+                .map(|m| m.absolute_tick)
+                .max()
+                .unwrap_or(0)
+        };
+        let last_end_of_track = self
+            .track_metas
+            .iter()
+            .map(|meta| meta.end_tick)
+            .max()
+            .unwrap_or(0);
+        self.end_tick().max(last_meta).max(last_end_of_track)
+    }
+
+    /// The song's length in time; in format 2, that of its longest track.
+    pub fn total_duration_ns(&self) -> u64 {
+        if self.track_tempo_maps.is_empty() {
+            return self.tempo_map.tick_to_ns(self.total_duration_tick());
+        }
+        let last_meta = self.metas.last().map_or(0, |m| m.absolute_ns);
+        let last_end_of_track = self
+            .track_metas
+            .iter()
+            .enumerate()
+            .map(|(track_index, meta)| self.track_map(track_index as u16).tick_to_ns(meta.end_tick))
+            .max()
+            .unwrap_or(0);
+        self.end_ns().max(last_meta).max(last_end_of_track)
+    }
+
+    /// The sequence number (meta 0x00) of a track. In format 2 files a track
+    /// without the meta defaults to its position in the file, as the SMF spec
+    /// prescribes.
+    pub fn track_sequence_number(&self, track_index: u16) -> Option<u16> {
+        if track_index >= self.header.tracks {
+            return None;
+        }
+        let explicit = self
+            .track_metas
+            .get(track_index as usize)
+            .and_then(|m| m.sequence_number);
+        match self.header.format {
+            2 => Some(explicit.unwrap_or(track_index)),
+            _ => explicit,
+        }
+    }
+
+    /// Maps sequence numbers to the track that holds them.
+    ///
+    /// For format 2 every track is an independent sequence and appears here;
+    /// for formats 0 and 1 the number (if any) in the first track identifies
+    /// the whole file and maps to track 0. On duplicate numbers the first
+    /// track wins.
+    pub fn sequence_index(&self) -> BTreeMap<u16, u16> {
+        let mut index = BTreeMap::new();
+        let tracks = match self.header.format {
+            2 => 0..self.header.tracks,
+            _ => 0..self.header.tracks.min(1),
+        };
+        for track in tracks {
+            if let Some(number) = self.track_sequence_number(track) {
+                index.entry(number).or_insert(track);
+            }
+        }
+        index
+    }
+
+    pub fn track_for_sequence_number(&self, number: u16) -> Option<u16> {
+        self.sequence_index().get(&number).copied()
+    }
+
+    /// Replaces the tempo map and recomputes every event's `absolute_ns` from
+    /// its tick position. The map must use the sequence's PPQN. In format 2
+    /// only the first track's map is replaced.
+    pub fn set_tempo_map(&mut self, tempo_map: TempoMap) {
+        assert_eq!(
+            tempo_map.ppqn(),
+            self.header.tempo_timing().0,
+            "tempo map PPQN does not match the sequence"
+        );
+        self.tempo_map = tempo_map;
+        self.retime();
+    }
+
+    pub fn edit_tempo_map<F: FnOnce(&mut TempoMap)>(&mut self, edit: F) {
+        edit(&mut self.tempo_map);
+        self.retime();
+    }
+
+    /// Merges redundant tempo changes (see `TempoMap::simplify`) and retimes
+    /// the events if any went. Returns the number of changes removed.
+    pub fn simplify_tempo_map(&mut self, epsilon_bpm: f64) -> usize {
+        let removed = self.tempo_map.simplify(epsilon_bpm);
+        if removed > 0 {
+            self.retime();
+        }
+        removed
+    }
+
+    pub(crate) fn retime(&mut self) {
+        if self.track_tempo_maps.is_empty() {
+            self.tempo_map.apply(&mut self.events);
+            for meta in &mut self.metas {
+                meta.absolute_ns = self.tempo_map.tick_to_ns(meta.absolute_tick);
+            }
+            return;
+        }
+        let (first, rest) = (&self.tempo_map, &self.track_tempo_maps);
+        self.events.par_iter_mut().for_each(|event| {
+            event.absolute_ns =
+                track_map(first, rest, event.track_index).tick_to_ns(event.absolute_tick);
+        });
+        for meta in &mut self.metas {
+            meta.absolute_ns =
+                track_map(first, rest, meta.track_index).tick_to_ns(meta.absolute_tick);
+        }
+        sort_by_time(&mut self.events, &mut self.metas);
+    }
+
+    /// Appends `other` after the end of this sequence
+    /// (`total_duration_tick`), `gap_ns` later.
+    ///
+    /// Ticks of `other` are rescaled to this sequence's PPQN (rounded to the
+    /// nearest tick), its tempo map is spliced in at the join point and its
+    /// tracks are numbered after the existing ones. An SMPTE sequence keeps
+    /// its fixed tick rate, so `other` is placed by time instead. If either
+    /// is format 2 the result is too, and each of `other`'s tracks brings its
+    /// own map, spliced the same way.
+    pub fn append(&mut self, other: &MidiSequence, gap_ns: u64) {
+        let ppqn = self.tempo_map.ppqn().max(1) as u64;
+        let other_ppqn = other.tempo_map.ppqn().max(1) as u64;
+        let fixed_map = self.header.tempo_timing().1.map(|_| self.tempo_map.clone());
+        // A tick of `other`'s track `track_index`.
+        let rescale = |tick: u64, track_index: u16| match &fixed_map {
+            Some(map) => map.ns_to_tick(
+                other.track_map(track_index).tick_to_ns(tick) + map.points()[0].tick_ns / 2,
+            ),
+            None => (tick * ppqn + other_ppqn / 2) / other_ppqn,
+        };
+
+        let end_tick = self.total_duration_tick();
+        let end_tick_ns = self
+            .tempo_map
+            .points()
+            .last()
+            .map_or(0, |p| p.tick_ns)
+            .max(1);
+        let offset_tick = end_tick + (gap_ns + end_tick_ns / 2) / end_tick_ns;
+
+        // This sequence's tempo changes, then those of one of `other`'s maps.
+        let first_tempo = self.tempo_map.points()[0].tempo_us;
+        let splice = |other_map: &TempoMap, track_index: u16| {
+            let tempo_changes = self.tempo_map.changes().skip(1).chain(
+                other_map
+                    .changes()
+                    .map(|(tick, tempo_us)| (offset_tick + rescale(tick, track_index), tempo_us)),
+            );
+            TempoMap::for_header(
+                &self.header,
+                std::iter::once((0, first_tempo)).chain(tempo_changes),
+                self.tempo_map.is_exact(),
+            )
+        };
+        let tempo_map = splice(&other.tempo_map, 0);
+        let track_offset = self.header.tracks;
+        let is_format_2 = self.header.format == 2 || other.header.format == 2;
+        if is_format_2 {
+            // Tracks that came without a map of their own play with the one
+            // they were timed against.
+            self.track_tempo_maps
+                .resize(track_offset.saturating_sub(1) as usize, tempo_map.clone());
+            if track_offset > 0 {
+                self.track_tempo_maps.push(tempo_map.clone());
+            }
+            let other_maps: Vec<TempoMap> = (1..other.header.tracks)
+                .map(|track_index| splice(other.track_map(track_index), track_index))
+                .collect();
+            self.track_tempo_maps.extend(other_maps);
+        }
+        self.tempo_map = tempo_map;
+
+        let sysex_offset = self.sysex.len() as u32;
+        let (first, rest) = (&self.tempo_map, &self.track_tempo_maps);
+        let appended = other.events.par_iter().map(|event| {
+            let absolute_tick = offset_tick + rescale(event.absolute_tick, event.track_index);
+            let track_index = event.track_index + track_offset;
+            MidiEvent {
+                absolute_ns: track_map(first, rest, track_index).tick_to_ns(absolute_tick),
+                absolute_tick,
+                track_index,
+                sysex_index: event.sysex_index.map(|index| index + sysex_offset),
+                ..*event
+            }
+        });
+        self.events.par_extend(appended);
+        self.sysex.extend(other.sysex.iter().cloned());
+
+        self.metas.extend(other.metas.iter().map(|meta| {
+            let absolute_tick = offset_tick + rescale(meta.absolute_tick, meta.track_index);
+            let track_index = meta.track_index + track_offset;
+            MetaEvent {
+                absolute_ns: track_map(first, rest, track_index).tick_to_ns(absolute_tick),
+                absolute_tick,
+                track_index,
+                ..meta.clone()
+            }
+        }));
+
+        self.track_metas
+            .resize(track_offset as usize, Default::default());
+        self.track_metas.extend(
+            other
+                .track_metas
+                .iter()
+                .enumerate()
+                .map(|(track_index, meta)| TrackMeta {
+                    end_tick: offset_tick + rescale(meta.end_tick, track_index as u16),
+                    ..meta.clone()
+                }),
+        );
+        // Offsets still point into the file `other` was parsed from.
+        self.warnings
+            .extend(other.warnings.iter().map(|warning| ParseWarning {
+                track: warning.track.map(|track| track + track_offset),
+                ..warning.clone()
+            }));
+
+        self.header.tracks += other.header.tracks;
+        self.header.format = if is_format_2 {
+            2
+        } else if self.header.tracks > 1 {
+            1
+        } else {
+            0
+        };
+        if is_format_2 {
+            sort_by_time(&mut self.events, &mut self.metas);
+        }
+    }
+}
+
+// The map of `track_index` given the first track's map and the other format 2
+// tracks' maps.
+pub(crate) fn track_map<'a>(
+    first: &'a TempoMap,
+    rest: &'a [TempoMap],
+    track_index: u16,
+) -> &'a TempoMap {
+    track_index
+        .checked_sub(1)
+        .and_then(|i| rest.get(i as usize))
+        .unwrap_or(first)
+}
+
+// Format 2 tracks each run on their own timeline, so their merged lists are
+// kept in time order, ties in track order. Stable, so a track's own events
+// keep their order.
+pub(crate) fn sort_by_time(events: &mut [MidiEvent], metas: &mut [MetaEvent]) {
+    events.par_sort_by_key(|e| (e.absolute_ns, e.track_index));
+    metas.sort_by_key(|m| (m.absolute_ns, m.track_index));
+}
@@ -0,0 +1,311 @@
+use std::fs::OpenOptions;
+use std::io;
+use std::mem::{align_of, size_of};
+use std::path::Path;
+
+use memmap2::{Mmap, MmapMut};
+use rayon::prelude::*;
+
+use crate::{MidiHeader, MidiSequence, TempoMap};
+
+const MAGIC: [u8; 8] = *b"KZMIDSHM";
+const VERSION: u32 = 1;
+
+/// Layout of the region, all integers in native byte order:
+///
+/// | offset          | contents                              |
+/// |-----------------|---------------------------------------|
+/// | 0               | `SharedHeader`                        |
+/// | `tempo_offset`  | `tempo_count` x `SharedTempoChange`   |
+/// | `event_offset`  | `event_count` x `SharedEvent`         |
+/// | `sysex_offset`  | `sysex_len` bytes of SysEx payloads   |
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SharedHeader {
+    pub magic: [u8; 8],
+    pub version: u32,
+    pub header_size: u32,
+    pub format: u16,
+    pub tracks: u16,
+    pub ppqn: u16,
+    // `SharedHeader::EXACT_TIMING` when the tempo map was exact.
+    pub flags: u16,
+    pub tempo_count: u64,
+    pub tempo_offset: u64,
+    pub event_count: u64,
+    pub event_offset: u64,
+    pub sysex_len: u64,
+    pub sysex_offset: u64,
+}
+
+impl SharedHeader {
+    pub const EXACT_TIMING: u16 = 1 << 0;
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SharedTempoChange {
+    pub absolute_tick: u64,
+    pub tempo_us: u32,
+    pub reserved: u32,
+}
+
+/// One event as stored in the shared region. For SysEx events
+/// (`status == 0xF0`) and F7 escape packets (`status == 0xF7`) the payload
+/// is `sysex_len` bytes at `sysex_offset` within the SysEx section.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SharedEvent {
+    pub absolute_ns: u64,
+    pub absolute_tick: u64,
+    pub sysex_offset: u32,
+    pub sysex_len: u32,
+    pub track_index: u16,
+    pub status: u8,
+    pub data1: u8,
+    pub data2: u8,
+    pub reserved: [u8; 3],
+}
+
+fn align8(offset: usize) -> usize {
+    offset.div_ceil(8) * 8
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Safety: `T` must be one of the plain `repr(C)` records above, and the
+// range must lie inside `data` at an offset aligned for `T`.
+unsafe fn section<T>(data: &[u8], offset: usize, count: usize) -> &[T] {
+    unsafe { std::slice::from_raw_parts(data.as_ptr().add(offset) as *const T, count) }
+}
+
+unsafe fn section_mut<T>(data: &mut [u8], offset: usize, count: usize) -> &mut [T] {
+    unsafe { std::slice::from_raw_parts_mut(data.as_mut_ptr().add(offset) as *mut T, count) }
+}
+
+/// Parsed events placed in a memory-mapped file, so another process can map
+/// the same file and read them without copying or deserializing.
+///
+/// On Linux, a path under `/dev/shm` keeps the region in memory only.
+pub struct SharedEventStore {
+    map: Mmap,
+}
+
+impl SharedEventStore {
+    /// Writes `sequence` to `path`, replacing any existing file, and keeps
+    /// it mapped.
+    pub fn create<P: AsRef<Path>>(
+        path: P,
+        sequence: &MidiSequence,
+    ) -> io::Result<SharedEventStore> {
+        let events = sequence.events();
+        let changes: Vec<(u64, u32)> = sequence.tempo_map().changes().collect();
+        let sysex_len: usize = sequence.sysex_table().iter().map(Vec::len).sum();
+        if sysex_len > u32::MAX as usize {
+            return Err(invalid("SysEx data exceeds 4 GiB"));
+        }
+
+        let tempo_offset = align8(size_of::<SharedHeader>());
+        let event_offset = align8(tempo_offset + changes.len() * size_of::<SharedTempoChange>());
+        let sysex_offset = event_offset + events.len() * size_of::<SharedEvent>();
+        let total_len = sysex_offset + sysex_len;
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(total_len as u64)?;
+        let mut map = unsafe { MmapMut::map_mut(&file)? };
+
+        let header = SharedHeader {
+            magic: MAGIC,
+            version: VERSION,
+            header_size: size_of::<SharedHeader>() as u32,
+            format: sequence.header().format,
+            tracks: sequence.header().tracks,
+            ppqn: sequence.header().ppqn,
+            flags: if sequence.tempo_map().is_exact() {
+                SharedHeader::EXACT_TIMING
+            } else {
+                0
+            },
+            tempo_count: changes.len() as u64,
+            tempo_offset: tempo_offset as u64,
+            event_count: events.len() as u64,
+            event_offset: event_offset as u64,
+            sysex_len: sysex_len as u64,
+            sysex_offset: sysex_offset as u64,
+        };
+        unsafe { section_mut::<SharedHeader>(&mut map, 0, 1)[0] = header };
+
+        let tempo =
+            unsafe { section_mut::<SharedTempoChange>(&mut map, tempo_offset, changes.len()) };
+        for (slot, &(absolute_tick, tempo_us)) in tempo.iter_mut().zip(&changes) {
+            *slot = SharedTempoChange {
+                absolute_tick,
+                tempo_us,
+                reserved: 0,
+            };
+        }
+
+        // The SysEx table is copied out whole, then the fixed-size records
+        // are filled in parallel with their payload's offset in it.
+        let (records, sysex) =
+            map[event_offset..].split_at_mut(events.len() * size_of::<SharedEvent>());
+        let mut sysex_offsets = Vec::with_capacity(sequence.sysex_table().len());
+        let mut cursor = 0;
+        for data in sequence.sysex_table() {
+            sysex[cursor..cursor + data.len()].copy_from_slice(data);
+            sysex_offsets.push(cursor as u32);
+            cursor += data.len();
+        }
+
+        let records = unsafe { section_mut::<SharedEvent>(records, 0, events.len()) };
+        records
+            .par_iter_mut()
+            .zip(events.par_iter())
+            .for_each(|(slot, event)| {
+                let (sysex_offset, sysex_len) = match event.sysex_index {
+                    Some(index) => (
+                        sysex_offsets[index as usize],
+                        sequence.sysex_table()[index as usize].len() as u32,
+                    ),
+                    None => (0, 0),
+                };
+                *slot = SharedEvent {
+                    absolute_ns: event.absolute_ns,
+                    absolute_tick: event.absolute_tick,
+                    sysex_offset,
+                    sysex_len,
+                    track_index: event.track_index,
+                    status: event.status,
+                    data1: event.data1,
+                    data2: event.data2,
+                    reserved: [0; 3],
+                };
+            });
+
+        map.flush()?;
+        Ok(SharedEventStore {
+            map: map.make_read_only()?,
+        })
+    }
+
+    /// Maps a region written by [`SharedEventStore::create`], possibly from
+    /// another process.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<SharedEventStore> {
+        let file = OpenOptions::new().read(true).open(path)?;
+        let map = unsafe { Mmap::map(&file)? };
+        if map.len() < size_of::<SharedHeader>() {
+            return Err(invalid("Region is smaller than the header"));
+        }
+        let store = SharedEventStore { map };
+        let header = store.raw_header();
+        if header.magic != MAGIC {
+            return Err(invalid("Not a kazumidiparser shared event store"));
+        }
+        if header.version != VERSION {
+            return Err(invalid("Unsupported shared event store version"));
+        }
+
+        let in_bounds = |offset: u64, len: u64, align: usize| {
+            (offset as usize).is_multiple_of(align)
+                && offset
+                    .checked_add(len)
+                    .is_some_and(|end| end <= store.map.len() as u64)
+        };
+        if !in_bounds(
+            header.tempo_offset,
+            header
+                .tempo_count
+                .saturating_mul(size_of::<SharedTempoChange>() as u64),
+            align_of::<SharedTempoChange>(),
+        ) || !in_bounds(
+            header.event_offset,
+            header
+                .event_count
+                .saturating_mul(size_of::<SharedEvent>() as u64),
+            align_of::<SharedEvent>(),
+        ) || !in_bounds(header.sysex_offset, header.sysex_len, 1)
+        {
+            return Err(invalid("Shared event store sections are out of bounds"));
+        }
+        // `tempo_map` needs them in tick order.
+        if store
+            .tempo_changes()
+            .windows(2)
+            .any(|pair| pair[1].absolute_tick < pair[0].absolute_tick)
+        {
+            return Err(invalid(
+                "Shared event store tempo changes are not in tick order",
+            ));
+        }
+        Ok(store)
+    }
+
+    fn raw_header(&self) -> &SharedHeader {
+        unsafe { &section::<SharedHeader>(&self.map, 0, 1)[0] }
+    }
+
+    pub fn header(&self) -> MidiHeader {
+        let header = self.raw_header();
+        MidiHeader {
+            format: header.format,
+            tracks: header.tracks,
+            ppqn: header.ppqn,
+        }
+    }
+
+    pub fn events(&self) -> &[SharedEvent] {
+        let header = self.raw_header();
+        unsafe {
+            section(
+                &self.map,
+                header.event_offset as usize,
+                header.event_count as usize,
+            )
+        }
+    }
+
+    pub fn tempo_changes(&self) -> &[SharedTempoChange] {
+        let header = self.raw_header();
+        unsafe {
+            section(
+                &self.map,
+                header.tempo_offset as usize,
+                header.tempo_count as usize,
+            )
+        }
+    }
+
+    /// The tempo map the events were timed with: fixed for SMPTE files, and
+    /// exact if the sequence's was.
+    pub fn tempo_map(&self) -> TempoMap {
+        let exact = self.raw_header().flags & SharedHeader::EXACT_TIMING != 0;
+        TempoMap::for_header(
+            &self.header(),
+            self.tempo_changes()
+                .iter()
+                .map(|c| (c.absolute_tick, c.tempo_us)),
+            exact,
+        )
+    }
+
+    pub fn sysex(&self, event: &SharedEvent) -> Option<&[u8]> {
+        if !matches!(event.status, 0xF0 | 0xF7) {
+            return None;
+        }
+        let header = self.raw_header();
+        let start = header.sysex_offset as usize + event.sysex_offset as usize;
+        self.map.get(start..start + event.sysex_len as usize)
+    }
+
+    /// The whole mapped region.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.map
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{MidiEvent, MidiSequence};
+
+pub const PITCH_BEND_CENTER: u16 = 8192;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SoundingNote {
+    pub key: u8,
+    pub velocity: u8,
+    pub track_index: u16,
+    pub start_ns: u64,
+    pub start_tick: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelState {
+    pub program: Option<u8>,
+    // Bank select MSB (CC 0) and LSB (CC 32) as last received.
+    pub bank_msb: Option<u8>,
+    pub bank_lsb: Option<u8>,
+    // Indexed by controller number; `None` until the controller is first set.
+    pub controllers: [Option<u8>; 128],
+    // 14-bit value, 8192 is centered.
+    pub pitch_bend: u16,
+    pub channel_pressure: Option<u8>,
+    // Keys held down, in note-on order. Notes released while the sustain
+    // pedal is down are not included.
+    pub notes: Vec<SoundingNote>,
+}
+
+impl Default for ChannelState {
+    fn default() -> Self {
+        ChannelState {
+            program: None,
+            bank_msb: None,
+            bank_lsb: None,
+            controllers: [None; 128],
+            pitch_bend: PITCH_BEND_CENTER,
+            channel_pressure: None,
+            notes: Vec::new(),
+        }
+    }
+}
+
+impl ChannelState {
+    /// Combined 14-bit bank number, treating an unset half as 0.
+    pub fn bank(&self) -> u16 {
+        (self.bank_msb.unwrap_or(0) as u16) << 7 | self.bank_lsb.unwrap_or(0) as u16
+    }
+
+    pub fn controller(&self, controller: u8) -> Option<u8> {
+        self.controllers.get(controller as usize).copied().flatten()
+    }
+
+    /// Pitch bend relative to center, -8192..=8191.
+    pub fn pitch_bend_offset(&self) -> i16 {
+        self.pitch_bend as i16 - PITCH_BEND_CENTER as i16
+    }
+
+    pub(crate) fn apply(&mut self, event: &MidiEvent) {
+        match event.status & 0xF0 {
+            0x90 if event.data2 > 0 => self.notes.push(SoundingNote {
+                key: event.data1,
+                velocity: event.data2,
+                track_index: event.track_index,
+                start_ns: event.absolute_ns,
+                start_tick: event.absolute_tick,
+            }),
+            // Note off, or note on with velocity 0; overlapping notes on the
+            // same key are released first-in, first-out.
+            0x80 | 0x90 => {
+                if let Some(i) = self.notes.iter().position(|n| n.key == event.data1) {
+                    self.notes.remove(i);
+                }
+            }
+            0xB0 => self.apply_controller(event.data1, event.data2),
+            0xC0 => self.program = Some(event.data1),
+            0xD0 => self.channel_pressure = Some(event.data1),
+            0xE0 => self.pitch_bend = (event.data2 as u16) << 7 | event.data1 as u16,
+            _ => {}
+        }
+    }
+
+    fn apply_controller(&mut self, controller: u8, value: u8) {
+        match controller {
+            0 => self.bank_msb = Some(value),
+            32 => self.bank_lsb = Some(value),
+            // All Sound Off / All Notes Off
+            120 | 123..=127 => self.notes.clear(),
+            // Reset All Controllers, per RP-015: bank, volume, pan and the
+            // other sound controllers keep their values.
+            121 => {
+                for cc in [1, 2, 4, 5, 11, 64, 65, 66, 67, 68, 69] {
+                    self.controllers[cc] = None;
+                }
+                self.pitch_bend = PITCH_BEND_CENTER;
+                self.channel_pressure = None;
+            }
+            _ => {}
+        }
+        if let Some(slot) = self.controllers.get_mut(controller as usize) {
+            *slot = Some(value);
+        }
+    }
+}
+
+/// Per-channel controller, program, pitch bend and note state at one point
+/// in time, as a synthesizer that received every earlier event would hold it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelStateSnapshot {
+    pub time_ns: u64,
+    pub channels: [ChannelState; 16],
+}
+
+impl Default for ChannelStateSnapshot {
+    fn default() -> Self {
+        ChannelStateSnapshot {
+            time_ns: 0,
+            channels: std::array::from_fn(|_| ChannelState::default()),
+        }
+    }
+}
+
+impl ChannelStateSnapshot {
+    pub fn channel(&self, channel: u8) -> &ChannelState {
+        &self.channels[channel as usize & 0x0F]
+    }
+
+    /// Advances the snapshot past `event`. Feeding events in time order
+    /// keeps a snapshot current while playing, without rescanning from the
+    /// start.
+    pub fn apply(&mut self, event: &MidiEvent) {
+        self.time_ns = self.time_ns.max(event.absolute_ns);
+        if event.status < 0xF0 {
+            self.channels[(event.status & 0x0F) as usize].apply(event);
+        }
+    }
+}
+
+impl MidiSequence {
+    /// State of every channel after all events at or before `ns`.
+    pub fn state_at(&self, ns: u64) -> ChannelStateSnapshot {
+        let end = self.events.partition_point(|e| e.absolute_ns <= ns);
+        let mut snapshot = ChannelStateSnapshot::default();
+        for event in &self.events[..end] {
+            snapshot.apply(event);
+        }
+        snapshot.time_ns = ns;
+        snapshot
+    }
+}
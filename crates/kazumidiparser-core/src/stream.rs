@@ -0,0 +1,230 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+
+use crate::decode::TrackEventKind;
+use crate::sequence;
+use crate::{EventOrder, EventReader, MidiEvent, MidiHeader, ParseError, ParseOptions, TempoMap};
+
+/// Time-ordered, timed events straight off the disk, for files too big to
+/// hold as a `MidiSequence`.
+///
+/// Built on `EventReader`'s per-track merge, so memory stays at one read
+/// buffer per track plus the tempo changes seen so far. Yields the same
+/// events with the same times and order as `MidiParser::parse_file` with the
+/// same options, except that a format 2 file's events stay in tick order
+/// where `parse_file` puts them in time order; meta events only feed the
+/// tempo maps. Unlike `parse_file`, chunk lengths are trusted, so a file
+/// that needs resynchronizing fails, and `ParseOptions::strict` has no
+/// effect. A SysEx split into several packets is not reassembled either: its
+/// F7 continuation packets come through as events of their own with status
+/// 0xF7, and each payload is only kept until the next event is read.
+pub struct EventStream<R> {
+    events: EventReader<R>,
+    tempo_map: TempoMap,
+    // Format 2 only: the maps of the tracks after the first, as in
+    // `MidiSequence`.
+    track_tempo_maps: Vec<TempoMap>,
+    options: ParseOptions,
+    finished: bool,
+    // With `EventOrder::NoteOffsFirst`, the reordered rest of the current
+    // tick and track, then the first item past it.
+    pending: VecDeque<MidiEvent>,
+    lookahead: Option<Result<MidiEvent, ParseError>>,
+    // Payloads of the SysEx events returned by the last `next` or still
+    // pending, by their `sysex_index`.
+    sysex: Vec<(u32, Vec<u8>)>,
+    sysex_count: u32,
+}
+
+impl EventStream<File> {
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        options: ParseOptions,
+    ) -> Result<EventStream<File>, ParseError> {
+        Self::with_options(File::open(path)?, options)
+    }
+}
+
+impl<R: Read + Seek> EventStream<R> {
+    pub fn new(reader: R) -> Result<EventStream<R>, ParseError> {
+        Self::with_options(reader, ParseOptions::default())
+    }
+
+    pub fn with_options(reader: R, options: ParseOptions) -> Result<EventStream<R>, ParseError> {
+        let mut events = EventReader::new(reader)?;
+        events.set_lenient_running_status(options.lenient_running_status);
+        let fixed = options.fixed_tempo_us().map(|tempo_us| (0, tempo_us));
+        let tempo_map = TempoMap::for_header(events.header(), fixed, options.exact_timing);
+        let track_tempo_maps = match events.header() {
+            header if header.format == 2 => {
+                vec![tempo_map.clone(); header.tracks.max(1) as usize - 1]
+            }
+            _ => Vec::new(),
+        };
+        Ok(EventStream {
+            events,
+            tempo_map,
+            track_tempo_maps,
+            options,
+            finished: false,
+            pending: VecDeque::new(),
+            lookahead: None,
+            sysex: Vec::new(),
+            sysex_count: 0,
+        })
+    }
+
+    pub fn header(&self) -> &MidiHeader {
+        self.events.header()
+    }
+
+    /// The tempo changes read so far. Complete once the stream is exhausted.
+    /// In format 2, those of the first track.
+    pub fn tempo_map(&self) -> &TempoMap {
+        &self.tempo_map
+    }
+
+    /// The bytes of a SysEx or F7 packet event from this stream. Only the
+    /// payload of the event the last `next` returned is kept, so look it up
+    /// before asking for the next event.
+    pub fn sysex(&self, event: &MidiEvent) -> Option<&[u8]> {
+        let index = event.sysex_index?;
+        self.sysex
+            .iter()
+            .find(|(i, _)| *i == index)
+            .map(|(_, data)| data.as_slice())
+    }
+
+    pub fn set_block_size(&mut self, block_size: usize) {
+        self.events.set_block_size(block_size);
+    }
+}
+
+impl<R: Read + Seek> EventStream<R> {
+    // The next kept event in track order.
+    fn next_in_track_order(&mut self) -> Option<Result<MidiEvent, ParseError>> {
+        if let Some(item) = self.lookahead.take() {
+            return Some(item);
+        }
+        if self.finished {
+            return None;
+        }
+        if let Err(e) = self.options.check_cancelled() {
+            self.finished = true;
+            return Some(Err(e));
+        }
+
+        let header = self.events.header();
+        let fixed_tempo =
+            self.options.fixed_tempo_us().is_some() || header.tempo_timing().1.is_some();
+        let format_2 = header.format == 2;
+        loop {
+            let event = match self.events.next()? {
+                Ok(event) => event,
+                Err(e) => return Some(Err(e)),
+            };
+            let absolute_tick = event.absolute_tick;
+            let keep_track = self.options.keeps_track(event.track_index);
+            let (status, data1, data2, sysex_data) = match event.kind {
+                TrackEventKind::Channel {
+                    status,
+                    data1,
+                    data2,
+                } => (status, data1, data2, None),
+                TrackEventKind::SysEx { data } => (0xF0, 0, 0, Some(data)),
+                TrackEventKind::SysExContinuation { data } | TrackEventKind::Escape { data } => {
+                    (0xF7, 0, 0, Some(data))
+                }
+                TrackEventKind::Meta {
+                    meta_type: 0x51,
+                    data: &[a, b, c],
+                } if !fixed_tempo && (keep_track || !format_2) => {
+                    let tempo_us = u32::from_be_bytes([0, a, b, c]);
+                    let map = match event.track_index.checked_sub(1) {
+                        Some(i) if format_2 => &mut self.track_tempo_maps[i as usize],
+                        _ => &mut self.tempo_map,
+                    };
+                    map.push_change(absolute_tick, tempo_us);
+                    continue;
+                }
+                TrackEventKind::Meta { .. } | TrackEventKind::Other { .. } => continue,
+            };
+            if !keep_track
+                || !self.options.event_mask.keeps_status(status)
+                || (status < 0xF0 && !self.options.keeps_channel(status & 0x0F))
+            {
+                continue;
+            }
+            let map =
+                sequence::track_map(&self.tempo_map, &self.track_tempo_maps, event.track_index);
+            return Some(Ok(MidiEvent {
+                absolute_ns: map.tick_to_ns(absolute_tick),
+                absolute_tick,
+                status,
+                data1,
+                data2,
+                track_index: event.track_index,
+                sysex_index: sysex_data.map(|data| {
+                    let index = self.sysex_count;
+                    self.sysex_count = self.sysex_count.wrapping_add(1);
+                    self.sysex.push((index, data.to_vec()));
+                    index
+                }),
+            }));
+        }
+    }
+}
+
+impl<R: Read + Seek> Iterator for EventStream<R> {
+    type Item = Result<MidiEvent, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Drop the payloads of events already handed out.
+        let lookahead = match &self.lookahead {
+            Some(Ok(event)) => Some(event),
+            _ => None,
+        };
+        let held: Vec<u32> = self
+            .pending
+            .iter()
+            .chain(lookahead)
+            .filter_map(|event| event.sysex_index)
+            .collect();
+        self.sysex.retain(|(index, _)| held.contains(index));
+
+        if let Some(event) = self.pending.pop_front() {
+            return Some(Ok(event));
+        }
+        let order = self.options.event_order;
+        let first = match self.next_in_track_order()? {
+            Ok(event) => event,
+            Err(e) => return Some(Err(e)),
+        };
+        if order == EventOrder::Track {
+            return Some(Ok(first));
+        }
+
+        // Gather the rest of this tick on this track and reorder it the way
+        // `MidiParser`'s sort does.
+        let mut group = vec![first];
+        while let Some(item) = self.next_in_track_order() {
+            match item {
+                Ok(event)
+                    if event.absolute_tick == group[0].absolute_tick
+                        && event.track_index == group[0].track_index =>
+                {
+                    group.push(event);
+                }
+                item => {
+                    self.lookahead = Some(item);
+                    break;
+                }
+            }
+        }
+        group.sort_by_key(|e| order.rank(e.status, e.data2));
+        self.pending.extend(group);
+        self.pending.pop_front().map(Ok)
+    }
+}
@@ -0,0 +1,268 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::chunk::{read_header, read_track_header};
+use crate::decode::{RunningStatus, Step, TrackEventKind, decode_step};
+use crate::{MidiEvent, MidiHeader, ParseError, TempoMap};
+
+#[derive(Debug, Clone, Copy)]
+enum TailState {
+    Header,
+    TrackHeader,
+    // `None` when the chunk length is still a placeholder (0 or 0xFFFFFFFF).
+    TrackBody { remaining: Option<u64> },
+    Skip { remaining: u64 },
+    Finished,
+}
+
+/// Follows a MIDI file that is still being written, parsing only the bytes
+/// appended since the previous `poll`.
+///
+/// Events are returned in file order (track by track), timed against the
+/// tempo changes seen so far; in format 2, against those of their own
+/// track. Only the incomplete tail of the file is kept
+/// in memory between polls, along with the SysEx payloads of the last
+/// batch (see `sysex`). SysEx continuation packets come through as status
+/// 0xF7 events rather than being joined to their SysEx.
+pub struct TailParser {
+    path: PathBuf,
+    offset: u64,
+    pending: Vec<u8>,
+    state: TailState,
+    header: Option<MidiHeader>,
+    tempo_map: TempoMap,
+    track_index: u16,
+    running_status: RunningStatus,
+    absolute_tick: u64,
+    // Payloads of the SysEx events returned by the last `poll`.
+    sysex: Vec<Vec<u8>>,
+}
+
+impl TailParser {
+    pub fn new<P: AsRef<Path>>(path: P) -> TailParser {
+        TailParser {
+            path: path.as_ref().to_path_buf(),
+            offset: 0,
+            pending: Vec::new(),
+            state: TailState::Header,
+            header: None,
+            tempo_map: TempoMap::new(0),
+            track_index: 0,
+            running_status: RunningStatus::default(),
+            absolute_tick: 0,
+            sysex: Vec::new(),
+        }
+    }
+
+    pub fn header(&self) -> Option<&MidiHeader> {
+        self.header.as_ref()
+    }
+
+    /// In format 2, the map of the track being read.
+    pub fn tempo_map(&self) -> &TempoMap {
+        &self.tempo_map
+    }
+
+    // Number of file bytes read so far.
+    pub fn position(&self) -> u64 {
+        self.offset
+    }
+
+    // SMPTE files tick at a fixed rate whatever their tempo events say.
+    fn fixed_tempo(&self) -> bool {
+        self.header
+            .as_ref()
+            .is_some_and(|header| header.tempo_timing().1.is_some())
+    }
+
+    pub fn is_finished(&self) -> bool {
+        matches!(self.state, TailState::Finished)
+    }
+
+    /// The bytes of a SysEx or F7 packet event returned by the last `poll`.
+    pub fn sysex(&self, event: &MidiEvent) -> Option<&[u8]> {
+        self.sysex
+            .get(event.sysex_index? as usize)
+            .map(Vec::as_slice)
+    }
+
+    pub fn poll(&mut self) -> Result<Vec<MidiEvent>, ParseError> {
+        let mut file = File::open(&self.path)?;
+        let file_len = file.metadata()?.len();
+        if file_len < self.offset {
+            return Err(ParseError::FileShrank {
+                read: self.offset,
+                length: file_len,
+            });
+        }
+        file.seek(SeekFrom::Start(self.offset))?;
+        self.offset += file.read_to_end(&mut self.pending)? as u64;
+
+        // File offset of `pending[0]`.
+        let base = self.offset - self.pending.len() as u64;
+        let mut events = Vec::new();
+        self.sysex.clear();
+        let mut pos = 0;
+        loop {
+            let available = &self.pending[pos..];
+            match self.state {
+                TailState::Header => {
+                    if available.len() < 14 {
+                        break;
+                    }
+                    let header = read_header(&mut &available[..14])?;
+                    pos += 14;
+                    self.tempo_map = TempoMap::for_header(&header, [], false);
+                    self.state = if header.tracks == 0 {
+                        TailState::Finished
+                    } else {
+                        TailState::TrackHeader
+                    };
+                    self.header = Some(header);
+                }
+                TailState::TrackHeader => {
+                    if available.len() < 8 {
+                        break;
+                    }
+                    let length = read_track_header(
+                        &mut &available[..8],
+                        self.track_index,
+                        base + pos as u64,
+                    )?;
+                    pos += 8;
+                    if let Some(header) = &self.header
+                        && header.format == 2
+                        && self.track_index > 0
+                    {
+                        // Every format 2 track is timed on its own.
+                        self.tempo_map = TempoMap::for_header(header, [], false);
+                    }
+                    self.running_status = RunningStatus::default();
+                    self.absolute_tick = 0;
+                    self.state = TailState::TrackBody {
+                        remaining: match length {
+                            0 | u32::MAX => None,
+                            length => Some(length as u64),
+                        },
+                    };
+                }
+                TailState::TrackBody { remaining } => {
+                    let (window, eof) = match remaining {
+                        Some(r) if r <= available.len() as u64 => (&available[..r as usize], true),
+                        _ => (available, false),
+                    };
+                    match decode_step(window, self.running_status, true, false, eof) {
+                        Step::Event {
+                            len,
+                            delta_ticks,
+                            kind,
+                            running_status,
+                            ..
+                        } => {
+                            pos += len;
+                            self.running_status = running_status;
+                            self.absolute_tick += delta_ticks as u64;
+                            let remaining = remaining.map(|r| r - len as u64);
+                            let absolute_tick = self.absolute_tick;
+
+                            match kind {
+                                TrackEventKind::Meta {
+                                    meta_type: 0x51,
+                                    data,
+                                } if data.len() == 3 && !self.fixed_tempo() => {
+                                    let tempo_us = ((data[0] as u32) << 16)
+                                        | ((data[1] as u32) << 8)
+                                        | (data[2] as u32);
+                                    // Appended in O(1) when in tick order, as on a
+                                    // conductor track; a later track's earlier
+                                    // change rebuilds the map.
+                                    let last_tick =
+                                        self.tempo_map.points().last().unwrap().absolute_tick;
+                                    if absolute_tick >= last_tick {
+                                        self.tempo_map.push_change(absolute_tick, tempo_us);
+                                    } else {
+                                        self.tempo_map.set_tempo(absolute_tick, tempo_us);
+                                    }
+                                }
+                                TrackEventKind::Channel {
+                                    status,
+                                    data1,
+                                    data2,
+                                } => events.push(MidiEvent {
+                                    absolute_ns: self.tempo_map.tick_to_ns(absolute_tick),
+                                    absolute_tick,
+                                    status,
+                                    data1,
+                                    data2,
+                                    track_index: self.track_index,
+                                    sysex_index: None,
+                                }),
+                                TrackEventKind::SysEx { data }
+                                | TrackEventKind::SysExContinuation { data }
+                                | TrackEventKind::Escape { data } => {
+                                    events.push(MidiEvent {
+                                        absolute_ns: self.tempo_map.tick_to_ns(absolute_tick),
+                                        absolute_tick,
+                                        status: if matches!(kind, TrackEventKind::SysEx { .. }) {
+                                            0xF0
+                                        } else {
+                                            0xF7
+                                        },
+                                        data1: 0,
+                                        data2: 0,
+                                        track_index: self.track_index,
+                                        sysex_index: Some(self.sysex.len() as u32),
+                                    });
+                                    self.sysex.push(data.to_vec());
+                                }
+                                _ => {}
+                            }
+
+                            if kind.is_end_of_track() || remaining == Some(0) {
+                                self.end_track(remaining.unwrap_or(0));
+                            } else {
+                                self.state = TailState::TrackBody { remaining };
+                            }
+                        }
+                        Step::NeedMore => break,
+                        Step::End => self.end_track(remaining.unwrap_or(0)),
+                        Step::Error(e) => {
+                            return Err(ParseError::Decode {
+                                track: self.track_index,
+                                error: e,
+                            });
+                        }
+                    }
+                }
+                TailState::Skip { remaining } => {
+                    let n = remaining.min(available.len() as u64);
+                    pos += n as usize;
+                    if n < remaining {
+                        self.state = TailState::Skip {
+                            remaining: remaining - n,
+                        };
+                        break;
+                    }
+                    self.end_track(0);
+                }
+                TailState::Finished => break,
+            }
+        }
+
+        self.pending.drain(..pos);
+        Ok(events)
+    }
+
+    fn end_track(&mut self, unread: u64) {
+        let tracks = self.header.as_ref().map_or(0, |h| h.tracks);
+        self.state = if unread > 0 {
+            TailState::Skip { remaining: unread }
+        } else if self.track_index + 1 < tracks {
+            self.track_index += 1;
+            TailState::TrackHeader
+        } else {
+            TailState::Finished
+        };
+    }
+}
@@ -0,0 +1,308 @@
+use rayon::prelude::*;
+
+use crate::{MidiEvent, MidiHeader};
+
+pub const DEFAULT_TEMPO_US: u32 = 500_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TempoPoint {
+    pub absolute_tick: u64,
+    pub absolute_ns: u64,
+    pub tempo_us: u32,
+    pub tick_ns: u64,
+}
+
+/// Tick-sorted tempo changes with their pre-computed absolute times.
+///
+/// The first point is always at tick 0 (120 BPM unless the file sets a tempo
+/// there), so every tick maps to exactly one segment.
+///
+/// By default every tick lasts `tick_ns`, truncated to whole nanoseconds,
+/// which drifts over long files when the tempo does not divide by the PPQN.
+/// An exact map (`exact_from_changes`) computes times in 128-bit integers
+/// and rounds only the final result, at some cost per conversion.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "TempoMapRepr", try_from = "TempoMapRepr")
+)]
+pub struct TempoMap {
+    ppqn: u16,
+    points: Vec<TempoPoint>,
+    // For exact maps, each point's time in ns * ppqn, which is an integer.
+    exact_scaled_ns: Option<Vec<u128>>,
+}
+
+// A tempo map as serialized: its points, from whose ticks and tempos the
+// rest is rebuilt on the way back in.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct TempoMapRepr {
+    ppqn: u16,
+    exact: bool,
+    points: Vec<TempoPoint>,
+}
+
+#[cfg(feature = "serde")]
+impl From<TempoMap> for TempoMapRepr {
+    fn from(map: TempoMap) -> TempoMapRepr {
+        TempoMapRepr {
+            ppqn: map.ppqn,
+            exact: map.is_exact(),
+            points: map.points,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<TempoMapRepr> for TempoMap {
+    type Error = &'static str;
+
+    fn try_from(repr: TempoMapRepr) -> Result<TempoMap, Self::Error> {
+        if repr
+            .points
+            .windows(2)
+            .any(|pair| pair[1].absolute_tick < pair[0].absolute_tick)
+        {
+            return Err("Tempo changes are not in tick order");
+        }
+        let changes = repr.points.iter().map(|p| (p.absolute_tick, p.tempo_us));
+        Ok(TempoMap::build(repr.ppqn, changes, repr.exact))
+    }
+}
+
+pub(crate) fn tempo_to_tick_ns(tempo_us: u32, ppqn: u16) -> u64 {
+    (tempo_us as u64 * 1000) / ppqn.max(1) as u64
+}
+
+fn sorted_changes<I>(changes: I) -> Vec<(u64, u32)>
+where
+    I: IntoIterator<Item = (u64, u32)>,
+{
+    let mut changes: Vec<(u64, u32)> = changes.into_iter().collect();
+    changes.sort_by_key(|&(absolute_tick, _)| absolute_tick);
+    changes
+}
+
+impl TempoMap {
+    pub fn new(ppqn: u16) -> TempoMap {
+        Self::from_changes(ppqn, std::iter::empty())
+    }
+
+    // `changes` are `(absolute_tick, tempo_us)` pairs. They are sorted by
+    // tick, keeping their order on a tick, so the last change on a tick wins.
+    pub fn from_changes<I>(ppqn: u16, changes: I) -> TempoMap
+    where
+        I: IntoIterator<Item = (u64, u32)>,
+    {
+        Self::build(ppqn, sorted_changes(changes), false)
+    }
+
+    pub fn exact_from_changes<I>(ppqn: u16, changes: I) -> TempoMap
+    where
+        I: IntoIterator<Item = (u64, u32)>,
+    {
+        Self::build(ppqn, sorted_changes(changes), true)
+    }
+
+    // A map for a file with this header. SMPTE files tick at a fixed rate,
+    // so `changes` only apply to metrical ones.
+    pub(crate) fn for_header<I>(header: &MidiHeader, changes: I, exact: bool) -> TempoMap
+    where
+        I: IntoIterator<Item = (u64, u32)>,
+    {
+        match header.tempo_timing() {
+            (ppqn, Some(tempo_us)) => Self::build(ppqn, [(0, tempo_us)], exact),
+            (ppqn, None) => Self::build(ppqn, changes, exact),
+        }
+    }
+
+    pub(crate) fn build<I>(ppqn: u16, changes: I, exact: bool) -> TempoMap
+    where
+        I: IntoIterator<Item = (u64, u32)>,
+    {
+        let mut map = TempoMap {
+            ppqn,
+            points: vec![TempoPoint {
+                absolute_tick: 0,
+                absolute_ns: 0,
+                tempo_us: DEFAULT_TEMPO_US,
+                tick_ns: tempo_to_tick_ns(DEFAULT_TEMPO_US, ppqn),
+            }],
+            exact_scaled_ns: exact.then(|| vec![0]),
+        };
+        for (absolute_tick, tempo_us) in changes {
+            map.push_change(absolute_tick, tempo_us);
+        }
+        map
+    }
+
+    // Appends a change at or after the last point in O(1). A change on the
+    // same tick as the last point replaces it.
+    pub(crate) fn push_change(&mut self, absolute_tick: u64, tempo_us: u32) {
+        let last_index = self.points.len() - 1;
+        let last = self.points[last_index];
+        let delta = absolute_tick - last.absolute_tick;
+        let point = |absolute_ns| TempoPoint {
+            absolute_tick,
+            absolute_ns,
+            tempo_us,
+            tick_ns: tempo_to_tick_ns(tempo_us, self.ppqn),
+        };
+        let (point, scaled) = match &self.exact_scaled_ns {
+            Some(scaled) => {
+                let ppqn = self.ppqn.max(1) as u128;
+                let total = scaled[last_index] + delta as u128 * last.tempo_us as u128 * 1000;
+                (point(((total + ppqn / 2) / ppqn) as u64), Some(total))
+            }
+            None => (point(last.absolute_ns + delta * last.tick_ns), None),
+        };
+
+        if delta == 0 {
+            self.points[last_index] = point;
+        } else {
+            self.points.push(point);
+            if let (Some(scaled_ns), Some(total)) = (&mut self.exact_scaled_ns, scaled) {
+                scaled_ns.push(total);
+            }
+        }
+    }
+
+    pub fn is_exact(&self) -> bool {
+        self.exact_scaled_ns.is_some()
+    }
+
+    // Switches between truncated and exact conversion, recomputing the
+    // absolute time of every point.
+    pub fn set_exact(&mut self, exact: bool) {
+        if exact != self.is_exact() {
+            *self = Self::build(self.ppqn, self.changes().collect::<Vec<_>>(), exact);
+        }
+    }
+
+    pub fn ppqn(&self) -> u16 {
+        self.ppqn
+    }
+
+    pub fn points(&self) -> &[TempoPoint] {
+        &self.points
+    }
+
+    pub fn changes(&self) -> impl Iterator<Item = (u64, u32)> + '_ {
+        self.points.iter().map(|p| (p.absolute_tick, p.tempo_us))
+    }
+
+    fn index_at_tick(&self, tick: u64) -> usize {
+        self.points.partition_point(|p| p.absolute_tick <= tick) - 1
+    }
+
+    pub fn tempo_at_tick(&self, tick: u64) -> u32 {
+        self.points[self.index_at_tick(tick)].tempo_us
+    }
+
+    pub fn tick_to_ns(&self, tick: u64) -> u64 {
+        let index = self.index_at_tick(tick);
+        let point = &self.points[index];
+        match &self.exact_scaled_ns {
+            Some(scaled) => {
+                let ppqn = self.ppqn.max(1) as u128;
+                let delta = (tick - point.absolute_tick) as u128 * point.tempo_us as u128 * 1000;
+                ((scaled[index] + delta + ppqn / 2) / ppqn) as u64
+            }
+            None => point.absolute_ns + (tick - point.absolute_tick) * point.tick_ns,
+        }
+    }
+
+    // Rounds down to the tick that starts at or before `ns`.
+    pub fn ns_to_tick(&self, ns: u64) -> u64 {
+        let index = self.points.partition_point(|p| p.absolute_ns <= ns) - 1;
+        let point = &self.points[index];
+        match &self.exact_scaled_ns {
+            Some(scaled) => {
+                // The last tick whose rounded time is at most `ns`, i.e.
+                // scaled + ppqn / 2 < (ns + 1) * ppqn.
+                let ppqn = self.ppqn.max(1) as u128;
+                let per_tick = point.tempo_us as u128 * 1000;
+                let room = (ns as u128 + 1) * ppqn - ppqn / 2 - scaled[index];
+                if per_tick == 0 {
+                    return point.absolute_tick;
+                }
+                point.absolute_tick + ((room - 1) / per_tick) as u64
+            }
+            None => {
+                if point.tick_ns == 0 {
+                    return point.absolute_tick;
+                }
+                point.absolute_tick + (ns - point.absolute_ns) / point.tick_ns
+            }
+        }
+    }
+
+    // Inserts a tempo change, replacing any change already on that tick.
+    pub fn set_tempo(&mut self, tick: u64, tempo_us: u32) {
+        let mut changes: Vec<(u64, u32)> = self.changes().filter(|(t, _)| *t != tick).collect();
+        let position = changes.partition_point(|(t, _)| *t < tick);
+        changes.insert(position, (tick, tempo_us));
+        *self = Self::build(self.ppqn, changes, self.is_exact());
+    }
+
+    // Removing the change at tick 0 falls back to the default 120 BPM.
+    pub fn remove_tempo(&mut self, tick: u64) -> bool {
+        let existed = self
+            .points
+            .iter()
+            .any(|p| p.absolute_tick == tick && (tick != 0 || p.tempo_us != DEFAULT_TEMPO_US));
+        let changes: Vec<(u64, u32)> = self.changes().filter(|(t, _)| *t != tick).collect();
+        *self = Self::build(self.ppqn, changes, self.is_exact());
+        existed
+    }
+
+    // Drops every change whose BPM is within `epsilon_bpm` of the tempo in
+    // effect before it, always including exact repeats, and returns how many
+    // were dropped. Each change is compared with the last one kept, so a slow
+    // ramp still ends up at its final tempo.
+    pub fn simplify(&mut self, epsilon_bpm: f64) -> usize {
+        let bpm = |tempo_us: u32| 60_000_000.0 / tempo_us.max(1) as f64;
+        let mut kept: Vec<(u64, u32)> = Vec::with_capacity(self.points.len());
+        for (tick, tempo_us) in self.changes() {
+            match kept.last() {
+                Some(&(_, last)) if (bpm(tempo_us) - bpm(last)).abs() < epsilon_bpm => {}
+                Some(&(_, last)) if tempo_us == last => {}
+                _ => kept.push((tick, tempo_us)),
+            }
+        }
+        let removed = self.points.len() - kept.len();
+        if removed > 0 {
+            *self = Self::build(self.ppqn, kept, self.is_exact());
+        }
+        removed
+    }
+
+    // Recomputes `absolute_ns` from `absolute_tick` for every event, in parallel.
+    pub fn apply(&self, events: &mut [MidiEvent]) {
+        events.par_iter_mut().for_each(|event| {
+            event.absolute_ns = self.tick_to_ns(event.absolute_tick);
+        });
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deserialize_rejects_out_of_order_ticks() {
+        let point = |tick: u64| {
+            format!(r#"{{"absolute_tick":{tick},"absolute_ns":0,"tempo_us":500000,"tick_ns":0}}"#)
+        };
+        let json = format!(
+            r#"{{"ppqn":480,"exact":false,"points":[{},{},{}]}}"#,
+            point(0),
+            point(960),
+            point(10)
+        );
+        assert!(serde_json::from_str::<TempoMap>(&json).is_err());
+    }
+}
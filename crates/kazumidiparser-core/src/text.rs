@@ -0,0 +1,72 @@
+#[cfg(feature = "encoding")]
+use encoding_rs::{Encoding, SHIFT_JIS, UTF_8, WINDOWS_1252};
+
+/// How the text of meta events (names, lyrics, markers) is decoded. Only
+/// UTF-8 is available without the `encoding` feature.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TextEncoding {
+    /// Invalid sequences become U+FFFD.
+    #[default]
+    Utf8,
+    #[cfg(feature = "encoding")]
+    ShiftJis,
+    /// Read as windows-1252, the superset of ISO-8859-1 that text labelled
+    /// Latin-1 almost always is.
+    #[cfg(feature = "encoding")]
+    Latin1,
+    #[cfg(feature = "encoding")]
+    Other(&'static Encoding),
+    /// Chosen once for the whole file: UTF-8 if all its text is valid UTF-8,
+    /// else Shift-JIS if all of it is valid Shift-JIS, else Latin-1.
+    #[cfg(feature = "encoding")]
+    Auto,
+}
+
+impl TextEncoding {
+    pub fn decode(self, bytes: &[u8]) -> String {
+        #[cfg(feature = "encoding")]
+        {
+            let encoding = match self {
+                TextEncoding::Utf8 => UTF_8,
+                TextEncoding::ShiftJis => SHIFT_JIS,
+                TextEncoding::Latin1 => WINDOWS_1252,
+                TextEncoding::Other(encoding) => encoding,
+                TextEncoding::Auto => {
+                    return TextEncoding::Auto.resolve([bytes]).decode(bytes);
+                }
+            };
+            encoding.decode_without_bom_handling(bytes).0.into_owned()
+        }
+        #[cfg(not(feature = "encoding"))]
+        String::from_utf8_lossy(bytes).into_owned()
+    }
+
+    /// Picks the encoding for `Auto` from all of a file's text; any other
+    /// encoding is returned as it is.
+    #[cfg_attr(not(feature = "encoding"), allow(unused_variables))]
+    pub(crate) fn resolve<'a, I>(self, texts: I) -> TextEncoding
+    where
+        I: IntoIterator<Item = &'a [u8]>,
+        I::IntoIter: Clone,
+    {
+        #[cfg(feature = "encoding")]
+        if self == TextEncoding::Auto {
+            let texts = texts.into_iter();
+            let valid = |encoding: &'static Encoding| {
+                texts.clone().all(|text| {
+                    encoding
+                        .decode_without_bom_handling_and_without_replacement(text)
+                        .is_some()
+                })
+            };
+            return if valid(UTF_8) {
+                TextEncoding::Utf8
+            } else if valid(SHIFT_JIS) {
+                TextEncoding::ShiftJis
+            } else {
+                TextEncoding::Latin1
+            };
+        }
+        self
+    }
+}
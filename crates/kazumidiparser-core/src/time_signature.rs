@@ -0,0 +1,164 @@
+use std::fmt;
+
+use crate::{MetaKind, MidiSequence};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TimeSignaturePoint {
+    pub absolute_tick: u64,
+    // Zero-based bar the change starts.
+    pub bar: u64,
+    pub numerator: u8,
+    // The denominator is 2 to this power.
+    pub denominator_log2: u8,
+    pub ticks_per_beat: u64,
+}
+
+impl TimeSignaturePoint {
+    pub fn denominator(&self) -> u32 {
+        1u32.checked_shl(self.denominator_log2 as u32).unwrap_or(0)
+    }
+
+    pub fn ticks_per_bar(&self) -> u64 {
+        self.ticks_per_beat * self.numerator.max(1) as u64
+    }
+}
+
+/// A musical position: zero-based bar and beat, plus the ticks past the
+/// start of the beat. Displays one-based, as `bar.beat.tick`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BarBeat {
+    pub bar: u64,
+    pub beat: u64,
+    pub tick: u64,
+}
+
+impl fmt::Display for BarBeat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{:03}", self.bar + 1, self.beat + 1, self.tick)
+    }
+}
+
+/// Tick-sorted time signature changes with the bar each one starts on.
+///
+/// The first point is always at tick 0 (4/4 unless the file sets a time
+/// signature there). A beat is one note of the denominator, so 6/8 has six
+/// eighth-note beats. A change that does not fall on a barline cuts the bar
+/// before it short and starts a new bar.
+#[derive(Debug, Clone)]
+pub struct TimeSignatureMap {
+    ppqn: u16,
+    points: Vec<TimeSignaturePoint>,
+}
+
+impl TimeSignatureMap {
+    pub fn new(ppqn: u16) -> TimeSignatureMap {
+        Self::from_changes(ppqn, std::iter::empty())
+    }
+
+    // `changes` are `(absolute_tick, numerator, denominator_log2)` in tick
+    // order.
+    pub fn from_changes<I>(ppqn: u16, changes: I) -> TimeSignatureMap
+    where
+        I: IntoIterator<Item = (u64, u8, u8)>,
+    {
+        let mut map = TimeSignatureMap {
+            ppqn,
+            points: vec![TimeSignaturePoint {
+                absolute_tick: 0,
+                bar: 0,
+                numerator: 4,
+                denominator_log2: 2,
+                ticks_per_beat: Self::ticks_per_beat(ppqn, 2),
+            }],
+        };
+        for (absolute_tick, numerator, denominator_log2) in changes {
+            map.push_change(absolute_tick, numerator, denominator_log2);
+        }
+        map
+    }
+
+    fn ticks_per_beat(ppqn: u16, denominator_log2: u8) -> u64 {
+        ((ppqn.max(1) as u64 * 4) >> denominator_log2.min(63)).max(1)
+    }
+
+    // A change on the same tick as the last point replaces it.
+    fn push_change(&mut self, absolute_tick: u64, numerator: u8, denominator_log2: u8) {
+        let last = *self.points.last().expect("the map starts with a point");
+        let delta = absolute_tick.saturating_sub(last.absolute_tick);
+        let point = TimeSignaturePoint {
+            absolute_tick: last.absolute_tick + delta,
+            bar: last.bar + delta.div_ceil(last.ticks_per_bar()),
+            numerator,
+            denominator_log2,
+            ticks_per_beat: Self::ticks_per_beat(self.ppqn, denominator_log2),
+        };
+        if delta == 0 {
+            *self.points.last_mut().unwrap() = point;
+        } else {
+            self.points.push(point);
+        }
+    }
+
+    pub fn ppqn(&self) -> u16 {
+        self.ppqn
+    }
+
+    pub fn points(&self) -> &[TimeSignaturePoint] {
+        &self.points
+    }
+
+    pub fn at_tick(&self, tick: u64) -> &TimeSignaturePoint {
+        &self.points[self.points.partition_point(|p| p.absolute_tick <= tick) - 1]
+    }
+
+    pub fn tick_to_bar_beat(&self, tick: u64) -> BarBeat {
+        let point = self.at_tick(tick);
+        let offset = tick - point.absolute_tick;
+        let ticks_per_bar = point.ticks_per_bar();
+        let in_bar = offset % ticks_per_bar;
+        BarBeat {
+            bar: point.bar + offset / ticks_per_bar,
+            beat: in_bar / point.ticks_per_beat,
+            tick: in_bar % point.ticks_per_beat,
+        }
+    }
+
+    /// The tick of `position`. Beats and ticks past the end of the bar carry
+    /// on into the following ones at the same time signature.
+    pub fn bar_beat_to_tick(&self, position: BarBeat) -> u64 {
+        let index = self.points.partition_point(|p| p.bar <= position.bar) - 1;
+        let point = &self.points[index];
+        point.absolute_tick
+            + (position.bar - point.bar) * point.ticks_per_bar()
+            + position.beat * point.ticks_per_beat
+            + position.tick
+    }
+
+    /// The tick bar `bar` (zero-based) starts on.
+    pub fn bar_start(&self, bar: u64) -> u64 {
+        self.bar_beat_to_tick(BarBeat {
+            bar,
+            ..Default::default()
+        })
+    }
+}
+
+impl MidiSequence {
+    /// The bars and beats of the song, from its time signature events. A
+    /// format 2 file's tracks are merged onto one tick line.
+    pub fn time_signature_map(&self) -> TimeSignatureMap {
+        let changes = self
+            .metas_of_type(0x58)
+            .filter_map(|meta| match meta.kind() {
+                MetaKind::TimeSignature {
+                    numerator,
+                    denominator_log2,
+                    ..
+                } => Some((meta.absolute_tick, numerator, denominator_log2)),
+                _ => None,
+            });
+        TimeSignatureMap::from_changes(self.tempo_map.ppqn(), changes)
+    }
+}
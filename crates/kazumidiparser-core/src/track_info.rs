@@ -0,0 +1,96 @@
+use crate::MidiSequence;
+use crate::gm::{self, PercussionTracker};
+
+#[derive(Debug, Clone, Default)]
+pub struct TrackInfo {
+    pub index: u16,
+    pub name: Option<String>,
+    pub instrument_name: Option<String>,
+    pub sequence_number: Option<u16>,
+    pub program: Option<u8>,
+    pub program_name: Option<&'static str>,
+    // 0-based MIDI channels, ascending.
+    pub channels: Vec<u8>,
+    pub event_count: usize,
+    pub note_count: usize,
+    pub lowest_key: Option<u8>,
+    pub highest_key: Option<u8>,
+    pub first_event_ns: Option<u64>,
+    pub last_event_ns: Option<u64>,
+}
+
+impl MidiSequence {
+    // Summarises every track in a single pass over the merged event list.
+    pub fn track_info(&self) -> Vec<TrackInfo> {
+        let track_count = self.header.tracks as usize;
+
+        let mut infos: Vec<TrackInfo> = (0..track_count)
+            .map(|i| {
+                let meta = self.track_metas.get(i).cloned().unwrap_or_default();
+                TrackInfo {
+                    index: i as u16,
+                    name: meta.name.map(|name| self.decode_text(&name)),
+                    instrument_name: meta.instrument_name.map(|name| self.decode_text(&name)),
+                    sequence_number: self.track_sequence_number(i as u16),
+                    ..TrackInfo::default()
+                }
+            })
+            .collect();
+        let mut channel_masks = vec![0u16; track_count];
+        let mut banks = vec![[0u16; 16]; track_count];
+        let mut percussion = vec![PercussionTracker::new(); track_count];
+
+        for event in &self.events {
+            let track = event.track_index as usize;
+            let Some(info) = infos.get_mut(track) else {
+                continue;
+            };
+
+            info.event_count += 1;
+            info.first_event_ns.get_or_insert(event.absolute_ns);
+            info.last_event_ns = Some(event.absolute_ns);
+
+            if event.status >= 0xF0 {
+                continue;
+            }
+            let channel = event.status & 0x0F;
+            channel_masks[track] |= 1 << channel;
+            percussion[track].observe(event);
+
+            match event.status & 0xF0 {
+                0x90 if event.data2 > 0 => {
+                    info.note_count += 1;
+                    info.lowest_key =
+                        Some(info.lowest_key.map_or(event.data1, |k| k.min(event.data1)));
+                    info.highest_key =
+                        Some(info.highest_key.map_or(event.data1, |k| k.max(event.data1)));
+                }
+                0xB0 if event.data1 == 0 => {
+                    let bank = &mut banks[track][channel as usize];
+                    *bank = (event.data2 as u16) << 7 | (*bank & 0x7F);
+                }
+                0xB0 if event.data1 == 32 => {
+                    let bank = &mut banks[track][channel as usize];
+                    *bank = (*bank & !0x7F) | event.data2 as u16;
+                }
+                0xC0 if info.program.is_none() => {
+                    info.program = Some(event.data1);
+                    info.program_name = if percussion[track].is_percussion(event) {
+                        gm::drum_kit_name(event.data1)
+                    } else {
+                        Some(gm::program_name(
+                            banks[track][channel as usize],
+                            event.data1,
+                        ))
+                    };
+                }
+                _ => {}
+            }
+        }
+
+        for (info, mask) in infos.iter_mut().zip(channel_masks) {
+            info.channels = (0..16).filter(|ch| mask & (1 << ch) != 0).collect();
+        }
+        infos
+    }
+}
@@ -0,0 +1,12 @@
+mod pipeline;
+mod quantize;
+mod remap;
+mod resample;
+mod thin;
+mod transpose;
+
+pub use pipeline::{EventTransform, PerTrack, Pipeline, TrackTransform};
+pub use quantize::Quantize;
+pub use remap::RemapChannels;
+pub use thin::ThinControllers;
+pub use transpose::Transpose;
@@ -0,0 +1,134 @@
+use rayon::prelude::*;
+
+use crate::{MidiEvent, MidiSequence};
+
+/// A post-parse edit of a whole sequence.
+pub trait EventTransform: Send + Sync {
+    fn apply(&self, sequence: &mut MidiSequence);
+}
+
+impl<F> EventTransform for F
+where
+    F: Fn(&mut MidiSequence) + Send + Sync,
+{
+    fn apply(&self, sequence: &mut MidiSequence) {
+        self(sequence)
+    }
+}
+
+/// A transform that only looks at one track at a time, so tracks can be
+/// processed in parallel.
+///
+/// `events` holds the track's events in time order. Implementations may
+/// change ticks, drop or insert events; the sequence is re-sorted and
+/// `absolute_ns` recomputed afterwards.
+pub trait TrackTransform: Send + Sync {
+    fn apply_track(&self, track_index: u16, events: &mut Vec<MidiEvent>);
+}
+
+/// Runs a `TrackTransform` as an `EventTransform`.
+pub struct PerTrack<T>(pub T);
+
+impl<T: TrackTransform> EventTransform for PerTrack<T> {
+    fn apply(&self, sequence: &mut MidiSequence) {
+        run_per_track(sequence, &[&self.0]);
+    }
+}
+
+fn run_per_track(sequence: &mut MidiSequence, transforms: &[&dyn TrackTransform]) {
+    let track_count = sequence
+        .events
+        .iter()
+        .map(|e| e.track_index as usize + 1)
+        .max()
+        .unwrap_or(0)
+        .max(sequence.header.tracks as usize);
+
+    let mut tracks: Vec<Vec<MidiEvent>> = vec![Vec::new(); track_count];
+    for event in sequence.events.drain(..) {
+        tracks[event.track_index as usize].push(event);
+    }
+
+    tracks
+        .par_iter_mut()
+        .enumerate()
+        .for_each(|(track_index, events)| {
+            for transform in transforms {
+                transform.apply_track(track_index as u16, events);
+            }
+        });
+
+    // Tracks are concatenated in order, so the stable sort keeps ties in
+    // track order and sorts each track's ties just like the parser does.
+    let order = sequence.event_order;
+    sequence.events = tracks.into_iter().flatten().collect();
+    sequence.events.par_sort_by_key(|e| {
+        (
+            e.absolute_tick,
+            e.track_index,
+            order.rank(e.status, e.data2),
+        )
+    });
+    sequence.retime();
+}
+
+enum Stage {
+    Sequence(Box<dyn EventTransform>),
+    Track(Box<dyn TrackTransform>),
+}
+
+/// An ordered chain of transforms. Consecutive per-track stages share one
+/// split of the sequence and run in parallel across tracks.
+#[derive(Default)]
+pub struct Pipeline {
+    stages: Vec<Stage>,
+}
+
+impl Pipeline {
+    pub fn new() -> Pipeline {
+        Self::default()
+    }
+
+    pub fn then<T: EventTransform + 'static>(mut self, transform: T) -> Pipeline {
+        self.stages.push(Stage::Sequence(Box::new(transform)));
+        self
+    }
+
+    pub fn then_per_track<T: TrackTransform + 'static>(mut self, transform: T) -> Pipeline {
+        self.stages.push(Stage::Track(Box::new(transform)));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.stages.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stages.is_empty()
+    }
+
+    pub fn run(&self, sequence: &mut MidiSequence) {
+        let mut batch: Vec<&dyn TrackTransform> = Vec::new();
+        for stage in &self.stages {
+            match stage {
+                Stage::Track(transform) => batch.push(transform.as_ref()),
+                Stage::Sequence(transform) => {
+                    if !batch.is_empty() {
+                        run_per_track(sequence, &batch);
+                        batch.clear();
+                    }
+                    transform.apply(sequence);
+                }
+            }
+        }
+        if !batch.is_empty() {
+            run_per_track(sequence, &batch);
+        }
+    }
+}
+
+impl EventTransform for Pipeline {
+    fn apply(&self, sequence: &mut MidiSequence) {
+        self.run(sequence);
+    }
+}
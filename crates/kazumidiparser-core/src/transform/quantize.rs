@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use super::{EventTransform, PerTrack, TrackTransform};
+use crate::{MidiEvent, MidiSequence};
+
+/// Moves each note on toward the nearest multiple of `grid_ticks`, by
+/// `strength` (0.0 leaves it, 1.0 snaps it onto the grid). The note off
+/// moves with it, so note lengths are kept; other events stay where they
+/// are.
+#[derive(Debug, Clone, Copy)]
+pub struct Quantize {
+    pub grid_ticks: u64,
+    pub strength: f64,
+}
+
+impl Quantize {
+    fn shift(&self, tick: u64) -> i64 {
+        let offset = tick % self.grid_ticks;
+        let nearest = if offset * 2 >= self.grid_ticks {
+            (self.grid_ticks - offset) as i64
+        } else {
+            -(offset as i64)
+        };
+        (nearest as f64 * self.strength.clamp(0.0, 1.0)).round() as i64
+    }
+}
+
+impl TrackTransform for Quantize {
+    fn apply_track(&self, _track_index: u16, events: &mut Vec<MidiEvent>) {
+        if self.grid_ticks == 0 || self.strength <= 0.0 {
+            return;
+        }
+        // Shifts of the notes still open, by status low nibble and key;
+        // overlapping notes on one key pair up first-in, first-out.
+        let mut open: HashMap<(u8, u8), Vec<i64>> = HashMap::new();
+        for event in events.iter_mut() {
+            let key = (event.status & 0x0F, event.data1);
+            let shift = match event.status & 0xF0 {
+                0x90 if event.data2 > 0 => {
+                    let shift = self.shift(event.absolute_tick);
+                    open.entry(key).or_default().push(shift);
+                    shift
+                }
+                0x80 | 0x90 => match open.get_mut(&key) {
+                    Some(shifts) if !shifts.is_empty() => shifts.remove(0),
+                    _ => 0,
+                },
+                _ => 0,
+            };
+            event.absolute_tick = event.absolute_tick.saturating_add_signed(shift);
+        }
+    }
+}
+
+impl MidiSequence {
+    /// Quantizes note starts to `grid_ticks`; see `transform::Quantize`.
+    /// `absolute_ns` is recomputed from the tempo map afterwards.
+    pub fn quantize(&mut self, grid_ticks: u64, strength: f64) {
+        PerTrack(Quantize {
+            grid_ticks,
+            strength,
+        })
+        .apply(self);
+    }
+}
@@ -0,0 +1,62 @@
+use rayon::prelude::*;
+
+use super::EventTransform;
+use crate::{MidiEvent, MidiSequence};
+
+/// Moves channel messages to other channels. Starts as the identity map;
+/// several channels may be sent to the same one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RemapChannels {
+    // 0-based target channel, by 0-based source channel.
+    pub targets: [u8; 16],
+}
+
+impl Default for RemapChannels {
+    fn default() -> Self {
+        RemapChannels {
+            targets: std::array::from_fn(|channel| channel as u8),
+        }
+    }
+}
+
+impl RemapChannels {
+    pub fn new() -> RemapChannels {
+        Self::default()
+    }
+
+    /// Sends channel `from` to channel `to`, both 0-based.
+    pub fn map(mut self, from: u8, to: u8) -> RemapChannels {
+        self.targets[(from & 0x0F) as usize] = to & 0x0F;
+        self
+    }
+
+    /// Exchanges two channels, both 0-based.
+    pub fn swap(self, a: u8, b: u8) -> RemapChannels {
+        let (to_a, to_b) = (
+            self.targets[(b & 0x0F) as usize],
+            self.targets[(a & 0x0F) as usize],
+        );
+        self.map(a, to_a).map(b, to_b)
+    }
+
+    pub fn apply_to(&self, events: &mut [MidiEvent]) {
+        events.par_iter_mut().for_each(|event| {
+            if event.status < 0xF0 {
+                event.status = event.status & 0xF0 | self.targets[(event.status & 0x0F) as usize];
+            }
+        });
+    }
+
+    /// A remapped copy of `events`.
+    pub fn to_events(&self, events: &[MidiEvent]) -> Vec<MidiEvent> {
+        let mut events = events.to_vec();
+        self.apply_to(&mut events);
+        events
+    }
+}
+
+impl EventTransform for RemapChannels {
+    fn apply(&self, sequence: &mut MidiSequence) {
+        self.apply_to(&mut sequence.events);
+    }
+}
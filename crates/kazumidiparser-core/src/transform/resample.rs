@@ -0,0 +1,55 @@
+use rayon::prelude::*;
+
+use crate::{MidiSequence, TempoMap};
+
+fn rescale_tick(tick: u64, from_ppqn: u64, to_ppqn: u64) -> u64 {
+    ((tick as u128 * to_ppqn as u128 + from_ppqn as u128 / 2) / from_ppqn as u128) as u64
+}
+
+impl MidiSequence {
+    /// Rescales every tick position (events, metas, tempo changes and track
+    /// ends) to `new_ppqn`. SMPTE-timed files have no PPQN: they are left as
+    /// they are and `false` is returned.
+    ///
+    /// Ticks are multiplied by `new_ppqn / old_ppqn` and rounded to the nearest
+    /// tick, halves rounding up. Rounding never reorders events, but when
+    /// downsampling, events closer together than one new tick can collapse onto
+    /// the same tick (and very short notes can become zero-length).
+    /// `absolute_ns` is recomputed from the rescaled tempo map afterwards.
+    pub fn resample_ppqn(&mut self, new_ppqn: u16) -> bool {
+        if self.header.tempo_timing().1.is_some() {
+            return false;
+        }
+        // Larger values would read as an SMPTE division.
+        let new_ppqn = new_ppqn.clamp(1, 0x7FFF);
+        let old_ppqn = self.header.ppqn.max(1) as u64;
+        if new_ppqn as u64 == old_ppqn {
+            return true;
+        }
+
+        let rescale_map = |map: &TempoMap| {
+            let tempo_changes: Vec<(u64, u32)> = map
+                .changes()
+                .map(|(tick, tempo_us)| (rescale_tick(tick, old_ppqn, new_ppqn as u64), tempo_us))
+                .collect();
+            TempoMap::build(new_ppqn, tempo_changes, map.is_exact())
+        };
+        self.tempo_map = rescale_map(&self.tempo_map);
+        for map in &mut self.track_tempo_maps {
+            *map = rescale_map(map);
+        }
+
+        self.events.par_iter_mut().for_each(|event| {
+            event.absolute_tick = rescale_tick(event.absolute_tick, old_ppqn, new_ppqn as u64);
+        });
+        for meta in &mut self.metas {
+            meta.absolute_tick = rescale_tick(meta.absolute_tick, old_ppqn, new_ppqn as u64);
+        }
+        for track_meta in &mut self.track_metas {
+            track_meta.end_tick = rescale_tick(track_meta.end_tick, old_ppqn, new_ppqn as u64);
+        }
+        self.retime();
+        self.header.ppqn = new_ppqn;
+        true
+    }
+}
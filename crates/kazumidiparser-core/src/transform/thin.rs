@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+
+use super::TrackTransform;
+use crate::MidiEvent;
+
+/// Drops controller and pitch bend events that follow the previous kept
+/// event of the same kind (channel + controller) by less than
+/// `min_interval_ticks`. The last event of each burst is always kept, so the
+/// final value is unchanged.
+#[derive(Debug, Clone, Copy)]
+pub struct ThinControllers {
+    pub min_interval_ticks: u64,
+}
+
+fn thin_key(event: &MidiEvent) -> Option<(u8, u8)> {
+    match event.status & 0xF0 {
+        0xB0 => Some((event.status, event.data1)),
+        0xE0 => Some((event.status, 0)),
+        _ => None,
+    }
+}
+
+impl TrackTransform for ThinControllers {
+    fn apply_track(&self, _track_index: u16, events: &mut Vec<MidiEvent>) {
+        if self.min_interval_ticks == 0 {
+            return;
+        }
+
+        // Whether another event of the same kind follows within the interval.
+        let mut next_tick: HashMap<(u8, u8), u64> = HashMap::new();
+        let mut followed = vec![false; events.len()];
+        for (i, event) in events.iter().enumerate().rev() {
+            if let Some(key) = thin_key(event) {
+                followed[i] = next_tick
+                    .insert(key, event.absolute_tick)
+                    .is_some_and(|next| next - event.absolute_tick < self.min_interval_ticks);
+            }
+        }
+
+        let mut last_kept: HashMap<(u8, u8), u64> = HashMap::new();
+        let mut index = 0;
+        events.retain(|event| {
+            let i = index;
+            index += 1;
+            let Some(key) = thin_key(event) else {
+                return true;
+            };
+            let keep = !followed[i]
+                || last_kept
+                    .get(&key)
+                    .is_none_or(|last| event.absolute_tick - last >= self.min_interval_ticks);
+            if keep {
+                last_kept.insert(key, event.absolute_tick);
+            }
+            keep
+        });
+    }
+}
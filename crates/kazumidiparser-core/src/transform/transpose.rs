@@ -0,0 +1,49 @@
+use rayon::prelude::*;
+
+use super::EventTransform;
+use crate::{MidiEvent, MidiSequence};
+
+/// Shifts the key of every note on, note off and poly pressure event by
+/// `semitones`. Keys pushed past 0 or 127 are clamped there, which keeps each
+/// note off on the same key as its note on.
+#[derive(Debug, Clone, Copy)]
+pub struct Transpose {
+    pub semitones: i8,
+    // Channels left alone, bit 0 being channel 1. Channel 10 by default,
+    // since drum keys pick instruments rather than pitches.
+    pub skip_channels: u16,
+}
+
+impl Transpose {
+    pub fn new(semitones: i8) -> Transpose {
+        Transpose {
+            semitones,
+            skip_channels: 1 << 9,
+        }
+    }
+
+    pub fn apply_to(&self, events: &mut [MidiEvent]) {
+        if self.semitones == 0 {
+            return;
+        }
+        events.par_iter_mut().for_each(|event| {
+            let skipped = self.skip_channels & (1 << (event.status & 0x0F)) != 0;
+            if matches!(event.status & 0xF0, 0x80 | 0x90 | 0xA0) && !skipped {
+                event.data1 = (event.data1 as i16 + self.semitones as i16).clamp(0, 127) as u8;
+            }
+        });
+    }
+
+    /// A transposed copy of `events`.
+    pub fn to_events(&self, events: &[MidiEvent]) -> Vec<MidiEvent> {
+        let mut events = events.to_vec();
+        self.apply_to(&mut events);
+        events
+    }
+}
+
+impl EventTransform for Transpose {
+    fn apply(&self, sequence: &mut MidiSequence) {
+        self.apply_to(&mut sequence.events);
+    }
+}
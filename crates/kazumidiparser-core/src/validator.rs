@@ -0,0 +1,367 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::chunk::{locate_track_chunk, read_header};
+use crate::decode::{TrackDecoder, TrackEventKind};
+use crate::{Division, MidiHeader, rmid};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    InvalidHeader,
+    InvalidDivision,
+    TrackCount,
+    MissingTrackChunk,
+    TrackLengthMismatch,
+    MissingEndOfTrack,
+    DataAfterEndOfTrack,
+    TruncatedEvent,
+    OrphanRunningStatus,
+    CancelledRunningStatus,
+    TempoOutsideFirstTrack,
+    InvalidMetaLength,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 12] = [
+        Rule::InvalidHeader,
+        Rule::InvalidDivision,
+        Rule::TrackCount,
+        Rule::MissingTrackChunk,
+        Rule::TrackLengthMismatch,
+        Rule::MissingEndOfTrack,
+        Rule::DataAfterEndOfTrack,
+        Rule::TruncatedEvent,
+        Rule::OrphanRunningStatus,
+        Rule::CancelledRunningStatus,
+        Rule::TempoOutsideFirstTrack,
+        Rule::InvalidMetaLength,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Rule::InvalidHeader => "invalid-header",
+            Rule::InvalidDivision => "invalid-division",
+            Rule::TrackCount => "track-count",
+            Rule::MissingTrackChunk => "missing-track-chunk",
+            Rule::TrackLengthMismatch => "track-length-mismatch",
+            Rule::MissingEndOfTrack => "missing-eot",
+            Rule::DataAfterEndOfTrack => "data-after-eot",
+            Rule::TruncatedEvent => "truncated-event",
+            Rule::OrphanRunningStatus => "orphan-running-status",
+            Rule::CancelledRunningStatus => "cancelled-running-status",
+            Rule::TempoOutsideFirstTrack => "tempo-outside-first-track",
+            Rule::InvalidMetaLength => "invalid-meta-length",
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            Rule::InvalidHeader
+            | Rule::InvalidDivision
+            | Rule::MissingTrackChunk
+            | Rule::MissingEndOfTrack
+            | Rule::TruncatedEvent
+            | Rule::OrphanRunningStatus => Severity::Error,
+            Rule::TrackCount
+            | Rule::TrackLengthMismatch
+            | Rule::DataAfterEndOfTrack
+            | Rule::CancelledRunningStatus
+            | Rule::TempoOutsideFirstTrack
+            | Rule::InvalidMetaLength => Severity::Warning,
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Rule::InvalidHeader => "The file does not start with a valid MThd chunk",
+            Rule::InvalidDivision => "The header division is zero or an unknown SMPTE rate",
+            Rule::TrackCount => "The track count does not fit the file format",
+            Rule::MissingTrackChunk => "A track announced by the header has no MTrk chunk",
+            Rule::TrackLengthMismatch => "An MTrk length field disagrees with the track data",
+            Rule::MissingEndOfTrack => "A track does not end with an End of Track event",
+            Rule::DataAfterEndOfTrack => "A track has bytes after its End of Track event",
+            Rule::TruncatedEvent => "A track ends in the middle of an event",
+            Rule::OrphanRunningStatus => "A data byte appears before any status byte",
+            Rule::CancelledRunningStatus => {
+                "A data byte relies on running status across a meta or SysEx event"
+            }
+            Rule::TempoOutsideFirstTrack => "A format 1 file has a tempo event outside track 0",
+            Rule::InvalidMetaLength => "A meta event has the wrong length for its type",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub rule: Rule,
+    pub severity: Severity,
+    pub track: Option<u16>,
+    // Byte offset in the file.
+    pub offset: u64,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]", self.severity, self.rule.id())?;
+        if let Some(track) = self.track {
+            write!(f, " track {}", track)?;
+        }
+        write!(f, " at byte {}: {}", self.offset, self.message)
+    }
+}
+
+fn expected_meta_length(meta_type: u8) -> Option<&'static [usize]> {
+    match meta_type {
+        0x00 => Some(&[0, 2]),
+        0x20 | 0x21 => Some(&[1]),
+        0x2F => Some(&[0]),
+        0x51 => Some(&[3]),
+        0x54 => Some(&[5]),
+        0x58 => Some(&[4]),
+        0x59 => Some(&[2]),
+        _ => None,
+    }
+}
+
+struct Findings(Vec<Finding>);
+
+impl Findings {
+    fn push(&mut self, rule: Rule, track: Option<u16>, offset: usize, message: String) {
+        self.0.push(Finding {
+            rule,
+            severity: rule.severity(),
+            track,
+            offset: offset as u64,
+            message,
+        });
+    }
+}
+
+/// Checks a complete file image against the Standard MIDI File spec. For an
+/// RMID file the embedded SMF is checked, and offsets count from its start.
+pub fn validate(data: &[u8]) -> Vec<Finding> {
+    let mut findings = Findings(Vec::new());
+
+    let data = if data.starts_with(&rmid::RIFF) {
+        match rmid::split_rmid(data) {
+            Ok(image) => image.smf,
+            Err(e) => {
+                findings.push(Rule::InvalidHeader, None, 0, e.to_string());
+                return findings.0;
+            }
+        }
+    } else {
+        data
+    };
+    let header = match read_header(&mut &data[..]) {
+        Ok(header) => header,
+        Err(e) => {
+            findings.push(Rule::InvalidHeader, None, 0, e.to_string());
+            return findings.0;
+        }
+    };
+    match header.division() {
+        Division::TicksPerQuarter(0) => {
+            findings.push(Rule::InvalidDivision, None, 12, "Division is 0".to_string());
+        }
+        Division::Smpte {
+            frames_per_second,
+            ticks_per_frame,
+        } => {
+            if ![24, 25, 29, 30].contains(&frames_per_second) {
+                findings.push(
+                    Rule::InvalidDivision,
+                    None,
+                    12,
+                    format!("SMPTE division at {} frames per second", frames_per_second),
+                );
+            }
+            if ticks_per_frame == 0 {
+                findings.push(
+                    Rule::InvalidDivision,
+                    None,
+                    13,
+                    "SMPTE division with 0 ticks per frame".to_string(),
+                );
+            }
+        }
+        Division::TicksPerQuarter(_) => {}
+    }
+    if header.format == 0 && header.tracks != 1 {
+        findings.push(
+            Rule::TrackCount,
+            None,
+            10,
+            format!("Format 0 file declares {} tracks", header.tracks),
+        );
+    }
+
+    let mut pos = 14;
+    for track_index in 0..header.tracks {
+        let is_last = track_index + 1 == header.tracks;
+        let (chunk, next) = match locate_track_chunk(data, pos, track_index, is_last) {
+            Ok(located) => located,
+            Err(e) => {
+                findings.push(
+                    Rule::MissingTrackChunk,
+                    Some(track_index),
+                    pos,
+                    e.to_string(),
+                );
+                break;
+            }
+        };
+        if let Some(mismatch) = chunk.mismatch {
+            findings.push(
+                Rule::TrackLengthMismatch,
+                Some(track_index),
+                pos,
+                format!(
+                    "Declared {} bytes, found {}{}",
+                    mismatch.declared_length,
+                    mismatch.actual_length,
+                    if mismatch.resynced {
+                        " (next MTrk found by scanning)"
+                    } else {
+                        ""
+                    }
+                ),
+            );
+        }
+        validate_track(
+            &mut findings,
+            &header,
+            track_index,
+            data,
+            chunk.start,
+            chunk.end,
+        );
+        pos = next;
+    }
+
+    findings.0
+}
+
+pub fn validate_file<P: AsRef<Path>>(path: P) -> std::io::Result<Vec<Finding>> {
+    Ok(validate(&std::fs::read(path)?))
+}
+
+fn validate_track(
+    findings: &mut Findings,
+    header: &MidiHeader,
+    track_index: u16,
+    data: &[u8],
+    start: usize,
+    end: usize,
+) {
+    let body = &data[start..end];
+    let track = Some(track_index);
+    // Lenient, so a cancelled running status is reported and the rest of
+    // the track is still checked.
+    let mut decoder = TrackDecoder::for_track(track_index, body).lenient_running_status(true);
+    let mut end_of_track = None;
+    let mut fallback_reported = false;
+
+    while let Some(event) = decoder.next() {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                findings.push(
+                    Rule::OrphanRunningStatus,
+                    track,
+                    start + e.offset(),
+                    "Data byte without a preceding status byte".to_string(),
+                );
+                return;
+            }
+        };
+        let offset = start + event.offset;
+        // Only the first one; sloppy writers tend to do it after every meta.
+        if decoder.running_status_fallbacks() == 1 && !fallback_reported {
+            findings.push(
+                Rule::CancelledRunningStatus,
+                track,
+                offset,
+                "Running status reused after a meta or SysEx event".to_string(),
+            );
+            fallback_reported = true;
+        }
+
+        if let TrackEventKind::Meta { meta_type, data } = event.kind {
+            if let Some(lengths) = expected_meta_length(meta_type)
+                && !lengths.contains(&data.len())
+            {
+                findings.push(
+                    Rule::InvalidMetaLength,
+                    track,
+                    offset,
+                    format!(
+                        "Meta 0x{:02X} has {} data bytes, expected {:?}",
+                        meta_type,
+                        data.len(),
+                        lengths
+                    ),
+                );
+            }
+            if meta_type == 0x51 && header.format == 1 && track_index != 0 {
+                findings.push(
+                    Rule::TempoOutsideFirstTrack,
+                    track,
+                    offset,
+                    format!("Tempo change at tick {}", event.absolute_tick),
+                );
+            }
+        }
+
+        if event.kind.is_end_of_track() {
+            end_of_track = Some(decoder.position());
+        }
+    }
+
+    match end_of_track {
+        Some(eot_end) if eot_end < body.len() => findings.push(
+            Rule::DataAfterEndOfTrack,
+            track,
+            start + eot_end,
+            format!("{} bytes after End of Track", body.len() - eot_end),
+        ),
+        Some(_) => {}
+        None => {
+            if decoder.position() < body.len() {
+                findings.push(
+                    Rule::TruncatedEvent,
+                    track,
+                    start + decoder.position(),
+                    format!(
+                        "{} trailing bytes do not form a complete event",
+                        body.len() - decoder.position()
+                    ),
+                );
+            }
+            findings.push(
+                Rule::MissingEndOfTrack,
+                track,
+                end,
+                "Track ends without an End of Track event".to_string(),
+            );
+        }
+    }
+}
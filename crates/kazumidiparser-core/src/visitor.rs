@@ -0,0 +1,51 @@
+use std::io::Read;
+
+use crate::chunk::{read_header, read_track_chunk};
+use crate::decode::{TrackDecoder, TrackEvent};
+use crate::{MidiHeader, ParseError};
+
+/// Receives parse callbacks from [`parse_with_visitor`].
+///
+/// Events arrive in file order, one track after another, with tick positions
+/// relative to the start of their track; no tempo conversion is performed.
+pub trait MidiVisitor {
+    fn header(&mut self, _header: &MidiHeader) {}
+
+    fn track_start(&mut self, _track_index: u16, _length: u32) {}
+
+    fn event(&mut self, track_index: u16, event: &TrackEvent<'_>);
+
+    fn track_end(&mut self, _track_index: u16) {}
+}
+
+/// Streams a Standard MIDI File through `visitor` without collecting events.
+///
+/// Only the chunk currently being decoded is held in memory, in a single
+/// buffer that is reused for every track.
+pub fn parse_with_visitor<R, V>(mut reader: R, visitor: &mut V) -> Result<(), ParseError>
+where
+    R: Read,
+    V: MidiVisitor + ?Sized,
+{
+    let header = read_header(&mut reader)?;
+    visitor.header(&header);
+
+    let mut track_data = Vec::new();
+    let mut offset = 14;
+    for track_index in 0..header.tracks {
+        read_track_chunk(&mut reader, track_index, offset, &mut track_data)?;
+        offset += 8 + track_data.len() as u64;
+        visitor.track_start(track_index, track_data.len() as u32);
+
+        for event in TrackDecoder::for_track(track_index, &track_data) {
+            let event = event.map_err(|error| ParseError::Decode {
+                track: track_index,
+                error,
+            })?;
+            visitor.event(track_index, &event);
+        }
+
+        visitor.track_end(track_index);
+    }
+    Ok(())
+}
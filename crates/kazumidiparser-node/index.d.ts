@@ -0,0 +1,43 @@
+export interface ParseOptions {
+  /** Ignore the file's tempo and play at this many beats per minute. */
+  bpm?: number
+  /** Fail on bad track data instead of keeping what was read of the track. */
+  strict?: boolean
+}
+
+export interface Header {
+  format: number
+  tracks: number
+  ppqn: number
+}
+
+/**
+ * One typed array per event field, all `eventCount` long and in event order.
+ * The arrays are handed over without copying where the runtime allows it.
+ */
+export interface EventColumns {
+  /** Nanoseconds from the start of the song. */
+  timestamps: BigUint64Array
+  ticks: BigUint64Array
+  status: Uint8Array
+  data1: Uint8Array
+  data2: Uint8Array
+  track: Uint16Array
+}
+
+export class Sequence {
+  get header(): Header
+  get eventCount(): number
+  get durationNs(): bigint
+  /** Builds a fresh set of arrays on every call. */
+  events(): EventColumns
+  /**
+   * The payload of the `index`th event, or null if it is not a SysEx
+   * (0xF0) or escape (0xF7) event.
+   */
+  sysex(index: number): Buffer | null
+}
+
+export function parse(path: string, options?: ParseOptions): Sequence
+export function parseBuffer(data: Buffer, options?: ParseOptions): Sequence
+export function parseAsync(path: string, options?: ParseOptions): Promise<Sequence>
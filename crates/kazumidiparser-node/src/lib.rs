@@ -0,0 +1,151 @@
+use kazumidiparser_core::{EventColumns, MidiParser, MidiSequence, ParseError, ParseOptions};
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+
+#[napi(object, js_name = "ParseOptions")]
+pub struct JsParseOptions {
+    // Ignore the file's tempo and play at this many beats per minute.
+    pub bpm: Option<f64>,
+    // Fail on bad track data instead of keeping what was read of the track.
+    pub strict: Option<bool>,
+}
+
+impl JsParseOptions {
+    fn into_options(options: Option<JsParseOptions>) -> ParseOptions {
+        let mut parse_options = ParseOptions::default();
+        if let Some(options) = options {
+            parse_options.fixed_bpm = options.bpm;
+            parse_options.strict = options.strict.unwrap_or(parse_options.strict);
+        }
+        parse_options
+    }
+}
+
+#[napi(object)]
+pub struct Header {
+    pub format: u16,
+    pub tracks: u16,
+    pub ppqn: u16,
+}
+
+/// One typed array per event field, all `eventCount` long and in event order.
+/// The arrays are handed over without copying where the runtime allows it.
+#[napi(object, js_name = "EventColumns")]
+pub struct JsEventColumns {
+    // Nanoseconds from the start of the song.
+    pub timestamps: BigUint64Array,
+    pub ticks: BigUint64Array,
+    pub status: Uint8Array,
+    pub data1: Uint8Array,
+    pub data2: Uint8Array,
+    pub track: Uint16Array,
+}
+
+#[napi]
+pub struct Sequence {
+    sequence: MidiSequence,
+}
+
+#[napi]
+impl Sequence {
+    #[napi(getter)]
+    pub fn header(&self) -> Header {
+        let header = self.sequence.header();
+        Header {
+            format: header.format,
+            tracks: header.tracks,
+            ppqn: header.ppqn,
+        }
+    }
+
+    #[napi(getter)]
+    pub fn event_count(&self) -> u32 {
+        self.sequence.events().len() as u32
+    }
+
+    #[napi(getter)]
+    pub fn duration_ns(&self) -> u64 {
+        self.sequence.total_duration_ns()
+    }
+
+    /// Builds a fresh set of arrays on every call.
+    #[napi]
+    pub fn events(&self) -> JsEventColumns {
+        let columns = EventColumns::from_events(self.sequence.events());
+        JsEventColumns {
+            timestamps: columns.timestamps.into(),
+            ticks: columns.ticks.into(),
+            status: columns.status.into(),
+            data1: columns.data1.into(),
+            data2: columns.data2.into(),
+            track: columns.track.into(),
+        }
+    }
+
+    /// The payload of the `index`th event, or null if it is not a SysEx
+    /// (0xF0) or escape (0xF7) event.
+    #[napi]
+    pub fn sysex(&self, index: u32) -> Option<Buffer> {
+        let event = self.sequence.events().get(index as usize)?;
+        self.sequence.sysex(event).map(|data| data.to_vec().into())
+    }
+}
+
+fn to_js_error(error: ParseError) -> Error {
+    Error::from_reason(error.to_string())
+}
+
+fn parse_with(
+    options: Option<JsParseOptions>,
+    parse: impl FnOnce(&mut MidiParser) -> std::result::Result<(), ParseError>,
+) -> Result<Sequence> {
+    let mut parser =
+        MidiParser::with_options(JsParseOptions::into_options(options)).without_events_copy();
+    parse(&mut parser).map_err(to_js_error)?;
+    Ok(Sequence {
+        sequence: parser.into_sequence().unwrap(),
+    })
+}
+
+// parse(path, { bpm, strict })
+#[napi]
+pub fn parse(path: String, options: Option<JsParseOptions>) -> Result<Sequence> {
+    parse_with(options, |parser| parser.parse_file(&path))
+}
+
+// parseBuffer(data, { bpm, strict }), for a file already in memory.
+#[napi]
+pub fn parse_buffer(data: Buffer, options: Option<JsParseOptions>) -> Result<Sequence> {
+    parse_with(options, |parser| parser.parse_bytes(&data))
+}
+
+pub struct ParseTask {
+    path: String,
+    options: Option<ParseOptions>,
+}
+
+impl Task for ParseTask {
+    type Output = MidiSequence;
+    type JsValue = Sequence;
+
+    fn compute(&mut self) -> Result<MidiSequence> {
+        let mut parser =
+            MidiParser::with_options(self.options.take().unwrap_or_default()).without_events_copy();
+        parser.parse_file(&self.path).map_err(to_js_error)?;
+        Ok(parser.into_sequence().unwrap())
+    }
+
+    fn resolve(&mut self, _env: Env, sequence: MidiSequence) -> Result<Sequence> {
+        Ok(Sequence { sequence })
+    }
+}
+
+// parseAsync(path, { bpm, strict }): parses on the libuv thread pool and
+// resolves to a Sequence, keeping the event loop free.
+#[napi(ts_return_type = "Promise<Sequence>")]
+pub fn parse_async(path: String, options: Option<JsParseOptions>) -> AsyncTask<ParseTask> {
+    AsyncTask::new(ParseTask {
+        path,
+        options: Some(JsParseOptions::into_options(options)),
+    })
+}
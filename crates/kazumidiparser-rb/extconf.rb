@@ -0,0 +1,9 @@
+# frozen_string_literal: true
+
+require "mkmf"
+require "rb_sys/mkmf"
+require_relative "vendor_core"
+
+KazuMIDIParserVendor.vendor!
+
+create_rust_makefile("kazumidiparser/kazumidiparser")
@@ -0,0 +1,8 @@
+# frozen_string_literal: true
+
+begin
+  ruby_version = RUBY_VERSION[/\d+\.\d+/]
+  require_relative "kazumidiparser/#{ruby_version}/kazumidiparser"
+rescue LoadError
+  require_relative "kazumidiparser/kazumidiparser"
+end
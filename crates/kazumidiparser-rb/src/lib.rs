@@ -0,0 +1,181 @@
+use kazumidiparser_core::{MidiEvent, MidiParser, MidiSequence, ParseOptions};
+use magnus::scan_args::{get_kwargs, scan_args};
+use magnus::value::Lazy;
+use magnus::{
+    Error, ExceptionClass, RHash, RModule, RString, Ruby, Value, function, method, prelude::*,
+};
+
+static PARSE_ERROR: Lazy<ExceptionClass> = Lazy::new(|ruby| {
+    ruby.class_object()
+        .const_get::<_, RModule>("KazuMIDIParser")
+        .unwrap()
+        .const_get("ParseError")
+        .unwrap()
+});
+
+// The event and, for a SysEx, its own copy of the payload.
+#[magnus::wrap(class = "KazuMIDIParser::Event", free_immediately, size)]
+struct Event(MidiEvent, Option<Vec<u8>>);
+
+impl Event {
+    fn absolute_ns(&self) -> u64 {
+        self.0.absolute_ns
+    }
+
+    fn absolute_tick(&self) -> u64 {
+        self.0.absolute_tick
+    }
+
+    fn status(&self) -> u8 {
+        self.0.status
+    }
+
+    fn data1(&self) -> u8 {
+        self.0.data1
+    }
+
+    fn data2(&self) -> u8 {
+        self.0.data2
+    }
+
+    fn track_index(&self) -> u16 {
+        self.0.track_index
+    }
+
+    // 0-based, nil for SysEx.
+    fn channel(&self) -> Option<u8> {
+        (self.0.status < 0xF0).then_some(self.0.status & 0x0F)
+    }
+
+    fn sysex(ruby: &Ruby, rb_self: &Self) -> Option<RString> {
+        rb_self.1.as_deref().map(|data| ruby.str_from_slice(data))
+    }
+
+    fn inspect(&self) -> String {
+        format!(
+            "#<KazuMIDIParser::Event tick={} ns={} track={} status=0x{:02X} data1={} data2={}>",
+            self.0.absolute_tick,
+            self.0.absolute_ns,
+            self.0.track_index,
+            self.0.status,
+            self.0.data1,
+            self.0.data2
+        )
+    }
+}
+
+#[magnus::wrap(class = "KazuMIDIParser::Sequence", free_immediately, size)]
+struct Sequence(MidiSequence);
+
+impl Sequence {
+    fn header(ruby: &Ruby, rb_self: &Self) -> Result<RHash, Error> {
+        let header = rb_self.0.header();
+        let hash = ruby.hash_new();
+        hash.aset(ruby.to_symbol("format"), header.format)?;
+        hash.aset(ruby.to_symbol("tracks"), header.tracks)?;
+        hash.aset(ruby.to_symbol("ppqn"), header.ppqn)?;
+        Ok(hash)
+    }
+
+    fn event_count(&self) -> usize {
+        self.0.events().len()
+    }
+
+    fn wrap_event(&self, event: &MidiEvent) -> Event {
+        Event(*event, self.0.sysex(event).map(<[u8]>::to_vec))
+    }
+
+    fn events(&self) -> Vec<Event> {
+        self.0
+            .events()
+            .iter()
+            .map(|event| self.wrap_event(event))
+            .collect()
+    }
+
+    fn each_event(ruby: &Ruby, rb_self: &Self) -> Result<(), Error> {
+        for event in rb_self.0.events() {
+            let _: Value = ruby.yield_value(rb_self.wrap_event(event))?;
+        }
+        Ok(())
+    }
+
+    fn duration_ns(&self) -> u64 {
+        self.0.total_duration_ns()
+    }
+
+    fn stats(ruby: &Ruby, rb_self: &Self) -> Result<RHash, Error> {
+        let sequence = &rb_self.0;
+        let tracks = sequence.track_info();
+        let hash = ruby.hash_new();
+        hash.aset(ruby.to_symbol("tracks"), sequence.header().tracks)?;
+        hash.aset(ruby.to_symbol("events"), sequence.events().len())?;
+        hash.aset(
+            ruby.to_symbol("notes"),
+            tracks.iter().map(|t| t.note_count).sum::<usize>(),
+        )?;
+        hash.aset(
+            ruby.to_symbol("tempo_changes"),
+            sequence.metas_of_type(0x51).count(),
+        )?;
+        hash.aset(ruby.to_symbol("duration_ns"), sequence.total_duration_ns())?;
+        hash.aset(
+            ruby.to_symbol("duration_seconds"),
+            sequence.total_duration_ns() as f64 / 1e9,
+        )?;
+        hash.aset(
+            ruby.to_symbol("lowest_key"),
+            tracks.iter().filter_map(|t| t.lowest_key).min(),
+        )?;
+        hash.aset(
+            ruby.to_symbol("highest_key"),
+            tracks.iter().filter_map(|t| t.highest_key).max(),
+        )?;
+        Ok(hash)
+    }
+}
+
+// KazuMIDIParser.parse(path, bpm: nil)
+fn parse(ruby: &Ruby, args: &[Value]) -> Result<Sequence, Error> {
+    let args = scan_args::<(String,), (), (), (), RHash, ()>(args)?;
+    let (path,) = args.required;
+    let kwargs = get_kwargs::<_, (), (Option<Option<f64>>,), ()>(args.keywords, &[], &["bpm"])?;
+    let (bpm,) = kwargs.optional;
+
+    let mut parser = MidiParser::with_options(ParseOptions {
+        fixed_bpm: bpm.flatten(),
+        ..Default::default()
+    })
+    .without_events_copy();
+    parser
+        .parse_file(&path)
+        .map_err(|e| Error::new(ruby.get_inner(&PARSE_ERROR), e.to_string()))?;
+    Ok(Sequence(parser.into_sequence().unwrap()))
+}
+
+#[magnus::init]
+fn init(ruby: &Ruby) -> Result<(), Error> {
+    let module = ruby.define_module("KazuMIDIParser")?;
+    module.define_error("ParseError", ruby.exception_standard_error())?;
+    module.define_singleton_method("parse", function!(parse, -1))?;
+
+    let sequence = module.define_class("Sequence", ruby.class_object())?;
+    sequence.define_method("header", method!(Sequence::header, 0))?;
+    sequence.define_method("event_count", method!(Sequence::event_count, 0))?;
+    sequence.define_method("events", method!(Sequence::events, 0))?;
+    sequence.define_method("each_event", method!(Sequence::each_event, 0))?;
+    sequence.define_method("duration_ns", method!(Sequence::duration_ns, 0))?;
+    sequence.define_method("stats", method!(Sequence::stats, 0))?;
+
+    let event = module.define_class("Event", ruby.class_object())?;
+    event.define_method("absolute_ns", method!(Event::absolute_ns, 0))?;
+    event.define_method("absolute_tick", method!(Event::absolute_tick, 0))?;
+    event.define_method("status", method!(Event::status, 0))?;
+    event.define_method("data1", method!(Event::data1, 0))?;
+    event.define_method("data2", method!(Event::data2, 0))?;
+    event.define_method("track_index", method!(Event::track_index, 0))?;
+    event.define_method("channel", method!(Event::channel, 0))?;
+    event.define_method("sysex", method!(Event::sysex, 0))?;
+    event.define_method("inspect", method!(Event::inspect, 0))?;
+    Ok(())
+}
@@ -0,0 +1,45 @@
+# frozen_string_literal: true
+
+# Copies kazumidiparser-core into vendor/ as `cargo package` lays it out, with
+# its workspace settings resolved, so the gem builds outside this checkout.
+
+require "fileutils"
+require "rubygems/package"
+require "tmpdir"
+require "zlib"
+
+module KazuMIDIParserVendor
+  ROOT = __dir__
+  CORE = File.expand_path("../kazumidiparser-core", ROOT)
+  VENDOR = File.join(ROOT, "vendor", "kazumidiparser-core")
+
+  # Re-vendors the core crate when building from the repository. A gem has no
+  # ../kazumidiparser-core and uses the copy it shipped with.
+  def self.vendor!
+    return unless File.directory?(CORE)
+
+    Dir.mktmpdir do |target_dir|
+      system("cargo", "package", "--no-verify", "--allow-dirty",
+             "--target-dir", target_dir, chdir: CORE, exception: true)
+      crate = Dir[File.join(target_dir, "package", "kazumidiparser-core-*.crate")].first
+      FileUtils.rm_rf(VENDOR)
+      extract(crate, VENDOR)
+    end
+    system("cargo", "generate-lockfile", chdir: ROOT, exception: true) unless File.exist?(File.join(ROOT, "Cargo.lock"))
+  end
+
+  # Unpacks a .crate tarball, dropping its top-level `name-version/` directory.
+  def self.extract(crate, dest)
+    Zlib::GzipReader.open(crate) do |gz|
+      Gem::Package::TarReader.new(gz) do |tar|
+        tar.each do |entry|
+          next unless entry.file?
+
+          path = File.join(dest, entry.full_name.split("/", 2).last)
+          FileUtils.mkdir_p(File.dirname(path))
+          File.binwrite(path, entry.read)
+        end
+      end
+    end
+  end
+end
@@ -0,0 +1,437 @@
+// Header-only C++ wrapper over the C API in kazumidiparser.h (generated by
+// generate_header.sh). Needs C++17; with C++20 the views also convert to
+// std::span.
+//
+// Errors are reported as `kazumidiparser::Error` exceptions by the throwing
+// calls, or as a `ParseStatus` by the `try_` calls. Define
+// KAZUMIDIPARSER_NO_EXCEPTIONS to drop the throwing calls entirely.
+
+#pragma once
+
+#include <cstddef>
+#include <cstdint>
+#include <memory>
+#include <optional>
+#include <string>
+#include <string_view>
+#include <utility>
+
+#if __cplusplus >= 202002L && __has_include(<span>)
+#include <span>
+#define KAZUMIDIPARSER_HAS_SPAN 1
+#endif
+
+#ifndef KAZUMIDIPARSER_NO_EXCEPTIONS
+#include <stdexcept>
+#endif
+
+#include "kazumidiparser.h"
+
+namespace kazumidiparser {
+
+enum class ParseStatus : int {
+    Idle = 0,
+    Running = 1,
+    Succeeded = 2,
+    Failed = 3,
+    Cancelled = 4,
+};
+
+enum class ErrorCode : int {
+    None = 0,
+    InvalidArgument = 1,
+    InvalidPath = 2,
+    Busy = 3,
+    Io = 4,
+    InvalidHeader = 5,
+    UnexpectedHeaderLength = 6,
+    UnexpectedChunk = 7,
+    TruncatedTrack = 8,
+    Decode = 9,
+    Cancelled = 10,
+    Internal = 11,
+};
+
+enum class LogLevel : int {
+    Error = 0,
+    Warn = 1,
+    Info = 2,
+    Debug = 3,
+};
+
+inline const char *to_string(ParseStatus status) noexcept {
+    switch (status) {
+    case ParseStatus::Idle: return "idle";
+    case ParseStatus::Running: return "running";
+    case ParseStatus::Succeeded: return "succeeded";
+    case ParseStatus::Failed: return "failed";
+    case ParseStatus::Cancelled: return "cancelled";
+    }
+    return "unknown";
+}
+
+#ifndef KAZUMIDIPARSER_NO_EXCEPTIONS
+class Error : public std::runtime_error {
+public:
+    explicit Error(ParseStatus status)
+        : std::runtime_error(std::string("MIDI parse ") + to_string(status)), status_(status) {}
+
+    /// `what()` is `message`, as from `midiparser_last_error_message`.
+    Error(ParseStatus status, ErrorCode code, const std::string &message)
+        : std::runtime_error(message), status_(status), code_(code) {}
+
+    ParseStatus status() const noexcept { return status_; }
+    ErrorCode code() const noexcept { return code_; }
+
+private:
+    ParseStatus status_;
+    ErrorCode code_ = ErrorCode::None;
+};
+#endif
+
+using Header = KazuMIDIParserHeader;
+using Event = KazuMIDIParserMidiEvent;
+using EventRecord = KazuMIDIParserEvent;
+using MetaEvent = KazuMIDIParserMetaEvent;
+
+inline bool is_note_on(const Event &e) noexcept { return (e.status & 0xF0) == 0x90 && e.data2 != 0; }
+inline bool is_note_off(const Event &e) noexcept {
+    return (e.status & 0xF0) == 0x80 || ((e.status & 0xF0) == 0x90 && e.data2 == 0);
+}
+inline std::uint8_t channel(const Event &e) noexcept { return e.status & 0x0F; }
+inline bool is_note_on(const EventRecord &e) noexcept { return (e.status & 0xF0) == 0x90 && e.data2 != 0; }
+inline bool is_note_off(const EventRecord &e) noexcept {
+    return (e.status & 0xF0) == 0x80 || ((e.status & 0xF0) == 0x90 && e.data2 == 0);
+}
+inline std::uint8_t channel(const EventRecord &e) noexcept { return e.status & 0x0F; }
+
+/// Non-owning, contiguous view of `T`. Works with range-for and, from C++20,
+/// converts to `std::span<const T>`.
+template <typename T> class View {
+public:
+    using value_type = T;
+    using const_iterator = const T *;
+
+    View() noexcept = default;
+    View(const T *data, std::size_t size) noexcept : data_(size ? data : nullptr), size_(size) {}
+
+    const T *data() const noexcept { return data_; }
+    std::size_t size() const noexcept { return size_; }
+    bool empty() const noexcept { return size_ == 0; }
+    const T &operator[](std::size_t i) const noexcept { return data_[i]; }
+    const T *begin() const noexcept { return data_; }
+    const T *end() const noexcept { return data_ + size_; }
+
+#ifdef KAZUMIDIPARSER_HAS_SPAN
+    std::span<const T> span() const noexcept { return {data_, size_}; }
+    operator std::span<const T>() const noexcept { return span(); }
+#endif
+
+private:
+    const T *data_ = nullptr;
+    std::size_t size_ = 0;
+};
+
+/// The SysEx payload of an event, empty for channel messages.
+inline View<std::uint8_t> sysex(const Event &e) noexcept { return {e.sysex_data, e.sysex_len}; }
+
+/// Raw payload of a meta event; not NUL-terminated.
+inline View<std::uint8_t> payload(const MetaEvent &m) noexcept { return {m.data, m.data_len}; }
+
+/// An owned copy of every event, as returned by `midiparser_get_events`.
+/// `sysex_data` still points into the parser, so the list must not outlive it
+/// or its next parse.
+class EventList {
+public:
+    EventList() noexcept = default;
+    EventList(const EventList &) = delete;
+    EventList &operator=(const EventList &) = delete;
+    EventList(EventList &&other) noexcept
+        : events_(std::exchange(other.events_, nullptr)), len_(std::exchange(other.len_, 0)) {}
+    EventList &operator=(EventList &&other) noexcept {
+        if (this != &other) {
+            reset();
+            events_ = std::exchange(other.events_, nullptr);
+            len_ = std::exchange(other.len_, 0);
+        }
+        return *this;
+    }
+    ~EventList() { reset(); }
+
+    View<Event> view() const noexcept { return {events_, len_}; }
+    const Event *data() const noexcept { return events_; }
+    std::size_t size() const noexcept { return len_; }
+    bool empty() const noexcept { return len_ == 0; }
+    const Event &operator[](std::size_t i) const noexcept { return events_[i]; }
+    const Event *begin() const noexcept { return events_; }
+    const Event *end() const noexcept { return events_ + len_; }
+
+#ifdef KAZUMIDIPARSER_HAS_SPAN
+    operator std::span<const Event>() const noexcept { return {events_, len_}; }
+#endif
+
+private:
+    friend class Parser;
+    EventList(Event *events, std::size_t len) noexcept : events_(events), len_(events ? len : 0) {}
+
+    void reset() noexcept {
+        if (events_) midiparser_events_free(events_, len_);
+        events_ = nullptr;
+        len_ = 0;
+    }
+
+    Event *events_ = nullptr;
+    std::size_t len_ = 0;
+};
+
+/// One array per event field, owned by the parser and valid until its next
+/// parse or destruction.
+struct EventColumns {
+    View<std::uint64_t> timestamps;
+    View<std::uint64_t> ticks;
+    View<std::uint8_t> status;
+    View<std::uint8_t> data1;
+    View<std::uint8_t> data2;
+    View<std::uint16_t> track;
+
+    std::size_t size() const noexcept { return timestamps.size(); }
+};
+
+/// Random-access range over the events of one track, read lazily through
+/// `midiparser_get_track_event`.
+class TrackEvents {
+public:
+    class iterator {
+    public:
+        using value_type = Event;
+        using difference_type = std::ptrdiff_t;
+
+        Event operator*() const noexcept { return (*track_)[nth_]; }
+        iterator &operator++() noexcept {
+            ++nth_;
+            return *this;
+        }
+        iterator operator++(int) noexcept {
+            iterator old = *this;
+            ++nth_;
+            return old;
+        }
+        bool operator==(const iterator &other) const noexcept { return nth_ == other.nth_; }
+        bool operator!=(const iterator &other) const noexcept { return nth_ != other.nth_; }
+
+    private:
+        friend class TrackEvents;
+        iterator(const TrackEvents *track, std::size_t nth) noexcept : track_(track), nth_(nth) {}
+
+        const TrackEvents *track_;
+        std::size_t nth_;
+    };
+
+    std::uint16_t track_index() const noexcept { return track_; }
+    std::size_t size() const noexcept { return len_; }
+    bool empty() const noexcept { return len_ == 0; }
+
+    Event operator[](std::size_t nth) const noexcept {
+        Event event{};
+        midiparser_get_track_event(handle_, track_, nth, &event);
+        return event;
+    }
+
+    iterator begin() const noexcept { return {this, 0}; }
+    iterator end() const noexcept { return {this, len_}; }
+
+private:
+    friend class Parser;
+    TrackEvents(KazuMIDIParserPtr *handle, std::uint16_t track) noexcept
+        : handle_(handle), track_(track), len_(midiparser_track_event_count(handle, track)) {}
+
+    KazuMIDIParserPtr *handle_;
+    std::uint16_t track_;
+    std::size_t len_;
+};
+
+/// Owns a `KazuMIDIParserPtr`. Move-only; the destructor cancels and joins a
+/// parse still running in the background.
+class Parser {
+public:
+    Parser() : handle_(midiparser_new()) {
+#ifndef KAZUMIDIPARSER_NO_EXCEPTIONS
+        if (!handle_) throw std::bad_alloc();
+#endif
+    }
+    Parser(const Parser &) = delete;
+    Parser &operator=(const Parser &) = delete;
+    Parser(Parser &&other) noexcept : handle_(std::exchange(other.handle_, nullptr)) {}
+    Parser &operator=(Parser &&other) noexcept {
+        if (this != &other) {
+            reset();
+            handle_ = std::exchange(other.handle_, nullptr);
+        }
+        return *this;
+    }
+    ~Parser() { reset(); }
+
+    /// The underlying handle, for C calls the wrapper does not cover.
+    KazuMIDIParserPtr *get() const noexcept { return handle_; }
+    explicit operator bool() const noexcept { return handle_ != nullptr; }
+
+    /// `ParseStatus::Running` if a background parse has not finished yet.
+    ParseStatus try_parse(const std::string &path) noexcept {
+        if (midiparser_parse_midi_file(handle_, path.c_str())) return ParseStatus::Succeeded;
+        return status();
+    }
+
+    /// Parses a file already in memory; `data` is not kept.
+    ParseStatus try_parse_data(const void *data, std::size_t size) noexcept {
+        if (midiparser_parse_midi_data(handle_, static_cast<const std::uint8_t *>(data), size))
+            return ParseStatus::Succeeded;
+        return status();
+    }
+
+    /// Starts a background parse. Returns false if one is already running.
+    bool try_parse_async(const std::string &path) noexcept {
+        return midiparser_parse_midi_file_async(handle_, path.c_str());
+    }
+
+    ParseStatus try_wait() noexcept {
+        midiparser_wait(handle_);
+        return status();
+    }
+
+#ifndef KAZUMIDIPARSER_NO_EXCEPTIONS
+    void parse(const std::string &path) {
+        ParseStatus status = try_parse(path);
+        if (status != ParseStatus::Succeeded) throw last_error(status);
+    }
+
+    void parse_data(const void *data, std::size_t size) {
+        ParseStatus status = try_parse_data(data, size);
+        if (status != ParseStatus::Succeeded) throw last_error(status);
+    }
+
+    void parse_async(const std::string &path) {
+        if (!try_parse_async(path)) throw last_error(status());
+    }
+
+    void wait() {
+        ParseStatus status = try_wait();
+        if (status != ParseStatus::Succeeded) throw last_error(status);
+    }
+#endif
+
+    /// Why the last parse failed, or `ErrorCode::None`.
+    ErrorCode last_error_code() const noexcept {
+        return static_cast<ErrorCode>(static_cast<int>(midiparser_last_error_code(handle_)));
+    }
+
+    /// A readable description of the last parse failure; empty if there was
+    /// none.
+    std::string last_error_message() const {
+        const char *message = midiparser_last_error_message(handle_);
+        return message ? message : "";
+    }
+
+    ParseStatus status() const noexcept {
+        return static_cast<ParseStatus>(static_cast<int>(midiparser_parse_status(handle_)));
+    }
+
+    /// Safe to call from another thread while this one parses or waits.
+    void cancel() const noexcept { midiparser_cancel(handle_); }
+
+    std::optional<Header> header() const noexcept {
+        KazuMIDIParserHeader *raw = midiparser_get_header(handle_);
+        if (!raw) return std::nullopt;
+        Header header = *raw;
+        midiparser_header_free(raw);
+        return header;
+    }
+
+    std::size_t event_count() const noexcept { return midiparser_get_events_len(handle_); }
+
+    /// Copies every event out of the parser.
+    EventList events() const noexcept {
+        std::size_t len = midiparser_get_events_len(handle_);
+        return EventList(len ? midiparser_get_events(handle_) : nullptr, len);
+    }
+
+    /// The parser's own events, without copying. Valid until the next parse
+    /// or destruction.
+    View<EventRecord> events_view() const noexcept {
+        KazuMIDIParserEventsView view = midiparser_events_view(handle_);
+        return {view.events, view.len};
+    }
+
+    /// The SysEx payload of the `index`th event of `events_view()`, empty for
+    /// channel messages.
+    View<std::uint8_t> event_sysex(std::size_t index) const noexcept {
+        const std::uint8_t *data = nullptr;
+        std::size_t len = 0;
+        if (!midiparser_get_event_sysex(handle_, index, &data, &len)) return {};
+        return {data, len};
+    }
+
+    std::optional<EventColumns> columns() const noexcept {
+        KazuMIDIParserEventArrays arrays{};
+        if (!midiparser_get_event_arrays(handle_, &arrays)) return std::nullopt;
+        return EventColumns{
+            {arrays.timestamps, arrays.len}, {arrays.ticks, arrays.len}, {arrays.status, arrays.len},
+            {arrays.data1, arrays.len},      {arrays.data2, arrays.len}, {arrays.track, arrays.len},
+        };
+    }
+
+    TrackEvents track(std::uint16_t track_index) const noexcept { return {handle_, track_index}; }
+
+    std::size_t meta_count() const noexcept { return midiparser_get_meta_count(handle_); }
+
+    std::optional<MetaEvent> meta(std::size_t index) const noexcept {
+        MetaEvent meta{};
+        if (!midiparser_get_meta(handle_, index, &meta)) return std::nullopt;
+        return meta;
+    }
+
+    /// The text of a text meta (0x01-0x0F) as UTF-8, or nothing for other
+    /// metas.
+    std::optional<std::string> meta_text(std::size_t index) const {
+        std::unique_ptr<char, void (*)(char *)> text(midiparser_get_meta_text(handle_, index),
+                                                      [](char *s) { midiparser_string_free(s); });
+        if (!text) return std::nullopt;
+        return std::string(text.get());
+    }
+
+private:
+#ifndef KAZUMIDIPARSER_NO_EXCEPTIONS
+    Error last_error(ParseStatus status) const {
+        std::string message = last_error_message();
+        if (message.empty()) return Error(status);
+        return Error(status, last_error_code(), message);
+    }
+#endif
+
+    void reset() noexcept {
+        if (handle_) midiparser_free(handle_);
+        handle_ = nullptr;
+    }
+
+    KazuMIDIParserPtr *handle_ = nullptr;
+};
+
+/// Stops forwarding the library's log messages.
+inline void set_log_callback(std::nullptr_t) noexcept { kazumidiparser_set_log_callback(nullptr, nullptr); }
+
+/// Routes the library's log messages to `callback`, called as
+/// `(LogLevel, std::string_view)`. It may run on several parser threads at
+/// once and must stay alive until replaced.
+template <typename F> void set_log_callback(F *callback) noexcept {
+    if (!callback) {
+        set_log_callback(nullptr);
+        return;
+    }
+    kazumidiparser_set_log_callback(
+        [](KazuMIDIParserLogLevel level, const char *message, void *user_data) {
+            (*static_cast<F *>(user_data))(static_cast<LogLevel>(static_cast<int>(level)),
+                                           std::string_view(message));
+        },
+        const_cast<void *>(static_cast<const void *>(callback)));
+}
+
+} // namespace kazumidiparser